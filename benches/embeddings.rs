@@ -0,0 +1,64 @@
+//! Compares decoding a batch of embeddings returned as floats and as base64, through
+//! `embeddings_create` answered by a `MockTransport`, so that only the client's own parsing of
+//! the response is measured.
+//!
+//! Run with `cargo bench --bench embeddings`.
+
+use std::time::{Duration, Instant};
+
+use base64::Engine;
+use openai_api_rust::embeddings::{EmbeddingsApi, EmbeddingsBody};
+use openai_api_rust::testing::{MockResponse, MockTransport};
+use openai_api_rust::{Auth, OpenAI};
+
+const BATCH: usize = 2048;
+const DIMENSIONS: usize = 1536;
+const ROUNDS: u32 = 5;
+
+fn response(embedding: impl Fn(&[f32]) -> serde_json::Value) -> String {
+	let data: Vec<serde_json::Value> = (0..BATCH)
+		.map(|index| {
+			let floats: Vec<f32> =
+				(0..DIMENSIONS).map(|i| ((index * DIMENSIONS + i) as f32).sin()).collect();
+			serde_json::json!({"object": "embedding", "embedding": embedding(&floats), "index": index})
+		})
+		.collect();
+	serde_json::json!({
+		"object": "list",
+		"data": data,
+		"model": "text-embedding-3-small",
+		"usage": {"prompt_tokens": 1, "total_tokens": 1},
+	})
+	.to_string()
+}
+
+fn bench(name: &str, body: &str) {
+	let mock = MockTransport::new().with_response("embeddings", MockResponse::new(200, body));
+	let openai =
+		OpenAI::new(Auth::new("sk-test"), "https://api.openai.com/v1/").with_transport(mock);
+	let request = EmbeddingsBody {
+		model: "text-embedding-3-small".into(),
+		input: "bench".into(),
+		dimensions: None,
+		encoding_format: Some(name.to_string()),
+		user: None,
+	};
+	let mut best = Duration::MAX;
+	for _ in 0..ROUNDS {
+		let start = Instant::now();
+		let embeddings = openai.embeddings_create(&request).unwrap();
+		best = best.min(start.elapsed());
+		assert_eq!(embeddings.data.unwrap().len(), BATCH);
+	}
+	println!("{name:>6}: {best:?} for {BATCH} embeddings, {} bytes", body.len());
+}
+
+fn main() {
+	let float = response(|floats| serde_json::json!(floats));
+	let base64 = response(|floats| {
+		let bytes: Vec<u8> = floats.iter().flat_map(|f| f.to_le_bytes()).collect();
+		base64::engine::general_purpose::STANDARD.encode(bytes).into()
+	});
+	bench("float", &float);
+	bench("base64", &base64);
+}
@@ -0,0 +1,781 @@
+// Build assistants that can call models and use tools, and run them on threads of messages.
+// See: https://platform.openai.com/docs/api-reference/assistants
+
+//! Assistants API (v2)
+
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::models::ModelName;
+use crate::requests::Requests;
+use crate::stream::Stream;
+use crate::*;
+
+use super::{
+	chat::FunctionDef, Content, ListParams, Paginated, Paginator, PollConfig, Role, ToolCall,
+	Usage, ASSISTANTS, ASSISTANTS_RETRIEVE, THREADS, THREADS_RETRIEVE,
+};
+
+/// The maximum page size of the list endpoints.
+const ASSISTANTS_MAX_LIMIT: u32 = 100;
+/// The beta feature sent in the `OpenAI-Beta` header of every request of this API.
+const ASSISTANTS_BETA: &str = "assistants=v2";
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AssistantBody {
+	/// ID of the model to use. Required to create an assistant,
+	/// left empty to keep the current model when modifying one.
+	#[serde(skip_serializing_if = "ModelName::is_empty")]
+	pub model: ModelName,
+	/// The name of the assistant. The maximum length is 256 characters.
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub name: Option<String>,
+	/// The description of the assistant. The maximum length is 512 characters.
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub description: Option<String>,
+	/// The system instructions that the assistant uses. The maximum length is 256,000 characters.
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub instructions: Option<String>,
+	/// A list of tool enabled on the assistant. There can be a maximum of 128 tools per assistant.
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub tools: Option<Vec<AssistantTool>>,
+	/// What sampling temperature to use, between 0 and 2.
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub temperature: Option<f32>,
+	/// An alternative to sampling with temperature, called nucleus sampling.
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub top_p: Option<f32>,
+	/// Set of 16 key-value pairs that can be attached to an object.
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub metadata: Option<HashMap<String, String>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AssistantTool {
+	CodeInterpreter,
+	FileSearch,
+	Function {
+		function: FunctionDef,
+	},
+	/// A tool type this crate doesn't know yet.
+	#[serde(other)]
+	Other,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Assistant {
+	pub id: String,
+	pub object: Option<String>,
+	/// The Unix timestamp (in seconds) for when the assistant was created.
+	pub created_at: u64,
+	pub name: Option<String>,
+	pub description: Option<String>,
+	pub model: String,
+	pub instructions: Option<String>,
+	#[serde(default)]
+	pub tools: Vec<AssistantTool>,
+	pub metadata: Option<HashMap<String, String>>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DeletedAssistant {
+	pub id: String,
+	pub object: Option<String>,
+	pub deleted: bool,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ThreadBody {
+	/// A list of messages to start the thread with.
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub messages: Option<Vec<ThreadMessageBody>>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub metadata: Option<HashMap<String, String>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ThreadMessageBody {
+	/// Either user or assistant.
+	pub role: Role,
+	pub content: Content,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub metadata: Option<HashMap<String, String>>,
+}
+
+impl ThreadMessageBody {
+	pub fn user(content: impl Into<Content>) -> Self {
+		Self { role: Role::User, content: content.into(), metadata: None }
+	}
+
+	pub fn assistant(content: impl Into<Content>) -> Self {
+		Self { role: Role::Assistant, content: content.into(), metadata: None }
+	}
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Thread {
+	pub id: String,
+	pub object: Option<String>,
+	/// The Unix timestamp (in seconds) for when the thread was created.
+	pub created_at: u64,
+	pub metadata: Option<HashMap<String, String>>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ThreadMessage {
+	pub id: String,
+	pub object: Option<String>,
+	/// The Unix timestamp (in seconds) for when the message was created.
+	pub created_at: u64,
+	pub thread_id: String,
+	pub role: Role,
+	#[serde(default)]
+	pub content: Vec<MessageContent>,
+	/// The assistant that authored this message, if any.
+	pub assistant_id: Option<String>,
+	/// The run during which this message was created, if any.
+	pub run_id: Option<String>,
+}
+
+impl ThreadMessage {
+	/// The text parts of the message, joined.
+	pub fn text(&self) -> String {
+		self.content
+			.iter()
+			.filter_map(|content| match content {
+				MessageContent::Text { text } => Some(text.value.as_str()),
+				_ => None,
+			})
+			.collect()
+	}
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum MessageContent {
+	Text {
+		text: MessageText,
+	},
+	ImageFile {
+		image_file: Json,
+	},
+	ImageUrl {
+		image_url: Json,
+	},
+	/// A content type this crate doesn't know yet.
+	#[serde(other)]
+	Other,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MessageText {
+	pub value: String,
+	#[serde(default)]
+	pub annotations: Vec<Json>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RunBody {
+	/// The ID of the assistant to use to execute this run.
+	pub assistant_id: String,
+	/// Overrides the model of the assistant.
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub model: Option<ModelName>,
+	/// Overrides the instructions of the assistant.
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub instructions: Option<String>,
+	/// Appends additional instructions at the end of the instructions for the run.
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub additional_instructions: Option<String>,
+	/// Overrides the tools the assistant can use for this run.
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub tools: Option<Vec<AssistantTool>>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub metadata: Option<HashMap<String, String>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RunStatus {
+	Queued,
+	InProgress,
+	/// The run waits for the outputs of the tool calls of `Run::required_action`.
+	RequiresAction,
+	Cancelling,
+	Cancelled,
+	Failed,
+	Completed,
+	Incomplete,
+	Expired,
+}
+
+impl RunStatus {
+	/// Whether the run stopped, at least until tool outputs are submitted.
+	pub fn is_terminal(&self) -> bool {
+		!matches!(self, RunStatus::Queued | RunStatus::InProgress | RunStatus::Cancelling)
+	}
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Run {
+	pub id: String,
+	pub object: Option<String>,
+	/// The Unix timestamp (in seconds) for when the run was created.
+	pub created_at: u64,
+	pub thread_id: String,
+	pub assistant_id: String,
+	pub status: RunStatus,
+	/// Details on the action required to continue the run, when it requires action.
+	pub required_action: Option<RequiredAction>,
+	/// The last error associated with this run, when it failed.
+	pub last_error: Option<RunError>,
+	pub model: Option<String>,
+	pub instructions: Option<String>,
+	/// Usage statistics related to the run, once it is in a terminal state.
+	pub usage: Option<Usage>,
+}
+
+impl Run {
+	/// The tool calls to submit outputs for, when the run requires action.
+	pub fn tool_calls(&self) -> &[ToolCall] {
+		match &self.required_action {
+			Some(action) => &action.submit_tool_outputs.tool_calls,
+			None => &[],
+		}
+	}
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RequiredAction {
+	/// Always submit_tool_outputs.
+	#[serde(rename = "type")]
+	pub kind: String,
+	pub submit_tool_outputs: SubmitToolOutputs,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SubmitToolOutputs {
+	pub tool_calls: Vec<ToolCall>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RunError {
+	/// One of server_error, rate_limit_exceeded, or invalid_prompt.
+	pub code: String,
+	pub message: String,
+}
+
+/// The output of the tool call `tool_call_id`, see `AssistantsApi::run_submit_tool_outputs`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ToolOutput {
+	pub tool_call_id: String,
+	pub output: String,
+}
+
+impl ToolOutput {
+	/// The output `text`, sent as is.
+	pub fn text(tool_call_id: impl Into<String>, text: impl Into<String>) -> ToolOutput {
+		ToolOutput { tool_call_id: tool_call_id.into(), output: text.into() }
+	}
+
+	/// The output `value` serialized as JSON, e.g. a struct returned by the function called.
+	/// Strings are quoted, use `text` to send them as is. Fails with `Error::InvalidRequest`
+	/// if `value` can't be serialized.
+	pub fn json(tool_call_id: impl Into<String>, value: &impl Serialize) -> ApiResult<ToolOutput> {
+		let output =
+			serde_json::to_string(value).map_err(|e| Error::InvalidRequest(e.to_string()))?;
+		Ok(ToolOutput { tool_call_id: tool_call_id.into(), output })
+	}
+}
+
+/// An event of a run streamed by `run_submit_tool_outputs_stream`, told apart by its event
+/// type, e.g. `thread.run.completed`.
+#[derive(Debug)]
+pub enum AssistantStreamEvent {
+	/// A change of the status of the run, e.g. to requires_action when it calls tools again.
+	Run(Run),
+	/// Text added to a message as it is generated, `thread.message.delta`.
+	MessageDelta(MessageDelta),
+	/// A message once it is complete, `thread.message.completed`.
+	MessageCompleted(ThreadMessage),
+	/// The events this crate doesn't model, e.g. of run steps.
+	Other { event: String, data: Json },
+}
+
+impl<'de> Deserialize<'de> for AssistantStreamEvent {
+	fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+		#[derive(Deserialize)]
+		struct Tagged {
+			event: Option<String>,
+			data: Json,
+		}
+		let Tagged { event, data } = Tagged::deserialize(deserializer)?;
+		let event = event.unwrap_or_default();
+		let parsed = match event.as_str() {
+			"thread.message.delta" => serde_json::from_value(data).map(Self::MessageDelta),
+			"thread.message.completed" => serde_json::from_value(data).map(Self::MessageCompleted),
+			run if run.starts_with("thread.run.") && !run.starts_with("thread.run.step.") => {
+				serde_json::from_value(data).map(Self::Run)
+			},
+			_ => return Ok(Self::Other { event, data }),
+		};
+		parsed.map_err(serde::de::Error::custom)
+	}
+}
+
+impl AssistantStreamEvent {
+	/// The text added by a message delta, empty for the other events.
+	pub fn text_delta(&self) -> String {
+		match self {
+			AssistantStreamEvent::MessageDelta(delta) => delta
+				.delta
+				.content
+				.iter()
+				.filter_map(|content| match content {
+					MessageContent::Text { text } => Some(text.value.as_str()),
+					_ => None,
+				})
+				.collect(),
+			_ => String::new(),
+		}
+	}
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MessageDelta {
+	/// The ID of the message.
+	pub id: String,
+	pub delta: MessageDeltaContent,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MessageDeltaContent {
+	/// The parts added to the message.
+	#[serde(default)]
+	pub content: Vec<MessageContent>,
+}
+
+pub type AssistantStream = Stream<AssistantStreamEvent>;
+
+/// Requests of this API carry the `OpenAI-Beta: assistants=v2` header.
+pub trait AssistantsApi {
+	/// Creates an assistant with a model and instructions.
+	fn assistant_create(&self, assistant_body: &AssistantBody) -> ApiResult<Assistant>;
+	/// Returns a list of assistants, most recent first.
+	fn assistants_list(&self, params: &ListParams) -> ApiResult<Paginated<Assistant>>;
+	/// Iterates over all the assistants starting from `params`,
+	/// fetching pages of the maximum size unless `params.limit` is set.
+	fn assistants_iter(&self, params: ListParams) -> Paginator<'_, Assistant>;
+	/// Retrieves an assistant.
+	fn assistant_retrieve(&self, assistant_id: &str) -> ApiResult<Assistant>;
+	/// Modifies an assistant, only the fields set in `assistant_body` are changed.
+	fn assistant_modify(
+		&self,
+		assistant_id: &str,
+		assistant_body: &AssistantBody,
+	) -> ApiResult<Assistant>;
+	/// Deletes an assistant.
+	fn assistant_delete(&self, assistant_id: &str) -> ApiResult<DeletedAssistant>;
+	/// Creates a thread.
+	fn thread_create(&self, thread_body: &ThreadBody) -> ApiResult<Thread>;
+	/// Creates a message in a thread.
+	fn thread_message_create(
+		&self,
+		thread_id: &str,
+		message_body: &ThreadMessageBody,
+	) -> ApiResult<ThreadMessage>;
+	/// Returns a list of messages for a given thread, most recent first.
+	fn thread_messages_list(
+		&self,
+		thread_id: &str,
+		params: &ListParams,
+	) -> ApiResult<Paginated<ThreadMessage>>;
+	/// Creates a run, executing the assistant on the thread.
+	fn run_create(&self, thread_id: &str, run_body: &RunBody) -> ApiResult<Run>;
+	/// Retrieves a run.
+	fn run_retrieve(&self, thread_id: &str, run_id: &str) -> ApiResult<Run>;
+	/// Cancels a run that is in progress.
+	fn run_cancel(&self, thread_id: &str, run_id: &str) -> ApiResult<Run>;
+	/// Submits the outputs of the tool calls of a run requiring action, resuming it.
+	fn run_submit_tool_outputs(
+		&self,
+		thread_id: &str,
+		run_id: &str,
+		tool_outputs: &[ToolOutput],
+	) -> ApiResult<Run>;
+	/// Same as `run_submit_tool_outputs`, streaming the events of the resumed run until it
+	/// stops, e.g. completed or requiring action again.
+	fn run_submit_tool_outputs_stream(
+		&self,
+		thread_id: &str,
+		run_id: &str,
+		tool_outputs: &[ToolOutput],
+	) -> ApiResult<AssistantStream>;
+	/// Retrieves the run until its status is terminal, see `RunStatus::is_terminal`,
+	/// waiting 500ms before the second retrieval and twice longer each time, up to 5s.
+	///
+	/// A run requiring action is returned with its `Run::tool_calls`, poll it again after
+	/// `run_submit_tool_outputs`.
+	fn run_poll_until_complete(&self, thread_id: &str, run_id: &str) -> ApiResult<Run>;
+	/// Same as `run_poll_until_complete`, with the delays and the timeout of `poll`.
+	/// Fails with `Error::Timeout` and the last status retrieved once the timeout elapses.
+	fn run_poll(&self, thread_id: &str, run_id: &str, poll: &PollConfig) -> ApiResult<Run>;
+}
+
+impl OpenAI {
+	/// A clone of the client sending the `OpenAI-Beta` header of the Assistants API.
+	pub(crate) fn assistants_beta(&self) -> OpenAI {
+		self.clone().with_beta(ASSISTANTS_BETA)
+	}
+}
+
+impl AssistantsApi for OpenAI {
+	fn assistant_create(&self, assistant_body: &AssistantBody) -> ApiResult<Assistant> {
+		let request_body = serde_json::to_value(assistant_body).unwrap();
+		let res = self.assistants_beta().post(ASSISTANTS, request_body)?;
+		let assistant: Assistant = from_json(&res)?;
+		Ok(assistant)
+	}
+
+	fn assistants_list(&self, params: &ListParams) -> ApiResult<Paginated<Assistant>> {
+		let res = self.assistants_beta().get(&params.apply(ASSISTANTS))?;
+		let assistants: Paginated<Assistant> = from_json(&res)?;
+		Ok(assistants)
+	}
+
+	fn assistants_iter(&self, params: ListParams) -> Paginator<'_, Assistant> {
+		Paginator::new(
+			params,
+			ASSISTANTS_MAX_LIMIT,
+			|assistant| assistant.id.clone(),
+			|params| self.assistants_list(params),
+		)
+	}
+
+	fn assistant_retrieve(&self, assistant_id: &str) -> ApiResult<Assistant> {
+		let res = self.assistants_beta().get(&(ASSISTANTS_RETRIEVE.to_owned() + assistant_id))?;
+		let assistant: Assistant = from_json(&res)?;
+		Ok(assistant)
+	}
+
+	fn assistant_modify(
+		&self,
+		assistant_id: &str,
+		assistant_body: &AssistantBody,
+	) -> ApiResult<Assistant> {
+		let request_body = serde_json::to_value(assistant_body).unwrap();
+		let sub_url = ASSISTANTS_RETRIEVE.to_owned() + assistant_id;
+		let res = self.assistants_beta().post(&sub_url, request_body)?;
+		let assistant: Assistant = from_json(&res)?;
+		Ok(assistant)
+	}
+
+	fn assistant_delete(&self, assistant_id: &str) -> ApiResult<DeletedAssistant> {
+		let res =
+			self.assistants_beta().delete(&(ASSISTANTS_RETRIEVE.to_owned() + assistant_id))?;
+		let deleted: DeletedAssistant = from_json(&res)?;
+		Ok(deleted)
+	}
+
+	fn thread_create(&self, thread_body: &ThreadBody) -> ApiResult<Thread> {
+		let request_body = serde_json::to_value(thread_body).unwrap();
+		let res = self.assistants_beta().post(THREADS, request_body)?;
+		let thread: Thread = from_json(&res)?;
+		Ok(thread)
+	}
+
+	fn thread_message_create(
+		&self,
+		thread_id: &str,
+		message_body: &ThreadMessageBody,
+	) -> ApiResult<ThreadMessage> {
+		let request_body = serde_json::to_value(message_body).unwrap();
+		let sub_url = THREADS_RETRIEVE.to_owned() + thread_id + "/messages";
+		let res = self.assistants_beta().post(&sub_url, request_body)?;
+		let message: ThreadMessage = from_json(&res)?;
+		Ok(message)
+	}
+
+	fn thread_messages_list(
+		&self,
+		thread_id: &str,
+		params: &ListParams,
+	) -> ApiResult<Paginated<ThreadMessage>> {
+		let sub_url = THREADS_RETRIEVE.to_owned() + thread_id + "/messages";
+		let res = self.assistants_beta().get(&params.apply(&sub_url))?;
+		let messages: Paginated<ThreadMessage> = from_json(&res)?;
+		Ok(messages)
+	}
+
+	fn run_create(&self, thread_id: &str, run_body: &RunBody) -> ApiResult<Run> {
+		let request_body = serde_json::to_value(run_body).unwrap();
+		let sub_url = THREADS_RETRIEVE.to_owned() + thread_id + "/runs";
+		let res = self.assistants_beta().post(&sub_url, request_body)?;
+		let run: Run = from_json(&res)?;
+		Ok(run)
+	}
+
+	fn run_retrieve(&self, thread_id: &str, run_id: &str) -> ApiResult<Run> {
+		let sub_url = THREADS_RETRIEVE.to_owned() + thread_id + "/runs/" + run_id;
+		let res = self.assistants_beta().get(&sub_url)?;
+		let run: Run = from_json(&res)?;
+		Ok(run)
+	}
+
+	fn run_cancel(&self, thread_id: &str, run_id: &str) -> ApiResult<Run> {
+		let sub_url = THREADS_RETRIEVE.to_owned() + thread_id + "/runs/" + run_id + "/cancel";
+		let res = self.assistants_beta().post(&sub_url, serde_json::json!({}))?;
+		let run: Run = from_json(&res)?;
+		Ok(run)
+	}
+
+	fn run_submit_tool_outputs(
+		&self,
+		thread_id: &str,
+		run_id: &str,
+		tool_outputs: &[ToolOutput],
+	) -> ApiResult<Run> {
+		let request_body = serde_json::json!({ "tool_outputs": tool_outputs });
+		let sub_url =
+			THREADS_RETRIEVE.to_owned() + thread_id + "/runs/" + run_id + "/submit_tool_outputs";
+		let res = self.assistants_beta().post(&sub_url, request_body)?;
+		let run: Run = from_json(&res)?;
+		Ok(run)
+	}
+
+	fn run_submit_tool_outputs_stream(
+		&self,
+		thread_id: &str,
+		run_id: &str,
+		tool_outputs: &[ToolOutput],
+	) -> ApiResult<AssistantStream> {
+		let request_body = serde_json::json!({ "tool_outputs": tool_outputs, "stream": true });
+		let sub_url =
+			THREADS_RETRIEVE.to_owned() + thread_id + "/runs/" + run_id + "/submit_tool_outputs";
+		let reader = self.assistants_beta().post_stream(&sub_url, request_body)?;
+		Ok(Stream::new(reader).tagging_events())
+	}
+
+	fn run_poll_until_complete(&self, thread_id: &str, run_id: &str) -> ApiResult<Run> {
+		self.run_poll(thread_id, run_id, &PollConfig::default())
+	}
+
+	fn run_poll(&self, thread_id: &str, run_id: &str, poll: &PollConfig) -> ApiResult<Run> {
+		poll.poll(
+			|| self.run_retrieve(thread_id, run_id),
+			|run| {
+				let status = serde_json::to_value(run.status).unwrap();
+				(!run.status.is_terminal()).then(|| status.as_str().unwrap_or_default().to_string())
+			},
+		)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::{
+		apis::assistants::{
+			AssistantBody, AssistantStreamEvent, AssistantTool, AssistantsApi, RunBody, RunStatus,
+			ThreadBody, ThreadMessageBody, ToolOutput,
+		},
+		chat::FunctionDef,
+		openai::new_test_mock,
+		testing::{MockResponse, MockTransport},
+		Error, ListParams, PollConfig,
+	};
+
+	const ASSISTANT: &str = r#"{"id": "asst_abc", "object": "assistant", "created_at": 1700000000,
+		"name": "Math Tutor", "description": null, "model": "gpt-4o",
+		"instructions": "You are a math tutor.", "tools": [{"type": "code_interpreter"},
+		{"type": "function", "function": {"name": "get_weather"}}],
+		"metadata": {}}"#;
+
+	fn run(status: &str) -> String {
+		let required_action = if status == "requires_action" {
+			r#"{"type": "submit_tool_outputs", "submit_tool_outputs": {"tool_calls": [{"id": "call_1",
+				"type": "function", "function": {"name": "get_weather", "arguments": "{}"}}]}}"#
+		} else {
+			"null"
+		};
+		format!(
+			r#"{{"id": "run_abc", "object": "thread.run", "created_at": 1700000000,
+			"thread_id": "thread_abc", "assistant_id": "asst_abc", "status": "{status}",
+			"required_action": {required_action}, "last_error": null, "model": "gpt-4o",
+			"instructions": null, "usage": null}}"#
+		)
+	}
+
+	#[test]
+	fn test_assistants() {
+		let assistants =
+			format!(r#"{{"object": "list", "data": [{ASSISTANT}], "has_more": false}}"#);
+		let mock = MockTransport::new()
+			.with_response("assistants", MockResponse::new(200, ASSISTANT))
+			.with_response("assistants", MockResponse::new(200, assistants))
+			.with_response("assistants/asst_abc", MockResponse::new(200, ASSISTANT))
+			.with_response(
+				"threads",
+				MockResponse::new(200, r#"{"id": "thread_abc", "created_at": 1700000000}"#),
+			)
+			.with_response(
+				"threads/thread_abc/messages",
+				MockResponse::new(
+					200,
+					r#"{"id": "msg_abc", "created_at": 1700000000, "thread_id": "thread_abc",
+					"role": "user", "content": [{"type": "text", "text": {"value": "1+1?",
+					"annotations": []}}], "assistant_id": null, "run_id": null}"#,
+				),
+			);
+		let openai = new_test_mock(&mock);
+
+		let body = AssistantBody {
+			model: "gpt-4o".into(),
+			name: Some("Math Tutor".to_string()),
+			tools: Some(vec![
+				AssistantTool::CodeInterpreter,
+				AssistantTool::Function {
+					function: FunctionDef {
+						name: "get_weather".to_string(),
+						description: None,
+						parameters: None,
+					},
+				},
+			]),
+			..Default::default()
+		};
+		let assistant = openai.assistant_create(&body).unwrap();
+		assert_eq!(assistant.tools, body.tools.unwrap());
+		let assistants: Vec<_> =
+			openai.assistants_iter(ListParams::default()).map(Result::unwrap).collect();
+		assert_eq!(assistants[0].id, "asst_abc");
+		let body = AssistantBody { name: Some("Tutor".to_string()), ..Default::default() };
+		openai.assistant_modify("asst_abc", &body).unwrap();
+
+		let thread = openai.thread_create(&ThreadBody::default()).unwrap();
+		let message =
+			openai.thread_message_create(&thread.id, &ThreadMessageBody::user("1+1?")).unwrap();
+		assert_eq!(message.text(), "1+1?");
+
+		let requests = mock.requests();
+		assert!(requests.iter().all(|r| r.header("OpenAI-Beta") == Some("assistants=v2")));
+		assert_eq!(requests[0].json().unwrap()["tools"][0]["type"], "code_interpreter");
+		assert_eq!(requests[1].path, "/v1/assistants");
+		assert_eq!(requests[1].url, "https://api.openai.com/v1/assistants?limit=100");
+		assert_eq!(requests[2].path, "/v1/assistants/asst_abc");
+		assert_eq!(requests[2].json().unwrap(), serde_json::json!({"name": "Tutor"}));
+		assert_eq!(
+			requests[4].json().unwrap(),
+			serde_json::json!({"role": "user", "content": "1+1?"})
+		);
+	}
+
+	#[test]
+	fn test_runs() {
+		let mock = MockTransport::new()
+			.with_response("threads/thread_abc/runs", MockResponse::new(200, run("queued")))
+			.with_response("runs/run_abc", MockResponse::new(200, run("in_progress")))
+			.with_response("runs/run_abc", MockResponse::new(200, run("requires_action")))
+			.with_response(
+				"runs/run_abc/submit_tool_outputs",
+				MockResponse::new(200, run("queued")),
+			);
+		let openai = new_test_mock(&mock);
+
+		let body = RunBody { assistant_id: "asst_abc".to_string(), ..Default::default() };
+		let run = openai.run_create("thread_abc", &body).unwrap();
+		assert_eq!(run.status, RunStatus::Queued);
+		let run = openai.run_poll_until_complete("thread_abc", &run.id).unwrap();
+		assert_eq!(run.status, RunStatus::RequiresAction);
+		assert_eq!(run.tool_calls()[0].function.name, "get_weather");
+		let outputs = [ToolOutput {
+			tool_call_id: run.tool_calls()[0].id.clone(),
+			output: "Sunny".to_string(),
+		}];
+		let run = openai.run_submit_tool_outputs("thread_abc", &run.id, &outputs).unwrap();
+		assert!(run.tool_calls().is_empty());
+
+		let requests = mock.requests();
+		assert_eq!(requests.len(), 4);
+		assert_eq!(requests[1].path, "/v1/threads/thread_abc/runs/run_abc");
+		assert_eq!(
+			requests[3].json().unwrap(),
+			serde_json::json!({"tool_outputs": [{"tool_call_id": "call_1", "output": "Sunny"}]})
+		);
+	}
+
+	#[test]
+	fn test_run_submit_tool_outputs_stream() {
+		// The JSON of the chunks is sent on a single line.
+		let event = |event: &str, data: &str| {
+			format!("event: {event}\ndata: {}\n\n", data.replace('\n', " "))
+		};
+		let delta = |text: &str| {
+			format!(
+				r#"{{"id": "msg_abc", "object": "thread.message.delta", "delta": {{"content":
+				[{{"index": 0, "type": "text", "text": {{"value": "{text}"}}}}]}}}}"#
+			)
+		};
+		let events = [
+			event("thread.run.queued", &run("queued")),
+			event("thread.run.step.created", r#"{"id": "step_abc"}"#),
+			event("thread.message.delta", &delta("It is ")),
+			event("thread.message.delta", &delta("sunny.")),
+			event("thread.run.completed", &run("completed")),
+			event("done", "[DONE]"),
+		]
+		.concat();
+		let mock = MockTransport::new()
+			.with_response("runs/run_abc", MockResponse::new(200, run("requires_action")))
+			.with_response("runs/run_abc/submit_tool_outputs", MockResponse::new(200, events));
+		let openai = new_test_mock(&mock);
+
+		let run = openai.run_retrieve("thread_abc", "run_abc").unwrap();
+		let outputs: Vec<ToolOutput> = run
+			.tool_calls()
+			.iter()
+			.map(|call| ToolOutput::json(&call.id, &serde_json::json!({"forecast": "sunny"})))
+			.collect::<Result<_, _>>()
+			.unwrap();
+		let stream = openai.run_submit_tool_outputs_stream("thread_abc", &run.id, &outputs);
+		let events: Vec<_> = stream.unwrap().map(Result::unwrap).collect();
+		assert_eq!(events.len(), 5);
+		assert!(
+			matches!(&events[0], AssistantStreamEvent::Run(run) if run.status == RunStatus::Queued)
+		);
+		assert!(matches!(&events[1], AssistantStreamEvent::Other { event, data }
+				if event == "thread.run.step.created" && data["id"] == "step_abc"));
+		let text: String = events.iter().map(AssistantStreamEvent::text_delta).collect();
+		assert_eq!(text, "It is sunny.");
+		let AssistantStreamEvent::Run(run) = &events[4] else { panic!("{:?}", events[4]) };
+		assert_eq!(run.status, RunStatus::Completed);
+
+		let request = &mock.requests()[1];
+		assert_eq!(request.header("OpenAI-Beta"), Some("assistants=v2"));
+		assert_eq!(
+			request.json().unwrap(),
+			serde_json::json!({"stream": true,
+				"tool_outputs": [{"tool_call_id": "call_1", "output": r#"{"forecast":"sunny"}"#}]})
+		);
+		assert_eq!(ToolOutput::text("call_1", "Sunny").output, "Sunny");
+	}
+
+	#[test]
+	fn test_run_poll_timeout() {
+		use std::time::Duration;
+
+		let mock = MockTransport::new()
+			.with_response("runs/run_abc", MockResponse::new(200, run("queued")))
+			.with_response("runs/run_abc", MockResponse::new(200, run("in_progress")));
+		let openai = new_test_mock(&mock);
+		let poll = PollConfig {
+			min_delay: Duration::from_millis(1),
+			max_delay: Duration::from_millis(4),
+			timeout: Some(Duration::from_millis(50)),
+		};
+		let rs = openai.run_poll("thread_abc", "run_abc", &poll);
+		assert!(matches!(rs, Err(Error::Timeout(status)) if status == "in_progress"));
+		assert!(mock.requests().len() > 2);
+	}
+}
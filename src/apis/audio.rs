@@ -3,26 +3,44 @@
 //! Audio API
 
 use std::fs::File;
+use std::io::{self, BufReader, Read};
+use std::path::Path;
 
-use crate::multipart::Multipart;
+use mime::Mime;
+
+use crate::multipart::{mimes, Multipart};
 use serde::{Deserialize, Serialize};
 
+use crate::models::ModelName;
 use crate::requests::Requests;
+use crate::stream::Stream;
 use crate::*;
 
-use super::{AUDIO_TRANSCRIPTION_CREATE, AUDIO_TRANSLATIONS_CREATE};
+use super::{AUDIO_SPEECH_CREATE, AUDIO_TRANSCRIPTION_CREATE, AUDIO_TRANSLATIONS_CREATE};
+
+/// The largest audio file the API accepts, in bytes.
+pub const MAX_AUDIO_FILE_SIZE: u64 = 25 * 1024 * 1024;
 
 #[derive(Debug)]
-pub struct AudioBody {
+pub struct AudioBody<R: Read = File> {
 	/// The audio file to transcribe,
 	/// in one of these formats: mp3, mp4, mpeg, mpga, m4a, wav, or webm.
-	pub file: File,
+	pub file: R,
+	/// The filename sent with `file`, whose extension tells the API the audio format,
+	/// and gives the content type of the file, see `audio_mime`.
+	/// Defaults to audio.mp3
+	pub filename: Option<String>,
+	/// The length of `file` in bytes, if known, to reject files larger than
+	/// `MAX_AUDIO_FILE_SIZE` before uploading them. Unknown lengths are left to the API.
+	pub file_len: Option<u64>,
 	/// ID of the model to use. Only whisper-1 is currently available.
-	pub model: String,
+	pub model: ModelName,
 	/// An optional text to guide the model's style or continue a previous audio segment.
 	/// The prompt should match the audio language.
 	pub prompt: Option<String>,
 	/// The format of the transcript output, in one of these options: json, text, srt, verbose_json, or vtt.
+	/// For formats other than json and verbose_json, e.g. text, srt and vtt, the whole response
+	/// body is returned in `Audio::text`.
 	pub response_format: Option<String>,
 	/// The sampling temperature, between 0 and 1.
 	/// Higher values like 0.8 will make the output more random,
@@ -32,77 +50,353 @@ pub struct AudioBody {
 	/// The language of the input audio. Supplying the input language in ISO-639-1 format will improve accuracy and latency.
 	/// ISO-639-1: https://en.wikipedia.org/wiki/List_of_ISO_639-1_codes
 	pub language: Option<String>,
+	/// The timestamp granularities to populate for this transcription, word and/or segment.
+	/// response_format must be set to verbose_json to use timestamp granularities.
+	pub timestamp_granularities: Option<Vec<String>>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Audio {
 	pub text: Option<String>,
+	/// The detected language, only returned for verbose_json.
+	pub language: Option<String>,
+	/// The duration of the input audio in seconds, only returned for verbose_json.
+	pub duration: Option<f64>,
+	/// Extracted words and their timestamps, only returned for the word timestamp granularity.
+	pub words: Option<Vec<Word>>,
+	/// Segments of the transcribed text and their details,
+	/// returned for verbose_json unless only the word timestamp granularity is requested.
+	pub segments: Option<Vec<Segment>>,
+}
+
+impl Audio {
+	/// The segments as a SubRip subtitle file, which avoids requesting the srt format
+	/// separately. Empty without segments.
+	pub fn to_srt(&self) -> String {
+		let mut srt = String::new();
+		for (index, segment) in self.segments.iter().flatten().enumerate() {
+			let (start, end) = segment.timestamps(',');
+			srt.push_str(&format!("{}\n{start} --> {end}\n{}\n\n", index + 1, segment.cue_text()));
+		}
+		srt
+	}
+
+	/// The segments as a WebVTT subtitle file, which avoids requesting the vtt format
+	/// separately. Only the header without segments.
+	pub fn to_vtt(&self) -> String {
+		let mut vtt = String::from("WEBVTT\n\n");
+		for segment in self.segments.iter().flatten() {
+			let (start, end) = segment.timestamps('.');
+			vtt.push_str(&format!("{start} --> {end}\n{}\n\n", segment.cue_text()));
+		}
+		vtt
+	}
+}
+
+/// An event of `audio_transcription_stream`, either text added to the transcript or the
+/// complete transcript.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TranscriptionEvent {
+	/// Either transcript.text.delta or transcript.text.done.
+	#[serde(rename = "type")]
+	pub kind: String,
+	/// The text added to the transcript, for deltas.
+	pub delta: Option<String>,
+	/// The complete transcript, for the final event.
+	pub text: Option<String>,
+}
+
+impl TranscriptionEvent {
+	/// Whether this is the complete transcript, which ends the stream.
+	pub fn is_final(&self) -> bool {
+		self.kind == TRANSCRIPT_DONE
+	}
+}
+
+const TRANSCRIPT_DONE: &str = "transcript.text.done";
+
+pub type TranscriptionStream = Stream<TranscriptionEvent>;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Word {
+	pub word: String,
+	/// Start time of the word in seconds.
+	pub start: f64,
+	/// End time of the word in seconds.
+	pub end: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Segment {
+	pub id: u32,
+	/// Seek offset of the segment.
+	pub seek: Option<u32>,
+	/// Start time of the segment in seconds.
+	pub start: f64,
+	/// End time of the segment in seconds.
+	pub end: f64,
+	pub text: String,
+	/// Array of token IDs for the text content.
+	pub tokens: Option<Vec<u32>>,
+	/// Temperature parameter used for generating the segment.
+	pub temperature: Option<f64>,
+	/// Average logprob of the segment. If the value is lower than -1, consider the logprobs failed.
+	pub avg_logprob: Option<f64>,
+	/// Compression ratio of the segment. If the value is greater than 2.4,
+	/// consider the compression failed.
+	pub compression_ratio: Option<f64>,
+	/// Probability of no speech in the segment. If the value is higher than 1.0
+	/// and the avg_logprob is below -1, consider this segment silent.
+	pub no_speech_prob: Option<f64>,
+}
+
+impl Segment {
+	/// The start and end of the segment as `HH:MM:SS{separator}mmm`, hours having more digits
+	/// past 99 hours. An end before the start is moved to the start.
+	fn timestamps(&self, separator: char) -> (String, String) {
+		let millis = |seconds: f64| (seconds.max(0.0) * 1000.0).round() as u64;
+		let format = |millis: u64| {
+			let (seconds, millis) = (millis / 1000, millis % 1000);
+			let (hours, minutes, seconds) = (seconds / 3600, seconds / 60 % 60, seconds % 60);
+			format!("{hours:02}:{minutes:02}:{seconds:02}{separator}{millis:03}")
+		};
+		let start = millis(self.start);
+		(format(start), format(millis(self.end).max(start)))
+	}
+
+	/// The text without the blank lines that would end its cue.
+	fn cue_text(&self) -> String {
+		let lines: Vec<_> =
+			self.text.lines().map(str::trim).filter(|line| !line.is_empty()).collect();
+		lines.join("\n")
+	}
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SpeechBody {
+	/// One of the available TTS models: tts-1 or tts-1-hd.
+	pub model: ModelName,
+	/// The text to generate audio for. The maximum length is 4096 characters.
+	pub input: String,
+	/// The voice to use when generating the audio,
+	/// e.g. alloy, echo, fable, onyx, nova or shimmer.
+	pub voice: String,
+	/// The format to audio in. Supported formats are mp3, opus, aac, flac, wav, and pcm,
+	/// whose samples can be read with `PcmSamples`.
+	/// Defaults to mp3
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub response_format: Option<String>,
+	/// The speed of the generated audio. Select a value from 0.25 to 4.0.
+	/// Defaults to 1
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub speed: Option<f32>,
+}
+
+/// The content type of an audio file, from its extension. The formats accepted by the API
+/// are mapped to the types it expects, which `mime_guess` doesn't always return, e.g. for m4a.
+/// Other extensions fall back to `mime_guess`, and to `application/octet-stream`.
+pub fn audio_mime(path: impl AsRef<Path>) -> Mime {
+	let path = path.as_ref();
+	let extension = path.extension().and_then(|e| e.to_str()).map(str::to_ascii_lowercase);
+	match extension.as_deref() {
+		Some("mp3" | "mpeg" | "mpga") => mimes::audio_mpeg(),
+		Some("mp4" | "m4a") => mimes::audio_mp4(),
+		Some("wav") => mimes::audio_wav(),
+		Some("webm") => mimes::audio_webm(),
+		_ => mime_guess::from_path(path).first_or_octet_stream(),
+	}
+}
+
+/// The sample rate of speech generated in the pcm format, in Hz.
+pub const PCM_SAMPLE_RATE: u32 = 24_000;
+
+/// The samples of speech generated in the pcm format, i.e. mono signed 16-bit little-endian
+/// samples at `PCM_SAMPLE_RATE` without any header, read from the reader returned by
+/// `audio_speech_create`. Samples split across reads are reassembled, a trailing incomplete
+/// sample is an `UnexpectedEof` error.
+pub struct PcmSamples<R> {
+	reader: BufReader<R>,
+}
+
+impl<R: Read> PcmSamples<R> {
+	pub fn new(reader: R) -> Self {
+		Self { reader: BufReader::new(reader) }
+	}
+
+	/// The samples as `f32` between -1.0 and 1.0, as expected by most audio APIs.
+	pub fn normalized(self) -> impl Iterator<Item = io::Result<f32>> {
+		self.map(|sample| sample.map(|sample| sample as f32 / 32768.0))
+	}
+}
+
+impl<R: Read> Iterator for PcmSamples<R> {
+	type Item = io::Result<i16>;
+
+	fn next(&mut self) -> Option<Self::Item> {
+		let mut bytes = [0; 2];
+		let mut read = 0;
+		while read < 2 {
+			match self.reader.read(&mut bytes[read..]) {
+				Ok(0) if read == 0 => return None,
+				Ok(0) => return Some(Err(io::ErrorKind::UnexpectedEof.into())),
+				Ok(n) => read += n,
+				Err(e) if e.kind() == io::ErrorKind::Interrupted => {},
+				Err(e) => return Some(Err(e)),
+			}
+		}
+		Some(Ok(i16::from_le_bytes(bytes)))
+	}
 }
 
 pub trait AudioApi {
 	/// Transcribes audio into the input language.
-	fn audio_transcription_create(&self, audio_body: AudioBody) -> ApiResult<Audio>;
+	fn audio_transcription_create<R: Read>(&self, audio_body: AudioBody<R>) -> ApiResult<Audio>;
+	/// Same as `audio_transcription_create`, streaming the transcript as it is generated,
+	/// followed by the complete transcript. Only supported by the newer models, e.g.
+	/// gpt-4o-transcribe, not by whisper-1.
+	fn audio_transcription_stream<R: Read>(
+		&self,
+		audio_body: AudioBody<R>,
+	) -> ApiResult<TranscriptionStream>;
 	/// Translates audio into into English.
-	fn audio_translation_create(&self, audio_body: AudioBody) -> ApiResult<Audio>;
+	/// `language` and `timestamp_granularities` only apply to transcriptions and are not sent.
+	fn audio_translation_create<R: Read>(&self, audio_body: AudioBody<R>) -> ApiResult<Audio>;
+	/// The transcript of the audio file at `path`, transcribed with `model` in the json format.
+	/// The content type is given by the extension of the file, see `audio_mime`, and files
+	/// larger than `MAX_AUDIO_FILE_SIZE` are rejected before uploading them. Fails with
+	/// `Error::RequestError` if the file can't be opened.
+	fn transcribe_file(&self, path: impl AsRef<Path>, model: &str) -> ApiResult<String>;
+	/// Generates audio from the input text.
+	/// The audio is read from the connection as the returned reader is read.
+	fn audio_speech_create(
+		&self,
+		speech_body: &SpeechBody,
+	) -> ApiResult<Box<dyn Read + Send + Sync>>;
 }
 
 impl AudioApi for OpenAI {
-	fn audio_transcription_create(&self, audio_body: AudioBody) -> ApiResult<Audio> {
-		let mut send_data = Multipart::new();
+	fn audio_transcription_create<R: Read>(&self, audio_body: AudioBody<R>) -> ApiResult<Audio> {
+		self.audio_create(AUDIO_TRANSCRIPTION_CREATE, audio_body)
+	}
 
-		send_data.add_text("model", audio_body.model);
-		if let Some(prompt) = audio_body.prompt {
-			send_data.add_text("prompt", prompt);
-		}
-		if let Some(response_format) = audio_body.response_format {
-			send_data.add_text("response_format", response_format);
-		}
-		if let Some(temperature) = audio_body.temperature {
-			send_data.add_text("temperature", temperature.to_string());
-		}
-		if let Some(language) = audio_body.language {
-			send_data.add_text("language", language);
-		}
+	fn audio_transcription_stream<R: Read>(
+		&self,
+		audio_body: AudioBody<R>,
+	) -> ApiResult<TranscriptionStream> {
+		let mut send_data = audio_multipart(AUDIO_TRANSCRIPTION_CREATE, audio_body)?;
+		send_data.add_text("stream", "true");
+		let reader = self.post_multipart_stream(AUDIO_TRANSCRIPTION_CREATE, send_data)?;
+		Ok(Stream::new(reader).ending_with(TRANSCRIPT_DONE))
+	}
 
-		send_data.add_stream("file", audio_body.file, Some("mp3"), None);
+	fn audio_translation_create<R: Read>(&self, audio_body: AudioBody<R>) -> ApiResult<Audio> {
+		self.audio_create(AUDIO_TRANSLATIONS_CREATE, audio_body)
+	}
 
-		let res = self.post_multipart(AUDIO_TRANSCRIPTION_CREATE, send_data)?;
-		let audio: Audio = serde_json::from_value(res.clone()).unwrap();
-		Ok(audio)
+	fn transcribe_file(&self, path: impl AsRef<Path>, model: &str) -> ApiResult<String> {
+		let path = path.as_ref();
+		let open_error = |e: io::Error| Error::RequestError(format!("{}: {e}", path.display()));
+		let file = File::open(path).map_err(open_error)?;
+		let file_len = file.metadata().map_err(open_error)?.len();
+		let audio_body = AudioBody {
+			file,
+			filename: path.file_name().map(|name| name.to_string_lossy().into_owned()),
+			file_len: Some(file_len),
+			model: model.into(),
+			prompt: None,
+			response_format: Some("json".to_string()),
+			temperature: None,
+			language: None,
+			timestamp_granularities: None,
+		};
+		let audio = self.audio_transcription_create(audio_body)?;
+		audio.text.ok_or_else(|| Error::ApiError("No text".to_string()))
 	}
 
-	fn audio_translation_create(&self, audio_body: AudioBody) -> ApiResult<Audio> {
-		let mut send_data = Multipart::new();
+	fn audio_speech_create(
+		&self,
+		speech_body: &SpeechBody,
+	) -> ApiResult<Box<dyn Read + Send + Sync>> {
+		let request_body = serde_json::to_value(speech_body).unwrap();
+		self.post_raw(AUDIO_SPEECH_CREATE, request_body)
+	}
+}
 
-		send_data.add_text("model", audio_body.model);
-		if let Some(prompt) = audio_body.prompt {
-			send_data.add_text("prompt", prompt);
-		}
-		if let Some(response_format) = audio_body.response_format {
-			send_data.add_text("response_format", response_format);
-		}
-		if let Some(temperature) = audio_body.temperature {
-			send_data.add_text("temperature", temperature.to_string());
+impl OpenAI {
+	fn audio_create<R: Read>(&self, sub_url: &str, audio_body: AudioBody<R>) -> ApiResult<Audio> {
+		// Formats added to the API after this version are returned as text too.
+		let is_text =
+			!matches!(audio_body.response_format.as_deref(), None | Some("json" | "verbose_json"));
+		let send_data = audio_multipart(sub_url, audio_body)?;
+		if is_text {
+			let text = self.post_multipart_text(sub_url, send_data)?;
+			return Ok(Audio {
+				text: Some(text),
+				language: None,
+				duration: None,
+				words: None,
+				segments: None,
+			});
 		}
+		let res = self.post_multipart(sub_url, send_data)?;
+		let audio: Audio = from_json(&res)?;
+		Ok(audio)
+	}
+}
+
+/// The fields of the request to `sub_url` for `audio_body`.
+fn audio_multipart<'a, R: Read + 'a>(
+	sub_url: &str,
+	audio_body: AudioBody<R>,
+) -> ApiResult<Multipart<'a>> {
+	if let Some(len) = audio_body.file_len.filter(|len| *len > MAX_AUDIO_FILE_SIZE) {
+		return Err(Error::InvalidRequest(format!(
+			"audio file of {len} bytes exceeds the limit of {MAX_AUDIO_FILE_SIZE} bytes"
+		)));
+	}
+	let mut send_data = Multipart::new();
+
+	send_data.add_text("model", String::from(audio_body.model));
+	if let Some(prompt) = audio_body.prompt {
+		send_data.add_text("prompt", prompt);
+	}
+	if let Some(response_format) = audio_body.response_format {
+		send_data.add_text("response_format", response_format);
+	}
+	if let Some(temperature) = audio_body.temperature {
+		send_data.add_text("temperature", temperature.to_string());
+	}
+	if sub_url == AUDIO_TRANSCRIPTION_CREATE {
 		if let Some(language) = audio_body.language {
 			send_data.add_text("language", language);
 		}
+		for granularity in audio_body.timestamp_granularities.into_iter().flatten() {
+			send_data.add_text("timestamp_granularities[]", granularity);
+		}
+	}
 
-		send_data.add_stream("file", audio_body.file, Some("mp3"), None);
-
-		let res = self.post_multipart(AUDIO_TRANSLATIONS_CREATE, send_data)?;
-		let audio: Audio = serde_json::from_value(res.clone()).unwrap();
-		Ok(audio)
+	let filename = audio_body.filename.unwrap_or_else(|| "audio.mp3".to_string());
+	let mime = Some(audio_mime(&filename));
+	match audio_body.file_len {
+		Some(len) => send_data.add_sized_stream("file", audio_body.file, len, Some(filename), mime),
+		None => send_data.add_stream("file", audio_body.file, Some(filename), mime),
 	}
+	Ok(send_data)
 }
 
 #[cfg(test)]
 mod tests {
 	use std::fs::File;
+	use std::io::Read;
 
 	use crate::{
-		apis::audio::{AudioApi, AudioBody},
-		openai::new_test_openai,
+		apis::audio::{
+			audio_mime, Audio, AudioApi, AudioBody, PcmSamples, SpeechBody, MAX_AUDIO_FILE_SIZE,
+		},
+		openai::{
+			new_test_mock, new_test_openai, new_test_response, new_test_server, Auth, OpenAI,
+		},
+		Error,
 	};
 
 	#[test]
@@ -111,11 +405,14 @@ mod tests {
 		let file = File::open("test_files/audio.mp3").unwrap();
 		let multipart = AudioBody {
 			file,
-			model: "whisper-1".to_string(),
+			filename: None,
+			file_len: None,
+			model: "whisper-1".into(),
 			prompt: None,
 			response_format: None,
 			temperature: None,
 			language: Some("zh".to_string()),
+			timestamp_granularities: None,
 		};
 		let rs = openai.audio_transcription_create(multipart);
 		let audio = rs.unwrap();
@@ -129,15 +426,317 @@ mod tests {
 		let file = File::open("test_files/audio.mp3").unwrap();
 		let multipart = AudioBody {
 			file,
-			model: "whisper-1".to_string(),
+			filename: None,
+			file_len: None,
+			model: "whisper-1".into(),
 			prompt: None,
 			response_format: None,
 			temperature: None,
 			language: None,
+			timestamp_granularities: None,
 		};
 		let rs = openai.audio_translation_create(multipart);
 		let audio = rs.unwrap();
 		let text = audio.text.unwrap();
 		assert!(text.contains("thousands of miles"));
 	}
+
+	#[test]
+	fn test_audio_response_format() {
+		let srt = "1\n00:00:00,000 --> 00:00:01,000\nHello\n";
+		let (url, server) = new_test_server(vec![
+			new_test_response(200, &[], srt),
+			new_test_response(200, &[], "[0] Hello"),
+			new_test_response(
+				200,
+				&[],
+				r#"{"text": "Hello", "language": "english", "duration": 1.0}"#,
+			),
+		]);
+		let openai = OpenAI::new(Auth::new("sk-test"), &url);
+		let body = |response_format: &str| AudioBody {
+			file: &b"ID3"[..],
+			filename: Some("hello.wav".to_string()),
+			file_len: None,
+			model: "whisper-1".into(),
+			prompt: None,
+			response_format: Some(response_format.to_string()),
+			temperature: None,
+			language: None,
+			timestamp_granularities: None,
+		};
+
+		let audio = openai.audio_transcription_create(body("srt")).unwrap();
+		assert_eq!(audio.text.as_deref(), Some(srt));
+		let audio = openai.audio_transcription_create(body("new_format")).unwrap();
+		assert_eq!(audio.text.as_deref(), Some("[0] Hello"));
+		let audio = openai.audio_transcription_create(body("verbose_json")).unwrap();
+		assert_eq!(audio.text.as_deref(), Some("Hello"));
+		assert_eq!(audio.language.as_deref(), Some("english"));
+		assert_eq!(audio.duration, Some(1.0));
+
+		let requests = server.join().unwrap();
+		assert!(requests[0].starts_with("POST /v1/audio/transcriptions "));
+		assert!(requests[0].contains("filename=\"hello.wav\"\r\nContent-Type: audio/wav\r\n"));
+		assert!(requests[1].contains("\r\n\r\nnew_format\r\n"));
+		assert!(requests[2].contains("verbose_json"));
+	}
+
+	#[test]
+	fn test_audio_timestamp_granularities() {
+		let verbose = r#"{
+			"text": "Hello world",
+			"words": [{"word": "Hello", "start": 0.0, "end": 0.5}, {"word": "world", "start": 0.5, "end": 1.0}],
+			"segments": [{"id": 0, "seek": 0, "start": 0.0, "end": 1.0, "text": "Hello world", "tokens": [1, 2],
+				"temperature": 0.0, "avg_logprob": -0.2, "compression_ratio": 0.8, "no_speech_prob": 0.01}]
+		}"#;
+		let (url, server) = new_test_server(vec![
+			new_test_response(200, &[], verbose),
+			new_test_response(200, &[], r#"{"text": "Hello world"}"#),
+		]);
+		let openai = OpenAI::new(Auth::new("sk-test"), &url);
+		let body = |timestamp_granularities| AudioBody {
+			file: &b"ID3"[..],
+			filename: None,
+			file_len: None,
+			model: "whisper-1".into(),
+			prompt: None,
+			response_format: Some("verbose_json".to_string()),
+			temperature: None,
+			language: None,
+			timestamp_granularities,
+		};
+
+		let granularities = vec!["word".to_string(), "segment".to_string()];
+		let audio = openai.audio_transcription_create(body(Some(granularities))).unwrap();
+		let words = audio.words.unwrap();
+		assert_eq!(words[1].word, "world");
+		assert_eq!(words[1].start, 0.5);
+		let segments = audio.segments.unwrap();
+		assert_eq!(segments[0].text, "Hello world");
+		assert_eq!(segments[0].tokens, Some(vec![1, 2]));
+
+		let audio = openai.audio_transcription_create(body(None)).unwrap();
+		assert!(audio.words.is_none() && audio.segments.is_none());
+
+		let requests = server.join().unwrap();
+		let field = "name=\"timestamp_granularities[]\"";
+		assert_eq!(requests[0].matches(field).count(), 2);
+		assert!(!requests[1].contains(field));
+	}
+
+	#[test]
+	fn test_audio_subtitles() {
+		let segment = |start: f64, end: f64, text: &str| serde_json::json!({"id": 0, "start": start, "end": end, "text": text});
+		let json = serde_json::json!({
+			"text": "Hello world. Again. Bye.",
+			"segments": [
+				segment(0.0, 1.5, " Hello world."),
+				segment(2.0, 2.0, " Again."),
+				segment(3725.0004, 3726.9996, " Bye.\n\nSee you."),
+			],
+		});
+		let audio: Audio = serde_json::from_value(json).unwrap();
+		assert_eq!(
+			audio.to_srt(),
+			"1\n00:00:00,000 --> 00:00:01,500\nHello world.\n\n\
+			2\n00:00:02,000 --> 00:00:02,000\nAgain.\n\n\
+			3\n01:02:05,000 --> 01:02:07,000\nBye.\nSee you.\n\n"
+		);
+		assert_eq!(
+			audio.to_vtt(),
+			"WEBVTT\n\n00:00:00.000 --> 00:00:01.500\nHello world.\n\n\
+			00:00:02.000 --> 00:00:02.000\nAgain.\n\n\
+			01:02:05.000 --> 01:02:07.000\nBye.\nSee you.\n\n"
+		);
+
+		let audio: Audio = serde_json::from_value(serde_json::json!({"text": "Hi"})).unwrap();
+		assert_eq!(audio.to_srt(), "");
+		assert_eq!(audio.to_vtt(), "WEBVTT\n\n");
+	}
+
+	#[test]
+	fn test_audio_translation_request() {
+		let vtt = "WEBVTT\n\n00:00:00.000 --> 00:00:01.000\nHello\n";
+		let (url, server) = new_test_server(vec![new_test_response(200, &[], vtt)]);
+		let openai = OpenAI::new(Auth::new("sk-test"), &url);
+		let body = AudioBody {
+			file: &b"ID3"[..],
+			filename: None,
+			file_len: None,
+			model: "whisper-1".into(),
+			prompt: Some("Greetings".to_string()),
+			response_format: Some("vtt".to_string()),
+			temperature: Some(0.5),
+			language: Some("de".to_string()),
+			timestamp_granularities: Some(vec!["word".to_string()]),
+		};
+		let audio = openai.audio_translation_create(body).unwrap();
+		assert_eq!(audio.text.as_deref(), Some(vtt));
+
+		let request = &server.join().unwrap()[0];
+		assert!(request.starts_with("POST /v1/audio/translations "));
+		for field in ["model", "prompt", "response_format", "temperature", "file"] {
+			assert!(request.contains(&format!("name=\"{field}\"")), "{field}");
+		}
+		assert!(!request.contains("name=\"language\""));
+		assert!(!request.contains("timestamp_granularities"));
+	}
+
+	#[test]
+	fn test_audio_file_size() {
+		let (url, server) = new_test_server(vec![new_test_response(200, &[], r#"{"text": "Hi"}"#)]);
+		let openai = OpenAI::new(Auth::new("sk-test"), &url);
+		let body = |file_len| AudioBody {
+			file: &b"ID3"[..],
+			filename: None,
+			file_len,
+			model: "whisper-1".into(),
+			prompt: None,
+			response_format: None,
+			temperature: None,
+			language: None,
+			timestamp_granularities: None,
+		};
+		let rs = openai.audio_transcription_create(body(Some(MAX_AUDIO_FILE_SIZE + 1)));
+		assert!(matches!(rs, Err(Error::InvalidRequest(_))));
+		let audio = openai.audio_transcription_create(body(Some(3))).unwrap();
+		assert_eq!(audio.text.as_deref(), Some("Hi"));
+
+		assert_eq!(server.join().unwrap().len(), 1);
+	}
+
+	#[test]
+	fn test_transcribe_file() {
+		use crate::testing::{MockResponse, MockTransport};
+
+		let mock = MockTransport::new()
+			.with_response("audio/transcriptions", MockResponse::new(200, r#"{"text": "Hello"}"#));
+		let openai = new_test_mock(&mock);
+		let text = openai.transcribe_file("test_files/audio.mp3", "whisper-1").unwrap();
+		assert_eq!(text, "Hello");
+		let request = &mock.requests()[0];
+		let body = String::from_utf8_lossy(&request.body);
+		assert!(body.contains("name=\"response_format\"\r\n\r\njson\r\n"));
+		assert!(body.contains("filename=\"audio.mp3\"\r\nContent-Type: audio/mpeg\r\n"));
+		assert_eq!(request.header("content-length"), Some(request.body.len().to_string().as_str()));
+
+		let rs = openai.transcribe_file("test_files/missing.mp3", "whisper-1");
+		assert!(matches!(rs, Err(Error::RequestError(msg)) if msg.contains("missing.mp3")));
+		assert_eq!(mock.requests().len(), 1);
+	}
+
+	#[test]
+	fn test_audio_transcription_stream() {
+		use crate::testing::{MockResponse, MockTransport};
+
+		let events = "data: {\"type\": \"transcript.text.delta\", \"delta\": \"Hello\"}\n\n\
+			data: {\"type\": \"transcript.text.delta\", \"delta\": \", world.\"}\n\n\
+			data: {\"type\": \"transcript.text.done\", \"text\": \"Hello, world.\",\
+			 \"usage\": {\"type\": \"tokens\", \"total_tokens\": 12}}\n\n";
+		let mock = MockTransport::new()
+			.with_response("audio/transcriptions", MockResponse::new(200, events));
+		let openai = new_test_mock(&mock);
+		let audio_body = AudioBody {
+			file: &b"audio"[..],
+			filename: None,
+			file_len: Some(5),
+			model: "gpt-4o-transcribe".into(),
+			prompt: None,
+			response_format: None,
+			temperature: None,
+			language: None,
+			timestamp_granularities: None,
+		};
+		let mut stream = openai.audio_transcription_stream(audio_body).unwrap();
+		let events: Vec<_> = stream.by_ref().map(Result::unwrap).collect();
+		let deltas: String = events.iter().filter_map(|event| event.delta.as_deref()).collect();
+		assert_eq!(deltas, "Hello, world.");
+		assert_eq!(events.len(), 3);
+		assert!(events[2].is_final() && !events[1].is_final());
+		assert_eq!(events[2].text.as_deref(), Some("Hello, world."));
+		assert_eq!(stream.usage().unwrap().total_tokens, Some(12));
+
+		let request = &mock.requests()[0];
+		assert_eq!(request.header("accept"), Some("text/event-stream"));
+		let body = String::from_utf8_lossy(&request.body);
+		assert!(body.contains("name=\"stream\"\r\n\r\ntrue\r\n"));
+		assert!(
+			body.contains("filename=\"audio.mp3\"\r\nContent-Type: audio/mpeg\r\n\r\naudio\r\n")
+		);
+	}
+
+	#[test]
+	fn test_audio_speech() {
+		let (url, server) = new_test_server(vec![
+			"HTTP/1.1 200 OK\r\nConnection: close\r\nContent-Type: audio/mpeg\r\n\
+			 Content-Length: 4\r\n\r\nID3\x04"
+				.to_string(),
+			new_test_response(
+				400,
+				&[],
+				r#"{"error": {"message": "Invalid voice", "type": "invalid_request_error",
+					"param": "voice", "code": null}}"#,
+			),
+		]);
+		let openai = OpenAI::new(Auth::new("sk-test"), &url);
+		let body = SpeechBody {
+			model: "tts-1".into(),
+			input: "Hello".to_string(),
+			voice: "alloy".to_string(),
+			response_format: Some("mp3".to_string()),
+			speed: None,
+		};
+		let mut audio = Vec::new();
+		openai.audio_speech_create(&body).unwrap().read_to_end(&mut audio).unwrap();
+		assert_eq!(audio, b"ID3\x04");
+
+		let body = SpeechBody { voice: "robot".to_string(), ..body };
+		let rs = openai.audio_speech_create(&body);
+		assert!(matches!(rs, Err(Error::Api(err)) if err.param.as_deref() == Some("voice")));
+
+		let request = &server.join().unwrap()[0];
+		assert!(request.starts_with("POST /v1/audio/speech "));
+		assert!(request.ends_with(
+			r#"{"input":"Hello","model":"tts-1","response_format":"mp3","voice":"alloy"}"#
+		));
+	}
+
+	#[test]
+	fn test_pcm_samples() {
+		/// Yields one byte per read, so that every sample is split across reads.
+		struct OneByte<'a>(&'a [u8]);
+
+		impl Read for OneByte<'_> {
+			fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+				let Some((&byte, rest)) = self.0.split_first() else { return Ok(0) };
+				buf[0] = byte;
+				self.0 = rest;
+				Ok(1)
+			}
+		}
+
+		let bytes = [0x00, 0x00, 0xff, 0x7f, 0x00, 0x80, 0x01];
+		let mut samples = PcmSamples::new(OneByte(&bytes));
+		assert_eq!(samples.next().unwrap().unwrap(), 0);
+		assert_eq!(samples.next().unwrap().unwrap(), i16::MAX);
+		assert_eq!(samples.next().unwrap().unwrap(), i16::MIN);
+		let err = samples.next().unwrap().unwrap_err();
+		assert_eq!(err.kind(), std::io::ErrorKind::UnexpectedEof);
+		assert!(samples.next().is_none());
+
+		let samples: Vec<f32> =
+			PcmSamples::new(&bytes[..6]).normalized().collect::<Result<_, _>>().unwrap();
+		assert_eq!(samples, [0.0, 32767.0 / 32768.0, -1.0]);
+	}
+
+	#[test]
+	fn test_audio_mime() {
+		assert_eq!(audio_mime("voice.m4a"), "audio/mp4");
+		assert_eq!(audio_mime("voice.MP3"), "audio/mpeg");
+		assert_eq!(audio_mime("dir/voice.mpga"), "audio/mpeg");
+		assert_eq!(audio_mime("voice.webm"), "audio/webm");
+		assert_eq!(audio_mime("voice.ogg"), "audio/ogg");
+		assert_eq!(audio_mime("voice"), mime::APPLICATION_OCTET_STREAM);
+	}
 }
@@ -0,0 +1,411 @@
+// Create large batches of API requests for asynchronous processing.
+// The Batch API returns completions within 24 hours for a 50% discount.
+// See: https://platform.openai.com/docs/api-reference/batch
+
+//! Batch API
+//!
+//! The input is a JSONL file uploaded with the `batch` purpose, see `chat_batch_input`,
+//! and the output is downloaded with `FilesApi::file_content` and read with `batch_output`.
+
+use std::collections::{HashMap, HashSet};
+use std::io::{BufRead, BufReader, Read, Write};
+
+use serde::{Deserialize, Serialize};
+
+use crate::requests::Requests;
+use crate::*;
+
+use super::{
+	chat::ChatBody, ListParams, Paginated, Paginator, PollConfig, BATCHES, BATCHES_RETRIEVE,
+};
+
+/// The maximum page size of the list endpoint.
+const BATCHES_MAX_LIMIT: u32 = 100;
+/// The statuses of batches that won't change anymore.
+const BATCH_TERMINAL_STATUSES: [&str; 4] = ["completed", "failed", "expired", "cancelled"];
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BatchBody {
+	/// The ID of an uploaded file that contains requests for the new batch.
+	pub input_file_id: String,
+	/// The endpoint to be used for all requests in the batch, e.g. /v1/chat/completions.
+	pub endpoint: String,
+	/// The time frame within which the batch should be processed. Currently only 24h is supported.
+	pub completion_window: String,
+	/// Optional custom metadata for the batch.
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub metadata: Option<HashMap<String, String>>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Batch {
+	pub id: String,
+	pub object: Option<String>,
+	pub endpoint: String,
+	/// The errors of a batch that failed validation.
+	pub errors: Option<Json>,
+	pub input_file_id: String,
+	pub completion_window: String,
+	/// One of validating, failed, in_progress, finalizing, completed, expired, cancelling
+	/// or cancelled.
+	pub status: String,
+	/// The ID of the file containing the outputs of successfully executed requests.
+	pub output_file_id: Option<String>,
+	/// The ID of the file containing the outputs of requests with errors.
+	pub error_file_id: Option<String>,
+	/// The Unix timestamp (in seconds) for when the batch was created.
+	pub created_at: u64,
+	pub completed_at: Option<u64>,
+	pub request_counts: Option<RequestCounts>,
+	pub metadata: Option<HashMap<String, String>>,
+}
+
+/// The request counts for different statuses within the batch.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RequestCounts {
+	pub total: u32,
+	pub completed: u32,
+	pub failed: u32,
+}
+
+/// A line of a batch input file.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BatchRequest<B> {
+	/// A developer-provided ID, matching the output line with its request.
+	pub custom_id: String,
+	pub method: String,
+	pub url: String,
+	pub body: B,
+}
+
+/// A line of a batch output or error file.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BatchOutput {
+	pub id: String,
+	pub custom_id: String,
+	pub response: Option<BatchResponse>,
+	pub error: Option<Json>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BatchResponse {
+	pub status_code: u16,
+	pub request_id: Option<String>,
+	/// The body the endpoint answered the request with, e.g. a `Completion`.
+	pub body: Json,
+}
+
+/// The JSONL input of a batch sending `requests` to the chat completions endpoint,
+/// each with its custom ID.
+pub fn chat_batch_input<'a>(requests: impl IntoIterator<Item = (String, &'a ChatBody)>) -> String {
+	let mut input = String::new();
+	for (custom_id, body) in requests {
+		let request = BatchRequest {
+			custom_id,
+			method: "POST".to_string(),
+			url: "/v1/chat/completions".to_string(),
+			body,
+		};
+		input.push_str(&serde_json::to_string(&request).unwrap());
+		input.push('\n');
+	}
+	input
+}
+
+/// Writes a batch input file one request per line, so that large batches don't have to be
+/// held in memory. Only the custom IDs are kept, to reject duplicates.
+///
+/// ```
+/// # use openai_api_rust::batches::BatchInputWriter;
+/// # use openai_api_rust::chat::ChatBody;
+/// let mut writer = BatchInputWriter::new(Vec::new(), "/v1/chat/completions");
+/// let body = ChatBody::builder().model("gpt-4o-mini").user("Hello!").build().unwrap();
+/// writer.write("req-1", &body).unwrap();
+/// assert!(writer.write("req-1", &body).is_err());
+/// let input = writer.into_inner().unwrap();
+/// ```
+pub struct BatchInputWriter<W: Write> {
+	writer: W,
+	url: String,
+	custom_ids: HashSet<String>,
+	line: Vec<u8>,
+}
+
+impl<W: Write> BatchInputWriter<W> {
+	/// Writes requests to `url`, the endpoint of the batch, e.g. /v1/chat/completions.
+	pub fn new(writer: W, url: &str) -> Self {
+		Self { writer, url: url.to_string(), custom_ids: HashSet::new(), line: Vec::new() }
+	}
+
+	/// Writes the line of the request `body` with its `custom_id`,
+	/// failing with `Error::InvalidRequest` if the ID was already written.
+	pub fn write<B: Serialize>(&mut self, custom_id: &str, body: &B) -> ApiResult<()> {
+		if self.custom_ids.contains(custom_id) {
+			return Err(Error::InvalidRequest(format!("duplicate custom_id {custom_id}")));
+		}
+		let request = BatchRequest {
+			custom_id: custom_id.to_string(),
+			method: "POST".to_string(),
+			url: self.url.clone(),
+			body,
+		};
+		// Serialized first, so that a body failing to serialize doesn't leave half a line.
+		self.line.clear();
+		serde_json::to_writer(&mut self.line, &request)
+			.map_err(|e| Error::InvalidRequest(e.to_string()))?;
+		self.line.push(b'\n');
+		self.writer.write_all(&self.line).map_err(|e| Error::RequestError(e.to_string()))?;
+		self.custom_ids.insert(request.custom_id);
+		Ok(())
+	}
+
+	/// The number of requests written.
+	pub fn len(&self) -> usize {
+		self.custom_ids.len()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.custom_ids.is_empty()
+	}
+
+	/// Flushes and returns the underlying writer.
+	pub fn into_inner(mut self) -> ApiResult<W> {
+		self.writer.flush().map_err(|e| Error::RequestError(e.to_string()))?;
+		Ok(self.writer)
+	}
+}
+
+/// Reads the lines of a batch output or error file.
+pub fn batch_output(reader: impl Read) -> ApiResult<Vec<BatchOutput>> {
+	let mut outputs = Vec::new();
+	for line in BufReader::new(reader).lines() {
+		let line = line.map_err(|e| Error::RequestError(e.to_string()))?;
+		if line.trim().is_empty() {
+			continue;
+		}
+		let output = serde_json::from_str(&line).map_err(|e| Error::ApiError(e.to_string()))?;
+		outputs.push(output);
+	}
+	Ok(outputs)
+}
+
+pub trait BatchesApi {
+	/// Creates and executes a batch from an uploaded file of requests.
+	fn batch_create(&self, batch_body: &BatchBody) -> ApiResult<Batch>;
+	/// Retrieves a batch.
+	fn batch_retrieve(&self, batch_id: &str) -> ApiResult<Batch>;
+	/// Lists your organization's batches.
+	fn batches_list(&self, params: &ListParams) -> ApiResult<Paginated<Batch>>;
+	/// Iterates over all your organization's batches starting from `params`,
+	/// fetching pages of the maximum size unless `params.limit` is set.
+	fn batches_iter(&self, params: ListParams) -> Paginator<'_, Batch>;
+	/// Cancels an in-progress batch. The batch will be in status cancelling for up to
+	/// 10 minutes, before changing to cancelled.
+	fn batch_cancel(&self, batch_id: &str) -> ApiResult<Batch>;
+	/// Retrieves the batch until it is completed, failed, expired or cancelled, with the delays
+	/// and the timeout of `poll`. Fails with `Error::Timeout` and the last status once the
+	/// timeout elapses. Batches take up to their 24 hour completion window, so poll with long
+	/// delays, e.g. a `min_delay` of a minute.
+	fn batch_poll(&self, batch_id: &str, poll: &PollConfig) -> ApiResult<Batch>;
+}
+
+impl BatchesApi for OpenAI {
+	fn batch_create(&self, batch_body: &BatchBody) -> ApiResult<Batch> {
+		let request_body = serde_json::to_value(batch_body).unwrap();
+		let res = self.post(BATCHES, request_body)?;
+		let batch: Batch = from_json(&res)?;
+		Ok(batch)
+	}
+
+	fn batch_retrieve(&self, batch_id: &str) -> ApiResult<Batch> {
+		let res = self.get(&(BATCHES_RETRIEVE.to_owned() + batch_id))?;
+		let batch: Batch = from_json(&res)?;
+		Ok(batch)
+	}
+
+	fn batches_list(&self, params: &ListParams) -> ApiResult<Paginated<Batch>> {
+		let res = self.get(&params.apply(BATCHES))?;
+		let batches: Paginated<Batch> = from_json(&res)?;
+		Ok(batches)
+	}
+
+	fn batches_iter(&self, params: ListParams) -> Paginator<'_, Batch> {
+		Paginator::new(
+			params,
+			BATCHES_MAX_LIMIT,
+			|batch| batch.id.clone(),
+			|params| self.batches_list(params),
+		)
+	}
+
+	fn batch_cancel(&self, batch_id: &str) -> ApiResult<Batch> {
+		let sub_url = BATCHES_RETRIEVE.to_owned() + batch_id + "/cancel";
+		let res = self.post(&sub_url, serde_json::json!({}))?;
+		let batch: Batch = from_json(&res)?;
+		Ok(batch)
+	}
+
+	fn batch_poll(&self, batch_id: &str, poll: &PollConfig) -> ApiResult<Batch> {
+		poll.poll(
+			|| self.batch_retrieve(batch_id),
+			|batch| {
+				let terminal = BATCH_TERMINAL_STATUSES.contains(&batch.status.as_str());
+				(!terminal).then(|| batch.status.clone())
+			},
+		)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::{
+		apis::batches::{batch_output, chat_batch_input, BatchBody, BatchInputWriter, BatchesApi},
+		apis::files::{FilesApi, FilesBody},
+		chat::ChatBody,
+		openai::new_test_mock,
+		testing::{MockResponse, MockTransport},
+		Error, Message, PollConfig,
+	};
+
+	fn batch(status: &str, output_file_id: &str) -> String {
+		format!(
+			r#"{{"id": "batch_abc", "object": "batch", "endpoint": "/v1/chat/completions",
+			"errors": null, "input_file_id": "file-in", "completion_window": "24h",
+			"status": "{status}", "output_file_id": {output_file_id}, "error_file_id": null,
+			"created_at": 1700000000, "completed_at": null,
+			"request_counts": {{"total": 2, "completed": 2, "failed": 0}}, "metadata": null}}"#
+		)
+	}
+
+	#[test]
+	fn test_batches() {
+		let output = concat!(
+			r#"{"id": "batch_req_1", "custom_id": "req-1", "response": {"status_code": 200, "#,
+			r#""request_id": "req_abc", "body": {"id": "chatcmpl-1"}}, "error": null}"#,
+			"\n",
+			r#"{"id": "batch_req_2", "custom_id": "req-2", "response": null, "error": {}}"#,
+			"\n",
+		);
+		let mock = MockTransport::new()
+			.with_response(
+				"files",
+				MockResponse::new(
+					200,
+					r#"{"id": "file-in", "bytes": 1, "created_at": 1700000000,
+					"filename": "batch.jsonl", "purpose": "batch"}"#,
+				),
+			)
+			.with_response("batches", MockResponse::new(200, batch("validating", "null")))
+			.with_response(
+				"batches/batch_abc",
+				MockResponse::new(200, batch("completed", r#""file-out""#)),
+			)
+			.with_response(
+				"batches/batch_abc/cancel",
+				MockResponse::new(200, batch("cancelling", "null")),
+			)
+			.with_response("files/file-out/content", MockResponse::new(200, output));
+		let openai = new_test_mock(&mock);
+
+		let bodies: Vec<ChatBody> = ["Hello!", "Bye!"]
+			.into_iter()
+			.map(|text| ChatBody {
+				model: "gpt-4o-mini".into(),
+				messages: vec![Message::user(text)],
+				..Default::default()
+			})
+			.collect();
+		let input = chat_batch_input(
+			bodies.iter().enumerate().map(|(i, body)| (format!("req-{}", i + 1), body)),
+		);
+		let lines: Vec<serde_json::Value> =
+			input.lines().map(|line| serde_json::from_str(line).unwrap()).collect();
+		assert_eq!(lines.len(), 2);
+		assert_eq!(lines[1]["custom_id"], "req-2");
+		assert_eq!(lines[1]["url"], "/v1/chat/completions");
+		assert_eq!(lines[1]["body"]["messages"][0]["content"], "Bye!");
+
+		let file = openai
+			.file_upload(FilesBody {
+				file: input.as_bytes(),
+				filename: "batch.jsonl".to_string(),
+				purpose: "batch".to_string(),
+			})
+			.unwrap();
+		let body = BatchBody {
+			input_file_id: file.id,
+			endpoint: "/v1/chat/completions".to_string(),
+			completion_window: "24h".to_string(),
+			metadata: None,
+		};
+		assert_eq!(openai.batch_create(&body).unwrap().status, "validating");
+		let batch = openai.batch_retrieve("batch_abc").unwrap();
+		assert_eq!(batch.request_counts.unwrap().completed, 2);
+		let outputs =
+			batch_output(openai.file_content(&batch.output_file_id.unwrap()).unwrap()).unwrap();
+		assert_eq!(outputs[0].custom_id, "req-1");
+		assert_eq!(outputs[0].response.as_ref().unwrap().body["id"], "chatcmpl-1");
+		assert!(outputs[1].error.is_some());
+		assert_eq!(openai.batch_cancel("batch_abc").unwrap().status, "cancelling");
+
+		let requests = mock.requests();
+		assert!(String::from_utf8_lossy(&requests[0].body).contains(r#""custom_id":"req-1""#));
+		assert_eq!(requests[1].json().unwrap()["input_file_id"], "file-in");
+		assert_eq!(requests[4].method, "POST");
+	}
+
+	#[test]
+	fn test_batch_poll() {
+		use std::time::Duration;
+
+		let mock = MockTransport::new()
+			.with_response("batches/batch_abc", MockResponse::new(200, batch("validating", "null")))
+			.with_response(
+				"batches/batch_abc",
+				MockResponse::new(200, batch("in_progress", "null")),
+			)
+			.with_response("batches/batch_abc", MockResponse::new(200, batch("finalizing", "null")))
+			.with_response("batches/batch_abc", MockResponse::new(200, batch("expired", "null")));
+		let openai = new_test_mock(&mock);
+		let poll = PollConfig {
+			min_delay: Duration::from_millis(1),
+			max_delay: Duration::from_millis(4),
+			timeout: None,
+		};
+		assert_eq!(openai.batch_poll("batch_abc", &poll).unwrap().status, "expired");
+		assert_eq!(mock.requests().len(), 4);
+
+		let mock = MockTransport::new().with_response(
+			"batches/batch_abc",
+			MockResponse::new(200, batch("in_progress", "null")),
+		);
+		let openai = openai.with_transport(mock);
+		let poll = PollConfig { timeout: Some(Duration::from_millis(20)), ..poll };
+		let rs = openai.batch_poll("batch_abc", &poll);
+		assert!(matches!(rs, Err(Error::Timeout(status)) if status == "in_progress"));
+	}
+
+	#[test]
+	fn test_batch_input_writer() {
+		let body = ChatBody::builder().model("gpt-4o-mini").user("Hello!").build().unwrap();
+		let mut writer = BatchInputWriter::new(Vec::new(), "/v1/chat/completions");
+		assert!(writer.is_empty());
+		writer.write("req-1", &body).unwrap();
+		writer.write("req-2", &serde_json::json!({"model": "gpt-4o-mini"})).unwrap();
+		assert!(matches!(writer.write("req-1", &body), Err(Error::InvalidRequest(_))));
+		assert_eq!(writer.len(), 2);
+
+		let input = String::from_utf8(writer.into_inner().unwrap()).unwrap();
+		assert!(input.ends_with("}\n"));
+		assert_eq!(
+			input,
+			chat_batch_input([("req-1".to_string(), &body)]) + input.lines().nth(1).unwrap() + "\n"
+		);
+		let line: serde_json::Value = serde_json::from_str(input.lines().nth(1).unwrap()).unwrap();
+		assert_eq!(
+			line,
+			serde_json::json!({"custom_id": "req-2", "method": "POST", "url": "/v1/chat/completions",
+				"body": {"model": "gpt-4o-mini"}})
+		);
+	}
+}
@@ -4,18 +4,29 @@
 //! Chat API
 
 use std::collections::HashMap;
+use std::io::{Read, Write};
 
+use crate::models::ModelName;
 use crate::requests::Requests;
+use crate::stream::{Stream, StreamHandle};
 use crate::*;
 use serde::{Deserialize, Serialize};
 
-use super::{completions::Completion, CHAT_COMPLETION_CREATE};
+use super::{
+	alters_temperature_and_top_p, check_metadata, check_penalty, check_sampling,
+	completions::Completion, ContentFilterResults, ListParams, Logprobs, Paginated, Paginator,
+	PromptFilterResult, ServiceTier, Stop, Usage, CHAT_COMPLETIONS_RETRIEVE,
+	CHAT_COMPLETION_CREATE,
+};
 
-#[derive(Debug, Serialize, Deserialize)]
+/// Bodies deserialize from JSON, e.g. prompt templates read with `ChatBody::from_reader`,
+/// in which every field is optional.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
 pub struct ChatBody {
 	/// ID of the model to use.
 	/// See the model endpoint compatibility table for details on which models work with the Chat API.
-	pub model: String,
+	pub model: ModelName,
 	/// The messages to generate chat completions for, in the chat format.
 	pub messages: Vec<Message>,
 	/// What sampling temperature to use, between 0 and 2.
@@ -42,15 +53,31 @@ pub struct ChatBody {
 	/// Defaults to false
 	#[serde(skip_serializing_if = "Option::is_none")]
 	pub stream: Option<bool>,
+	/// Options for streaming response. Only set this when you set stream: true.
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub stream_options: Option<StreamOptions>,
 	/// Up to 4 sequences where the API will stop generating further tokens.
 	/// Defaults to null
 	#[serde(skip_serializing_if = "Option::is_none")]
-	pub stop: Option<Vec<String>>,
+	pub stop: Option<Stop>,
 	/// The maximum number of tokens to generate in the chat completion.
 	/// The total length of input tokens and generated tokens is limited by the model's context length.
 	/// Defaults to inf
+	///
+	/// Deprecated in favor of `max_completion_tokens`, and rejected by the o-series reasoning
+	/// models. Can't be set together with `max_completion_tokens`.
 	#[serde(skip_serializing_if = "Option::is_none")]
 	pub max_tokens: Option<i32>,
+	/// An upper bound for the number of tokens that can be generated for a completion,
+	/// including visible output tokens and reasoning tokens.
+	/// Can't be set together with `max_tokens`.
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub max_completion_tokens: Option<i32>,
+	/// Constrains effort on reasoning for reasoning models. Reducing reasoning effort
+	/// can result in faster responses and fewer tokens used on reasoning in a response.
+	/// Defaults to medium
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub reasoning_effort: Option<ReasoningEffort>,
 	/// Number between -2.0 and 2.0.
 	/// Positive values penalize new tokens based on whether they appear in the text so far,
 	/// increasing the model's likelihood to talk about new topics.
@@ -72,38 +99,1036 @@ pub struct ChatBody {
 	/// values like -100 or 100 should result in a ban or exclusive selection of the relevant token.
 	/// Defaults to null
 	#[serde(skip_serializing_if = "Option::is_none")]
-	pub logit_bias: Option<HashMap<String, String>>,
+	pub logit_bias: Option<HashMap<u32, i32>>,
+	/// Whether to return log probabilities of the output tokens or not.
+	/// If true, returns the log probabilities of each output token in the content of message.
+	/// Defaults to false
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub logprobs: Option<bool>,
+	/// An integer between 0 and 20 specifying the number of most likely tokens to return
+	/// at each token position. logprobs must be set to true if this parameter is used.
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub top_logprobs: Option<u8>,
+	/// If specified, the system will make a best effort to sample deterministically,
+	/// such that repeated requests with the same seed and parameters should return the same result.
+	/// Determinism is not guaranteed, refer to the system_fingerprint response parameter
+	/// to monitor changes in the backend.
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub seed: Option<i64>,
 	/// A unique identifier representing your end-user,
 	/// which can help OpenAI to monitor and detect abuse. Learn more.
 	#[serde(skip_serializing_if = "Option::is_none")]
 	pub user: Option<String>,
+	/// A list of tools the model may call. Currently, only functions are supported as a tool.
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub tools: Option<Vec<Tool>>,
+	/// Controls which (if any) tool is called by the model.
+	/// Defaults to none when no tools are present, auto otherwise
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub tool_choice: Option<ToolChoice>,
+	/// Whether the model may call several tools in one response, false to get at most one
+	/// call at a time. Only valid along with `tools`.
+	/// Defaults to true
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub parallel_tool_calls: Option<bool>,
+	/// The processing tier of the request, the response tells which one was used.
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub service_tier: Option<ServiceTier>,
+	/// The format that the model must output.
+	/// JSON mode guarantees the message the model generates is valid JSON,
+	/// structured outputs guarantee it matches a JSON schema.
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub response_format: Option<ResponseFormat>,
+	/// Predicted Outputs, the text the completion is expected to mostly match, e.g. a file being
+	/// edited, which speeds up its generation.
+	/// See: https://platform.openai.com/docs/guides/predicted-outputs
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub prediction: Option<Prediction>,
+	/// Whether to store the completion, for model distillation or evals, and to show it in the
+	/// dashboard.
+	/// Defaults to false
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub store: Option<bool>,
+	/// Up to 16 pairs to tag stored completions with, keys of up to 64 characters and values
+	/// of up to 512.
+	#[serde(default, skip_serializing_if = "HashMap::is_empty")]
+	pub metadata: HashMap<String, String>,
+	/// The fields not modeled above, kept when the body is deserialized and sent as is, for
+	/// parameters newer than this crate.
+	#[serde(flatten)]
+	pub extra: serde_json::Map<String, Json>,
+}
+
+/// The instruction of the user message added by `ChatBody::continuation`.
+pub const CONTINUE_PROMPT: &str =
+	"Continue exactly where you left off, without repeating or introducing what you wrote.";
+
+impl ChatBody {
+	/// Checks the parameters the API would reject, failing with `Error::InvalidRequest`.
+	/// Done before sending the body. Setting both `temperature` and `top_p` only logs a warning,
+	/// unless built with `ChatBodyBuilder::strict_sampling`.
+	pub fn validate(&self) -> ApiResult<()> {
+		if self.messages.is_empty() {
+			return Err(Error::InvalidRequest("at least one message is required".to_string()));
+		}
+		// Roles are checked by `Role`, only the fields they require are left to check.
+		for (index, message) in self.messages.iter().enumerate() {
+			if message.role == Role::Tool && message.tool_call_id.is_none() {
+				return Err(Error::InvalidRequest(format!(
+					"messages[{index}]: tool messages require a tool_call_id"
+				)));
+			}
+			if let Some(Content::Parts(parts)) = &message.content {
+				for part in parts {
+					if let ContentPart::ImageUrl { image_url } = part {
+						image_url.warn_if_costly();
+					}
+				}
+			}
+		}
+		if self.parallel_tool_calls.is_some() && self.tools.as_ref().is_none_or(Vec::is_empty) {
+			return Err(Error::InvalidRequest(
+				"parallel_tool_calls is only valid along with tools".to_string(),
+			));
+		}
+		if self.max_tokens.is_some() && self.max_completion_tokens.is_some() {
+			return Err(Error::InvalidRequest(
+				"max_tokens and max_completion_tokens are mutually exclusive".to_string(),
+			));
+		}
+		check_penalty("presence_penalty", self.presence_penalty)?;
+		check_penalty("frequency_penalty", self.frequency_penalty)?;
+		check_sampling(self.temperature, self.top_p, self.n)?;
+		check_metadata(&self.metadata)?;
+		let max_output_tokens = self.max_completion_tokens.or(self.max_tokens);
+		if let (Some(tokens), Some(limit)) =
+			(max_output_tokens, output_token_limit(self.model.as_str()))
+		{
+			if tokens > limit {
+				return Err(Error::InvalidRequest(format!(
+					"{} generates at most {limit} tokens, {tokens} requested",
+					self.model
+				)));
+			}
+		}
+		if alters_temperature_and_top_p(self.temperature, self.top_p) {
+			log::warn!("Both temperature and top_p are set, OpenAI recommends altering only one");
+		}
+		Ok(())
+	}
+
+	/// A copy of this body asking the model to continue `partial`, its reply cut off by the
+	/// maximum number of tokens, see `CompletionResponse::was_truncated`. The partial reply is appended
+	/// as an assistant message, followed by a user message with `CONTINUE_PROMPT`, so the text
+	/// of the reply to the new body is appended to `partial`.
+	pub fn continuation(&self, partial: impl Into<String>) -> ChatBody {
+		let mut body = self.clone();
+		body.messages.push(Message::assistant(partial.into()));
+		body.messages.push(Message::user(CONTINUE_PROMPT));
+		body
+	}
+
+	/// Reads a body from JSON, e.g. a prompt template whose model or messages are then set
+	/// programmatically. Fails with `Error::InvalidRequest` if the JSON isn't a chat body.
+	///
+	/// ```
+	/// # use openai_api_rust::{chat::ChatBody, Message};
+	/// let template = r#"{"messages": [{"role": "system", "content": "Be concise."}],
+	///     "temperature": 0.2, "new_parameter": true}"#;
+	/// let mut body = ChatBody::from_reader(template.as_bytes()).unwrap();
+	/// body.model = "gpt-4o".into();
+	/// body.messages.push(Message::user("Hello!"));
+	/// assert_eq!(serde_json::to_value(&body).unwrap()["new_parameter"], true);
+	/// ```
+	pub fn from_reader(reader: impl Read) -> ApiResult<ChatBody> {
+		serde_json::from_reader(reader).map_err(|e| Error::InvalidRequest(e.to_string()))
+	}
+
+	pub fn builder() -> ChatBodyBuilder {
+		ChatBodyBuilder::default()
+	}
+
+	/// Turns the system messages into developer messages when `model` is an o-series
+	/// reasoning model, e.g. `o1` or `o3-mini`, and leaves the other models' messages as is.
+	pub fn use_developer_role(mut self) -> Self {
+		if is_reasoning_model(self.model.as_str()) {
+			for message in &mut self.messages {
+				if message.role == Role::System {
+					message.role = Role::Developer;
+				}
+			}
+		}
+		self
+	}
+}
+
+/// Whether `model` is an o-series reasoning model, e.g. `o1` or `o3-mini`.
+fn is_reasoning_model(model: &str) -> bool {
+	let mut chars = model.chars();
+	chars.next() == Some('o') && chars.next().is_some_and(|c| c.is_ascii_digit())
+}
+
+/// The maximum number of tokens `model` generates in a reply, reasoning tokens included,
+/// `None` if the model is unknown, see `ModelInfo::lookup`.
+pub fn output_token_limit(model: &str) -> Option<i32> {
+	let tokens = crate::model_info::ModelInfo::lookup(model)?.max_output_tokens?;
+	i32::try_from(tokens).ok()
+}
+
+/// Builds a `ChatBody`, pushing messages in the order they are given.
+///
+/// ```
+/// # use openai_api_rust::chat::ChatBody;
+/// let body = ChatBody::builder()
+///     .model("gpt-4o")
+///     .system("You are a helpful assistant.")
+///     .user("Hello!")
+///     .temperature(0.7)
+///     .build()
+///     .unwrap();
+/// assert_eq!(body.messages.len(), 2);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct ChatBodyBuilder {
+	body: ChatBody,
+	strict_sampling: bool,
+	/// The limit set by `build` in the field the model accepts, see `max_output_tokens`.
+	max_output_tokens: Option<i32>,
+	/// The model whose context window the messages are truncated to, see `truncate_to_fit`.
+	#[cfg(feature = "tokenizer")]
+	truncate_to: Option<String>,
+	/// The texts biased by `build`, see `logit_bias_text`.
+	#[cfg(feature = "tokenizer")]
+	logit_bias_text: Vec<(String, i32)>,
+}
+
+impl ChatBodyBuilder {
+	pub fn model(mut self, model: impl Into<ModelName>) -> Self {
+		self.body.model = model.into();
+		self
+	}
+
+	pub fn message(mut self, message: Message) -> Self {
+		self.body.messages.push(message);
+		self
+	}
+
+	pub fn messages(mut self, messages: impl IntoIterator<Item = Message>) -> Self {
+		self.body.messages.extend(messages);
+		self
+	}
+
+	pub fn system(self, content: impl Into<Content>) -> Self {
+		self.message(Message::system(content))
+	}
+
+	pub fn developer(self, content: impl Into<Content>) -> Self {
+		self.message(Message::developer(content))
+	}
+
+	pub fn user(self, content: impl Into<Content>) -> Self {
+		self.message(Message::user(content))
+	}
+
+	pub fn assistant(self, content: impl Into<Content>) -> Self {
+		self.message(Message::assistant(content))
+	}
+
+	/// The result of the tool call `tool_call_id`, see `Message::tool`.
+	pub fn tool(self, tool_call_id: impl Into<String>, content: impl Into<Content>) -> Self {
+		self.message(Message::tool(tool_call_id, content))
+	}
+
+	pub fn temperature(mut self, temperature: f32) -> Self {
+		self.body.temperature = Some(temperature);
+		self
+	}
+
+	pub fn top_p(mut self, top_p: f32) -> Self {
+		self.body.top_p = Some(top_p);
+		self
+	}
+
+	pub fn n(mut self, n: i32) -> Self {
+		self.body.n = Some(n);
+		self
+	}
+
+	pub fn stop(mut self, stop: impl Into<Stop>) -> Self {
+		self.body.stop = Some(stop.into());
+		self
+	}
+
+	pub fn max_tokens(mut self, max_tokens: i32) -> Self {
+		self.body.max_tokens = Some(max_tokens);
+		self
+	}
+
+	pub fn max_completion_tokens(mut self, max_completion_tokens: i32) -> Self {
+		self.body.max_completion_tokens = Some(max_completion_tokens);
+		self
+	}
+
+	/// Makes `build` limit the reply to `tokens` with the field the model of the body accepts:
+	/// `max_completion_tokens` for the o-series reasoning models, which reject `max_tokens`, and
+	/// when the model is left to the client, `max_tokens` for the other models, which
+	/// OpenAI-compatible servers support more widely. Replaces both fields, and fails in
+	/// `build` if the model is known to generate fewer tokens, see `output_token_limit`.
+	pub fn max_output_tokens(mut self, tokens: i32) -> Self {
+		self.max_output_tokens = Some(tokens);
+		self
+	}
+
+	pub fn reasoning_effort(mut self, reasoning_effort: ReasoningEffort) -> Self {
+		self.body.reasoning_effort = Some(reasoning_effort);
+		self
+	}
+
+	pub fn presence_penalty(mut self, presence_penalty: f32) -> Self {
+		self.body.presence_penalty = Some(presence_penalty);
+		self
+	}
+
+	pub fn frequency_penalty(mut self, frequency_penalty: f32) -> Self {
+		self.body.frequency_penalty = Some(frequency_penalty);
+		self
+	}
+
+	pub fn logit_bias(mut self, logit_bias: HashMap<u32, i32>) -> Self {
+		self.body.logit_bias = Some(logit_bias);
+		self
+	}
+
+	/// Makes `build` add the tokens of each text to `logit_bias`, tokenized for the model of
+	/// the body, see `tokenizer::logit_bias`. Texts of several tokens are logged as warnings.
+	#[cfg(feature = "tokenizer")]
+	pub fn logit_bias_text<S: Into<String>>(
+		mut self,
+		biases: impl IntoIterator<Item = (S, i32)>,
+	) -> Self {
+		self.logit_bias_text.extend(biases.into_iter().map(|(text, bias)| (text.into(), bias)));
+		self
+	}
+
+	pub fn seed(mut self, seed: i64) -> Self {
+		self.body.seed = Some(seed);
+		self
+	}
+
+	/// Sets `ChatBody::user`, the identifier of the end-user, as `user` pushes a message.
+	pub fn user_id(mut self, user: impl Into<String>) -> Self {
+		self.body.user = Some(user.into());
+		self
+	}
+
+	pub fn tools(mut self, tools: Vec<Tool>) -> Self {
+		self.body.tools = Some(tools);
+		self
+	}
+
+	pub fn tool_choice(mut self, tool_choice: ToolChoice) -> Self {
+		self.body.tool_choice = Some(tool_choice);
+		self
+	}
+
+	pub fn parallel_tool_calls(mut self, parallel_tool_calls: bool) -> Self {
+		self.body.parallel_tool_calls = Some(parallel_tool_calls);
+		self
+	}
+
+	pub fn service_tier(mut self, service_tier: ServiceTier) -> Self {
+		self.body.service_tier = Some(service_tier);
+		self
+	}
+
+	pub fn response_format(mut self, response_format: ResponseFormat) -> Self {
+		self.body.response_format = Some(response_format);
+		self
+	}
+
+	pub fn store(mut self, store: bool) -> Self {
+		self.body.store = Some(store);
+		self
+	}
+
+	/// Adds a `metadata` pair, replacing the value of `key` if it was already set.
+	pub fn metadata(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+		self.body.metadata.insert(key.into(), value.into());
+		self
+	}
+
+	pub fn prediction(mut self, content: impl Into<String>) -> Self {
+		self.body.prediction = Some(Prediction::content(content));
+		self
+	}
+
+	/// Makes `build` fail rather than log a warning when both `temperature` and `top_p` are set
+	/// to other values than 1.
+	pub fn strict_sampling(mut self) -> Self {
+		self.strict_sampling = true;
+		self
+	}
+
+	/// Makes `build` drop the oldest messages until the prompt fits in the context window of
+	/// `model`, minus the `max_completion_tokens` or `max_tokens` reserved for the reply, see
+	/// `tokenizer::truncate_to_fit`. Tool definitions and images aren't counted, so leave them
+	/// some room with `max_completion_tokens`.
+	#[cfg(feature = "tokenizer")]
+	pub fn truncate_to_fit(mut self, model: impl Into<String>) -> Self {
+		self.truncate_to = Some(model.into());
+		self
+	}
+
+	/// Fails with `Error::InvalidRequest` without a message, or if `ChatBody::validate` fails.
+	/// Without a model, the default chat model of the client is used, see `DefaultModels`.
+	pub fn build(self) -> ApiResult<ChatBody> {
+		if self.strict_sampling
+			&& alters_temperature_and_top_p(self.body.temperature, self.body.top_p)
+		{
+			return Err(Error::InvalidRequest(
+				"temperature and top_p should not both be altered".to_string(),
+			));
+		}
+		let mut body = self.body;
+		if let Some(tokens) = self.max_output_tokens {
+			if body.model.is_empty() || is_reasoning_model(body.model.as_str()) {
+				(body.max_tokens, body.max_completion_tokens) = (None, Some(tokens));
+			} else {
+				(body.max_tokens, body.max_completion_tokens) = (Some(tokens), None);
+			}
+		}
+		#[cfg(feature = "tokenizer")]
+		let body = add_logit_bias_text(body, &self.logit_bias_text);
+		#[cfg(feature = "tokenizer")]
+		let body = match &self.truncate_to {
+			Some(model) => truncate_to_context_window(body, model)?,
+			None => body,
+		};
+		body.validate()?;
+		Ok(body)
+	}
+}
+
+#[cfg(feature = "tokenizer")]
+fn add_logit_bias_text(mut body: ChatBody, biases: &[(String, i32)]) -> ChatBody {
+	if !biases.is_empty() {
+		let biases = biases.iter().map(|(text, bias)| (text.as_str(), *bias));
+		let logit_bias = crate::tokenizer::logit_bias(body.model.as_str(), biases);
+		body.logit_bias.get_or_insert_with(HashMap::new).extend(logit_bias);
+	}
+	body
+}
+
+#[cfg(feature = "tokenizer")]
+fn truncate_to_context_window(mut body: ChatBody, model: &str) -> ApiResult<ChatBody> {
+	let window = crate::tokenizer::context_window(model).ok_or_else(|| {
+		Error::InvalidRequest(format!("the context window of {model} is unknown"))
+	})?;
+	let reply = body.max_completion_tokens.or(body.max_tokens).unwrap_or(0).max(0);
+	let max_tokens = window.saturating_sub(reply as usize);
+	crate::tokenizer::truncate_to_fit(model, &mut body.messages, max_tokens)?;
+	Ok(body)
+}
+
+/// The expected output of a chat request, see `ChatBody::prediction`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Prediction {
+	Content { content: String },
+}
+
+impl Prediction {
+	pub fn content(content: impl Into<String>) -> Self {
+		Self::Content { content: content.into() }
+	}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReasoningEffort {
+	Low,
+	Medium,
+	High,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ResponseFormat {
+	Text,
+	/// JSON mode, the model must also be instructed to produce JSON in a message.
+	JsonObject,
+	/// Structured outputs.
+	JsonSchema {
+		json_schema: JsonSchema,
+	},
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JsonSchema {
+	/// The name of the response format.
+	/// Must be a-z, A-Z, 0-9, or contain underscores and dashes, with a maximum length of 64.
+	pub name: String,
+	/// A description of what the response format is for,
+	/// used by the model to determine how to respond in the format.
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub description: Option<String>,
+	/// The schema for the response format, described as a JSON Schema object
+	/// and sent verbatim.
+	pub schema: Json,
+	/// Whether to enable strict schema adherence when generating the output.
+	/// Only a subset of JSON Schema is supported when strict is true.
+	/// Defaults to false
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub strict: Option<bool>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StreamOptions {
+	/// If set, an additional chunk will be streamed before the data: [DONE] message.
+	/// The usage field on this chunk shows the token usage statistics for the entire request,
+	/// and the choices field will always be an empty array. See `Stream::usage`.
+	pub include_usage: bool,
+	/// Whether to pad the chunks with an `obfuscation` field of random characters, which
+	/// normalizes their sizes against side-channel attacks. Defaults to true, disable it to
+	/// save bandwidth on trusted networks.
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub include_obfuscation: Option<bool>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Tool {
+	/// The type of the tool. Currently, only function is supported.
+	#[serde(rename = "type")]
+	pub kind: String,
+	pub function: FunctionDef,
+}
+
+impl Tool {
+	pub fn function(function: FunctionDef) -> Tool {
+		Tool { kind: "function".to_string(), function }
+	}
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FunctionDef {
+	/// The name of the function to be called.
+	/// Must be a-z, A-Z, 0-9, or contain underscores and dashes, with a maximum length of 64.
+	pub name: String,
+	/// A description of what the function does,
+	/// used by the model to choose when and how to call the function.
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub description: Option<String>,
+	/// The parameters the functions accepts, described as a JSON Schema object.
+	/// Omitting parameters defines a function with an empty parameter list.
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub parameters: Option<Json>,
+}
+
+/// Which tool the model calls, serialized as `"auto"`, `"none"`, `"required"`
+/// or `{"type": "function", "function": {"name": ...}}`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(into = "ToolChoiceRepr", try_from = "ToolChoiceRepr")]
+pub enum ToolChoice {
+	/// The model can pick between generating a message or calling one or more tools.
+	Auto,
+	/// The model will not call any tool and instead generates a message.
+	None,
+	/// The model must call one or more tools.
+	Required,
+	/// The model must call the named function.
+	Function(String),
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum ToolChoiceRepr {
+	Mode(String),
+	Named {
+		#[serde(rename = "type")]
+		kind: String,
+		function: FunctionName,
+	},
+}
+
+#[derive(Serialize, Deserialize)]
+struct FunctionName {
+	name: String,
+}
+
+impl From<ToolChoice> for ToolChoiceRepr {
+	fn from(choice: ToolChoice) -> Self {
+		match choice {
+			ToolChoice::Auto => Self::Mode("auto".to_string()),
+			ToolChoice::None => Self::Mode("none".to_string()),
+			ToolChoice::Required => Self::Mode("required".to_string()),
+			ToolChoice::Function(name) => {
+				Self::Named { kind: "function".to_string(), function: FunctionName { name } }
+			},
+		}
+	}
+}
+
+impl TryFrom<ToolChoiceRepr> for ToolChoice {
+	type Error = String;
+
+	fn try_from(repr: ToolChoiceRepr) -> Result<Self, Self::Error> {
+		match repr {
+			ToolChoiceRepr::Mode(mode) => match mode.as_str() {
+				"auto" => Ok(Self::Auto),
+				"none" => Ok(Self::None),
+				"required" => Ok(Self::Required),
+				_ => Err(format!("unknown tool_choice: {mode}")),
+			},
+			ToolChoiceRepr::Named { function, .. } => Ok(Self::Function(function.name)),
+		}
+	}
+}
+
+/// A chunk of a streamed chat completion.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ChatCompletionChunk {
+	pub id: Option<String>,
+	pub object: Option<String>,
+	/// The Unix timestamp in seconds, `None` if omitted, e.g. by some OpenAI-compatible servers.
+	pub created: Option<u64>,
+	pub model: Option<String>,
+	/// Empty for the usage chunk.
+	pub choices: Vec<ChunkChoice>,
+	/// Only set for the last chunk, when `stream_options.include_usage` is set.
+	pub usage: Option<Usage>,
+	/// The backend configuration that the model runs with.
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub system_fingerprint: Option<String>,
+	/// The processing tier used for the request.
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub service_tier: Option<ServiceTier>,
+	/// The annotations of the Azure OpenAI content filters on the prompt, `None` for OpenAI.
+	/// Azure sends them in a first chunk without choices.
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub prompt_filter_results: Option<Vec<PromptFilterResult>>,
+	/// The fields not modeled above, e.g. fields added to the API after this crate.
+	/// Unknown fields are never an error, including in the nested objects.
+	#[serde(flatten)]
+	pub extra: serde_json::Map<String, Json>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ChunkChoice {
+	#[serde(default)]
+	pub index: u32,
+	pub delta: Delta,
+	/// The log probabilities of the tokens of this delta.
+	pub logprobs: Option<Logprobs>,
+	pub finish_reason: Option<FinishReason>,
+	/// The annotations of the Azure OpenAI content filters on the content streamed so far,
+	/// `None` for OpenAI.
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub content_filter_results: Option<ContentFilterResults>,
+}
+
+/// The part of the message carried by a chunk.
+/// The first chunk usually only has a `role`, the following ones only `content`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Delta {
+	pub role: Option<Role>,
+	pub content: Option<String>,
+	/// A part of the refusal of the model, see `Message::refusal`.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub refusal: Option<String>,
+	/// Parts of the tool calls of the message, see `ToolCallsAccumulator`.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub tool_calls: Option<Vec<ToolCallDelta>>,
+}
+
+/// A part of a streamed tool call. The first part of a call has its `id`, type and function
+/// name, the following ones only parts of its arguments.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ToolCallDelta {
+	/// The position of the tool call in the message.
+	pub index: u32,
+	pub id: Option<String>,
+	#[serde(rename = "type")]
+	pub kind: Option<String>,
+	pub function: Option<FunctionCallDelta>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FunctionCallDelta {
+	pub name: Option<String>,
+	pub arguments: Option<String>,
+}
+
+pub type ChatStream = Stream<ChatCompletionChunk>;
+
+/// The fragments of text of the first choice of a `ChatStream`, in order, skipping the chunks
+/// without text, such as the role, tool call and usage chunks.
+pub struct TextStream(ChatStream);
+
+impl TextStream {
+	/// See `Stream::handle`.
+	pub fn handle(&self) -> StreamHandle {
+		self.0.handle()
+	}
+
+	/// See `Stream::usage`.
+	pub fn usage(&self) -> Option<&Usage> {
+		self.0.usage()
+	}
+}
+
+impl Iterator for TextStream {
+	type Item = ApiResult<String>;
+
+	fn next(&mut self) -> Option<Self::Item> {
+		for chunk in &mut self.0 {
+			let mut chunk = match chunk {
+				Ok(chunk) => chunk,
+				Err(e) => return Some(Err(e)),
+			};
+			let choice = chunk.choices.iter_mut().find(|choice| choice.index == 0);
+			if let Some(text) = choice.and_then(|choice| choice.delta.content.take()) {
+				if !text.is_empty() {
+					return Some(Ok(text));
+				}
+			}
+		}
+		None
+	}
+}
+
+impl ChatStream {
+	/// Yields only the text of the first choice, see `TextStream`.
+	pub fn text(self) -> TextStream {
+		TextStream(self)
+	}
+
+	/// Reads the stream to the end, reassembling its choices, see `ChoicesAccumulator`.
+	pub fn collect_choices(self) -> ApiResult<Vec<StreamedChoice>> {
+		let mut choices = ChoicesAccumulator::new();
+		for chunk in self {
+			choices.push(&chunk?);
+		}
+		Ok(choices.into_choices())
+	}
+
+	/// Reads the stream to the end, reassembling its tool calls, see `ToolCallsAccumulator`.
+	pub fn collect_tool_calls(self) -> ApiResult<Vec<ToolCall>> {
+		let mut tool_calls = ToolCallsAccumulator::new();
+		for chunk in self {
+			tool_calls.push(&chunk?);
+		}
+		Ok(tool_calls.into_tool_calls())
+	}
+}
+
+/// A choice of a chat stream, reassembled from its deltas.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StreamedChoice {
+	pub index: u32,
+	pub role: Option<Role>,
+	pub content: String,
+	pub finish_reason: Option<FinishReason>,
+}
+
+impl StreamedChoice {
+	/// Whether the choice was cut off by the maximum number of tokens of the request, see
+	/// `ChatBody::continuation`.
+	pub fn was_truncated(&self) -> bool {
+		self.finish_reason == Some(FinishReason::Length)
+	}
+}
+
+/// Reassembles the choices of a chat stream from its chunks. When `n` is above 1, the deltas of
+/// the choices are interleaved, each is appended to the choice of its `index`.
+#[derive(Debug, Default)]
+pub struct ChoicesAccumulator {
+	choices: Vec<StreamedChoice>,
+}
+
+impl ChoicesAccumulator {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	pub fn push(&mut self, chunk: &ChatCompletionChunk) {
+		for choice in &chunk.choices {
+			let i = self.choices.partition_point(|c| c.index < choice.index);
+			if self.choices.get(i).is_none_or(|c| c.index != choice.index) {
+				self.choices
+					.insert(i, StreamedChoice { index: choice.index, ..Default::default() });
+			}
+			let streamed = &mut self.choices[i];
+			if let Some(role) = &choice.delta.role {
+				streamed.role = Some(role.clone());
+			}
+			if let Some(content) = &choice.delta.content {
+				streamed.content.push_str(content);
+			}
+			if let Some(finish_reason) = choice.finish_reason {
+				streamed.finish_reason = Some(finish_reason);
+			}
+		}
+	}
+
+	/// The choices so far, ordered by index.
+	pub fn choices(&self) -> &[StreamedChoice] {
+		&self.choices
+	}
+
+	pub fn into_choices(self) -> Vec<StreamedChoice> {
+		self.choices
+	}
+}
+
+/// Reassembles the tool calls of a chat stream, whose arguments arrive in parts across chunks.
+///
+/// The API streams the calls of a choice one after the other: a call is complete once the next
+/// one starts or the choice finishes. Its arguments are only concatenated, never parsed, so
+/// they are left as the model generated them, valid JSON or not.
+#[derive(Debug, Default)]
+pub struct ToolCallsAccumulator {
+	/// The calls ordered by choice and index, with whether they are complete.
+	calls: Vec<(u32, u32, ToolCall, bool)>,
+}
+
+impl ToolCallsAccumulator {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Appends the parts of tool calls of `chunk`, returning the calls it completes.
+	pub fn push(&mut self, chunk: &ChatCompletionChunk) -> Vec<ToolCall> {
+		let mut completed = Vec::new();
+		for choice in &chunk.choices {
+			for delta in choice.delta.tool_calls.iter().flatten() {
+				let key = (choice.index, delta.index);
+				let i = self.calls.partition_point(|(c, t, ..)| (*c, *t) < key);
+				if self.calls.get(i).is_none_or(|(c, t, ..)| (*c, *t) != key) {
+					self.complete(choice.index, |index| index < delta.index, &mut completed);
+					let call = ToolCall {
+						id: String::new(),
+						kind: "function".to_string(),
+						function: FunctionCall { name: String::new(), arguments: String::new() },
+					};
+					self.calls.insert(i, (choice.index, delta.index, call, false));
+				}
+				let call = &mut self.calls[i].2;
+				if let Some(id) = &delta.id {
+					call.id.push_str(id);
+				}
+				if let Some(kind) = &delta.kind {
+					call.kind.clone_from(kind);
+				}
+				if let Some(function) = &delta.function {
+					call.function.name.push_str(function.name.as_deref().unwrap_or_default());
+					call.function
+						.arguments
+						.push_str(function.arguments.as_deref().unwrap_or_default());
+				}
+			}
+			if choice.finish_reason.is_some() {
+				self.complete(choice.index, |_| true, &mut completed);
+			}
+		}
+		completed
+	}
+
+	/// Marks the calls of `choice` whose index matches as complete, adding them to `completed`.
+	fn complete(
+		&mut self,
+		choice: u32,
+		index: impl Fn(u32) -> bool,
+		completed: &mut Vec<ToolCall>,
+	) {
+		for (c, t, call, done) in &mut self.calls {
+			if *c == choice && index(*t) && !*done {
+				*done = true;
+				completed.push(call.clone());
+			}
+		}
+	}
+
+	/// All the calls, complete or not, ordered by choice and index.
+	pub fn into_tool_calls(self) -> Vec<ToolCall> {
+		self.calls.into_iter().map(|(_, _, call, _)| call).collect()
+	}
+}
+
+const CHAT_COMPLETIONS_MAX_LIMIT: u32 = 100;
+
+/// A message of a stored chat completion, see `ChatApi::chat_completion_messages`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct StoredMessage {
+	pub id: String,
+	#[serde(flatten)]
+	pub message: Message,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DeletedChatCompletion {
+	pub id: String,
+	pub object: Option<String>,
+	pub deleted: bool,
 }
 
 pub trait ChatApi {
 	/// Creates a completion for the chat message
 	fn chat_completion_create(&self, chat_body: &ChatBody) -> ApiResult<Completion>;
+	/// Creates a completion for the chat message, streaming it back chunk by chunk.
+	/// `chat_body.stream` is ignored, it is always sent as `true`.
+	fn chat_completion_stream(&self, chat_body: &ChatBody) -> ApiResult<ChatStream>;
+	/// Streams a completion for the chat message into `writer`, writing the content of the first
+	/// choice as it is received and flushing after every chunk, e.g. to print it to stdout.
+	///
+	/// Returns the assembled message, and the usage if `stream_options.include_usage` is set.
+	/// When the stream fails, the error is returned once the content received is written.
+	fn chat_completion_stream_to(
+		&self,
+		chat_body: &ChatBody,
+		writer: &mut impl Write,
+	) -> ApiResult<(Message, Option<Usage>)>;
+	/// Streams a completion for the chat message, yielding only the fragments of text of the
+	/// first choice as they are received, see `TextStream`.
+	fn chat_completion_stream_text(&self, chat_body: &ChatBody) -> ApiResult<TextStream>;
+	/// Returns a list of the completions created with `store` set, oldest first.
+	fn chat_completions_list(&self, params: &ListParams) -> ApiResult<Paginated<Completion>>;
+	/// Iterates over all the stored completions starting from `params`,
+	/// fetching pages of the maximum size unless `params.limit` is set.
+	fn chat_completions_iter(&self, params: ListParams) -> Paginator<'_, Completion>;
+	/// Retrieves a stored completion.
+	fn chat_completion_retrieve(&self, completion_id: &str) -> ApiResult<Completion>;
+	/// Returns a list of the messages of the request of a stored completion.
+	fn chat_completion_messages(
+		&self,
+		completion_id: &str,
+		params: &ListParams,
+	) -> ApiResult<Paginated<StoredMessage>>;
+	/// Replaces the `metadata` of a stored completion, the only field that can be modified.
+	fn chat_completion_update(
+		&self,
+		completion_id: &str,
+		metadata: &HashMap<String, String>,
+	) -> ApiResult<Completion>;
+	/// Deletes a stored completion.
+	fn chat_completion_delete(&self, completion_id: &str) -> ApiResult<DeletedChatCompletion>;
 }
 
 impl ChatApi for OpenAI {
 	fn chat_completion_create(&self, chat_body: &ChatBody) -> ApiResult<Completion> {
-		let request_body = serde_json::to_value(chat_body).unwrap();
+		chat_body.validate()?;
+		let mut request_body = serde_json::to_value(chat_body).unwrap();
+		self.default_model(&mut request_body, &self.default_models.chat)?;
 		let res = self.post(CHAT_COMPLETION_CREATE, request_body)?;
-		let completion: Completion = serde_json::from_value(res.clone()).unwrap();
+		let completion: Completion = from_json(&res)?;
 		Ok(completion)
 	}
+
+	fn chat_completion_stream(&self, chat_body: &ChatBody) -> ApiResult<ChatStream> {
+		chat_body.validate()?;
+		let mut request_body = serde_json::to_value(chat_body).unwrap();
+		self.default_model(&mut request_body, &self.default_models.chat)?;
+		request_body["stream"] = Json::Bool(true);
+		let reader = self.post_stream(CHAT_COMPLETION_CREATE, request_body)?;
+		Ok(Stream::new(reader).skipping_empty_choices())
+	}
+
+	fn chat_completion_stream_to(
+		&self,
+		chat_body: &ChatBody,
+		writer: &mut impl Write,
+	) -> ApiResult<(Message, Option<Usage>)> {
+		let write_error = |e: std::io::Error| Error::RequestError(e.to_string());
+		let mut stream = self.chat_completion_stream_text(chat_body)?;
+		let mut content = String::new();
+		for text in &mut stream {
+			let text = text?;
+			writer.write_all(text.as_bytes()).map_err(write_error)?;
+			writer.flush().map_err(write_error)?;
+			content.push_str(&text);
+		}
+		let usage = stream.usage().cloned();
+		Ok((Message::assistant(content), usage))
+	}
+
+	fn chat_completion_stream_text(&self, chat_body: &ChatBody) -> ApiResult<TextStream> {
+		Ok(self.chat_completion_stream(chat_body)?.text())
+	}
+
+	fn chat_completions_list(&self, params: &ListParams) -> ApiResult<Paginated<Completion>> {
+		let res = self.get(&params.apply(CHAT_COMPLETION_CREATE))?;
+		let completions: Paginated<Completion> = from_json(&res)?;
+		Ok(completions)
+	}
+
+	fn chat_completions_iter(&self, params: ListParams) -> Paginator<'_, Completion> {
+		Paginator::new(
+			params,
+			CHAT_COMPLETIONS_MAX_LIMIT,
+			|completion| completion.id.clone().unwrap_or_default(),
+			|params| self.chat_completions_list(params),
+		)
+	}
+
+	fn chat_completion_retrieve(&self, completion_id: &str) -> ApiResult<Completion> {
+		let sub_url = CHAT_COMPLETIONS_RETRIEVE.to_owned() + completion_id;
+		let res = self.get(&sub_url)?;
+		let completion: Completion = from_json(&res)?;
+		Ok(completion)
+	}
+
+	fn chat_completion_messages(
+		&self,
+		completion_id: &str,
+		params: &ListParams,
+	) -> ApiResult<Paginated<StoredMessage>> {
+		let sub_url = format!("{CHAT_COMPLETIONS_RETRIEVE}{completion_id}/messages");
+		let res = self.get(&params.apply(&sub_url))?;
+		let messages: Paginated<StoredMessage> = from_json(&res)?;
+		Ok(messages)
+	}
+
+	fn chat_completion_update(
+		&self,
+		completion_id: &str,
+		metadata: &HashMap<String, String>,
+	) -> ApiResult<Completion> {
+		check_metadata(metadata)?;
+		let sub_url = CHAT_COMPLETIONS_RETRIEVE.to_owned() + completion_id;
+		let res = self.post(&sub_url, serde_json::json!({ "metadata": metadata }))?;
+		let completion: Completion = from_json(&res)?;
+		Ok(completion)
+	}
+
+	fn chat_completion_delete(&self, completion_id: &str) -> ApiResult<DeletedChatCompletion> {
+		let sub_url = CHAT_COMPLETIONS_RETRIEVE.to_owned() + completion_id;
+		let res = self.delete(&sub_url)?;
+		let deleted: DeletedChatCompletion = from_json(&res)?;
+		Ok(deleted)
+	}
 }
 
 #[cfg(test)]
 mod tests {
-	use crate::{apis::chat::ChatBody, openai::new_test_openai, Message, Role};
+	use crate::{
+		apis::chat::ChatBody,
+		apis::completions::Completion,
+		openai::{new_test_mock, new_test_openai},
+		stream::new_test_stream,
+		testing::{MockResponse, MockTransport},
+		Auth, CompletionResponse, Content, ContentPart, Error, FinishReason, ListParams, Message,
+		OpenAI, Role,
+	};
 
-	use super::ChatApi;
+	use super::{
+		output_token_limit, ChatApi, ChatStream, FunctionDef, JsonSchema, ReasoningEffort,
+		ResponseFormat, Tool, ToolCallsAccumulator, ToolChoice,
+	};
 
 	#[test]
 	fn test_chat_completion() {
 		let openai = new_test_openai();
 		let body = ChatBody {
-			model: "gpt-3.5-turbo".to_string(),
+			model: "gpt-3.5-turbo".into(),
 			max_tokens: Some(7),
 			temperature: Some(0_f32),
 			top_p: Some(0_f32),
@@ -114,11 +1139,904 @@ mod tests {
 			frequency_penalty: None,
 			logit_bias: None,
 			user: None,
-			messages: vec![Message { role: Role::User, content: "Hello!".to_string() }],
+			messages: vec![Message::user("Hello!")],
+			..Default::default()
 		};
 		let rs = openai.chat_completion_create(&body);
 		let choice = rs.unwrap().choices;
 		let message = &choice[0].message.as_ref().unwrap();
-		assert!(message.content.contains("Hello"));
+		assert!(message.text().unwrap().contains("Hello"));
+	}
+
+	#[test]
+	fn test_chat_tools() {
+		let body = ChatBody {
+			model: "gpt-4o".into(),
+			messages: vec![
+				Message::user("What's the weather in Paris?"),
+				Message::tool("call_1", r#"{"celsius": 21}"#),
+			],
+			tools: Some(vec![Tool::function(FunctionDef {
+				name: "get_weather".to_string(),
+				description: None,
+				parameters: Some(serde_json::json!({
+					"type": "object",
+					"properties": {"city": {"type": "string"}},
+				})),
+			})]),
+			tool_choice: Some(ToolChoice::Function("get_weather".to_string())),
+			..Default::default()
+		};
+		let json = serde_json::to_value(&body).unwrap();
+		assert_eq!(
+			json["tools"][0],
+			serde_json::json!({
+				"type": "function",
+				"function": {
+					"name": "get_weather",
+					"parameters": {"type": "object", "properties": {"city": {"type": "string"}}},
+				},
+			})
+		);
+		assert_eq!(
+			json["tool_choice"],
+			serde_json::json!({"type": "function", "function": {"name": "get_weather"}})
+		);
+		assert_eq!(
+			json["messages"][1],
+			serde_json::json!({"role": "tool", "content": "{\"celsius\": 21}", "tool_call_id": "call_1"})
+		);
+		for (choice, expected) in [
+			(ToolChoice::Auto, "auto"),
+			(ToolChoice::None, "none"),
+			(ToolChoice::Required, "required"),
+		] {
+			assert_eq!(serde_json::to_value(&choice).unwrap(), expected);
+			assert_eq!(serde_json::from_value::<ToolChoice>(expected.into()).unwrap(), choice);
+		}
+
+		let completion: Completion = serde_json::from_value(serde_json::json!({
+			"id": "chatcmpl-1",
+			"object": "chat.completion",
+			"created": 1,
+			"model": "gpt-4o",
+			"choices": [{
+				"index": 0,
+				"message": {
+					"role": "assistant",
+					"content": null,
+					"tool_calls": [{
+						"id": "call_1",
+						"type": "function",
+						"function": {"name": "get_weather", "arguments": "{\"city\":\"Paris\"}"},
+					}],
+				},
+				"finish_reason": "tool_calls",
+			}],
+			"usage": {"prompt_tokens": 1, "completion_tokens": 1, "total_tokens": 2},
+		}))
+		.unwrap();
+		let message = completion.choices[0].message.as_ref().unwrap();
+		assert!(message.content.is_none());
+		let tool_call = &message.tool_calls.as_ref().unwrap()[0];
+		assert_eq!(tool_call.function.arguments, "{\"city\":\"Paris\"}");
+	}
+
+	#[test]
+	fn test_chat_content_parts() {
+		let message = Message::user(vec![
+			ContentPart::text("What's in this image?"),
+			ContentPart::image_from_bytes(b"\x89PNG", mime::IMAGE_PNG),
+		]);
+		assert_eq!(
+			serde_json::to_value(&message).unwrap(),
+			serde_json::json!({
+				"role": "user",
+				"content": [
+					{"type": "text", "text": "What's in this image?"},
+					{"type": "image_url", "image_url": {"url": "data:image/png;base64,iVBORw=="}},
+				],
+			})
+		);
+
+		let message = Message::user(vec![ContentPart::text("Hello!")]);
+		assert_eq!(
+			serde_json::to_value(&message).unwrap(),
+			serde_json::json!({"role": "user", "content": "Hello!"})
+		);
+
+		let content: Content = serde_json::from_value(serde_json::json!("Hello!")).unwrap();
+		assert_eq!(content, Content::Text("Hello!".to_string()));
+		let content: Content =
+			serde_json::from_value(serde_json::json!([{"type": "text", "text": "Hello!"}]))
+				.unwrap();
+		assert_eq!(content, Content::Parts(vec![ContentPart::text("Hello!")]));
+	}
+
+	#[test]
+	fn test_chat_response_format() {
+		let body = ChatBody {
+			model: "gpt-4o".into(),
+			messages: vec![Message::user("Reply in JSON")],
+			response_format: Some(ResponseFormat::JsonObject),
+			..Default::default()
+		};
+		assert_eq!(
+			serde_json::to_value(&body).unwrap(),
+			serde_json::json!({
+				"model": "gpt-4o",
+				"messages": [{"role": "user", "content": "Reply in JSON"}],
+				"response_format": {"type": "json_object"},
+			})
+		);
+
+		let schema = serde_json::json!({
+			"type": "object",
+			"properties": {"answer": {"type": "string"}},
+			"required": ["answer"],
+			"additionalProperties": false,
+		});
+		let body = ChatBody {
+			response_format: Some(ResponseFormat::JsonSchema {
+				json_schema: JsonSchema {
+					name: "answer".to_string(),
+					description: None,
+					schema: schema.clone(),
+					strict: Some(true),
+				},
+			}),
+			..body
+		};
+		assert_eq!(
+			serde_json::to_value(&body).unwrap()["response_format"],
+			serde_json::json!({
+				"type": "json_schema",
+				"json_schema": {"name": "answer", "schema": schema, "strict": true},
+			})
+		);
+	}
+
+	#[test]
+	fn test_chat_penalties() {
+		let body = ChatBody {
+			model: "gpt-4o".into(),
+			messages: vec![Message::user("Hello!")],
+			presence_penalty: Some(0.5),
+			frequency_penalty: Some(-2.0),
+			logit_bias: Some([(50256, -100)].into()),
+			..Default::default()
+		};
+		assert!(body.validate().is_ok());
+		let json = serde_json::to_value(&body).unwrap();
+		assert_eq!(json["logit_bias"], serde_json::json!({"50256": -100}));
+		assert_eq!(json["frequency_penalty"], -2.0);
+
+		let body = ChatBody { presence_penalty: Some(2.5), ..body };
+		assert!(matches!(body.validate(), Err(Error::InvalidRequest(_))));
+		let openai = OpenAI::new(Auth::new("sk-test"), "http://127.0.0.1:1/v1/");
+		assert!(matches!(openai.chat_completion_create(&body), Err(Error::InvalidRequest(_))));
+	}
+
+	#[test]
+	fn test_chat_builder() {
+		let body = ChatBody::builder()
+			.model("gpt-4o")
+			.system("Be brief.")
+			.user("What's the weather?")
+			.assistant("Let me check.")
+			.tool("call_1", "Sunny")
+			.max_tokens(16)
+			.stop("\n")
+			.user_id("user-1")
+			.build()
+			.unwrap();
+		assert!(matches!(
+			[&body.messages[0].role, &body.messages[1].role, &body.messages[2].role],
+			[Role::System, Role::User, Role::Assistant]
+		));
+		assert!(matches!(body.messages[3].role, Role::Tool));
+		assert_eq!(body.messages[3].tool_call_id.as_deref(), Some("call_1"));
+		assert_eq!(body.max_tokens, Some(16));
+		assert_eq!(body.user.as_deref(), Some("user-1"));
+
+		assert!(ChatBody::builder().user("Hi").build().unwrap().model.is_empty());
+		assert!(matches!(
+			ChatBody::builder().model("gpt-4o").build(),
+			Err(Error::InvalidRequest(_))
+		));
+		let rs = ChatBody::builder().model("gpt-4o").user("Hi").frequency_penalty(3.0).build();
+		assert!(matches!(rs, Err(Error::InvalidRequest(_))));
+	}
+
+	#[test]
+	fn test_chat_sampling() {
+		let builder = || ChatBody::builder().model("gpt-4o").user("Hi");
+		assert!(builder().temperature(2.0).top_p(0.0).n(1).build().is_ok());
+		for builder in [builder().temperature(2.5), builder().top_p(-0.1), builder().n(0)] {
+			assert!(matches!(builder.build(), Err(Error::InvalidRequest(_))));
+		}
+
+		assert!(builder().temperature(0.5).top_p(0.9).build().is_ok());
+		let rs = builder().temperature(0.5).top_p(0.9).strict_sampling().build();
+		assert!(matches!(rs, Err(Error::InvalidRequest(_))));
+		let rs = builder().temperature(0.5).top_p(1.0).strict_sampling().build();
+		assert!(rs.is_ok());
+	}
+
+	#[test]
+	fn test_chat_messages() {
+		let rs = ChatBody::builder().model("gpt-4o").build();
+		assert!(matches!(rs, Err(Error::InvalidRequest(msg)) if msg.contains("one message")));
+
+		let tool = Message { tool_call_id: None, ..Message::tool("call_1", "{}") };
+		let rs = ChatBody::builder().model("gpt-4o").user("Hi").message(tool).build();
+		assert!(matches!(rs, Err(Error::InvalidRequest(msg)) if msg.starts_with("messages[1]")));
+		let rs =
+			ChatBody::builder().model("gpt-4o").user("Hi").message(Message::tool("call_1", "{}"));
+		assert!(rs.build().is_ok());
+	}
+
+	#[test]
+	fn test_chat_developer_role() {
+		let message = Message::developer("Answer in French.");
+		assert_eq!(
+			serde_json::to_value(&message).unwrap(),
+			serde_json::json!({"role": "developer", "content": "Answer in French."})
+		);
+		let message: Message =
+			serde_json::from_str(r#"{"role": "developer", "content": "Hi"}"#).unwrap();
+		assert_eq!(message.role, Role::Developer);
+
+		let body = |model| ChatBody::builder().model(model).system("Be brief.").user("Hi").build();
+		let roles =
+			|body: ChatBody| body.messages.iter().map(|m| m.role.clone()).collect::<Vec<_>>();
+		let body_o1 = body("o1-mini").unwrap().use_developer_role();
+		assert_eq!(roles(body_o1), [Role::Developer, Role::User]);
+		let body_gpt = body("gpt-4o").unwrap().use_developer_role();
+		assert_eq!(roles(body_gpt), [Role::System, Role::User]);
+	}
+
+	#[test]
+	fn test_chat_store() {
+		let builder = || ChatBody::builder().model("gpt-4o").user("Hi");
+		let body = builder().store(true).metadata("team", "search").build().unwrap();
+		let json = serde_json::to_value(&body).unwrap();
+		assert_eq!(json["store"], true);
+		assert_eq!(json["metadata"], serde_json::json!({"team": "search"}));
+		let json = serde_json::to_value(builder().build().unwrap()).unwrap();
+		assert!(json.get("store").is_none() && json.get("metadata").is_none());
+
+		let rs = builder().metadata("k".repeat(65), "v").build();
+		assert!(matches!(rs, Err(Error::InvalidRequest(_))));
+		let rs = builder().metadata("k", "v".repeat(513)).build();
+		assert!(matches!(rs, Err(Error::InvalidRequest(_))));
+		let many = (0..17).fold(builder(), |builder, i| builder.metadata(i.to_string(), "v"));
+		assert!(matches!(many.build(), Err(Error::InvalidRequest(_))));
+	}
+
+	#[test]
+	fn test_chat_reasoning() {
+		let body = ChatBody::builder()
+			.model("o3-mini")
+			.user("Prove it.")
+			.max_completion_tokens(1024)
+			.reasoning_effort(ReasoningEffort::High)
+			.build()
+			.unwrap();
+		let json = serde_json::to_value(&body).unwrap();
+		assert_eq!(json["max_completion_tokens"], 1024);
+		assert_eq!(json["reasoning_effort"], "high");
+		assert!(json.get("max_tokens").is_none());
+
+		let body = ChatBody { max_tokens: Some(1024), ..body };
+		assert!(matches!(body.validate(), Err(Error::InvalidRequest(_))));
+	}
+
+	#[test]
+	fn test_chat_max_output_tokens() {
+		let body = |model: &str, tokens| {
+			ChatBody::builder()
+				.model(model)
+				.user("Hi")
+				.max_tokens(8)
+				.max_output_tokens(tokens)
+				.build()
+		};
+		let o3 = body("o3-mini-2025-01-31", 50_000).unwrap();
+		assert_eq!((o3.max_tokens, o3.max_completion_tokens), (None, Some(50_000)));
+		let gpt = body("gpt-4o", 1000).unwrap();
+		assert_eq!((gpt.max_tokens, gpt.max_completion_tokens), (Some(1000), None));
+		let default = body("", 1000).unwrap();
+		assert_eq!((default.max_tokens, default.max_completion_tokens), (None, Some(1000)));
+		assert_eq!(body("llama3", 100_000).unwrap().max_tokens, Some(100_000));
+
+		assert!(matches!(body("gpt-4o", 20_000), Err(Error::InvalidRequest(_))));
+		assert!(matches!(body("gpt-4o-2024-05-13", 8_000), Err(Error::InvalidRequest(_))));
+		assert!(body("gpt-4o-2024-08-06", 8_000).is_ok());
+		assert_eq!(output_token_limit("o1-mini-2024-09-12"), Some(65_536));
+		assert_eq!(output_token_limit("gpt-4o-mini"), Some(16_384));
+		assert_eq!(output_token_limit("my-model"), None);
+	}
+
+	#[test]
+	fn test_chat_prediction() {
+		let body = ChatBody::builder()
+			.model("gpt-4o")
+			.user("Rename x to y.")
+			.prediction("let x = 1;")
+			.build()
+			.unwrap();
+		let json = serde_json::to_value(&body).unwrap();
+		assert_eq!(
+			json["prediction"],
+			serde_json::json!({"type": "content", "content": "let x = 1;"})
+		);
+		let body = ChatBody { prediction: None, ..body };
+		assert!(serde_json::to_value(&body).unwrap().get("prediction").is_none());
+
+		let completion: Completion = serde_json::from_str(
+			r#"{"id": "chatcmpl-1", "object": "chat.completion", "created": 1, "model": "gpt-4o",
+			"choices": [], "usage": {"prompt_tokens": 20, "completion_tokens": 10, "total_tokens": 30,
+			"completion_tokens_details": {"reasoning_tokens": 0, "accepted_prediction_tokens": 6,
+			"rejected_prediction_tokens": 2}}}"#,
+		)
+		.unwrap();
+		let details = completion.usage.unwrap().completion_tokens_details.unwrap();
+		assert_eq!(details.accepted_prediction_tokens, Some(6));
+		assert_eq!(details.rejected_prediction_tokens, Some(2));
+	}
+
+	#[test]
+	fn test_chat_stream_decode() {
+		let body =
+			"data: {\"id\":\"chatcmpl-1\",\"object\":\"chat.completion.chunk\",\"created\":1,\
+			\"model\":\"gpt-3.5-turbo\",\"choices\":[{\"index\":0,\"delta\":{\"role\":\"assistant\"},\
+			\"finish_reason\":null}]}\n\n\
+			data: {\"id\":\"chatcmpl-1\",\"object\":\"chat.completion.chunk\",\"created\":1,\
+			\"model\":\"gpt-3.5-turbo\",\"choices\":[{\"index\":0,\"delta\":{\"content\":\"Hello\"},\
+			\"finish_reason\":null}]}\n\n\
+			data: {\"id\":\"chatcmpl-1\",\"object\":\"chat.completion.chunk\",\"created\":1,\
+			\"model\":\"gpt-3.5-turbo\",\"choices\":[{\"index\":0,\"delta\":{},\
+			\"finish_reason\":\"stop\"}]}\n\n\
+			data: [DONE]\n\n";
+		let stream: ChatStream = new_test_stream(body, 5);
+		let chunks: Vec<_> = stream.map(Result::unwrap).collect();
+		assert_eq!(chunks.len(), 3);
+		assert!(matches!(chunks[0].choices[0].delta.role, Some(Role::Assistant)));
+		assert!(chunks[0].choices[0].delta.content.is_none());
+		assert_eq!(chunks[1].choices[0].delta.content.as_deref(), Some("Hello"));
+		assert!(chunks[1].choices[0].delta.role.is_none());
+		assert_eq!(chunks[2].choices[0].finish_reason, Some(FinishReason::Stop));
+	}
+
+	#[test]
+	fn test_chat_choices() {
+		let completion: Completion = serde_json::from_str(
+			r#"{"id": "chatcmpl-1", "object": "chat.completion", "created": 1, "model": "gpt-4o",
+			"choices": [
+				{"index": 0, "finish_reason": "stop", "message": {"role": "assistant", "content": "A"}},
+				{"index": 1, "finish_reason": "length", "message": {"role": "assistant", "content": "B"}}
+			],
+			"usage": {"prompt_tokens": 1, "completion_tokens": 2, "total_tokens": 3}}"#,
+		)
+		.unwrap();
+		assert_eq!(completion.first_message().unwrap().text(), Some("A"));
+		let texts: Vec<_> = completion.messages().map(|message| message.text().unwrap()).collect();
+		assert_eq!(texts, ["A", "B"]);
+		assert_eq!(completion.choices[1].finish_reason, Some(FinishReason::Length));
+
+		let chunk = |index: u32, delta: &str, finish_reason: &str| {
+			format!(
+				"data: {{\"id\":\"chatcmpl-1\",\"created\":1,\"choices\":[{{\"index\":{index},\
+				\"delta\":{delta},\"finish_reason\":{finish_reason}}}]}}\n\n"
+			)
+		};
+		let body = [
+			chunk(1, r#"{"role":"assistant","content":""}"#, "null"),
+			chunk(0, r#"{"role":"assistant","content":""}"#, "null"),
+			chunk(0, r#"{"content":"Hel"}"#, "null"),
+			chunk(1, r#"{"content":"Bon"}"#, "null"),
+			chunk(1, r#"{"content":"jour"}"#, "null"),
+			chunk(0, r#"{"content":"lo"}"#, "null"),
+			chunk(0, "{}", r#""stop""#),
+			chunk(1, "{}", r#""length""#),
+			"data: [DONE]\n\n".to_string(),
+		]
+		.concat();
+		let stream: ChatStream = new_test_stream(Box::leak(body.into_boxed_str()), 16);
+		let choices = stream.collect_choices().unwrap();
+		assert_eq!(choices.len(), 2);
+		assert_eq!((choices[0].index, choices[0].content.as_str()), (0, "Hello"));
+		assert_eq!((choices[1].index, choices[1].content.as_str()), (1, "Bonjour"));
+		assert_eq!(choices[0].role, Some(Role::Assistant));
+		assert_eq!(choices[0].finish_reason, Some(FinishReason::Stop));
+		assert_eq!(choices[1].finish_reason, Some(FinishReason::Length));
+	}
+
+	#[test]
+	fn test_chat_continuation() {
+		let completion = |finish_reason: &str| -> Completion {
+			serde_json::from_value(serde_json::json!({
+				"id": "chatcmpl-1", "object": "chat.completion", "created": 1, "model": "gpt-4o",
+				"choices": [{"index": 0, "finish_reason": finish_reason,
+					"message": {"role": "assistant", "content": "Once upon a"}}],
+				"usage": {"prompt_tokens": 1, "completion_tokens": 3, "total_tokens": 4}}))
+			.unwrap()
+		};
+		let truncated = completion("length");
+		assert!(truncated.was_truncated() && truncated.choices[0].was_truncated());
+		assert!(!completion("stop").was_truncated());
+
+		let chunk = |delta: &str, finish_reason: &str| {
+			format!(
+				"data: {{\"id\":\"chatcmpl-1\",\"created\":1,\"choices\":[{{\"index\":0,\
+				\"delta\":{delta},\"finish_reason\":{finish_reason}}}]}}\n\n"
+			)
+		};
+		let stream = |finish_reason: &str| -> ChatStream {
+			let body = [
+				chunk(r#"{"role":"assistant","content":"Once upon a"}"#, "null"),
+				chunk("{}", finish_reason),
+				"data: [DONE]\n\n".to_string(),
+			]
+			.concat();
+			new_test_stream(Box::leak(body.into_boxed_str()), 16)
+		};
+		let choices = stream(r#""length""#).collect_choices().unwrap();
+		assert!(choices[0].was_truncated());
+		assert!(!stream(r#""stop""#).collect_choices().unwrap()[0].was_truncated());
+
+		let body = ChatBody::builder().model("gpt-4o").user("Tell me a story.").build().unwrap();
+		let partial = truncated.first_message().unwrap().text().unwrap();
+		let next = body.continuation(partial);
+		assert_eq!(next.model, "gpt-4o");
+		let messages: Vec<_> =
+			next.messages.iter().map(|message| (message.role.clone(), message.text())).collect();
+		assert_eq!(
+			messages,
+			[
+				(Role::User, Some("Tell me a story.")),
+				(Role::Assistant, Some("Once upon a")),
+				(Role::User, Some(super::CONTINUE_PROMPT)),
+			]
+		);
+		assert!(next.validate().is_ok());
+	}
+
+	#[test]
+	fn test_chat_stream_tool_calls() {
+		let chunk = |tool_calls: &str, finish_reason: &str| {
+			format!(
+				"data: {{\"id\":\"chatcmpl-1\",\"created\":1,\"choices\":[{{\"index\":0,\
+				\"delta\":{{\"tool_calls\":{tool_calls}}},\"finish_reason\":{finish_reason}}}]}}\n\n"
+			)
+		};
+		let body = [
+			chunk(
+				r#"[{"index":0,"id":"call_1","type":"function","function":{"name":"get_weather","arguments":""}}]"#,
+				"null",
+			),
+			chunk(r#"[{"index":0,"function":{"arguments":"{\"city\": \"Par"}}]"#, "null"),
+			chunk(r#"[{"index":0,"function":{"arguments":"is\"}"}}]"#, "null"),
+			chunk(
+				r#"[{"index":1,"id":"call_2","type":"function","function":{"name":"get_time","arguments":"{}"}}]"#,
+				"null",
+			),
+			chunk("null", r#""tool_calls""#),
+			"data: [DONE]\n\n".to_string(),
+		];
+
+		let mut tool_calls = ToolCallsAccumulator::new();
+		let mut completed = Vec::new();
+		for chunk in &body[..5] {
+			let chunk = serde_json::from_str(chunk.strip_prefix("data: ").unwrap()).unwrap();
+			completed.push(tool_calls.push(&chunk).len());
+		}
+		assert_eq!(completed, [0, 0, 0, 1, 1]);
+		let calls = tool_calls.into_tool_calls();
+		assert_eq!(calls[0].function.arguments, r#"{"city": "Paris"}"#);
+
+		let stream: ChatStream = new_test_stream(Box::leak(body.concat().into_boxed_str()), 7);
+		let calls = stream.collect_tool_calls().unwrap();
+		assert_eq!(calls.len(), 2);
+		assert_eq!(
+			(calls[0].id.as_str(), calls[0].function.name.as_str()),
+			("call_1", "get_weather")
+		);
+		assert_eq!(calls[0].function.arguments, r#"{"city": "Paris"}"#);
+		assert_eq!((calls[1].id.as_str(), calls[1].function.arguments.as_str()), ("call_2", "{}"));
+		assert_eq!(calls[1].kind, "function");
+	}
+
+	#[test]
+	fn test_chat_refusal() {
+		let completion: Completion = serde_json::from_str(
+			r#"{"id": "chatcmpl-1", "object": "chat.completion", "created": 1, "model": "gpt-4o",
+			"choices": [{"index": 0, "finish_reason": "stop", "logprobs": null, "message": {
+				"role": "assistant", "content": null, "refusal": "I can't help with that."}}],
+			"usage": {"prompt_tokens": 9, "completion_tokens": 6, "total_tokens": 15}}"#,
+		)
+		.unwrap();
+		let message = completion.first_message().unwrap();
+		assert!(message.content.is_none());
+		assert_eq!(message.refusal.as_deref(), Some("I can't help with that."));
+		assert!(serde_json::to_value(Message::user("Hi")).unwrap().get("refusal").is_none());
+
+		let reasons: Vec<FinishReason> =
+			serde_json::from_str(r#"["stop", "length", "tool_calls", "content_filter", "new"]"#)
+				.unwrap();
+		assert_eq!(
+			reasons,
+			[
+				FinishReason::Stop,
+				FinishReason::Length,
+				FinishReason::ToolCalls,
+				FinishReason::ContentFilter,
+				FinishReason::Other
+			]
+		);
+	}
+
+	#[test]
+	fn test_chat_logprobs() {
+		let completion: Completion = serde_json::from_str(
+			r#"{
+				"id": "chatcmpl-9",
+				"object": "chat.completion",
+				"created": 1717000000,
+				"model": "gpt-4o-2024-05-13",
+				"choices": [{
+					"index": 0,
+					"message": {"role": "assistant", "content": "Hello!"},
+					"logprobs": {
+						"content": [
+							{"token": "Hello", "logprob": -0.31725305, "bytes": [72, 101, 108, 108, 111],
+							 "top_logprobs": [
+								{"token": "Hello", "logprob": -0.31725305, "bytes": [72, 101, 108, 108, 111]},
+								{"token": "Hi", "logprob": -1.3190403, "bytes": [72, 105]}
+							 ]},
+							{"token": "!", "logprob": -0.02380986, "bytes": [33], "top_logprobs": []}
+						],
+						"refusal": null
+					},
+					"finish_reason": "stop"
+				}],
+				"usage": {"prompt_tokens": 9, "completion_tokens": 2, "total_tokens": 11}
+			}"#,
+		)
+		.unwrap();
+		let content = completion.choices[0].logprobs.as_ref().unwrap().content.as_ref().unwrap();
+		assert_eq!(content[0].token, "Hello");
+		assert_eq!(content[0].logprob, -0.31725305);
+		assert_eq!(content[0].bytes.as_deref(), Some(&b"Hello"[..]));
+		assert_eq!(content[0].top_logprobs[1].token, "Hi");
+		assert!(content[1].top_logprobs.is_empty());
+
+		let body = "data: {\"id\":\"chatcmpl-9\",\"created\":1,\"choices\":[{\"index\":0,\
+			\"delta\":{\"content\":\"Hello\"},\"logprobs\":{\"content\":[{\"token\":\"Hello\",\
+			\"logprob\":-0.3,\"bytes\":[72,101,108,108,111],\"top_logprobs\":[]}]},\
+			\"finish_reason\":null}]}\n\ndata: [DONE]\n\n";
+		let chunks: Vec<_> =
+			new_test_stream::<super::ChatCompletionChunk>(body, 1024).map(Result::unwrap).collect();
+		let logprobs = chunks[0].choices[0].logprobs.as_ref().unwrap();
+		assert_eq!(logprobs.content.as_ref().unwrap()[0].logprob, -0.3);
+	}
+
+	#[test]
+	fn test_chat_stream_to() {
+		let chunk = |delta: &str| {
+			format!(
+				"data: {{\"id\":\"chatcmpl-1\",\"created\":1,\"choices\":[{{\"index\":0,\
+				\"delta\":{{\"content\":\"{delta}\"}},\"finish_reason\":null}}]}}\n\n"
+			)
+		};
+		let usage = "data: {\"id\":\"chatcmpl-1\",\"created\":1,\"choices\":[],\
+			\"usage\":{\"prompt_tokens\":5,\"completion_tokens\":2,\"total_tokens\":7}}\n\n";
+		let body = [chunk("Hello"), chunk(", world"), usage.to_string(), "data: [DONE]\n\n".into()];
+		let mock = MockTransport::new()
+			.with_response("chat/completions", MockResponse::new(200, body.concat()))
+			.with_response("chat/completions", MockResponse::new(200, chunk("Hel")));
+		let openai = new_test_mock(&mock);
+		let body = ChatBody::builder().model("gpt-4o").user("Hi").build().unwrap();
+
+		let mut out = Vec::new();
+		let (message, usage) = openai.chat_completion_stream_to(&body, &mut out).unwrap();
+		assert_eq!(out, b"Hello, world");
+		assert_eq!(message.text(), Some("Hello, world"));
+		assert_eq!(usage.unwrap().total_tokens, Some(7));
+
+		let mut out = Vec::new();
+		let rs = openai.chat_completion_stream_to(&body, &mut out);
+		assert!(matches!(rs, Err(Error::StreamInterrupted(1))));
+		assert_eq!(out, b"Hel");
+	}
+
+	#[test]
+	fn test_chat_stream_usage() {
+		let body =
+			"data: {\"id\":\"chatcmpl-1\",\"object\":\"chat.completion.chunk\",\"created\":1,\
+			\"model\":\"gpt-4o\",\"choices\":[{\"index\":0,\"delta\":{\"content\":\"Hi\"},\
+			\"finish_reason\":\"stop\"}],\"usage\":null}\n\n\
+			data: {\"id\":\"chatcmpl-1\",\"object\":\"chat.completion.chunk\",\"created\":1,\
+			\"model\":\"gpt-4o\",\"choices\":[],\
+			\"usage\":{\"prompt_tokens\":9,\"completion_tokens\":1,\"total_tokens\":10}}\n\n\
+			data: [DONE]\n\n";
+		let mut stream: ChatStream = new_test_stream(body, 7);
+		let chunk = stream.next().unwrap().unwrap();
+		assert!(chunk.usage.is_none());
+		assert!(stream.usage().is_none());
+		let chunk = stream.next().unwrap().unwrap();
+		assert!(chunk.choices.is_empty());
+		assert_eq!(chunk.usage.unwrap().total_tokens, Some(10));
+		assert!(stream.next().is_none());
+		assert_eq!(stream.usage().unwrap().prompt_tokens, Some(9));
+	}
+
+	#[test]
+	fn test_chat_stream_keep_alive() {
+		let body = "data: {\"id\":\"chatcmpl-1\",\"created\":1,\"choices\":[]}\n\n\
+			data: {\"id\":\"chatcmpl-1\",\"created\":1,\"choices\":[{\"index\":0,\
+			\"delta\":{\"content\":\"Hi\"},\"finish_reason\":\"stop\"}]}\n\n\
+			data: {\"id\":\"chatcmpl-1\",\"created\":1,\"choices\":[]}\n\n\
+			data: {\"id\":\"chatcmpl-1\",\"created\":1,\"choices\":[],\
+			\"usage\":{\"prompt_tokens\":5,\"completion_tokens\":1,\"total_tokens\":6}}\n\n\
+			data: [DONE]\n\n";
+		let mock =
+			MockTransport::new().with_response("chat/completions", MockResponse::new(200, body));
+		let openai = new_test_mock(&mock);
+		let body = ChatBody::builder().model("gpt-4o").user("Hi").build().unwrap();
+		let chunks: Vec<_> =
+			openai.chat_completion_stream(&body).unwrap().map(Result::unwrap).collect();
+		assert_eq!(chunks.len(), 2);
+		assert_eq!(chunks[0].choices[0].delta.content.as_deref(), Some("Hi"));
+		assert!(chunks[1].choices.is_empty());
+		assert_eq!(chunks[1].usage.as_ref().unwrap().total_tokens, Some(6));
+	}
+
+	#[cfg(feature = "tokenizer")]
+	#[test]
+	fn test_chat_truncate_to_fit() {
+		let history = "Tell me more. ".repeat(2_000);
+		let body = ChatBody::builder()
+			.model("gpt-4")
+			.system("You are a helpful assistant.")
+			.user(history.as_str())
+			.assistant(history.as_str())
+			.user("Summarize it.")
+			.max_completion_tokens(1000)
+			.truncate_to_fit("gpt-4")
+			.build()
+			.unwrap();
+		let texts: Vec<_> = body.messages.iter().map(|message| message.text().unwrap()).collect();
+		assert_eq!(texts, ["You are a helpful assistant.", "Summarize it."]);
+
+		let rs = ChatBody::builder()
+			.user(history.as_str())
+			.max_completion_tokens(4000)
+			.truncate_to_fit("gpt-4")
+			.build();
+		assert!(matches!(rs, Err(Error::InvalidRequest(_))));
+		let rs = ChatBody::builder().user("Hi").truncate_to_fit("my-model").build();
+		assert!(matches!(rs, Err(Error::InvalidRequest(_))));
+	}
+
+	#[cfg(feature = "tokenizer")]
+	#[test]
+	fn test_chat_logit_bias_text() {
+		let body = ChatBody::builder()
+			.model("gpt-4o")
+			.user("What is the capital of France?")
+			.logit_bias([(1, 5)].into())
+			.logit_bias_text([(" Paris", -100), (" Lyon", 10)])
+			.build()
+			.unwrap();
+		let logit_bias = body.logit_bias.unwrap();
+		let expected = crate::tokenizer::logit_bias("gpt-4o", [(" Paris", -100), (" Lyon", 10)]);
+		assert_eq!(logit_bias.len(), 1 + expected.len());
+		assert_eq!(logit_bias[&1], 5);
+		assert!(expected.iter().all(|(token, bias)| logit_bias[token] == *bias));
+	}
+
+	#[test]
+	fn test_chat_compatible_server() {
+		use crate::apis::embeddings::{EmbeddingsApi, EmbeddingsBody};
+		use crate::openai::{new_test_response, new_test_server};
+
+		// The minimal responses of OpenAI-compatible servers, without id, created, usage or
+		// system_fingerprint.
+		let completion = r#"{"object": "chat.completion", "model": "llama3",
+			"choices": [{"message": {"role": "assistant", "content": "Hi!"}, "finish_reason": "stop"}]}"#;
+		let stream = "data: {\"choices\": [{\"delta\": {\"role\": \"assistant\", \"content\": \"Hi\"}}]}\n\n\
+			data: {\"choices\": [{\"delta\": {\"content\": \"!\"}, \"finish_reason\": \"stop\"}]}\n\n\
+			data: [DONE]\n\n";
+		let embeddings = r#"{"object": "list", "data": [{"embedding": [0.5, 0.25], "index": 0}]}"#;
+		let (url, server) = new_test_server(vec![
+			new_test_response(200, &[], completion),
+			new_test_response(200, &[("Content-Type", "text/event-stream")], stream),
+			new_test_response(200, &[], embeddings),
+		]);
+		let openai = OpenAI::new(Auth::new("ollama"), &url);
+		let body = ChatBody::builder().model("llama3").user("Hello!").build().unwrap();
+
+		let completion = openai.chat_completion_create(&body).unwrap();
+		assert_eq!(completion.first_message().unwrap().text(), Some("Hi!"));
+		assert!(completion.id.is_none() && completion.created.is_none());
+		assert!(completion.usage.is_none());
+		let chunks: Vec<_> =
+			openai.chat_completion_stream(&body).unwrap().map(Result::unwrap).collect();
+		let text: String =
+			chunks.iter().filter_map(|chunk| chunk.choices[0].delta.content.as_deref()).collect();
+		assert_eq!(text, "Hi!");
+		assert_eq!(chunks[1].choices[0].finish_reason, Some(FinishReason::Stop));
+		let body = EmbeddingsBody {
+			model: "nomic-embed-text".into(),
+			input: "Hi".into(),
+			dimensions: None,
+			encoding_format: None,
+			user: None,
+		};
+		let embeddings = openai.embeddings_create(&body).unwrap();
+		assert_eq!(embeddings.data.unwrap()[0].embedding.as_deref(), Some(&[0.5, 0.25][..]));
+
+		let requests = server.join().unwrap();
+		assert!(requests[0].starts_with("POST /v1/chat/completions "));
+		assert!(requests[0].to_lowercase().contains("authorization: bearer ollama\r\n"));
+	}
+
+	#[test]
+	fn test_chat_body_from_reader() {
+		let template = serde_json::json!({
+			"model": "gpt-4o-mini",
+			"messages": [{"role": "system", "content": "You are a poet."}],
+			"max_completion_tokens": 100,
+			"new_parameter": "new_value",
+			"web_search_options": {"search_context_size": "low"},
+		});
+		let mut body = ChatBody::from_reader(template.to_string().as_bytes()).unwrap();
+		assert_eq!(body.max_completion_tokens, Some(100));
+		assert_eq!(body.extra.len(), 2);
+		assert_eq!(serde_json::to_value(&body).unwrap(), template);
+
+		body.model = "gpt-4o".into();
+		body.messages.push(Message::user("Write a haiku."));
+		let json = serde_json::to_value(&body).unwrap();
+		assert_eq!(json["model"], "gpt-4o");
+		assert_eq!(json["messages"][1]["content"], "Write a haiku.");
+		assert_eq!(json["new_parameter"], "new_value");
+
+		let rs = ChatBody::from_reader(&br#"{"messages": "Hello!"}"#[..]);
+		assert!(matches!(rs, Err(Error::InvalidRequest(_))));
+	}
+
+	#[test]
+	fn test_chat_parallel_tool_calls() {
+		let tool = Tool::function(FunctionDef {
+			name: "get_weather".to_string(),
+			description: None,
+			parameters: None,
+		});
+		let builder = ChatBody::builder().model("gpt-4o").user("Weather in Paris and London?");
+		let body = builder.clone().tools(vec![tool.clone()]).build().unwrap();
+		assert!(serde_json::to_value(&body).unwrap().get("parallel_tool_calls").is_none());
+		let body = builder.clone().tools(vec![tool]).parallel_tool_calls(false).build().unwrap();
+		assert_eq!(serde_json::to_value(&body).unwrap()["parallel_tool_calls"], false);
+
+		let rs = builder.parallel_tool_calls(false).build();
+		assert!(matches!(rs, Err(Error::InvalidRequest(_))));
+	}
+
+	#[test]
+	fn test_chat_stream_text() {
+		let body = "data: {\"id\":\"chatcmpl-1\",\"created\":1,\"choices\":[{\"index\":0,\
+			\"delta\":{\"role\":\"assistant\",\"content\":\"\"},\"finish_reason\":null}]}\n\n\
+			data: {\"id\":\"chatcmpl-1\",\"created\":1,\"choices\":[{\"index\":0,\
+			\"delta\":{\"content\":\"Hello\"},\"finish_reason\":null}]}\n\n\
+			data: {\"id\":\"chatcmpl-1\",\"created\":1,\"choices\":[{\"index\":1,\
+			\"delta\":{\"content\":\"Bonjour\"},\"finish_reason\":null}]}\n\n\
+			data: {\"id\":\"chatcmpl-1\",\"created\":1,\"choices\":[{\"index\":0,\
+			\"delta\":{\"tool_calls\":[{\"index\":0,\"id\":\"call_1\",\"type\":\"function\",\
+			\"function\":{\"name\":\"f\",\"arguments\":\"\"}}]},\"finish_reason\":null}]}\n\n\
+			data: {\"id\":\"chatcmpl-1\",\"created\":1,\"choices\":[{\"index\":0,\
+			\"delta\":{\"content\":\", world\"},\"finish_reason\":\"stop\"}]}\n\n\
+			data: {\"id\":\"chatcmpl-1\",\"created\":1,\"choices\":[],\
+			\"usage\":{\"prompt_tokens\":5,\"completion_tokens\":2,\"total_tokens\":7}}\n\n\
+			data: [DONE]\n\n";
+		let mut stream = new_test_stream::<super::ChatCompletionChunk>(body, 9).text();
+		let texts: Vec<_> = (&mut stream).map(Result::unwrap).collect();
+		assert_eq!(texts, ["Hello", ", world"]);
+		assert_eq!(stream.usage().unwrap().total_tokens, Some(7));
+
+		let body = "data: {\"id\":\"chatcmpl-1\",\"created\":1,\"choices\":[{\"index\":0,\
+			\"delta\":{\"content\":\"Hel\"},\"finish_reason\":null}]}\n\n";
+		let mut stream = new_test_stream::<super::ChatCompletionChunk>(body, 9).text();
+		assert_eq!(stream.next().unwrap().unwrap(), "Hel");
+		assert!(matches!(stream.next(), Some(Err(Error::StreamInterrupted(1)))));
+	}
+
+	#[test]
+	fn test_chat_service_tier() {
+		use crate::ServiceTier;
+
+		let completion = r#"{"id": "chatcmpl-1", "object": "chat.completion", "created": 1,
+			"model": "gpt-4o", "service_tier": "flex", "choices": [{"index": 0,
+			"finish_reason": "stop", "message": {"role": "assistant", "content": "Hi!"}}],
+			"usage": {"prompt_tokens": 1, "completion_tokens": 1, "total_tokens": 2}}"#;
+		let mock = MockTransport::new()
+			.with_response("chat/completions", MockResponse::new(200, completion));
+		let openai = new_test_mock(&mock);
+		let builder = ChatBody::builder().model("o3-mini").user("Hi");
+		let body = builder.clone().build().unwrap();
+		assert!(serde_json::to_value(&body).unwrap().get("service_tier").is_none());
+
+		let body = builder.service_tier(ServiceTier::Flex).build().unwrap();
+		let completion = openai.chat_completion_create(&body).unwrap();
+		assert_eq!(completion.service_tier, Some(ServiceTier::Flex));
+		assert!(!completion.extra.contains_key("service_tier"));
+		assert_eq!(mock.requests()[0].json().unwrap()["service_tier"], "flex");
+
+		let tier: ServiceTier = serde_json::from_str(r#""scale""#).unwrap();
+		assert_eq!(tier, ServiceTier::Other);
+	}
+
+	#[test]
+	fn test_stored_chat_completions() {
+		let completion = r#"{"id": "chatcmpl-abc", "object": "chat.completion", "created": 1,
+			"model": "gpt-4o", "metadata": {"topic": "greeting"}, "choices": [{"index": 0,
+			"message": {"role": "assistant", "content": "Hi!"}, "finish_reason": "stop"}]}"#;
+		let completions = format!(
+			r#"{{"object": "list", "data": [{completion}], "first_id": "chatcmpl-abc",
+			"last_id": "chatcmpl-abc", "has_more": false}}"#
+		);
+		let messages = r#"{"object": "list", "data": [{"id": "chatcmpl-abc-0", "role": "user",
+			"content": "Hello", "name": null, "content_parts": null}], "has_more": false}"#;
+		let mock = MockTransport::new()
+			.with_response("chat/completions", MockResponse::new(200, completions))
+			.with_response("chat/completions/chatcmpl-abc", MockResponse::new(200, completion))
+			.with_response(
+				"chat/completions/chatcmpl-abc/messages",
+				MockResponse::new(200, messages),
+			)
+			.with_response(
+				"chat/completions/chatcmpl-abc",
+				MockResponse::new(200, completion.replace("greeting", "welcome")),
+			)
+			.with_response(
+				"chat/completions/chatcmpl-abc",
+				MockResponse::new(200, r#"{"id": "chatcmpl-abc", "deleted": true}"#),
+			);
+		let openai = new_test_mock(&mock);
+
+		let completions: Vec<_> =
+			openai.chat_completions_iter(ListParams::default()).map(Result::unwrap).collect();
+		assert_eq!(completions[0].first_message().unwrap().text(), Some("Hi!"));
+		let completion = openai.chat_completion_retrieve("chatcmpl-abc").unwrap();
+		assert_eq!(completion.metadata.unwrap()["topic"], "greeting");
+		let params = ListParams { limit: Some(10), ..Default::default() };
+		let messages = openai.chat_completion_messages("chatcmpl-abc", &params).unwrap();
+		assert_eq!(messages.data[0].id, "chatcmpl-abc-0");
+		assert_eq!(messages.data[0].message.role, Role::User);
+		let metadata = [("topic".to_string(), "welcome".to_string())].into();
+		let completion = openai.chat_completion_update("chatcmpl-abc", &metadata).unwrap();
+		assert_eq!(completion.metadata.unwrap()["topic"], "welcome");
+		assert!(openai.chat_completion_delete("chatcmpl-abc").unwrap().deleted);
+
+		let requests = mock.requests();
+		assert_eq!(requests[0].url, "https://api.openai.com/v1/chat/completions?limit=100");
+		assert_eq!(requests[1].method, "GET");
+		assert_eq!(
+			requests[2].url,
+			"https://api.openai.com/v1/chat/completions/chatcmpl-abc/messages?limit=10"
+		);
+		assert_eq!(requests[3].method, "POST");
+		assert_eq!(
+			requests[3].json().unwrap(),
+			serde_json::json!({"metadata": {"topic": "welcome"}})
+		);
+		assert_eq!(requests[4].method, "DELETE");
+
+		let metadata = [("k".repeat(65), String::new())].into();
+		let rs = openai.chat_completion_update("chatcmpl-abc", &metadata);
+		assert!(matches!(rs, Err(Error::InvalidRequest(_))));
+		assert_eq!(mock.requests().len(), 5);
 	}
 }
@@ -6,11 +6,16 @@
 
 use std::collections::HashMap;
 
+use crate::models::ModelName;
 use crate::requests::Requests;
+use crate::stream::Stream;
 use crate::*;
 use serde::{Deserialize, Serialize};
 
-use super::{Usage, COMPLETION_CREATE};
+use super::{
+	alters_temperature_and_top_p, chat::StreamOptions, check_penalty, check_sampling,
+	PromptFilterResult, Stop, Usage, COMPLETION_CREATE,
+};
 
 /// Given a prompt, the model will return one or more predicted completions,
 /// and can also return the probabilities of alternative tokens at each position.
@@ -18,22 +23,127 @@ use super::{Usage, COMPLETION_CREATE};
 pub struct Completion {
 	pub id: Option<String>,
 	pub object: Option<String>,
-	pub created: u64,
+	/// The Unix timestamp in seconds, `None` if omitted, e.g. by some OpenAI-compatible servers.
+	pub created: Option<u64>,
 	pub model: Option<String>,
 	pub choices: Vec<Choice>,
-	pub usage: Usage,
+	/// `None` when omitted, e.g. by some OpenAI-compatible servers.
+	pub usage: Option<Usage>,
+	/// The backend configuration that the model runs with.
+	/// Can be used with the seed request parameter to understand when backend changes
+	/// have been made that might impact determinism.
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub system_fingerprint: Option<String>,
+	/// The processing tier used for the request, for chat completions.
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub service_tier: Option<ServiceTier>,
+	/// The `metadata` of the request, for stored chat completions.
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub metadata: Option<HashMap<String, String>>,
+	/// The annotations of the Azure OpenAI content filters on the prompts, `None` for OpenAI.
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub prompt_filter_results: Option<Vec<PromptFilterResult>>,
+	/// The fields not modeled above, e.g. fields added to the API after this crate.
+	/// Unknown fields are never an error, including in the nested objects.
+	#[serde(flatten)]
+	pub extra: serde_json::Map<String, Json>,
 }
 
-/// Request body for `Create completion` API
+impl Completion {
+	/// The message of the first choice, for chat completions.
+	pub fn first_message(&self) -> Option<&Message> {
+		self.messages().next()
+	}
+
+	/// The messages of all the choices, for chat completions requested with `n` above 1.
+	pub fn messages(&self) -> impl Iterator<Item = &Message> {
+		self.choices.iter().filter_map(|choice| choice.message.as_ref())
+	}
+
+	/// The texts of all the choices, for completions.
+	pub fn texts(&self) -> impl Iterator<Item = &str> {
+		self.choices.iter().filter_map(|choice| choice.text.as_deref())
+	}
+
+	/// The choices of a `Prompt::Batch` grouped by prompt, in the order of the prompts, for
+	/// completions requested with `n` choices per prompt. The choices of the prompt `i` have
+	/// the indices `i * n` to `i * n + n - 1`, and are sorted by index.
+	pub fn choices_by_prompt(&self, n: u32) -> Vec<Vec<&Choice>> {
+		let n = n.max(1);
+		let mut groups: Vec<Vec<&Choice>> = Vec::new();
+		for choice in &self.choices {
+			let prompt = (choice.index / n) as usize;
+			if groups.len() <= prompt {
+				groups.resize_with(prompt + 1, Vec::new);
+			}
+			groups[prompt].push(choice);
+		}
+		for group in &mut groups {
+			group.sort_by_key(|choice| choice.index);
+		}
+		groups
+	}
+}
+
+/// A chunk of a streamed completion.
 #[derive(Debug, Serialize, Deserialize)]
+pub struct CompletionChunk {
+	pub id: Option<String>,
+	pub object: Option<String>,
+	/// The Unix timestamp in seconds, `None` if omitted, e.g. by some OpenAI-compatible servers.
+	pub created: Option<u64>,
+	pub model: Option<String>,
+	pub choices: Vec<Choice>,
+	/// Only set for the last chunk, when `stream_options.include_usage` is set.
+	pub usage: Option<Usage>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub system_fingerprint: Option<String>,
+}
+
+pub type CompletionStream = Stream<CompletionChunk>;
+
+/// A single prompt, or a batch of prompts completed in one request.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Prompt {
+	Single(String),
+	Batch(Vec<String>),
+}
+
+impl From<&str> for Prompt {
+	fn from(prompt: &str) -> Self {
+		Self::Single(prompt.to_string())
+	}
+}
+
+impl From<String> for Prompt {
+	fn from(prompt: String) -> Self {
+		Self::Single(prompt)
+	}
+}
+
+impl From<Vec<String>> for Prompt {
+	fn from(prompt: Vec<String>) -> Self {
+		Self::Batch(prompt)
+	}
+}
+
+impl From<Vec<&str>> for Prompt {
+	fn from(prompt: Vec<&str>) -> Self {
+		Self::Batch(prompt.into_iter().map(str::to_string).collect())
+	}
+}
+
+/// Request body for `Create completion` API
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct CompletionsBody {
 	/// ID of the model to use
-	pub model: String,
+	pub model: ModelName,
 	/// The prompt(s) to generate completions for,
 	/// encoded as a string, array of strings, array of tokens, or array of token arrays.
 	/// Defaults to <|endoftext|>
 	#[serde(skip_serializing_if = "Option::is_none")]
-	pub prompt: Option<Vec<String>>,
+	pub prompt: Option<Prompt>,
 	/// The suffix that comes after a completion of inserted text.
 	/// Defaults to null
 	#[serde(skip_serializing_if = "Option::is_none")]
@@ -71,6 +181,9 @@ pub struct CompletionsBody {
 	/// Defaults to false
 	#[serde(skip_serializing_if = "Option::is_none")]
 	pub stream: Option<bool>,
+	/// Options for streaming response. Only set this when you set stream: true.
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub stream_options: Option<StreamOptions>,
 	/// Include the log probabilities on the logprobs most likely tokens,
 	/// as well the chosen tokens. For example, if logprobs is 5,
 	/// the API will return a list of the 5 most likely tokens.
@@ -89,7 +202,7 @@ pub struct CompletionsBody {
 	/// The returned text will not contain the stop sequence.
 	/// Defaults to null
 	#[serde(skip_serializing_if = "Option::is_none")]
-	pub stop: Option<Vec<String>>,
+	pub stop: Option<Stop>,
 	/// Number between -2.0 and 2.0.
 	/// Positive values penalize new tokens based on whether they appear in the text so far,
 	/// increasing the model's likelihood to talk about new topics.
@@ -113,45 +226,117 @@ pub struct CompletionsBody {
 	/// Defaults to 1
 	#[serde(skip_serializing_if = "Option::is_none")]
 	pub best_of: Option<i32>,
+	/// If specified, the system will make a best effort to sample deterministically,
+	/// such that repeated requests with the same seed and parameters should return the same result.
+	/// Determinism is not guaranteed, refer to the system_fingerprint response parameter
+	/// to monitor changes in the backend.
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub seed: Option<i64>,
 	/// Modify the likelihood of specified tokens appearing in the completion.
 	/// Accepts a json object that maps tokens (specified by their token ID in the GPT tokenizer)
 	/// to an associated bias value from -100 to 100. You can use this tokenizer tool (which works for both GPT-2 and GPT-3) to convert text to token IDs. Mathematically, the bias is added to the logits generated by the model prior to sampling. The exact effect will vary per model, but values between -1 and 1 should decrease or increase likelihood of selection; values like -100 or 100 should result in a ban or exclusive selection of the relevant token.
 	/// As an example, you can pass {"50256": -100} to prevent the <|endoftext|> token from being generated.
 	/// Defaults to null
 	#[serde(skip_serializing_if = "Option::is_none")]
-	pub logit_bias: Option<HashMap<String, String>>,
+	pub logit_bias: Option<HashMap<u32, i32>>,
 	/// A unique identifier representing your end-user, which can help OpenAI to monitor and detect abuse.
 	/// Learn more: https://platform.openai.com/docs/guides/safety-best-practices/end-user-ids
 	#[serde(skip_serializing_if = "Option::is_none")]
 	pub user: Option<String>,
 }
 
+impl CompletionsBody {
+	/// Checks the parameters the API would reject, failing with `Error::InvalidRequest`.
+	/// Done before sending the body.
+	pub fn validate(&self) -> ApiResult<()> {
+		check_penalty("presence_penalty", self.presence_penalty)?;
+		check_penalty("frequency_penalty", self.frequency_penalty)?;
+		check_sampling(self.temperature, self.top_p, self.n)?;
+		if alters_temperature_and_top_p(self.temperature, self.top_p) {
+			log::warn!("Both temperature and top_p are set, OpenAI recommends altering only one");
+		}
+		Ok(())
+	}
+}
+
 pub trait CompletionsApi {
 	/// Creates a completion for the provided prompt and parameters
 	fn completion_create(&self, completions_body: &CompletionsBody) -> ApiResult<Completion>;
+	/// Creates a completion for the provided prompt and parameters, streaming it back chunk by chunk.
+	/// `completions_body.stream` is ignored, it is always sent as `true`.
+	fn completion_stream(&self, completions_body: &CompletionsBody) -> ApiResult<CompletionStream>;
 }
 
 impl CompletionsApi for OpenAI {
 	fn completion_create(&self, completions_body: &CompletionsBody) -> ApiResult<Completion> {
-		let request_body = serde_json::to_value(completions_body).unwrap();
+		completions_body.validate()?;
+		let mut request_body = serde_json::to_value(completions_body).unwrap();
+		self.default_model(&mut request_body, &self.default_models.completions)?;
 		let res = self.post(COMPLETION_CREATE, request_body)?;
-		let completion: Completion = serde_json::from_value(res.clone()).unwrap();
+		let completion: Completion = from_json(&res)?;
 		Ok(completion)
 	}
+
+	fn completion_stream(&self, completions_body: &CompletionsBody) -> ApiResult<CompletionStream> {
+		completions_body.validate()?;
+		let mut request_body = serde_json::to_value(completions_body).unwrap();
+		self.default_model(&mut request_body, &self.default_models.completions)?;
+		request_body["stream"] = Json::Bool(true);
+		let reader = self.post_stream(COMPLETION_CREATE, request_body)?;
+		Ok(Stream::new(reader).skipping_empty_choices())
+	}
 }
 
 #[cfg(test)]
 mod tests {
-	use crate::openai::new_test_openai;
+	use crate::openai::{new_test_mock, new_test_openai};
+
+	use super::{Completion, CompletionStream, CompletionsApi, CompletionsBody, Prompt, Stop};
+	use crate::apis::{total_usage, CompletionResponse};
+	use crate::{stream::new_test_stream, Error};
 
-	use super::{CompletionsApi, CompletionsBody};
+	#[test]
+	fn test_completion_unknown_fields() {
+		let completion: Completion = serde_json::from_str(
+			r#"{"id": "chatcmpl-1", "object": "chat.completion", "created": 1, "model": "gpt-4o",
+			"future_tier": "default", "future_field": {"nested": [1, 2]},
+			"choices": [{"index": 0, "finish_reason": "stop", "future_choice_field": 1,
+				"message": {"role": "assistant", "content": "Hi!", "future_message_field": "x"}}],
+			"usage": {"prompt_tokens": 1, "completion_tokens": 1, "total_tokens": 2,
+				"future_usage_field": 3}}"#,
+		)
+		.unwrap();
+		assert_eq!(completion.first_message().unwrap().text(), Some("Hi!"));
+		assert_eq!(completion.extra["future_tier"], "default");
+		assert_eq!(completion.extra["future_field"]["nested"][1], 2);
+		assert!(!completion.extra.contains_key("choices"));
+	}
+
+	#[test]
+	fn test_completion_without_usage() {
+		let completion: Completion = serde_json::from_str(
+			r#"{"id": "cmpl-1", "model": "llama3",
+			"choices": [{"index": 0, "finish_reason": "stop", "text": "Hi!"}]}"#,
+		)
+		.unwrap();
+		assert_eq!(completion.choice_text(0), Some("Hi!"));
+		assert!(completion.usage.is_none());
+
+		let other: Completion = serde_json::from_str(
+			r#"{"choices": [], "usage": {"prompt_tokens": 2, "completion_tokens": 3, "total_tokens": 5}}"#,
+		)
+		.unwrap();
+		let usage = total_usage([&completion, &other]);
+		assert_eq!((usage.prompt_tokens, usage.total_tokens), (Some(2), Some(5)));
+		assert_eq!(total_usage([&completion]).total_tokens, None);
+	}
 
 	#[test]
 	fn test_completions() {
 		let openai = new_test_openai();
 		let body = CompletionsBody {
-			model: "babbage".to_string(),
-			prompt: Some(vec!["Say this is a test".to_string()]),
+			model: "babbage".into(),
+			prompt: Some("Say this is a test".into()),
 			suffix: None,
 			max_tokens: Some(7),
 			temperature: Some(0_f32),
@@ -160,16 +345,158 @@ mod tests {
 			stream: Some(false),
 			logprobs: None,
 			echo: None,
-			stop: Some(vec!["\n".to_string()]),
+			stop: Some("\n".into()),
 			presence_penalty: None,
 			frequency_penalty: None,
 			best_of: None,
 			logit_bias: None,
 			user: None,
+			..Default::default()
 		};
 		let rs = openai.completion_create(&body);
 		let choice = rs.unwrap().choices;
 		let text = &choice[0].text.as_ref().unwrap();
 		assert!(text.contains("of the new system"));
 	}
+
+	#[test]
+	fn test_completions_seed() {
+		let body = CompletionsBody { model: "gpt-3.5-turbo-instruct".into(), ..Default::default() };
+		assert_eq!(
+			serde_json::to_value(&body).unwrap(),
+			serde_json::json!({"model": "gpt-3.5-turbo-instruct"})
+		);
+		let body = CompletionsBody { seed: Some(42), ..body };
+		assert_eq!(serde_json::to_value(&body).unwrap()["seed"], 42);
+
+		let json = serde_json::json!({
+			"id": "cmpl-1",
+			"object": "text_completion",
+			"created": 1,
+			"model": "gpt-3.5-turbo-instruct",
+			"choices": [],
+			"usage": {"prompt_tokens": 1, "completion_tokens": 0, "total_tokens": 1},
+			"system_fingerprint": "fp_44709d6fcb",
+		});
+		let completion: Completion = serde_json::from_value(json.clone()).unwrap();
+		assert_eq!(completion.system_fingerprint.as_deref(), Some("fp_44709d6fcb"));
+		assert_eq!(
+			serde_json::to_value(&completion).unwrap()["system_fingerprint"],
+			json["system_fingerprint"]
+		);
+	}
+
+	#[test]
+	fn test_completions_stop() {
+		let body = CompletionsBody { model: "gpt-3.5-turbo-instruct".into(), ..Default::default() };
+		assert!(serde_json::to_value(&body).unwrap().get("stop").is_none());
+		let body = CompletionsBody { stop: Some("\n".into()), ..body };
+		assert_eq!(serde_json::to_value(&body).unwrap()["stop"], "\n");
+		let body = CompletionsBody { stop: Some(Stop::new(["\n", "END"]).unwrap()), ..body };
+		assert_eq!(serde_json::to_value(&body).unwrap()["stop"], serde_json::json!(["\n", "END"]));
+
+		assert!(matches!(Stop::new(["1", "2", "3", "4", "5"]), Err(Error::InvalidRequest(_))));
+		// No sequences are left out rather than sent as `[]`.
+		assert!(matches!(Stop::new(Vec::<String>::new()), Err(Error::InvalidRequest(_))));
+		let body = CompletionsBody { stop: Stop::new(Vec::<String>::new()).ok(), ..body };
+		assert!(serde_json::to_value(&body).unwrap().get("stop").is_none());
+		let stop: Stop = serde_json::from_value(serde_json::json!(["a", "b"])).unwrap();
+		assert_eq!(stop.sequences(), ["a", "b"]);
+		assert!(
+			serde_json::from_value::<Stop>(serde_json::json!(["1", "2", "3", "4", "5"])).is_err()
+		);
+	}
+
+	#[test]
+	fn test_completions_penalties() {
+		let body = CompletionsBody {
+			model: "gpt-3.5-turbo-instruct".into(),
+			frequency_penalty: Some(1.0),
+			logit_bias: Some([(50256, -100), (1, 5)].into()),
+			..Default::default()
+		};
+		assert!(body.validate().is_ok());
+		let json = serde_json::to_value(&body).unwrap();
+		assert_eq!(json["logit_bias"], serde_json::json!({"50256": -100, "1": 5}));
+		let body: CompletionsBody = serde_json::from_value(json).unwrap();
+		assert_eq!(body.logit_bias.unwrap()[&50256], -100);
+
+		let body = CompletionsBody {
+			model: "gpt-3.5-turbo-instruct".into(),
+			frequency_penalty: Some(-2.1),
+			..Default::default()
+		};
+		assert!(matches!(body.validate(), Err(Error::InvalidRequest(_))));
+		let body = CompletionsBody { frequency_penalty: None, temperature: Some(2.1), ..body };
+		assert!(matches!(body.validate(), Err(Error::InvalidRequest(_))));
+	}
+
+	#[test]
+	fn test_completions_prompt() {
+		let body = CompletionsBody {
+			model: "gpt-3.5-turbo-instruct".into(),
+			prompt: Some("Say this is a test".into()),
+			..Default::default()
+		};
+		assert_eq!(serde_json::to_value(&body).unwrap()["prompt"], "Say this is a test");
+		let body =
+			CompletionsBody { prompt: Some(vec!["a".to_string(), "b".to_string()].into()), ..body };
+		assert_eq!(serde_json::to_value(&body).unwrap()["prompt"], serde_json::json!(["a", "b"]));
+		assert_eq!(
+			serde_json::from_value::<Prompt>("a".into()).unwrap(),
+			Prompt::Single("a".to_string())
+		);
+	}
+
+	#[test]
+	fn test_choices_by_prompt() {
+		use crate::testing::{MockResponse, MockTransport};
+
+		let choice = |index, text| serde_json::json!({"text": text, "index": index, "logprobs": null, "finish_reason": "stop"});
+		let completion = serde_json::json!({
+			"id": "cmpl-1",
+			"object": "text_completion",
+			"created": 1,
+			"model": "gpt-3.5-turbo-instruct",
+			"choices": [choice(1, "a2"), choice(0, "a1"), choice(2, "b1"), choice(3, "b2")],
+			"usage": {"prompt_tokens": 4, "completion_tokens": 8, "total_tokens": 12},
+		});
+		let mock = MockTransport::new()
+			.with_response("completions", MockResponse::new(200, completion.to_string()));
+		let openai = new_test_mock(&mock);
+		let body = CompletionsBody {
+			model: "gpt-3.5-turbo-instruct".into(),
+			prompt: Some(vec!["Say a", "Say b"].into()),
+			n: Some(2),
+			..Default::default()
+		};
+		let completion = openai.completion_create(&body).unwrap();
+		assert_eq!(
+			mock.requests()[0].json().unwrap()["prompt"],
+			serde_json::json!(["Say a", "Say b"])
+		);
+
+		let groups = completion.choices_by_prompt(2);
+		let texts: Vec<Vec<_>> = groups
+			.iter()
+			.map(|group| group.iter().map(|choice| choice.text.as_deref().unwrap()).collect())
+			.collect();
+		assert_eq!(texts, [["a1", "a2"], ["b1", "b2"]]);
+		assert_eq!(completion.choices_by_prompt(1).len(), 4);
+	}
+
+	#[test]
+	fn test_completions_stream_decode() {
+		let body = "data: {\"id\":\"cmpl-1\",\"object\":\"text_completion\",\"created\":1,\
+			\"model\":\"gpt-3.5-turbo-instruct\",\"choices\":[{\"text\":\"This\",\"index\":0,\
+			\"logprobs\":null,\"finish_reason\":null}]}\n\n\
+			data: {\"id\":\"cmpl-1\",\"object\":\"text_completion\",\"created\":1,\
+			\"model\":\"gpt-3.5-turbo-instruct\",\"choices\":[{\"text\":\" is a test\",\"index\":0,\
+			\"logprobs\":null,\"finish_reason\":\"stop\"}]}\n\n\
+			data: [DONE]\n\n";
+		let stream: CompletionStream = new_test_stream(body, 9);
+		let text: String =
+			stream.map(|chunk| chunk.unwrap().choices[0].text.clone().unwrap()).collect();
+		assert_eq!(text, "This is a test");
+	}
 }
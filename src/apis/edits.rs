@@ -2,15 +2,16 @@
 
 //! Edits API
 
+use crate::models::ModelName;
 use crate::requests::Requests;
 use crate::*;
 use serde::{Deserialize, Serialize};
 
 use super::{completions::Completion, EDIT_CREATE};
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct EditsBody {
-	pub model: String,
+	pub model: ModelName,
 	pub instruction: String,
 	#[serde(skip_serializing_if = "Option::is_none")]
 	pub input: Option<String>,
@@ -31,7 +32,7 @@ impl EditsApi for OpenAI {
 	fn edit_create(&self, chat_body: &EditsBody) -> ApiResult<Completion> {
 		let request_body = serde_json::to_value(chat_body).unwrap();
 		let res = self.post(EDIT_CREATE, request_body)?;
-		let completion: Completion = serde_json::from_value(res.clone()).unwrap();
+		let completion: Completion = from_json(&res)?;
 		Ok(completion)
 	}
 }
@@ -47,7 +48,7 @@ mod tests {
 	fn test_edit_create() {
 		let openai = new_test_openai();
 		let body = EditsBody {
-			model: "text-davinci-edit-001".to_string(),
+			model: "text-davinci-edit-001".into(),
 			temperature: Some(1.0),
 			top_p: None,
 			n: Some(2),
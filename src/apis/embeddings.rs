@@ -4,74 +4,559 @@
 
 //! Embeddings API
 
-use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Mutex;
 
-use crate::requests::Requests;
+use base64::Engine;
+use serde::de::{self, SeqAccess, Visitor};
+use serde::{Deserialize, Deserializer, Serialize};
+
+use crate::models::ModelName;
 use crate::*;
 
 use super::{Usage, EMBEDDINGS_CREATE};
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct EmbeddingsBody {
 	/// ID of the model to use. You can use the List models API to see all of your available models,
 	/// or see our Model overview for descriptions of them.
-	pub model: String,
+	pub model: ModelName,
 	/// Input text to get embeddings for, encoded as a string or array of tokens. To get embeddings for multiple inputs in a single request,
 	/// pass an array of strings or array of token arrays. Each input must not exceed 8192 tokens in length.
-	pub input: Vec<String>,
+	pub input: EmbeddingInput,
+	/// The number of dimensions the resulting output embeddings should have.
+	/// Only supported in `text-embedding-3` and later models.
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub dimensions: Option<u32>,
+	/// The format to return the embeddings in. Can be either float or base64.
+	/// Base64 embeddings are decoded into floats, so `EmbeddingData::embedding` is the same either way.
+	/// Defaults to float
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub encoding_format: Option<String>,
 	/// A unique identifier representing your end-user, which can help OpenAI to monitor and detect abuse.
 	#[serde(skip_serializing_if = "Option::is_none")]
 	pub user: Option<String>,
 }
 
+/// The most tokens of an input the embedding models accept.
+pub const EMBEDDING_TOKEN_LIMIT: usize = 8191;
+
+/// The most inputs of an embedding request.
+pub const EMBEDDING_BATCH_LIMIT: usize = 2048;
+
+#[cfg(feature = "tokenizer")]
+impl EmbeddingsBody {
+	/// Checks that every input fits in `EMBEDDING_TOKEN_LIMIT` tokens of the model, failing with
+	/// `Error::InvalidRequest`. Done before sending the body, see `EmbeddingsApi::embed_chunked`
+	/// to embed longer inputs.
+	pub fn validate(&self) -> ApiResult<()> {
+		let inputs = match &self.input {
+			EmbeddingInput::Single(input) => std::slice::from_ref(input),
+			EmbeddingInput::Batch(inputs) => inputs.as_slice(),
+		};
+		for (index, input) in inputs.iter().enumerate() {
+			let tokens = crate::tokenizer::count_tokens(self.model.as_str(), input);
+			if tokens > EMBEDDING_TOKEN_LIMIT {
+				return Err(Error::InvalidRequest(format!(
+					"input[{index}] is {tokens} tokens, embeddings accept at most {EMBEDDING_TOKEN_LIMIT}"
+				)));
+			}
+		}
+		Ok(())
+	}
+}
+
+/// A single input, or a batch of inputs embedded in one request.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum EmbeddingInput {
+	Single(String),
+	Batch(Vec<String>),
+}
+
+impl From<&str> for EmbeddingInput {
+	fn from(input: &str) -> Self {
+		Self::Single(input.to_string())
+	}
+}
+
+impl From<String> for EmbeddingInput {
+	fn from(input: String) -> Self {
+		Self::Single(input)
+	}
+}
+
+impl From<Vec<String>> for EmbeddingInput {
+	fn from(input: Vec<String>) -> Self {
+		Self::Batch(input)
+	}
+}
+
+impl From<Vec<&str>> for EmbeddingInput {
+	fn from(input: Vec<&str>) -> Self {
+		Self::Batch(input.into_iter().map(str::to_string).collect())
+	}
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Embeddings {
 	pub object: Option<String>,
 	pub data: Option<Vec<EmbeddingData>>,
+	#[serde(default)]
 	pub model: String,
-	pub usage: Usage,
+	/// `None` when omitted, e.g. by some OpenAI-compatible servers.
+	pub usage: Option<Usage>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct EmbeddingData {
 	pub object: Option<String>,
-	pub embedding: Option<Vec<f64>>,
+	#[serde(default, deserialize_with = "deserialize_embedding")]
+	pub embedding: Option<Vec<f32>>,
+	/// The position of the input this embedding was created for.
 	pub index: i32,
 }
 
+/// Accepts either an array of floats or, for `encoding_format: "base64"`,
+/// a base64 string of little-endian `f32`s.
+fn deserialize_embedding<'de, D: Deserializer<'de>>(
+	deserializer: D,
+) -> Result<Option<Vec<f32>>, D::Error> {
+	deserializer.deserialize_option(EmbeddingVisitor)
+}
+
+/// Decodes base64 embeddings from the borrowed string, without copying it first.
+struct EmbeddingVisitor;
+
+impl<'de> Visitor<'de> for EmbeddingVisitor {
+	type Value = Option<Vec<f32>>;
+
+	fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+		formatter.write_str("an array of floats or a base64 string")
+	}
+
+	fn visit_none<E: de::Error>(self) -> Result<Self::Value, E> {
+		Ok(None)
+	}
+
+	fn visit_unit<E: de::Error>(self) -> Result<Self::Value, E> {
+		Ok(None)
+	}
+
+	fn visit_some<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+		deserializer.deserialize_any(self)
+	}
+
+	fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+		let mut embedding = Vec::with_capacity(seq.size_hint().unwrap_or_default());
+		while let Some(value) = seq.next_element()? {
+			embedding.push(value);
+		}
+		Ok(Some(embedding))
+	}
+
+	fn visit_str<E: de::Error>(self, encoded: &str) -> Result<Self::Value, E> {
+		let bytes = base64::engine::general_purpose::STANDARD.decode(encoded).map_err(E::custom)?;
+		if bytes.len() % 4 != 0 {
+			return Err(E::custom("base64 embedding is not a list of f32"));
+		}
+		// `from_le_bytes` reads the API's little-endian layout whatever the host's endianness.
+		let embedding =
+			bytes.chunks_exact(4).map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]])).collect();
+		Ok(Some(embedding))
+	}
+}
+
+/// The embeddings of the chunks of an input, see `EmbeddingsApi::embed_chunked`.
+#[cfg(feature = "tokenizer")]
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChunkedEmbedding {
+	/// The chunks in the order of the input.
+	pub chunks: Vec<EmbeddedChunk>,
+}
+
+#[cfg(feature = "tokenizer")]
+#[derive(Debug, Clone, PartialEq)]
+pub struct EmbeddedChunk {
+	pub text: String,
+	/// The number of tokens of `text`, its weight in `ChunkedEmbedding::combined`.
+	pub tokens: usize,
+	pub embedding: Vec<f32>,
+}
+
+#[cfg(feature = "tokenizer")]
+impl ChunkedEmbedding {
+	/// A single embedding of the whole input: the average of the embeddings of the chunks,
+	/// weighted by their number of tokens, normalized to length 1 like the embeddings of the API.
+	/// Empty if there are no chunks.
+	pub fn combined(&self) -> Vec<f32> {
+		let dimensions = self.chunks.iter().map(|chunk| chunk.embedding.len()).max();
+		let mut combined = vec![0.0; dimensions.unwrap_or_default()];
+		for chunk in &self.chunks {
+			for (sum, x) in combined.iter_mut().zip(&chunk.embedding) {
+				*sum += x * chunk.tokens as f32;
+			}
+		}
+		let norm = combined.iter().map(|x| x * x).sum::<f32>().sqrt();
+		if norm > 0.0 {
+			combined.iter_mut().for_each(|x| *x /= norm);
+		}
+		combined
+	}
+}
+
 pub trait EmbeddingsApi {
 	/// Creates an embedding vector representing the input text.
 	fn embeddings_create(&self, embeddings_body: &EmbeddingsBody) -> ApiResult<Embeddings>;
+	/// Embeds many inputs by splitting them into requests of `batch_size` inputs,
+	/// sending up to `concurrency` requests at a time.
+	///
+	/// The embeddings are returned in the order of the inputs. Like the rest of the client, this
+	/// blocks, sending the requests from `concurrency` scoped threads, and doesn't cancel
+	/// requests: the first error stops dispatching the remaining batches, and is returned once
+	/// the requests in flight complete.
+	/// Before each request, waits for the rate limit to reset if it is exhausted, and requests
+	/// that couldn't be sent are retried according to `OpenAI::with_retry`.
+	fn embed_many(
+		&self,
+		embeddings_body: &EmbeddingsBody,
+		batch_size: usize,
+		concurrency: usize,
+	) -> ApiResult<Vec<Vec<f32>>>;
+	/// Embeds an input longer than the model accepts by splitting it into chunks of at most
+	/// `max_tokens` tokens, capped to `EMBEDDING_TOKEN_LIMIT`, see `tokenizer::split_tokens`.
+	/// The chunks are embedded with `embed_many`, `ChunkedEmbedding::combined` averages them.
+	/// Fails with `Error::InvalidRequest` for a batch input.
+	#[cfg(feature = "tokenizer")]
+	fn embed_chunked(
+		&self,
+		embeddings_body: &EmbeddingsBody,
+		max_tokens: usize,
+	) -> ApiResult<ChunkedEmbedding>;
 }
 
 impl EmbeddingsApi for OpenAI {
 	fn embeddings_create(&self, embeddings_body: &EmbeddingsBody) -> ApiResult<Embeddings> {
-		let request_body = serde_json::to_value(embeddings_body).unwrap();
-		let res = self.post(EMBEDDINGS_CREATE, request_body)?;
-		let embeddings: Embeddings = serde_json::from_value(res.clone()).unwrap();
-		Ok(embeddings)
+		#[cfg(feature = "tokenizer")]
+		embeddings_body.validate()?;
+		let mut request_body = serde_json::to_value(embeddings_body).unwrap();
+		self.default_model(&mut request_body, &self.default_models.embeddings)?;
+		// Parsed from the bytes of the response, decoding base64 embeddings without copies.
+		self.post_as(EMBEDDINGS_CREATE, request_body)
+	}
+
+	fn embed_many(
+		&self,
+		embeddings_body: &EmbeddingsBody,
+		batch_size: usize,
+		concurrency: usize,
+	) -> ApiResult<Vec<Vec<f32>>> {
+		let inputs = match &embeddings_body.input {
+			EmbeddingInput::Single(input) => std::slice::from_ref(input),
+			EmbeddingInput::Batch(inputs) => inputs.as_slice(),
+		};
+		let batch_size = batch_size.max(1);
+		let batches: Vec<_> = inputs.chunks(batch_size).collect();
+		let next = AtomicUsize::new(0);
+		let stop_dispatching = AtomicBool::new(false);
+		let error = Mutex::new(None);
+		let results = Mutex::new(vec![Vec::new(); inputs.len()]);
+
+		std::thread::scope(|scope| {
+			for _ in 0..concurrency.clamp(1, batches.len().max(1)) {
+				scope.spawn(|| {
+					while !stop_dispatching.load(Ordering::SeqCst) {
+						let batch = next.fetch_add(1, Ordering::SeqCst);
+						let Some(inputs) = batches.get(batch) else { break };
+						if let Some(delay) = self.rate_limit().and_then(|info| info.wait_time()) {
+							std::thread::sleep(delay);
+						}
+						let body = EmbeddingsBody {
+							model: embeddings_body.model.clone(),
+							input: inputs.to_vec().into(),
+							dimensions: embeddings_body.dimensions,
+							encoding_format: embeddings_body.encoding_format.clone(),
+							user: embeddings_body.user.clone(),
+						};
+						let embeddings = self
+							.embeddings_create(&body)
+							.and_then(|embeddings| ordered_embeddings(embeddings, inputs.len()));
+						match embeddings {
+							Ok(embeddings) => {
+								let mut results = results.lock().unwrap();
+								let start = batch * batch_size;
+								for (result, embedding) in
+									results[start..].iter_mut().zip(embeddings)
+								{
+									*result = embedding;
+								}
+							},
+							Err(err) => {
+								stop_dispatching.store(true, Ordering::SeqCst);
+								error.lock().unwrap().get_or_insert(err);
+								break;
+							},
+						}
+					}
+				});
+			}
+		});
+		match error.into_inner().unwrap() {
+			Some(err) => Err(err),
+			None => Ok(results.into_inner().unwrap()),
+		}
+	}
+
+	#[cfg(feature = "tokenizer")]
+	fn embed_chunked(
+		&self,
+		embeddings_body: &EmbeddingsBody,
+		max_tokens: usize,
+	) -> ApiResult<ChunkedEmbedding> {
+		let EmbeddingInput::Single(input) = &embeddings_body.input else {
+			return Err(Error::InvalidRequest("embed_chunked embeds a single input".to_string()));
+		};
+		let model = embeddings_body.model.as_str();
+		let max_tokens = max_tokens.min(EMBEDDING_TOKEN_LIMIT);
+		let texts = crate::tokenizer::split_tokens(model, input, max_tokens);
+		let body = EmbeddingsBody { input: texts.clone().into(), ..embeddings_body.clone() };
+		let embeddings = self.embed_many(&body, EMBEDDING_BATCH_LIMIT, 1)?;
+		let chunks = texts
+			.into_iter()
+			.zip(embeddings)
+			.map(|(text, embedding)| EmbeddedChunk {
+				tokens: crate::tokenizer::count_tokens(model, &text),
+				text,
+				embedding,
+			})
+			.collect();
+		Ok(ChunkedEmbedding { chunks })
+	}
+}
+
+/// The embeddings of a response to `len` inputs in the order of the inputs, failing with
+/// `Error::ApiError` unless the response has exactly one embedding per input.
+fn ordered_embeddings(embeddings: Embeddings, len: usize) -> ApiResult<Vec<Vec<f32>>> {
+	let mut ordered = vec![None; len];
+	for data in embeddings.data.into_iter().flatten() {
+		let slot = usize::try_from(data.index).ok().and_then(|index| ordered.get_mut(index));
+		match slot {
+			Some(slot @ None) => *slot = Some(data.embedding.unwrap_or_default()),
+			Some(Some(_)) => {
+				return Err(Error::ApiError(format!("duplicate embedding index {}", data.index)))
+			},
+			None => {
+				return Err(Error::ApiError(format!(
+					"embedding index {} out of range for {len} inputs",
+					data.index
+				)))
+			},
+		}
 	}
+	ordered
+		.into_iter()
+		.enumerate()
+		.map(|(index, embedding)| {
+			embedding.ok_or_else(|| Error::ApiError(format!("missing embedding index {index}")))
+		})
+		.collect()
 }
 
 #[cfg(test)]
 mod tests {
+	use base64::Engine;
+
 	use crate::{
-		apis::embeddings::{EmbeddingsApi, EmbeddingsBody},
-		openai::new_test_openai,
+		apis::embeddings::{EmbeddingData, Embeddings, EmbeddingsApi, EmbeddingsBody},
+		openai::{new_test_mock, new_test_openai},
+		testing::{MockResponse, MockTransport},
+		Error,
 	};
 
 	#[test]
 	fn test_embedding_create() {
 		let openai = new_test_openai();
 		let body = EmbeddingsBody {
-			model: "text-embedding-ada-002".to_string(),
-			input: vec!["The food was delicious and the waiter...".to_string()],
+			model: "text-embedding-ada-002".into(),
+			input: vec!["The food was delicious and the waiter...".to_string()].into(),
+			dimensions: None,
+			encoding_format: None,
 			user: None,
 		};
 		let rs = openai.embeddings_create(&body);
 		let embeddings = rs.unwrap().data;
-		let embedding = embeddings.as_ref().unwrap().get(0).unwrap();
+		let embedding = embeddings.as_ref().unwrap().first().unwrap();
 		let f = embedding.embedding.as_ref().unwrap();
 		assert!(!f.is_empty());
 	}
+
+	#[test]
+	fn test_embedding_body() {
+		let body = EmbeddingsBody {
+			model: "text-embedding-3-small".into(),
+			input: "hello".into(),
+			dimensions: Some(256),
+			encoding_format: None,
+			user: None,
+		};
+		assert_eq!(
+			serde_json::to_value(&body).unwrap(),
+			serde_json::json!({"model": "text-embedding-3-small", "input": "hello", "dimensions": 256})
+		);
+		let body = EmbeddingsBody { input: vec!["a", "b"].into(), dimensions: None, ..body };
+		assert_eq!(serde_json::to_value(&body).unwrap()["input"], serde_json::json!(["a", "b"]));
+	}
+
+	#[test]
+	fn test_embedding_decode() {
+		let floats = [0.5_f32, -1.25, 3.0];
+		let bytes: Vec<u8> = floats.iter().flat_map(|f| f.to_le_bytes()).collect();
+		let encoded = base64::engine::general_purpose::STANDARD.encode(bytes);
+		let json = serde_json::json!({
+			"object": "list",
+			"data": [
+				{"object": "embedding", "embedding": encoded, "index": 1},
+				{"object": "embedding", "embedding": [0.5, -1.25, 3.0], "index": 0},
+			],
+			"model": "text-embedding-3-small",
+			"usage": {"prompt_tokens": 2, "total_tokens": 2},
+		});
+		let embeddings: Embeddings = serde_json::from_value(json).unwrap();
+		let data = embeddings.data.unwrap();
+		assert_eq!(data[0].index, 1);
+		assert_eq!(data[0].embedding.as_deref(), Some(&floats[..]));
+		assert_eq!(data[1].embedding.as_deref(), Some(&floats[..]));
+	}
+
+	#[test]
+	fn test_embedding_decode_little_endian() {
+		// 1.0 and -2.0 as the bytes `00 00 80 3f 00 00 00 c0`, decoded from a borrowed string.
+		let json = r#"{"object": "embedding", "embedding": "AACAPwAAAMA=", "index": 0}"#;
+		let data: EmbeddingData = serde_json::from_str(json).unwrap();
+		assert_eq!(data.embedding.as_deref(), Some(&[1.0, -2.0][..]));
+
+		let json = r#"{"object": "embedding", "embedding": null, "index": 0}"#;
+		assert!(serde_json::from_str::<EmbeddingData>(json).unwrap().embedding.is_none());
+		let json = r#"{"object": "embedding", "embedding": "AACA", "index": 0}"#;
+		assert!(serde_json::from_str::<EmbeddingData>(json).is_err());
+	}
+
+	#[test]
+	fn test_embed_many() {
+		let response = |embeddings: &[f32]| {
+			let data: Vec<_> = embeddings
+				.iter()
+				.enumerate()
+				.rev()
+				.map(|(index, x)| serde_json::json!({"embedding": [x], "index": index}))
+				.collect();
+			let usage = serde_json::json!({"prompt_tokens": 1, "total_tokens": 1});
+			let json = serde_json::json!({"data": data, "model": "m", "usage": usage});
+			MockResponse::new(200, json.to_string())
+		};
+		let mock = MockTransport::new()
+			.with_response("embeddings", response(&[0.0, 1.0]))
+			.with_response("embeddings", response(&[2.0, 3.0]))
+			.with_response("embeddings", response(&[4.0]));
+		let openai = new_test_mock(&mock);
+		let body = EmbeddingsBody {
+			model: "text-embedding-3-small".into(),
+			input: vec!["a", "b", "c", "d", "e"].into(),
+			dimensions: Some(1),
+			encoding_format: None,
+			user: None,
+		};
+		// A single thread makes the batches sent in order, matching the responses.
+		let embeddings = openai.embed_many(&body, 2, 1).unwrap();
+		assert_eq!(embeddings, [[0.0], [1.0], [2.0], [3.0], [4.0]]);
+		let requests = mock.requests();
+		assert_eq!(requests.len(), 3);
+		assert_eq!(requests[1].json().unwrap()["input"], serde_json::json!(["c", "d"]));
+		assert_eq!(requests[2].json().unwrap()["dimensions"], 1);
+
+		let mock = MockTransport::new().with_response("embeddings", response(&[0.0]));
+		let embeddings = openai.clone().with_transport(mock.clone()).embed_many(&body, 1, 3);
+		assert_eq!(embeddings.unwrap().len(), 5);
+		assert_eq!(mock.requests().len(), 5);
+
+		let mock = MockTransport::new()
+			.with_response("embeddings", response(&[0.0]))
+			.with_response("embeddings", MockResponse::new(400, "{}"));
+		let rs = openai.clone().with_transport(mock.clone()).embed_many(&body, 1, 1);
+		assert!(matches!(rs, Err(Error::Api(err)) if err.status == 400));
+		assert_eq!(mock.requests().len(), 2);
+
+		// Responses must have one embedding per input of the batch.
+		let json = |data: serde_json::Value| {
+			let json = serde_json::json!({"data": data, "model": "m"});
+			MockResponse::new(200, json.to_string())
+		};
+		let missing = json(serde_json::json!([{"embedding": [0.0], "index": 1}]));
+		let duplicate = json(serde_json::json!([
+			{"embedding": [0.0], "index": 0},
+			{"embedding": [1.0], "index": 0},
+		]));
+		let out_of_range = json(serde_json::json!([
+			{"embedding": [0.0], "index": 0},
+			{"embedding": [1.0], "index": 2},
+		]));
+		for response in [missing, duplicate, out_of_range] {
+			let mock = MockTransport::new().with_response("embeddings", response);
+			let rs = openai.clone().with_transport(mock).embed_many(&body, 2, 1);
+			assert!(matches!(rs, Err(Error::ApiError(_))), "{rs:?}");
+		}
+	}
+
+	#[cfg(feature = "tokenizer")]
+	#[test]
+	fn test_embed_chunked() {
+		use crate::apis::embeddings::{EmbeddedChunk, EMBEDDING_TOKEN_LIMIT};
+		use crate::{Auth, OpenAI};
+
+		let openai = OpenAI::new(Auth::new("sk-test"), "https://api.openai.com/v1/");
+		let body = EmbeddingsBody {
+			model: "text-embedding-3-small".into(),
+			input: "tiktoken is great! ".repeat(EMBEDDING_TOKEN_LIMIT).into(),
+			dimensions: None,
+			encoding_format: None,
+			user: None,
+		};
+		let mock = MockTransport::new();
+		let rs = openai.clone().with_transport(mock.clone()).embeddings_create(&body);
+		assert!(matches!(rs, Err(Error::InvalidRequest(msg)) if msg.contains("at most 8191")));
+		assert!(mock.requests().is_empty());
+
+		let data = serde_json::json!([
+			{"embedding": [1.0, 0.0], "index": 0},
+			{"embedding": [0.0, 1.0], "index": 1},
+		]);
+		let json = serde_json::json!({"data": data, "model": "m"});
+		let mock = MockTransport::new()
+			.with_response("embeddings", MockResponse::new(200, json.to_string()));
+		let openai = openai.with_transport(mock.clone());
+		// 6 tokens, then 2.
+		let body = EmbeddingsBody { input: "tiktoken is great! tiktoken".into(), ..body };
+		let embedding = openai.embed_chunked(&body, 6).unwrap();
+		let chunk = |text: &str, tokens, embedding: [f32; 2]| EmbeddedChunk {
+			text: text.to_string(),
+			tokens,
+			embedding: embedding.to_vec(),
+		};
+		assert_eq!(
+			embedding.chunks,
+			[chunk("tiktoken is great!", 6, [1.0, 0.0]), chunk(" tiktoken", 2, [0.0, 1.0])]
+		);
+		let combined = embedding.combined();
+		assert!((combined[0] - 3.0 / 10f32.sqrt()).abs() < 1e-6);
+		assert!((combined[1] - 1.0 / 10f32.sqrt()).abs() < 1e-6);
+		let requests = mock.requests();
+		assert_eq!(requests.len(), 1);
+		assert_eq!(
+			requests[0].json().unwrap()["input"],
+			serde_json::json!(["tiktoken is great!", " tiktoken"])
+		);
+
+		let body = EmbeddingsBody { input: vec!["a", "b"].into(), ..body };
+		assert!(matches!(openai.embed_chunked(&body, 6), Err(Error::InvalidRequest(_))));
+	}
 }
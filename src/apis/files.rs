@@ -0,0 +1,233 @@
+// Files are used to upload documents that can be used with features like fine-tuning.
+// See: https://platform.openai.com/docs/api-reference/files
+
+//! Files API
+
+use std::fs::File;
+use std::io::{self, Read, Write};
+
+use serde::{Deserialize, Serialize};
+
+use crate::multipart::Multipart;
+use crate::requests::Requests;
+use crate::*;
+
+use super::{ListParams, Paginated, Paginator, FILES, FILES_RETRIEVE};
+
+/// The maximum page size of `files_list_page`.
+const FILES_MAX_LIMIT: u32 = 10000;
+
+#[derive(Debug)]
+pub struct FilesBody<R: Read = File> {
+	/// The file to be uploaded.
+	pub file: R,
+	/// The name of the uploaded file, e.g. training.jsonl.
+	pub filename: String,
+	/// The intended purpose of the uploaded file,
+	/// one of assistants, batch, fine-tune or vision.
+	pub purpose: String,
+}
+
+/// A document uploaded to OpenAI.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FileObject {
+	/// The file identifier, which can be referenced in the API endpoints.
+	pub id: String,
+	pub object: Option<String>,
+	/// The size of the file, in bytes.
+	pub bytes: u64,
+	/// The Unix timestamp (in seconds) for when the file was created.
+	pub created_at: u64,
+	pub filename: String,
+	pub purpose: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DeletedFile {
+	pub id: String,
+	pub object: Option<String>,
+	pub deleted: bool,
+}
+
+pub trait FilesApi {
+	/// Uploads a file that can be used across various endpoints.
+	fn file_upload<R: Read>(&self, files_body: FilesBody<R>) -> ApiResult<FileObject>;
+	/// Returns a list of files that belong to the user's organization.
+	fn files_list(&self) -> ApiResult<Vec<FileObject>>;
+	/// Returns the files with the given `purpose`, e.g. fine-tune or assistants.
+	fn files_list_by_purpose(&self, purpose: &str) -> ApiResult<Vec<FileObject>>;
+	/// Returns a page of the files that belong to the user's organization.
+	fn files_list_page(&self, params: &ListParams) -> ApiResult<Paginated<FileObject>>;
+	/// Iterates over all the files that belong to the user's organization starting from `params`,
+	/// fetching pages of the maximum size unless `params.limit` is set.
+	fn files_iter(&self, params: ListParams) -> Paginator<'_, FileObject>;
+	/// Returns information about a specific file.
+	fn file_retrieve(&self, file_id: &str) -> ApiResult<FileObject>;
+	/// Deletes a file.
+	fn file_delete(&self, file_id: &str) -> ApiResult<DeletedFile>;
+	/// Returns the contents of the specified file.
+	/// The body is read from the connection as the returned reader is read.
+	fn file_content(&self, file_id: &str) -> ApiResult<Box<dyn Read + Send + Sync>>;
+	/// Streams the contents of the specified file into `writer`, returning the number of bytes
+	/// written. With `expected_len`, e.g. the `FileObject::bytes` of the file, a download of
+	/// another length fails with `Error::RequestError`, to catch truncated downloads; `None`
+	/// skips the check when the size is unknown.
+	fn file_content_to(
+		&self,
+		file_id: &str,
+		writer: &mut impl Write,
+		expected_len: Option<u64>,
+	) -> ApiResult<u64>;
+}
+
+impl FilesApi for OpenAI {
+	fn file_upload<R: Read>(&self, files_body: FilesBody<R>) -> ApiResult<FileObject> {
+		let mut send_data = Multipart::new();
+
+		send_data.add_text("purpose", files_body.purpose);
+		send_data.add_stream("file", files_body.file, Some(files_body.filename), None);
+
+		let res = self.post_multipart(FILES, send_data)?;
+		let file: FileObject = from_json(&res)?;
+		Ok(file)
+	}
+
+	fn files_list(&self) -> ApiResult<Vec<FileObject>> {
+		self.files_list_url(FILES)
+	}
+
+	fn files_list_by_purpose(&self, purpose: &str) -> ApiResult<Vec<FileObject>> {
+		self.files_list_url(&format!("{FILES}?purpose={purpose}"))
+	}
+
+	fn files_list_page(&self, params: &ListParams) -> ApiResult<Paginated<FileObject>> {
+		let res = self.get(&params.apply(FILES))?;
+		let files: Paginated<FileObject> = from_json(&res)?;
+		Ok(files)
+	}
+
+	fn files_iter(&self, params: ListParams) -> Paginator<'_, FileObject> {
+		Paginator::new(
+			params,
+			FILES_MAX_LIMIT,
+			|file| file.id.clone(),
+			|params| self.files_list_page(params),
+		)
+	}
+
+	fn file_retrieve(&self, file_id: &str) -> ApiResult<FileObject> {
+		let res: Json = self.get(&(FILES_RETRIEVE.to_owned() + file_id))?;
+		let file: FileObject = from_json(&res)?;
+		Ok(file)
+	}
+
+	fn file_delete(&self, file_id: &str) -> ApiResult<DeletedFile> {
+		let res: Json = self.delete(&(FILES_RETRIEVE.to_owned() + file_id))?;
+		let deleted: DeletedFile = from_json(&res)?;
+		Ok(deleted)
+	}
+
+	fn file_content(&self, file_id: &str) -> ApiResult<Box<dyn Read + Send + Sync>> {
+		self.get_stream(&(FILES_RETRIEVE.to_owned() + file_id + "/content"))
+	}
+
+	fn file_content_to(
+		&self,
+		file_id: &str,
+		writer: &mut impl Write,
+		expected_len: Option<u64>,
+	) -> ApiResult<u64> {
+		let mut content = self.file_content(file_id)?;
+		let len = io::copy(&mut content, writer)
+			.and_then(|len| writer.flush().map(|_| len))
+			.map_err(|e| Error::RequestError(e.to_string()))?;
+		match expected_len {
+			Some(expected) if expected != len => Err(Error::RequestError(format!(
+				"downloaded {len} bytes of file {file_id}, expected {expected}"
+			))),
+			_ => Ok(len),
+		}
+	}
+}
+
+impl OpenAI {
+	fn files_list_url(&self, sub_url: &str) -> ApiResult<Vec<FileObject>> {
+		let res: Json = self.get(sub_url)?;
+		let data = res.as_object().unwrap().get("data");
+		if let Some(data) = data {
+			let files: Vec<FileObject> = from_json(data)?;
+			return Ok(files);
+		}
+		Err(Error::ApiError("No data".to_string()))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::io::Read;
+
+	use crate::{
+		apis::files::{FilesApi, FilesBody},
+		openai::{new_test_mock, new_test_response, new_test_server, Auth, OpenAI},
+	};
+
+	const FILE: &str = r#"{"id": "file-abc", "object": "file", "bytes": 12, "created_at": 1700000000,
+		"filename": "training.jsonl", "purpose": "fine-tune"}"#;
+
+	#[test]
+	fn test_files() {
+		let list = format!(r#"{{"object": "list", "data": [{FILE}]}}"#);
+		let (url, server) = new_test_server(vec![
+			new_test_response(200, &[], FILE),
+			new_test_response(200, &[], &list),
+			new_test_response(200, &[], &list),
+			new_test_response(200, &[], FILE),
+			new_test_response(200, &[], r#"{"id": "file-abc", "object": "file", "deleted": true}"#),
+			new_test_response(200, &[], "{\"prompt\": 1}\n"),
+		]);
+		let openai = OpenAI::new(Auth::new("sk-test"), &url);
+
+		let body = FilesBody {
+			file: &b"{\"prompt\": 1}\n"[..],
+			filename: "training.jsonl".to_string(),
+			purpose: "fine-tune".to_string(),
+		};
+		let file = openai.file_upload(body).unwrap();
+		assert_eq!(file.id, "file-abc");
+		assert_eq!(file.bytes, 12);
+		assert_eq!(openai.files_list().unwrap()[0].filename, "training.jsonl");
+		assert_eq!(openai.files_list_by_purpose("fine-tune").unwrap()[0].purpose, "fine-tune");
+		assert_eq!(openai.file_retrieve("file-abc").unwrap().purpose, "fine-tune");
+		assert!(openai.file_delete("file-abc").unwrap().deleted);
+		let mut content = String::new();
+		openai.file_content("file-abc").unwrap().read_to_string(&mut content).unwrap();
+		assert_eq!(content, "{\"prompt\": 1}\n");
+
+		let requests = server.join().unwrap();
+		assert!(requests[0].starts_with("POST /v1/files "));
+		assert!(requests[0].contains("name=\"file\"; filename=\"training.jsonl\""));
+		assert!(requests[1].starts_with("GET /v1/files "));
+		assert!(requests[2].starts_with("GET /v1/files?purpose=fine-tune "));
+		assert!(requests[3].starts_with("GET /v1/files/file-abc "));
+		assert!(requests[4].starts_with("DELETE /v1/files/file-abc "));
+		assert!(requests[5].starts_with("GET /v1/files/file-abc/content "));
+	}
+
+	#[test]
+	fn test_file_content_to() {
+		use crate::testing::{MockResponse, MockTransport};
+		use crate::Error;
+
+		let mock = MockTransport::new()
+			.with_response("files/file-abc/content", MockResponse::new(200, "{\"prompt\": 1}\n"));
+		let openai = new_test_mock(&mock);
+
+		let mut content = Vec::new();
+		assert_eq!(openai.file_content_to("file-abc", &mut content, Some(14)).unwrap(), 14);
+		assert_eq!(content, b"{\"prompt\": 1}\n");
+		let mut content = Vec::new();
+		assert_eq!(openai.file_content_to("file-abc", &mut content, None).unwrap(), 14);
+		let rs = openai.file_content_to("file-abc", &mut Vec::new(), Some(20));
+		assert!(matches!(rs, Err(Error::RequestError(msg)) if msg.contains("expected 20")));
+	}
+}
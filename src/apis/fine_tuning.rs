@@ -0,0 +1,405 @@
+// Manage fine-tuning jobs to tailor a model to your specific training data.
+// See: https://platform.openai.com/docs/api-reference/fine-tuning
+
+//! Fine-tuning API
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::models::ModelName;
+use crate::requests::Requests;
+use crate::*;
+
+use super::{ListParams, Paginated, Paginator, FINE_TUNING_JOBS, FINE_TUNING_JOBS_RETRIEVE};
+
+/// The maximum page size of the list endpoints.
+const FINE_TUNING_MAX_LIMIT: u32 = 100;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FineTuningBody {
+	/// The name of the model to fine-tune.
+	pub model: ModelName,
+	/// The ID of an uploaded file that contains training data.
+	/// The file must be uploaded with the purpose fine-tune.
+	pub training_file: String,
+	/// The ID of an uploaded file that contains validation data.
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub validation_file: Option<String>,
+	/// The hyperparameters used for the fine-tuning job, deprecated in favor of `method`.
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub hyperparameters: Option<Hyperparameters>,
+	/// The method used for the fine-tuning job, supervised when not set.
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub method: Option<FineTuningMethod>,
+	/// A string of up to 64 characters that will be added to your fine-tuned model name.
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub suffix: Option<String>,
+	/// The seed controls the reproducibility of the job.
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub seed: Option<i64>,
+}
+
+/// A hyperparameter, either chosen by the API, serialized as `"auto"`, or set to a value.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Hyperparameter<T> {
+	Auto,
+	Value(T),
+}
+
+impl<T> From<T> for Hyperparameter<T> {
+	fn from(value: T) -> Self {
+		Self::Value(value)
+	}
+}
+
+impl<T: Serialize> Serialize for Hyperparameter<T> {
+	fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+		match self {
+			Self::Auto => serializer.serialize_str("auto"),
+			Self::Value(value) => value.serialize(serializer),
+		}
+	}
+}
+
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for Hyperparameter<T> {
+	fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+		#[derive(Deserialize)]
+		#[serde(untagged)]
+		enum Raw<T> {
+			Value(T),
+			Text(String),
+		}
+		match Raw::deserialize(deserializer)? {
+			Raw::Value(value) => Ok(Self::Value(value)),
+			Raw::Text(text) if text == "auto" => Ok(Self::Auto),
+			Raw::Text(text) => Err(serde::de::Error::custom(format!(
+				"expected \"auto\" or a number, found \"{text}\""
+			))),
+		}
+	}
+}
+
+/// The hyperparameters of the supervised method, `None` leaving them to the API.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Hyperparameters {
+	/// Number of examples in each batch.
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub batch_size: Option<Hyperparameter<u32>>,
+	/// Scaling factor for the learning rate.
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub learning_rate_multiplier: Option<Hyperparameter<f64>>,
+	/// The number of epochs to train the model for.
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub n_epochs: Option<Hyperparameter<u32>>,
+}
+
+/// The hyperparameters of the DPO method, `None` leaving them to the API.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct DpoHyperparameters {
+	/// Number of examples in each batch.
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub batch_size: Option<Hyperparameter<u32>>,
+	/// Scaling factor for the learning rate.
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub learning_rate_multiplier: Option<Hyperparameter<f64>>,
+	/// The number of epochs to train the model for.
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub n_epochs: Option<Hyperparameter<u32>>,
+	/// The weight of the penalty between the policy and reference model, a higher beta keeping
+	/// the fine-tuned model closer to the base model.
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub beta: Option<Hyperparameter<f64>>,
+}
+
+/// How a model is fine-tuned, serialized as
+/// `{"type": "dpo", "dpo": {"hyperparameters": {...}}}`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum FineTuningMethod {
+	/// Training on examples of prompts and the expected replies.
+	Supervised {
+		#[serde(default)]
+		supervised: MethodConfig<Hyperparameters>,
+	},
+	/// Direct Preference Optimization, training on pairs of preferred and non-preferred
+	/// replies.
+	Dpo {
+		#[serde(default)]
+		dpo: MethodConfig<DpoHyperparameters>,
+	},
+}
+
+/// The configuration of a `FineTuningMethod`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct MethodConfig<H> {
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub hyperparameters: Option<H>,
+}
+
+impl FineTuningMethod {
+	pub fn supervised(hyperparameters: Hyperparameters) -> FineTuningMethod {
+		Self::Supervised { supervised: MethodConfig { hyperparameters: Some(hyperparameters) } }
+	}
+
+	pub fn dpo(hyperparameters: DpoHyperparameters) -> FineTuningMethod {
+		Self::Dpo { dpo: MethodConfig { hyperparameters: Some(hyperparameters) } }
+	}
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FineTuningJob {
+	pub id: String,
+	pub object: Option<String>,
+	/// The Unix timestamp (in seconds) for when the fine-tuning job was created.
+	pub created_at: u64,
+	/// The Unix timestamp (in seconds) for when the fine-tuning job was finished.
+	pub finished_at: Option<u64>,
+	/// The base model that is being fine-tuned.
+	pub model: String,
+	/// The name of the fine-tuned model that is being created, once the job succeeded.
+	pub fine_tuned_model: Option<String>,
+	pub organization_id: Option<String>,
+	/// One of validating_files, queued, running, succeeded, failed, or cancelled.
+	pub status: String,
+	pub hyperparameters: Option<Hyperparameters>,
+	#[serde(default)]
+	pub method: Option<FineTuningMethod>,
+	pub training_file: String,
+	pub validation_file: Option<String>,
+	/// The compiled results file ID(s) for the fine-tuning job.
+	#[serde(default)]
+	pub result_files: Vec<String>,
+	/// The total number of billable tokens processed by this fine-tuning job.
+	pub trained_tokens: Option<u64>,
+}
+
+/// A log line of a fine-tuning job.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FineTuningEvent {
+	pub id: String,
+	pub object: Option<String>,
+	/// The Unix timestamp (in seconds) for when the event was created.
+	pub created_at: u64,
+	/// One of info, warn or error.
+	pub level: String,
+	pub message: String,
+}
+
+pub trait FineTuningApi {
+	/// Creates a fine-tuning job which begins the process of creating a new model
+	/// from a given dataset.
+	fn fine_tuning_create(&self, fine_tuning_body: &FineTuningBody) -> ApiResult<FineTuningJob>;
+	/// Lists your organization's fine-tuning jobs.
+	fn fine_tuning_list(&self, params: &ListParams) -> ApiResult<Paginated<FineTuningJob>>;
+	/// Iterates over all your organization's fine-tuning jobs starting from `params`,
+	/// fetching pages of the maximum size unless `params.limit` is set.
+	fn fine_tuning_iter(&self, params: ListParams) -> Paginator<'_, FineTuningJob>;
+	/// Gets info about a fine-tuning job.
+	fn fine_tuning_retrieve(&self, job_id: &str) -> ApiResult<FineTuningJob>;
+	/// Immediately cancels a fine-tuning job.
+	fn fine_tuning_cancel(&self, job_id: &str) -> ApiResult<FineTuningJob>;
+	/// Gets status updates for a fine-tuning job, most recent first.
+	fn fine_tuning_list_events(
+		&self,
+		job_id: &str,
+		params: &ListParams,
+	) -> ApiResult<Paginated<FineTuningEvent>>;
+	/// Iterates over all the events of a fine-tuning job starting from `params`,
+	/// fetching pages of the maximum size unless `params.limit` is set.
+	fn fine_tuning_events_iter<'a>(
+		&'a self,
+		job_id: &'a str,
+		params: ListParams,
+	) -> Paginator<'a, FineTuningEvent>;
+}
+
+impl FineTuningApi for OpenAI {
+	fn fine_tuning_create(&self, fine_tuning_body: &FineTuningBody) -> ApiResult<FineTuningJob> {
+		let request_body = serde_json::to_value(fine_tuning_body).unwrap();
+		let res = self.post(FINE_TUNING_JOBS, request_body)?;
+		let job: FineTuningJob = from_json(&res)?;
+		Ok(job)
+	}
+
+	fn fine_tuning_list(&self, params: &ListParams) -> ApiResult<Paginated<FineTuningJob>> {
+		let res = self.get(&params.apply(FINE_TUNING_JOBS))?;
+		let jobs: Paginated<FineTuningJob> = from_json(&res)?;
+		Ok(jobs)
+	}
+
+	fn fine_tuning_iter(&self, params: ListParams) -> Paginator<'_, FineTuningJob> {
+		Paginator::new(
+			params,
+			FINE_TUNING_MAX_LIMIT,
+			|job| job.id.clone(),
+			|params| self.fine_tuning_list(params),
+		)
+	}
+
+	fn fine_tuning_retrieve(&self, job_id: &str) -> ApiResult<FineTuningJob> {
+		let res = self.get(&(FINE_TUNING_JOBS_RETRIEVE.to_owned() + job_id))?;
+		let job: FineTuningJob = from_json(&res)?;
+		Ok(job)
+	}
+
+	fn fine_tuning_cancel(&self, job_id: &str) -> ApiResult<FineTuningJob> {
+		let sub_url = FINE_TUNING_JOBS_RETRIEVE.to_owned() + job_id + "/cancel";
+		let res = self.post(&sub_url, serde_json::json!({}))?;
+		let job: FineTuningJob = from_json(&res)?;
+		Ok(job)
+	}
+
+	fn fine_tuning_list_events(
+		&self,
+		job_id: &str,
+		params: &ListParams,
+	) -> ApiResult<Paginated<FineTuningEvent>> {
+		let sub_url = FINE_TUNING_JOBS_RETRIEVE.to_owned() + job_id + "/events";
+		let res = self.get(&params.apply(&sub_url))?;
+		let events: Paginated<FineTuningEvent> = from_json(&res)?;
+		Ok(events)
+	}
+
+	fn fine_tuning_events_iter<'a>(
+		&'a self,
+		job_id: &'a str,
+		params: ListParams,
+	) -> Paginator<'a, FineTuningEvent> {
+		Paginator::new(
+			params,
+			FINE_TUNING_MAX_LIMIT,
+			|event| event.id.clone(),
+			move |params| self.fine_tuning_list_events(job_id, params),
+		)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::{
+		apis::fine_tuning::{
+			DpoHyperparameters, FineTuningApi, FineTuningBody, FineTuningMethod, Hyperparameter,
+			Hyperparameters,
+		},
+		openai::{new_test_response, new_test_server, Auth, OpenAI},
+		ListParams,
+	};
+
+	const JOB: &str = r#"{"id": "ftjob-abc", "object": "fine_tuning.job", "created_at": 1700000000,
+		"finished_at": null, "model": "gpt-4o-mini", "fine_tuned_model": null,
+		"organization_id": "org-123", "status": "queued", "hyperparameters": {"n_epochs": "auto"},
+		"training_file": "file-abc", "validation_file": null, "result_files": [],
+		"trained_tokens": null}"#;
+
+	#[test]
+	fn test_fine_tuning() {
+		let jobs = format!(r#"{{"object": "list", "data": [{JOB}], "has_more": true}}"#);
+		let events = r#"{"object": "list", "data": [{"id": "ftevent-1",
+			"object": "fine_tuning.job.event", "created_at": 1700000001, "level": "info",
+			"message": "Job started"}], "has_more": false}"#;
+		let (url, server) = new_test_server(vec![
+			new_test_response(200, &[], JOB),
+			new_test_response(200, &[], &jobs),
+			new_test_response(200, &[], JOB),
+			new_test_response(200, &[], &JOB.replace("queued", "cancelled")),
+			new_test_response(200, &[], events),
+		]);
+		let openai = OpenAI::new(Auth::new("sk-test"), &url);
+
+		let body = FineTuningBody {
+			model: "gpt-4o-mini".into(),
+			training_file: "file-abc".to_string(),
+			..Default::default()
+		};
+		assert_eq!(openai.fine_tuning_create(&body).unwrap().status, "queued");
+		let params = ListParams { after: Some("ftjob-xyz".to_string()), limit: Some(2) };
+		let jobs = openai.fine_tuning_list(&params).unwrap();
+		assert!(jobs.has_more);
+		assert_eq!(
+			jobs.data[0].hyperparameters.as_ref().unwrap().n_epochs,
+			Some(Hyperparameter::Auto)
+		);
+		assert_eq!(openai.fine_tuning_retrieve("ftjob-abc").unwrap().id, "ftjob-abc");
+		assert_eq!(openai.fine_tuning_cancel("ftjob-abc").unwrap().status, "cancelled");
+		let events = openai.fine_tuning_list_events("ftjob-abc", &ListParams::default()).unwrap();
+		assert!(!events.has_more);
+		assert_eq!(events.data[0].message, "Job started");
+		assert_eq!(events.data[0].level, "info");
+
+		let requests = server.join().unwrap();
+		assert!(requests[0].starts_with("POST /v1/fine_tuning/jobs "));
+		assert!(requests[0].ends_with(r#"{"model":"gpt-4o-mini","training_file":"file-abc"}"#));
+		assert!(requests[1].starts_with("GET /v1/fine_tuning/jobs?after=ftjob-xyz&limit=2 "));
+		assert!(requests[2].starts_with("GET /v1/fine_tuning/jobs/ftjob-abc "));
+		assert!(requests[3].starts_with("POST /v1/fine_tuning/jobs/ftjob-abc/cancel "));
+		assert!(requests[4].starts_with("GET /v1/fine_tuning/jobs/ftjob-abc/events "));
+	}
+
+	#[test]
+	fn test_fine_tuning_events_iter() {
+		let event = |id: &str| {
+			format!(
+				r#"{{"id": "{id}", "object": "fine_tuning.job.event", "created_at": 1,
+				"level": "info", "message": "{id}"}}"#
+			)
+		};
+		let (url, server) = new_test_server(vec![
+			new_test_response(
+				200,
+				&[],
+				&format!(r#"{{"data": [{}, {}], "has_more": true}}"#, event("e1"), event("e2")),
+			),
+			new_test_response(
+				200,
+				&[],
+				&format!(r#"{{"data": [{}], "has_more": false}}"#, event("e3")),
+			),
+		]);
+		let openai = OpenAI::new(Auth::new("sk-test"), &url);
+
+		let messages: Vec<String> = openai
+			.fine_tuning_events_iter("ftjob-abc", ListParams::default())
+			.map(|event| event.unwrap().message)
+			.collect();
+		assert_eq!(messages, ["e1", "e2", "e3"]);
+
+		let requests = server.join().unwrap();
+		assert!(requests[0].starts_with("GET /v1/fine_tuning/jobs/ftjob-abc/events?limit=100 "));
+		assert!(requests[1]
+			.starts_with("GET /v1/fine_tuning/jobs/ftjob-abc/events?after=e2&limit=100 "));
+	}
+
+	#[test]
+	fn test_fine_tuning_methods() {
+		let body = FineTuningBody {
+			model: "gpt-4o-mini".into(),
+			training_file: "file-abc".to_string(),
+			method: Some(FineTuningMethod::supervised(Hyperparameters {
+				n_epochs: Some(3.into()),
+				batch_size: Some(Hyperparameter::Auto),
+				..Default::default()
+			})),
+			..Default::default()
+		};
+		let json = serde_json::json!({"model": "gpt-4o-mini", "training_file": "file-abc",
+			"method": {"type": "supervised", "supervised": {"hyperparameters": {"batch_size": "auto", "n_epochs": 3}}}});
+		assert_eq!(serde_json::to_value(&body).unwrap(), json);
+		let parsed: FineTuningBody = serde_json::from_value(json).unwrap();
+		assert_eq!(parsed.method, body.method);
+
+		let method = FineTuningMethod::dpo(DpoHyperparameters {
+			beta: Some(0.1.into()),
+			learning_rate_multiplier: Some(Hyperparameter::Auto),
+			n_epochs: Some(2.into()),
+			..Default::default()
+		});
+		let json = serde_json::json!({"type": "dpo", "dpo": {"hyperparameters": {
+			"learning_rate_multiplier": "auto", "n_epochs": 2, "beta": 0.1}}});
+		assert_eq!(serde_json::to_value(&method).unwrap(), json);
+		assert_eq!(serde_json::from_value::<FineTuningMethod>(json).unwrap(), method);
+
+		let method: FineTuningMethod = serde_json::from_str(r#"{"type": "dpo"}"#).unwrap();
+		assert_eq!(method, FineTuningMethod::Dpo { dpo: Default::default() });
+		let invalid = r#"{"n_epochs": "many"}"#;
+		assert!(serde_json::from_str::<Hyperparameters>(invalid).is_err());
+	}
+}
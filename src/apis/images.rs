@@ -4,31 +4,214 @@
 //! Images API
 
 use super::{IMAGES_CREATE, IMAGES_EDIT, IMAGES_VARIATIONS};
+use crate::models::ModelName;
+use crate::multipart::Multipart;
 use crate::requests::Requests;
+use crate::stream::Stream;
 use crate::*;
-use crate::multipart::Multipart;
-use serde::{Deserialize, Serialize};
+use base64::Engine;
+use serde::{Deserialize, Deserializer, Serialize};
 use std::{fs::File, str};
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ImagesBody {
+	/// The model to use for image generation, e.g. dall-e-2 or dall-e-3.
+	/// Defaults to dall-e-2
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub model: Option<ModelName>,
 	/// A text description of the desired image(s). The maximum length is 1000 characters.
 	pub prompt: String,
 	/// The number of images to generate. Must be between 1 and 10.
 	/// Defaults to 1
 	#[serde(skip_serializing_if = "Option::is_none")]
 	pub n: Option<i32>,
-	/// The size of the generated images. Must be one of 256x256, 512x512, or 1024x1024.
+	/// The size of the generated images, depending on the model, see `ImageSize`.
 	/// Defaults to 1024x1024
 	#[serde(skip_serializing_if = "Option::is_none")]
-	pub size: Option<String>,
+	pub size: Option<ImageSize>,
+	/// The quality of the image that will be generated, depending on the model, see
+	/// `ImageQuality`.
+	/// Defaults to standard
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub quality: Option<ImageQuality>,
+	/// The style of the generated images. Must be one of vivid or natural.
+	/// Only supported for dall-e-3.
+	/// Defaults to vivid
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub style: Option<String>,
 	/// The format in which the generated images are returned. Must be one of url or b64_json.
+	/// b64_json images are decoded into `ImageData::b64_json`.
 	/// Defaults to url
 	#[serde(skip_serializing_if = "Option::is_none")]
 	pub response_format: Option<String>,
 	/// A unique identifier representing your end-user, which can help OpenAI to monitor and detect abuse.
 	#[serde(skip_serializing_if = "Option::is_none")]
 	pub user: Option<String>,
+	/// The number of partial images sent before the final image by `image_create_stream`,
+	/// between 0 and 3. Only supported for gpt-image-1.
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub partial_images: Option<u32>,
+}
+
+impl ImagesBody {
+	/// Checks that the model supports `size` and `quality`, failing with
+	/// `Error::InvalidRequest`. Done before sending the body.
+	///
+	/// dall-e-2, the default, and dall-e-3 and gpt-image-1 are checked, other models and
+	/// `Custom` values are left to the API.
+	pub fn validate(&self) -> ApiResult<()> {
+		let model = self.model.as_ref().map_or("dall-e-2", ModelName::as_str);
+		let Some((_, sizes, qualities)) =
+			IMAGE_MODELS.iter().find(|(name, ..)| model.starts_with(name))
+		else {
+			return Ok(());
+		};
+		let size = self.size.as_ref().filter(|size| !matches!(size, ImageSize::Custom(_)));
+		check_supported(model, "size", size.map(ImageSize::as_str), sizes)?;
+		let quality =
+			self.quality.as_ref().filter(|quality| !matches!(quality, ImageQuality::Custom(_)));
+		check_supported(model, "quality", quality.map(ImageQuality::as_str), qualities)
+	}
+}
+
+/// The sizes and qualities supported by the models checked by `ImagesBody::validate`.
+const IMAGE_MODELS: [(&str, &[&str], &[&str]); 3] = [
+	("dall-e-2", &["256x256", "512x512", "1024x1024"], &["standard"]),
+	("dall-e-3", &["1024x1024", "1792x1024", "1024x1792"], &["standard", "hd"]),
+	(
+		"gpt-image-1",
+		&["auto", "1024x1024", "1536x1024", "1024x1536"],
+		&["auto", "low", "medium", "high"],
+	),
+];
+
+fn check_supported(
+	model: &str,
+	name: &str,
+	value: Option<&str>,
+	supported: &[&str],
+) -> ApiResult<()> {
+	match value {
+		Some(value) if !supported.contains(&value) => Err(Error::InvalidRequest(format!(
+			"{name} {value} is not supported by {model}, use one of {}",
+			supported.join(", ")
+		))),
+		_ => Ok(()),
+	}
+}
+
+/// The size of generated images. dall-e-2 supports 256x256, 512x512 and 1024x1024, dall-e-3
+/// 1024x1024, 1792x1024 and 1024x1792, and gpt-image-1 auto, 1024x1024, 1536x1024 and
+/// 1024x1536.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(from = "String", into = "String")]
+pub enum ImageSize {
+	/// Chosen by the model, only for gpt-image-1.
+	Auto,
+	Square256,
+	Square512,
+	Square1024,
+	Landscape1536x1024,
+	Portrait1024x1536,
+	Landscape1792x1024,
+	Portrait1024x1792,
+	/// A size not listed here, e.g. of a model added after this version, sent as is.
+	Custom(String),
+}
+
+impl ImageSize {
+	pub fn as_str(&self) -> &str {
+		match self {
+			ImageSize::Auto => "auto",
+			ImageSize::Square256 => "256x256",
+			ImageSize::Square512 => "512x512",
+			ImageSize::Square1024 => "1024x1024",
+			ImageSize::Landscape1536x1024 => "1536x1024",
+			ImageSize::Portrait1024x1536 => "1024x1536",
+			ImageSize::Landscape1792x1024 => "1792x1024",
+			ImageSize::Portrait1024x1792 => "1024x1792",
+			ImageSize::Custom(size) => size,
+		}
+	}
+}
+
+impl From<String> for ImageSize {
+	fn from(size: String) -> Self {
+		match size.as_str() {
+			"auto" => ImageSize::Auto,
+			"256x256" => ImageSize::Square256,
+			"512x512" => ImageSize::Square512,
+			"1024x1024" => ImageSize::Square1024,
+			"1536x1024" => ImageSize::Landscape1536x1024,
+			"1024x1536" => ImageSize::Portrait1024x1536,
+			"1792x1024" => ImageSize::Landscape1792x1024,
+			"1024x1792" => ImageSize::Portrait1024x1792,
+			_ => ImageSize::Custom(size),
+		}
+	}
+}
+
+impl From<ImageSize> for String {
+	fn from(size: ImageSize) -> Self {
+		match size {
+			ImageSize::Custom(size) => size,
+			size => size.as_str().to_string(),
+		}
+	}
+}
+
+/// The quality of generated images. dall-e-2 only supports standard, dall-e-3 standard and
+/// hd, which creates images with finer details and greater consistency across the image, and
+/// gpt-image-1 auto, low, medium and high.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(from = "String", into = "String")]
+pub enum ImageQuality {
+	/// Chosen by the model, only for gpt-image-1.
+	Auto,
+	Standard,
+	Hd,
+	Low,
+	Medium,
+	High,
+	/// A quality not listed here, e.g. of a model added after this version, sent as is.
+	Custom(String),
+}
+
+impl ImageQuality {
+	pub fn as_str(&self) -> &str {
+		match self {
+			ImageQuality::Auto => "auto",
+			ImageQuality::Standard => "standard",
+			ImageQuality::Hd => "hd",
+			ImageQuality::Low => "low",
+			ImageQuality::Medium => "medium",
+			ImageQuality::High => "high",
+			ImageQuality::Custom(quality) => quality,
+		}
+	}
+}
+
+impl From<String> for ImageQuality {
+	fn from(quality: String) -> Self {
+		match quality.as_str() {
+			"auto" => ImageQuality::Auto,
+			"standard" => ImageQuality::Standard,
+			"hd" => ImageQuality::Hd,
+			"low" => ImageQuality::Low,
+			"medium" => ImageQuality::Medium,
+			"high" => ImageQuality::High,
+			_ => ImageQuality::Custom(quality),
+		}
+	}
+}
+
+impl From<ImageQuality> for String {
+	fn from(quality: ImageQuality) -> Self {
+		match quality {
+			ImageQuality::Custom(quality) => quality,
+			quality => quality.as_str().to_string(),
+		}
+	}
 }
 
 #[derive(Debug)]
@@ -49,14 +232,77 @@ pub struct Images {
 	pub data: Option<Vec<ImageData>>,
 }
 
+impl Images {
+	/// The generated images, in order, empty if the response has none.
+	pub fn images(&self) -> &[ImageData] {
+		self.data.as_deref().unwrap_or_default()
+	}
+
+	/// The URLs of the generated images, when requested with the url response_format.
+	pub fn urls(&self) -> Vec<&str> {
+		self.images().iter().filter_map(|image| image.url.as_deref()).collect()
+	}
+
+	/// The revised prompt of every image, in order, `None` for the images whose prompt wasn't
+	/// revised. Only dall-e-3 revises prompts.
+	pub fn revised_prompts(&self) -> Vec<Option<&str>> {
+		self.images().iter().map(|image| image.revised_prompt.as_deref()).collect()
+	}
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ImageData {
-	pub url: String,
+	/// The URL of the generated image, if response_format is url.
+	pub url: Option<String>,
+	/// The PNG bytes of the generated image, if response_format is b64_json.
+	#[serde(default, deserialize_with = "deserialize_b64_json")]
+	pub b64_json: Option<Vec<u8>>,
+	/// The prompt that was used to generate the image, if there was any revision to the prompt.
+	pub revised_prompt: Option<String>,
+}
+
+/// An image sent by `image_create_stream`, either partial or final.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ImageChunk {
+	/// Either image_generation.partial_image or image_generation.completed.
+	#[serde(rename = "type")]
+	pub kind: String,
+	/// The PNG bytes of the image, or of the format requested with `output_format`.
+	#[serde(default, deserialize_with = "deserialize_b64_json")]
+	pub b64_json: Option<Vec<u8>>,
+	/// The index of a partial image, starting at 0, `None` for the final image.
+	pub partial_image_index: Option<u32>,
+}
+
+impl ImageChunk {
+	/// Whether this is the complete image, which ends the stream.
+	pub fn is_final(&self) -> bool {
+		self.kind == IMAGE_COMPLETED
+	}
+}
+
+const IMAGE_COMPLETED: &str = "image_generation.completed";
+
+pub type ImageStream = Stream<ImageChunk>;
+
+fn deserialize_b64_json<'de, D: Deserializer<'de>>(
+	deserializer: D,
+) -> Result<Option<Vec<u8>>, D::Error> {
+	let Some(encoded) = Option::<String>::deserialize(deserializer)? else {
+		return Ok(None);
+	};
+	let bytes = base64::engine::general_purpose::STANDARD
+		.decode(encoded)
+		.map_err(serde::de::Error::custom)?;
+	Ok(Some(bytes))
 }
 
 pub trait ImagesApi {
 	/// Given a prompt and/or an input image, the model will generate a new image.
 	fn image_create(&self, images_body: &ImagesBody) -> ApiResult<Images>;
+	/// Same as `image_create`, streaming the `partial_images` as they are generated,
+	/// followed by the final image.
+	fn image_create_stream(&self, images_body: &ImagesBody) -> ApiResult<ImageStream>;
 	/// Creates an edited or extended image given an original image and a prompt.
 	fn image_edit(&self, images_edit_body: ImagesEditBody) -> ApiResult<Images>;
 	/// Creates a variation of a given image.
@@ -65,68 +311,87 @@ pub trait ImagesApi {
 
 impl ImagesApi for OpenAI {
 	fn image_create(&self, images_body: &ImagesBody) -> ApiResult<Images> {
+		images_body.validate()?;
 		let request_body = serde_json::to_value(images_body).unwrap();
 		let res = self.post(IMAGES_CREATE, request_body)?;
-		let images: Images = serde_json::from_value(res.clone()).unwrap();
+		let images: Images = from_json(&res)?;
 		Ok(images)
 	}
 
+	fn image_create_stream(&self, images_body: &ImagesBody) -> ApiResult<ImageStream> {
+		images_body.validate()?;
+		let mut request_body = serde_json::to_value(images_body).unwrap();
+		request_body["stream"] = Json::Bool(true);
+		let reader = self.post_stream(IMAGES_CREATE, request_body)?;
+		Ok(Stream::new(reader).ending_with(IMAGE_COMPLETED))
+	}
+
 	fn image_edit(&self, images_edit_body: ImagesEditBody) -> ApiResult<Images> {
+		images_edit_body.images_body.validate()?;
 		let mut send_data = Multipart::new();
 
-		send_data.add_text("prompt", images_edit_body.images_body.prompt);
-		if let Some(n) = images_edit_body.images_body.n {
-			send_data.add_text("n", n.to_string());
-		}
-		if let Some(size) = images_edit_body.images_body.size {
-			send_data.add_text("size", size.to_string());
-		}
-		if let Some(response_format) = images_edit_body.images_body.response_format {
-			send_data.add_text("response_format", response_format.to_string());
-		}
-		if let Some(user) = images_edit_body.images_body.user {
-			send_data.add_text("user", user.to_string());
-		}
+		send_data.add_text("prompt", images_edit_body.images_body.prompt.clone());
+		add_images_fields(&mut send_data, images_edit_body.images_body);
 		if let Some(mask) = images_edit_body.mask {
-			send_data.add_stream("mask", mask, Some("blob"), Some(mime::IMAGE_PNG));
+			send_data.add_stream("mask", mask, Some("mask.png"), Some(mime::IMAGE_PNG));
 		}
-		send_data.add_stream("image", images_edit_body.image, Some("blob"), Some(mime::IMAGE_PNG));
+		send_data.add_stream(
+			"image",
+			images_edit_body.image,
+			Some("image.png"),
+			Some(mime::IMAGE_PNG),
+		);
 
 		let res = self.post_multipart(IMAGES_EDIT, send_data)?;
-		let images: Images = serde_json::from_value(res.clone()).unwrap();
+		let images: Images = from_json(&res)?;
 		Ok(images)
 	}
 
 	fn image_variation(&self, images_edit_body: ImagesEditBody) -> ApiResult<Images> {
+		images_edit_body.images_body.validate()?;
 		let mut send_data = Multipart::new();
 
-		if let Some(n) = images_edit_body.images_body.n {
-			send_data.add_text("n", n.to_string());
-		}
-		if let Some(size) = images_edit_body.images_body.size {
-			send_data.add_text("size", size.to_string());
-		}
-		if let Some(response_format) = images_edit_body.images_body.response_format {
-			send_data.add_text("response_format", response_format.to_string());
-		}
-		if let Some(user) = images_edit_body.images_body.user {
-			send_data.add_text("user", user.to_string());
-		}
-		send_data.add_stream("image", images_edit_body.image, Some("blob"), Some(mime::IMAGE_PNG));
+		add_images_fields(&mut send_data, images_edit_body.images_body);
+		send_data.add_stream(
+			"image",
+			images_edit_body.image,
+			Some("image.png"),
+			Some(mime::IMAGE_PNG),
+		);
 
 		let res = self.post_multipart(IMAGES_VARIATIONS, send_data)?;
-		let images: Images = serde_json::from_value(res.clone()).unwrap();
+		let images: Images = from_json(&res)?;
 		Ok(images)
 	}
 }
 
+/// Adds the optional fields of `images_body` as text fields, the prompt is left to the caller
+/// since variations don't take one.
+fn add_images_fields(send_data: &mut Multipart, images_body: ImagesBody) {
+	let fields = [
+		("model", images_body.model.map(String::from)),
+		("n", images_body.n.map(|n| n.to_string())),
+		("size", images_body.size.map(String::from)),
+		("quality", images_body.quality.map(String::from)),
+		("style", images_body.style),
+		("response_format", images_body.response_format),
+		("user", images_body.user),
+	];
+	for (name, value) in fields {
+		if let Some(value) = value {
+			send_data.add_text(name, value);
+		}
+	}
+}
+
 #[cfg(test)]
 mod tests {
 	use std::fs::File;
 
 	use crate::{
-		apis::images::{ImagesApi, ImagesBody, ImagesEditBody},
-		openai::new_test_openai,
+		apis::images::{ImageQuality, ImageSize, Images, ImagesApi, ImagesBody, ImagesEditBody},
+		openai::{new_test_openai, new_test_response, new_test_server, Auth, OpenAI},
+		Error,
 	};
 
 	#[test]
@@ -135,14 +400,18 @@ mod tests {
 		let body = ImagesBody {
 			prompt: "A cute baby sea otter".to_string(),
 			n: Some(2),
-			size: Some("1024x1024".to_string()),
+			size: Some(ImageSize::Square1024),
+			model: None,
+			quality: None,
+			style: None,
 			response_format: None,
 			user: None,
+			partial_images: None,
 		};
 		let rs = openai.image_create(&body);
 		let images = rs.unwrap().data.unwrap();
-		let image = images.get(0).unwrap();
-		assert!(image.url.contains("http"));
+		let image = images.first().unwrap();
+		assert!(image.url.as_ref().unwrap().contains("http"));
 	}
 
 	#[test]
@@ -153,17 +422,21 @@ mod tests {
 			images_body: ImagesBody {
 				prompt: "A cute baby sea otter wearing a beret".to_string(),
 				n: Some(2),
-				size: Some("1024x1024".to_string()),
+				size: Some(ImageSize::Square1024),
+				model: None,
+				quality: None,
+				style: None,
 				response_format: None,
 				user: None,
+				partial_images: None,
 			},
 			image: file,
 			mask: None,
 		};
 		let rs = openai.image_edit(multipart);
 		let images = rs.unwrap().data.unwrap();
-		let image = images.get(0).unwrap();
-		assert!(image.url.contains("http"));
+		let image = images.first().unwrap();
+		assert!(image.url.as_ref().unwrap().contains("http"));
 	}
 
 	#[test]
@@ -174,16 +447,184 @@ mod tests {
 			images_body: ImagesBody {
 				prompt: "".to_string(),
 				n: Some(2),
-				size: Some("1024x1024".to_string()),
+				size: Some(ImageSize::Square1024),
+				model: None,
+				quality: None,
+				style: None,
 				response_format: None,
 				user: None,
+				partial_images: None,
 			},
 			image: file,
 			mask: None,
 		};
 		let rs = openai.image_variation(multipart);
 		let images = rs.unwrap().data.unwrap();
-		let image = images.get(0).unwrap();
-		assert!(image.url.contains("http"));
+		let image = images.first().unwrap();
+		assert!(image.url.as_ref().unwrap().contains("http"));
+	}
+
+	#[test]
+	fn test_image_b64_json() {
+		let json = serde_json::json!({
+			"created": 1700000000,
+			"data": [
+				{"b64_json": "iVBORw0KGgo=", "revised_prompt": "A cute baby sea otter, digital art"},
+				{"url": "https://example.com/otter.png"},
+			],
+		});
+		let images: Images = serde_json::from_value(json).unwrap();
+		let data = images.data.unwrap();
+		assert_eq!(data[0].b64_json.as_deref(), Some(&b"\x89PNG\r\n\x1a\n"[..]));
+		assert!(data[0].url.is_none());
+		assert_eq!(data[1].url.as_deref(), Some("https://example.com/otter.png"));
+		assert!(data[1].b64_json.is_none());
+	}
+
+	#[test]
+	fn test_image_revised_prompts() {
+		let json = serde_json::json!({
+			"created": 1700000000,
+			"data": [
+				{"url": "https://example.com/1.png", "revised_prompt": "A red fox in the snow"},
+				{"url": "https://example.com/2.png"},
+				{"url": "https://example.com/3.png", "revised_prompt": "A fox, watercolor"},
+			],
+		});
+		let images: Images = serde_json::from_value(json).unwrap();
+		assert_eq!(images.images().len(), 3);
+		assert_eq!(images.urls()[2], "https://example.com/3.png");
+		assert_eq!(
+			images.revised_prompts(),
+			[Some("A red fox in the snow"), None, Some("A fox, watercolor")]
+		);
+
+		let images: Images = serde_json::from_str(r#"{"created": 1}"#).unwrap();
+		assert!(images.images().is_empty() && images.revised_prompts().is_empty());
+	}
+
+	#[test]
+	fn test_image_edit_multipart() {
+		let (url, server) =
+			new_test_server(vec![new_test_response(200, &[], r#"{"created": 0, "data": []}"#)]);
+		let openai = OpenAI::new(Auth::new("sk-test"), &url);
+		let multipart = ImagesEditBody {
+			images_body: ImagesBody {
+				model: Some("dall-e-2".into()),
+				prompt: "A cute baby sea otter wearing a beret".to_string(),
+				n: None,
+				size: None,
+				quality: None,
+				style: None,
+				response_format: Some("b64_json".to_string()),
+				user: None,
+				partial_images: None,
+			},
+			image: File::open("test_files/image.png").unwrap(),
+			mask: Some(File::open("test_files/image.png").unwrap()),
+		};
+		openai.image_edit(multipart).unwrap();
+
+		let request = &server.join().unwrap()[0];
+		assert!(request.contains("name=\"image\"; filename=\"image.png\""));
+		assert!(request.contains("name=\"mask\"; filename=\"mask.png\""));
+		assert!(request.contains("name=\"model\"\r\n\r\ndall-e-2"));
+		assert!(!request.contains("name=\"quality\""));
+	}
+
+	#[test]
+	fn test_image_create_stream() {
+		let events = "event: image_generation.partial_image\n\
+			data: {\"type\": \"image_generation.partial_image\", \"b64_json\": \"AQI=\", \"partial_image_index\": 0}\n\n\
+			event: image_generation.completed\n\
+			data: {\"type\": \"image_generation.completed\", \"b64_json\": \"AQID\", \"usage\": {\"total_tokens\": 1}}\n\n";
+		let failed = "data: {\"type\": \"image_generation.partial_image\", \"b64_json\": \"AQI=\", \"partial_image_index\": 0}\n\n\
+			data: {\"error\": {\"message\": \"Server error\", \"type\": \"server_error\"}}\n\n";
+		let (url, server) = new_test_server(vec![
+			new_test_response(200, &[("Content-Type", "text/event-stream")], events),
+			new_test_response(200, &[("Content-Type", "text/event-stream")], failed),
+		]);
+		let openai = OpenAI::new(Auth::new("sk-test"), &url);
+		let body = ImagesBody {
+			model: Some("gpt-image-1".into()),
+			prompt: "A cute baby sea otter".to_string(),
+			n: None,
+			size: None,
+			quality: None,
+			style: None,
+			response_format: None,
+			user: None,
+			partial_images: Some(1),
+		};
+
+		let chunks: Vec<_> =
+			openai.image_create_stream(&body).unwrap().map(Result::unwrap).collect();
+		assert_eq!(chunks.len(), 2);
+		assert_eq!(chunks[0].partial_image_index, Some(0));
+		assert_eq!(chunks[0].b64_json.as_deref(), Some(&[1, 2][..]));
+		assert!(!chunks[0].is_final() && chunks[1].is_final());
+		assert_eq!(chunks[1].b64_json.as_deref(), Some(&[1, 2, 3][..]));
+
+		let mut stream = openai.image_create_stream(&body).unwrap();
+		assert!(stream.next().unwrap().is_ok());
+		assert!(matches!(stream.next(), Some(Err(Error::Api(_)))));
+		assert!(stream.next().is_none());
+
+		let request = &server.join().unwrap()[0];
+		assert!(request.contains(r#""partial_images":1"#));
+		assert!(request.contains(r#""stream":true"#));
+	}
+
+	#[test]
+	fn test_image_size_quality() {
+		let (url, server) =
+			new_test_server(vec![new_test_response(200, &[], r#"{"created": 0, "data": []}"#)]);
+		let openai = OpenAI::new(Auth::new("sk-test"), &url);
+		let mut body = ImagesBody {
+			model: Some("dall-e-3".into()),
+			prompt: "A cute baby sea otter".to_string(),
+			n: None,
+			size: Some(ImageSize::Landscape1792x1024),
+			quality: Some(ImageQuality::Hd),
+			style: None,
+			response_format: None,
+			user: None,
+			partial_images: None,
+		};
+		assert!(body.validate().is_ok());
+		openai.image_create(&body).unwrap();
+		let request = &server.join().unwrap()[0];
+		assert!(request.contains(r#""size":"1792x1024""#) && request.contains(r#""quality":"hd""#));
+
+		body.model = None;
+		let Err(Error::InvalidRequest(msg)) = openai.image_create(&body) else {
+			panic!("1792x1024 is not supported by dall-e-2");
+		};
+		assert_eq!(
+			msg,
+			"size 1792x1024 is not supported by dall-e-2, use one of 256x256, 512x512, 1024x1024"
+		);
+		body.size = Some(ImageSize::Square256);
+		assert!(
+			matches!(body.validate(), Err(Error::InvalidRequest(msg)) if msg.contains("quality hd"))
+		);
+		body.model = Some("gpt-image-1".into());
+		body.size = Some(ImageSize::Portrait1024x1536);
+		body.quality = Some(ImageQuality::High);
+		assert!(body.validate().is_ok());
+
+		body.size = Some(ImageSize::Custom("2048x2048".to_string()));
+		body.quality = Some("ultra".to_string().into());
+		assert!(body.validate().is_ok());
+		body.model = Some("my-image-model".into());
+		body.size = Some(ImageSize::Square256);
+		assert!(body.validate().is_ok());
+
+		let json = serde_json::to_value(&body).unwrap();
+		assert_eq!(json["size"], "256x256");
+		assert_eq!(json["quality"], "ultra");
+		let body: ImagesBody = serde_json::from_value(json).unwrap();
+		assert_eq!(body.size, Some(ImageSize::Square256));
+		assert_eq!(body.quality, Some(ImageQuality::Custom("ultra".to_string())));
 	}
 }
@@ -1,20 +1,37 @@
-use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
+use base64::Engine;
+use mime::Mime;
+use serde::{Deserialize, Serialize, Serializer};
+
+use crate::{ApiResult, Error};
+
+pub mod assistants;
 pub mod audio;
+pub mod batches;
 pub mod chat;
 pub mod completions;
 pub mod edits;
 pub mod embeddings;
+pub mod files;
+pub mod fine_tuning;
 pub mod images;
 pub mod models;
+pub mod moderations;
+pub mod vector_stores;
 
 // Models API
 const MODELS_LIST: &str = "models";
 const MODELS_RETRIEVE: &str = "models/";
 // Completions API
 const COMPLETION_CREATE: &str = "completions";
+// Batch API
+const BATCHES: &str = "batches";
+const BATCHES_RETRIEVE: &str = "batches/";
 // Chat API
 const CHAT_COMPLETION_CREATE: &str = "chat/completions";
+const CHAT_COMPLETIONS_RETRIEVE: &str = "chat/completions/";
 // Edits API
 const EDIT_CREATE: &str = "edits";
 // Images API
@@ -23,52 +40,1361 @@ const IMAGES_EDIT: &str = "images/edits";
 const IMAGES_VARIATIONS: &str = "images/variations";
 // Embeddings API
 const EMBEDDINGS_CREATE: &str = "embeddings";
+// Assistants API
+const ASSISTANTS: &str = "assistants";
+const ASSISTANTS_RETRIEVE: &str = "assistants/";
+const THREADS: &str = "threads";
+const THREADS_RETRIEVE: &str = "threads/";
 // Audio API
 const AUDIO_TRANSCRIPTION_CREATE: &str = "audio/transcriptions";
 const AUDIO_TRANSLATIONS_CREATE: &str = "audio/translations";
+const AUDIO_SPEECH_CREATE: &str = "audio/speech";
+// Files API
+const FILES: &str = "files";
+const FILES_RETRIEVE: &str = "files/";
+// Fine-tuning API
+const FINE_TUNING_JOBS: &str = "fine_tuning/jobs";
+const FINE_TUNING_JOBS_RETRIEVE: &str = "fine_tuning/jobs/";
+// Moderations API
+const MODERATIONS_CREATE: &str = "moderations";
+// Vector stores API
+const VECTOR_STORES: &str = "vector_stores";
+const VECTOR_STORES_RETRIEVE: &str = "vector_stores/";
 
-#[derive(Debug, Serialize, Deserialize)]
+/// The tokens used by a request. Usages add up, to total those of several requests.
+///
+/// ```
+/// # use openai_api_rust::Usage;
+/// let step = |prompt, completion| Usage {
+///     prompt_tokens: Some(prompt),
+///     completion_tokens: Some(completion),
+///     total_tokens: Some(prompt + completion),
+///     ..Default::default()
+/// };
+/// let total: Usage = [step(100, 20), step(150, 30)].into_iter().sum();
+/// assert_eq!(total.total_tokens, Some(300));
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
 pub struct Usage {
 	pub prompt_tokens: Option<u32>,
 	pub completion_tokens: Option<u32>,
 	pub total_tokens: Option<u32>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub completion_tokens_details: Option<CompletionTokensDetails>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub prompt_tokens_details: Option<PromptTokensDetails>,
+}
+
+/// A breakdown of `Usage::completion_tokens`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct CompletionTokensDetails {
+	/// The tokens generated by a reasoning model to reason, not part of the output.
+	pub reasoning_tokens: Option<u32>,
+	/// The tokens of the `prediction` of a chat request that appeared in the completion.
+	pub accepted_prediction_tokens: Option<u32>,
+	/// The tokens of the `prediction` of a chat request that didn't appear in the completion,
+	/// billed as completion tokens nonetheless.
+	pub rejected_prediction_tokens: Option<u32>,
+	/// The audio tokens generated by an audio model.
+	pub audio_tokens: Option<u32>,
+}
+
+/// A breakdown of `Usage::prompt_tokens`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PromptTokensDetails {
+	/// The tokens of the prompt prefix cached from previous requests, billed at a discount.
+	pub cached_tokens: Option<u32>,
+	/// The audio tokens of the input audio.
+	pub audio_tokens: Option<u32>,
+}
+
+impl Usage {
+	/// The estimated cost of the tokens in US dollars with the prices of `ModelInfo`, built-in
+	/// or registered, `None` if the model is unknown. See `PriceTable` to use other prices.
+	pub fn estimated_cost(&self, model: &str) -> Option<f64> {
+		let info = crate::model_info::ModelInfo::lookup(model)?;
+		Some(crate::pricing::ModelPrice::from_info(&info)?.cost(self))
+	}
+}
+
+/// Adds counts, which stay `None` only when missing from both sides, and saturate at
+/// `u32::MAX` rather than overflow when many usages are summed.
+fn add_tokens(a: Option<u32>, b: Option<u32>) -> Option<u32> {
+	match (a, b) {
+		(None, None) => None,
+		(a, b) => Some(a.unwrap_or(0).saturating_add(b.unwrap_or(0))),
+	}
+}
+
+impl std::ops::AddAssign for CompletionTokensDetails {
+	fn add_assign(&mut self, other: Self) {
+		self.reasoning_tokens = add_tokens(self.reasoning_tokens, other.reasoning_tokens);
+		self.accepted_prediction_tokens =
+			add_tokens(self.accepted_prediction_tokens, other.accepted_prediction_tokens);
+		self.rejected_prediction_tokens =
+			add_tokens(self.rejected_prediction_tokens, other.rejected_prediction_tokens);
+		self.audio_tokens = add_tokens(self.audio_tokens, other.audio_tokens);
+	}
+}
+
+impl std::ops::AddAssign for PromptTokensDetails {
+	fn add_assign(&mut self, other: Self) {
+		self.cached_tokens = add_tokens(self.cached_tokens, other.cached_tokens);
+		self.audio_tokens = add_tokens(self.audio_tokens, other.audio_tokens);
+	}
+}
+
+/// Adds breakdowns, which stay `None` only when missing from both sides.
+fn add_details<T: std::ops::AddAssign>(a: Option<T>, b: Option<T>) -> Option<T> {
+	match (a, b) {
+		(Some(mut a), Some(b)) => {
+			a += b;
+			Some(a)
+		},
+		(a, b) => a.or(b),
+	}
+}
+
+impl std::ops::AddAssign for Usage {
+	fn add_assign(&mut self, other: Self) {
+		self.prompt_tokens = add_tokens(self.prompt_tokens, other.prompt_tokens);
+		self.completion_tokens = add_tokens(self.completion_tokens, other.completion_tokens);
+		self.total_tokens = add_tokens(self.total_tokens, other.total_tokens);
+		self.completion_tokens_details =
+			add_details(self.completion_tokens_details.take(), other.completion_tokens_details);
+		self.prompt_tokens_details =
+			add_details(self.prompt_tokens_details.take(), other.prompt_tokens_details);
+	}
+}
+
+impl std::ops::Add for Usage {
+	type Output = Usage;
+
+	fn add(mut self, other: Self) -> Usage {
+		self += other;
+		self
+	}
+}
+
+impl std::iter::Sum for Usage {
+	fn sum<I: Iterator<Item = Usage>>(iter: I) -> Usage {
+		iter.fold(Usage::default(), |total, usage| total + usage)
+	}
+}
+
+/// The parts shared by the responses and stream chunks of the chat and completions APIs, so
+/// that helpers such as `total_usage` and `Stream::collect_text` apply to both.
+pub trait CompletionResponse {
+	/// The token usage, only set for the last chunk of a stream, when
+	/// `stream_options.include_usage` is set.
+	fn usage(&self) -> Option<&Usage>;
+
+	/// The text of the choice `index`: the message content of a chat completion, the text of
+	/// a completion, or the delta of a stream chunk.
+	fn choice_text(&self, index: u32) -> Option<&str>;
+
+	/// Why the choice `index` ended, `None` until the last chunk of a stream.
+	fn finish_reason(&self, index: u32) -> Option<FinishReason>;
+
+	/// Whether the first choice was cut off by the maximum number of tokens of the request,
+	/// see `chat::ChatBody::continuation`.
+	fn was_truncated(&self) -> bool {
+		self.finish_reason(0) == Some(FinishReason::Length)
+	}
+}
+
+impl CompletionResponse for completions::Completion {
+	fn usage(&self) -> Option<&Usage> {
+		self.usage.as_ref()
+	}
+
+	fn choice_text(&self, index: u32) -> Option<&str> {
+		let choice = self.choices.iter().find(|choice| choice.index == index)?;
+		match &choice.message {
+			Some(message) => message.text(),
+			None => choice.text.as_deref(),
+		}
+	}
+
+	fn finish_reason(&self, index: u32) -> Option<FinishReason> {
+		self.choices.iter().find(|choice| choice.index == index)?.finish_reason
+	}
+}
+
+impl CompletionResponse for completions::CompletionChunk {
+	fn usage(&self) -> Option<&Usage> {
+		self.usage.as_ref()
+	}
+
+	fn choice_text(&self, index: u32) -> Option<&str> {
+		self.choices.iter().find(|choice| choice.index == index)?.text.as_deref()
+	}
+
+	fn finish_reason(&self, index: u32) -> Option<FinishReason> {
+		self.choices.iter().find(|choice| choice.index == index)?.finish_reason
+	}
+}
+
+impl CompletionResponse for chat::ChatCompletionChunk {
+	fn usage(&self) -> Option<&Usage> {
+		self.usage.as_ref()
+	}
+
+	fn choice_text(&self, index: u32) -> Option<&str> {
+		self.choices.iter().find(|choice| choice.index == index)?.delta.content.as_deref()
+	}
+
+	fn finish_reason(&self, index: u32) -> Option<FinishReason> {
+		self.choices.iter().find(|choice| choice.index == index)?.finish_reason
+	}
+}
+
+/// The sum of the usage of `responses`, e.g. of the responses to a batch of requests, or of
+/// the chunks of a stream. Responses without usage count as zero.
+pub fn total_usage<'a, T: CompletionResponse + 'a>(
+	responses: impl IntoIterator<Item = &'a T>,
+) -> Usage {
+	responses.into_iter().filter_map(|response| response.usage().cloned()).sum()
+}
+
+/// Sets the `user` of a request, a stable identifier of the end-user on whose behalf it is
+/// made, which helps OpenAI to monitor and detect abuse.
+/// See: https://platform.openai.com/docs/guides/safety-best-practices/end-user-ids
+///
+/// ```
+/// # use openai_api_rust::EndUser;
+/// # use openai_api_rust::completions::CompletionsBody;
+/// let body = CompletionsBody::default().end_user("user-1");
+/// assert_eq!(body.user.as_deref(), Some("user-1"));
+/// ```
+pub trait EndUser: Sized {
+	fn end_user(self, id: impl Into<String>) -> Self;
+}
+
+macro_rules! impl_end_user {
+	($($body:ty),*) => {$(
+		impl EndUser for $body {
+			fn end_user(mut self, id: impl Into<String>) -> Self {
+				self.user = Some(id.into());
+				self
+			}
+		}
+	)*};
+}
+
+impl_end_user!(
+	chat::ChatBody,
+	completions::CompletionsBody,
+	embeddings::EmbeddingsBody,
+	images::ImagesBody,
+	moderations::ModerationsBody
+);
+
+impl EndUser for chat::ChatBodyBuilder {
+	fn end_user(self, id: impl Into<String>) -> Self {
+		self.user_id(id)
+	}
+}
+
+/// Objects with a creation time, sent by the API as a Unix timestamp in seconds, in their
+/// `created` or `created_at` field.
+pub trait Created {
+	/// The raw timestamp, in seconds. Only `None` for models and completions sent without a
+	/// `created` field, e.g. by OpenAI-compatible servers.
+	fn created_secs(&self) -> Option<u64>;
+
+	fn created_time(&self) -> Option<SystemTime> {
+		Some(UNIX_EPOCH + Duration::from_secs(self.created_secs()?))
+	}
+
+	#[cfg(feature = "chrono")]
+	fn created_datetime(&self) -> Option<chrono::DateTime<chrono::Utc>> {
+		chrono::DateTime::from_timestamp(self.created_secs()?.try_into().ok()?, 0)
+	}
+}
+
+macro_rules! impl_created {
+	($($object:ty => $field:ident),*) => {$(
+		impl Created for $object {
+			fn created_secs(&self) -> Option<u64> {
+				Some(self.$field)
+			}
+		}
+	)*};
+}
+
+impl_created!(
+	assistants::Assistant => created_at,
+	assistants::Thread => created_at,
+	assistants::ThreadMessage => created_at,
+	assistants::Run => created_at,
+	batches::Batch => created_at,
+	files::FileObject => created_at,
+	fine_tuning::FineTuningJob => created_at,
+	fine_tuning::FineTuningEvent => created_at,
+	images::Images => created,
+	models::Permission => created,
+	vector_stores::VectorStore => created_at,
+	vector_stores::FileBatch => created_at,
+	vector_stores::VectorStoreFile => created_at
+);
+
+macro_rules! impl_optional_created {
+	($($object:ty),*) => {$(
+		impl Created for $object {
+			fn created_secs(&self) -> Option<u64> {
+				self.created
+			}
+		}
+	)*};
+}
+
+impl_optional_created!(
+	chat::ChatCompletionChunk,
+	completions::Completion,
+	completions::CompletionChunk,
+	models::Model
+);
+
+/// Up to 4 sequences where the API will stop generating further tokens,
+/// sent as a plain string when there is only one. Bodies without stop sequences have no `Stop`.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(try_from = "StopRepr")]
+pub struct Stop(Vec<String>);
+
+/// The maximum number of stop sequences.
+const MAX_STOP: usize = 4;
+
+/// Fails with `Error::InvalidRequest` if the penalty `name` is outside `-2.0..=2.0`.
+pub(crate) fn check_penalty(name: &str, penalty: Option<f32>) -> ApiResult<()> {
+	match penalty {
+		Some(penalty) if !(-2.0..=2.0).contains(&penalty) => Err(Error::InvalidRequest(format!(
+			"{name} must be between -2.0 and 2.0, got {penalty}"
+		))),
+		_ => Ok(()),
+	}
+}
+
+/// Fails with `Error::InvalidRequest` if `temperature` is outside `0.0..=2.0`, `top_p` outside
+/// `0.0..=1.0` or `n` is below 1.
+pub(crate) fn check_sampling(
+	temperature: Option<f32>,
+	top_p: Option<f32>,
+	n: Option<i32>,
+) -> ApiResult<()> {
+	if let Some(temperature) = temperature.filter(|t| !(0.0..=2.0).contains(t)) {
+		return Err(Error::InvalidRequest(format!(
+			"temperature must be between 0.0 and 2.0, got {temperature}"
+		)));
+	}
+	if let Some(top_p) = top_p.filter(|p| !(0.0..=1.0).contains(p)) {
+		return Err(Error::InvalidRequest(format!(
+			"top_p must be between 0.0 and 1.0, got {top_p}"
+		)));
+	}
+	if let Some(n) = n.filter(|n| *n < 1) {
+		return Err(Error::InvalidRequest(format!("n must be at least 1, got {n}")));
+	}
+	Ok(())
+}
+
+/// Whether both `temperature` and `top_p` are set to other values than their default of 1,
+/// which OpenAI recommends against.
+pub(crate) fn alters_temperature_and_top_p(temperature: Option<f32>, top_p: Option<f32>) -> bool {
+	temperature.is_some_and(|t| t != 1.0) && top_p.is_some_and(|p| p != 1.0)
+}
+
+/// The maximum number of `metadata` pairs, and the maximum lengths of their keys and values.
+const MAX_METADATA: usize = 16;
+const MAX_METADATA_KEY: usize = 64;
+const MAX_METADATA_VALUE: usize = 512;
+
+/// Fails with `Error::InvalidRequest` if `metadata` has more than 16 pairs, a key longer than
+/// 64 characters or a value longer than 512.
+pub(crate) fn check_metadata(metadata: &HashMap<String, String>) -> ApiResult<()> {
+	if metadata.len() > MAX_METADATA {
+		return Err(Error::InvalidRequest(format!(
+			"metadata accepts up to {MAX_METADATA} pairs, got {}",
+			metadata.len()
+		)));
+	}
+	for (key, value) in metadata {
+		if key.chars().count() > MAX_METADATA_KEY {
+			return Err(Error::InvalidRequest(format!(
+				"metadata keys are limited to {MAX_METADATA_KEY} characters, got {key}"
+			)));
+		}
+		if value.chars().count() > MAX_METADATA_VALUE {
+			return Err(Error::InvalidRequest(format!(
+				"metadata values are limited to {MAX_METADATA_VALUE} characters, got one for {key}"
+			)));
+		}
+	}
+	Ok(())
+}
+
+impl Stop {
+	/// Fails with `Error::InvalidRequest` if there are no sequences, which would be sent as an
+	/// empty list rather than leaving `stop` out, or more than 4.
+	pub fn new(sequences: impl IntoIterator<Item = impl Into<String>>) -> ApiResult<Stop> {
+		let sequences: Vec<String> = sequences.into_iter().map(Into::into).collect();
+		if sequences.is_empty() {
+			return Err(Error::InvalidRequest("stop needs at least one sequence".to_string()));
+		}
+		if sequences.len() > MAX_STOP {
+			return Err(Error::InvalidRequest(format!(
+				"stop accepts up to {MAX_STOP} sequences, got {}",
+				sequences.len()
+			)));
+		}
+		Ok(Stop(sequences))
+	}
+
+	pub fn sequences(&self) -> &[String] {
+		&self.0
+	}
+}
+
+impl From<&str> for Stop {
+	fn from(sequence: &str) -> Self {
+		Stop(vec![sequence.to_string()])
+	}
+}
+
+impl From<String> for Stop {
+	fn from(sequence: String) -> Self {
+		Stop(vec![sequence])
+	}
+}
+
+impl Serialize for Stop {
+	fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+		match self.0.as_slice() {
+			[sequence] => serializer.serialize_str(sequence),
+			sequences => sequences.serialize(serializer),
+		}
+	}
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum StopRepr {
+	Single(String),
+	Multiple(Vec<String>),
+}
+
+impl TryFrom<StopRepr> for Stop {
+	type Error = Error;
+
+	fn try_from(repr: StopRepr) -> ApiResult<Stop> {
+		match repr {
+			StopRepr::Single(sequence) => Ok(sequence.into()),
+			StopRepr::Multiple(sequences) => Stop::new(sequences),
+		}
+	}
+}
+
+/// How the poll helpers, `AssistantsApi::run_poll`, `BatchesApi::batch_poll` and
+/// `VectorStoresApi::vector_store_file_batch_poll`, retrieve an object until it completes.
+#[derive(Debug, Clone)]
+pub struct PollConfig {
+	/// The delay before the second retrieval, doubled every time.
+	pub min_delay: Duration,
+	/// The maximum delay between two retrievals.
+	pub max_delay: Duration,
+	/// The time after which polling fails with `Error::Timeout`, `None` to poll until the object
+	/// completes.
+	pub timeout: Option<Duration>,
+}
+
+impl Default for PollConfig {
+	fn default() -> Self {
+		Self {
+			min_delay: Duration::from_millis(500),
+			max_delay: Duration::from_secs(5),
+			timeout: None,
+		}
+	}
+}
+
+impl PollConfig {
+	/// Calls `retrieve` until `pending`, which returns the status of objects still pending,
+	/// returns `None`.
+	pub(crate) fn poll<T>(
+		&self,
+		mut retrieve: impl FnMut() -> ApiResult<T>,
+		pending: impl Fn(&T) -> Option<String>,
+	) -> ApiResult<T> {
+		let deadline = self.timeout.map(|timeout| Instant::now() + timeout);
+		let mut delay = self.min_delay;
+		loop {
+			let object = retrieve()?;
+			let Some(status) = pending(&object) else {
+				return Ok(object);
+			};
+			let mut sleep = delay;
+			if let Some(deadline) = deadline {
+				let remaining = deadline.saturating_duration_since(Instant::now());
+				if remaining.is_zero() {
+					return Err(Error::Timeout(status));
+				}
+				sleep = sleep.min(remaining);
+			}
+			std::thread::sleep(sleep);
+			delay = (delay * 2).min(self.max_delay);
+		}
+	}
+}
+
+/// A page of a list endpoint.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Paginated<T> {
+	pub object: Option<String>,
+	pub data: Vec<T>,
+	/// Whether there are more items after this page.
+	#[serde(default)]
+	pub has_more: bool,
+	pub first_id: Option<String>,
+	/// The cursor to pass as `ListParams::after` to fetch the next page.
+	pub last_id: Option<String>,
+}
+
+/// Cursor pagination of list endpoints.
+#[derive(Debug, Clone, Default)]
+pub struct ListParams {
+	/// The identifier of the last item of the previous page.
+	pub after: Option<String>,
+	/// The number of items to retrieve.
+	pub limit: Option<u32>,
+}
+
+impl ListParams {
+	/// `sub_url` with the params as its query string.
+	pub(crate) fn apply(&self, sub_url: &str) -> String {
+		let mut params = Vec::new();
+		if let Some(after) = &self.after {
+			params.push(format!("after={after}"));
+		}
+		if let Some(limit) = self.limit {
+			params.push(format!("limit={limit}"));
+		}
+		if params.is_empty() {
+			return sub_url.to_string();
+		}
+		format!("{sub_url}?{}", params.join("&"))
+	}
+}
+
+type FetchPage<'a, T> = Box<dyn FnMut(&ListParams) -> ApiResult<Paginated<T>> + 'a>;
+
+/// An iterator over the items of a list endpoint, fetching the next page with
+/// `after` set to the last ID of the previous one until `has_more` is false.
+pub struct Paginator<'a, T> {
+	params: ListParams,
+	cursor: fn(&T) -> String,
+	fetch: FetchPage<'a, T>,
+	page: std::vec::IntoIter<T>,
+	done: bool,
+}
+
+impl<'a, T> Paginator<'a, T> {
+	/// Starts from `params`, with `max_limit` as the page size unless `params.limit` is set.
+	/// `cursor` returns the ID of an item, for pages without a `last_id`.
+	pub(crate) fn new(
+		mut params: ListParams,
+		max_limit: u32,
+		cursor: fn(&T) -> String,
+		fetch: impl FnMut(&ListParams) -> ApiResult<Paginated<T>> + 'a,
+	) -> Self {
+		params.limit.get_or_insert(max_limit);
+		Self { params, cursor, fetch: Box::new(fetch), page: Vec::new().into_iter(), done: false }
+	}
+}
+
+impl<T> Iterator for Paginator<'_, T> {
+	type Item = ApiResult<T>;
+
+	fn next(&mut self) -> Option<Self::Item> {
+		loop {
+			if let Some(item) = self.page.next() {
+				return Some(Ok(item));
+			}
+			if self.done {
+				return None;
+			}
+			let page = match (self.fetch)(&self.params) {
+				Ok(page) => page,
+				Err(err) => {
+					self.done = true;
+					return Some(Err(err));
+				},
+			};
+			self.params.after = page.last_id.or_else(|| page.data.last().map(self.cursor));
+			self.done = !page.has_more || page.data.is_empty();
+			self.page = page.data.into_iter();
+		}
+	}
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Choice {
 	pub text: Option<String>,
+	#[serde(default)]
 	pub index: u32,
-	pub logprobs: Option<String>,
-	pub finish_reason: Option<String>,
+	pub logprobs: Option<Logprobs>,
+	pub finish_reason: Option<FinishReason>,
 	pub message: Option<Message>,
+	/// The annotations of the Azure OpenAI content filters on this choice, `None` for OpenAI.
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub content_filter_results: Option<ContentFilterResults>,
+}
+
+impl Choice {
+	/// Whether the choice was cut off by `max_tokens` or `max_completion_tokens`, see
+	/// `ChatBody::continuation`.
+	pub fn was_truncated(&self) -> bool {
+		self.finish_reason == Some(FinishReason::Length)
+	}
+}
+
+/// Why the model stopped generating a choice.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FinishReason {
+	/// A natural stop point, or one of the `stop` sequences.
+	Stop,
+	/// The maximum number of tokens of the request was reached.
+	Length,
+	/// The model called tools.
+	ToolCalls,
+	/// Content was omitted by the content filters.
+	ContentFilter,
+	/// The model called a function, with the deprecated `functions` parameter.
+	FunctionCall,
+	/// A reason added to the API after this version.
+	#[serde(other)]
+	Other,
+}
+
+/// The processing tier of a request, trading latency for cost.
+/// See: https://platform.openai.com/docs/guides/flex-processing
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ServiceTier {
+	/// The tier of the project settings, default unless configured otherwise.
+	Auto,
+	Default,
+	/// Cheaper and slower, for non-urgent requests, with fewer models.
+	Flex,
+	Priority,
+	/// A tier added to the API after this version, only for responses.
+	#[serde(other)]
+	Other,
+}
+
+/// The annotations of the Azure OpenAI content filters on a prompt or a choice, by category.
+/// Categories are `None` when not annotated, e.g. with other filter configurations.
+/// See: https://learn.microsoft.com/azure/ai-services/openai/concepts/content-filter
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ContentFilterResults {
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub hate: Option<ContentFilterSeverity>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub self_harm: Option<ContentFilterSeverity>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub sexual: Option<ContentFilterSeverity>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub violence: Option<ContentFilterSeverity>,
+	/// Only annotated on prompts.
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub jailbreak: Option<ContentFilterDetection>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub profanity: Option<ContentFilterDetection>,
+	/// Only annotated on choices.
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub protected_material_text: Option<ContentFilterDetection>,
+	/// Only annotated on choices.
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub protected_material_code: Option<ContentFilterDetection>,
+	/// Set instead of the categories when the filters failed to run.
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub error: Option<ContentFilterError>,
+	/// The annotations not modeled above, e.g. custom blocklists.
+	#[serde(flatten)]
+	pub extra: serde_json::Map<String, serde_json::Value>,
+}
+
+impl ContentFilterResults {
+	/// Whether the content was filtered in any of the categories modeled above.
+	pub fn is_filtered(&self) -> bool {
+		let severities = [&self.hate, &self.self_harm, &self.sexual, &self.violence];
+		let detections = [
+			&self.jailbreak,
+			&self.profanity,
+			&self.protected_material_text,
+			&self.protected_material_code,
+		];
+		severities.iter().any(|result| result.as_ref().is_some_and(|result| result.filtered))
+			|| detections.iter().any(|result| result.as_ref().is_some_and(|result| result.filtered))
+	}
+}
+
+/// The annotation of a category graded by severity.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ContentFilterSeverity {
+	/// Whether the content was filtered, the severity is at or above the configured threshold.
+	pub filtered: bool,
+	pub severity: Option<FilterSeverity>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FilterSeverity {
+	Safe,
+	Low,
+	Medium,
+	High,
+}
+
+/// The annotation of a category that is either detected or not.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ContentFilterDetection {
+	pub filtered: bool,
+	pub detected: Option<bool>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ContentFilterError {
+	pub code: Option<String>,
+	pub message: Option<String>,
+}
+
+/// The annotations of the Azure OpenAI content filters on a prompt of the request.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PromptFilterResult {
+	/// The prompt annotated, always 0 for chat completions.
+	#[serde(default)]
+	pub prompt_index: u32,
+	#[serde(default)]
+	pub content_filter_results: ContentFilterResults,
+}
+
+/// Log probability information of a choice. Chat completions set `content`,
+/// completions set the other fields.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Logprobs {
+	/// The log probability of each message content token.
+	pub content: Option<Vec<TokenLogprob>>,
+	pub tokens: Option<Vec<String>>,
+	pub token_logprobs: Option<Vec<Option<f64>>>,
+	pub top_logprobs: Option<Vec<Option<HashMap<String, f64>>>>,
+	pub text_offset: Option<Vec<u32>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TokenLogprob {
+	pub token: String,
+	/// The log probability of this token, -9999.0 if it is very unlikely.
+	pub logprob: f64,
+	/// The UTF-8 bytes of the token, a character may be split across several tokens.
+	pub bytes: Option<Vec<u8>>,
+	/// The most likely tokens at this position, as many as requested with `top_logprobs`.
+	#[serde(default)]
+	pub top_logprobs: Vec<TopLogprob>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TopLogprob {
+	pub token: String,
+	pub logprob: f64,
+	pub bytes: Option<Vec<u8>>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Message {
 	pub role: Role,
-	pub content: String,
+	/// The contents of the message, `None` for assistant messages only calling tools.
+	pub content: Option<Content>,
+	/// An optional name for the participant,
+	/// to differentiate between participants of the same role.
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub name: Option<String>,
+	/// The tool calls generated by the model, for assistant messages.
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub tool_calls: Option<Vec<ToolCall>>,
+	/// The tool call this message is answering, for tool messages.
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub tool_call_id: Option<String>,
+	/// The refusal of the model to answer, for assistant messages, whose `content` is then
+	/// usually `None`.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub refusal: Option<String>,
+	/// The audio generated by the model, for assistant messages of audio-capable models.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub audio: Option<MessageAudio>,
+}
+
+/// The audio response of a model, requested with the `audio` modality.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MessageAudio {
+	/// The ID to refer to this audio in the next turns of the conversation.
+	pub id: String,
+	/// The audio bytes generated by the model, base64 encoded in the requested format,
+	/// see `MessageAudio::bytes`.
+	#[serde(default, skip_serializing_if = "String::is_empty")]
+	pub data: String,
+	/// The transcript of the audio.
+	#[serde(default, skip_serializing_if = "String::is_empty")]
+	pub transcript: String,
+	/// The Unix timestamp (in seconds) after which the audio can no longer be referred to.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub expires_at: Option<u64>,
+}
+
+impl MessageAudio {
+	/// The decoded `data`.
+	pub fn bytes(&self) -> ApiResult<Vec<u8>> {
+		base64::engine::general_purpose::STANDARD
+			.decode(&self.data)
+			.map_err(|e| Error::RequestError(e.to_string()))
+	}
 }
 
 impl Clone for Message {
 	fn clone(&self) -> Self {
-		Self { role: self.role.clone(), content: self.content.clone() }
+		Self {
+			role: self.role.clone(),
+			content: self.content.clone(),
+			name: self.name.clone(),
+			tool_calls: self.tool_calls.clone(),
+			tool_call_id: self.tool_call_id.clone(),
+			refusal: self.refusal.clone(),
+			audio: self.audio.clone(),
+		}
 	}
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+impl Message {
+	pub fn new(role: Role, content: impl Into<Content>) -> Message {
+		Message {
+			role,
+			content: Some(content.into()),
+			name: None,
+			tool_calls: None,
+			tool_call_id: None,
+			refusal: None,
+			audio: None,
+		}
+	}
+
+	pub fn system(content: impl Into<Content>) -> Message {
+		Message::new(Role::System, content)
+	}
+
+	pub fn developer(content: impl Into<Content>) -> Message {
+		Message::new(Role::Developer, content)
+	}
+
+	pub fn user(content: impl Into<Content>) -> Message {
+		Message::new(Role::User, content)
+	}
+
+	pub fn assistant(content: impl Into<Content>) -> Message {
+		Message::new(Role::Assistant, content)
+	}
+
+	/// The result of the tool call `tool_call_id`, sent back on the next turn.
+	pub fn tool(tool_call_id: impl Into<String>, content: impl Into<Content>) -> Message {
+		Message { tool_call_id: Some(tool_call_id.into()), ..Message::new(Role::Tool, content) }
+	}
+
+	/// The text of the message, see `Content::as_text`.
+	pub fn text(&self) -> Option<&str> {
+		self.content.as_ref()?.as_text()
+	}
+
+	/// The tool calls generated by the model, empty if there are none.
+	pub fn tool_calls(&self) -> &[ToolCall] {
+		self.tool_calls.as_deref().unwrap_or_default()
+	}
+}
+
+/// The contents of a message, either plain text or parts mixing text and images.
+///
+/// A single text part is sent as a plain string, for models that don't accept parts.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(untagged)]
+pub enum Content {
+	Text(String),
+	Parts(Vec<ContentPart>),
+}
+
+impl Content {
+	/// The text of a `Text` content or of a single text part.
+	pub fn as_text(&self) -> Option<&str> {
+		match self {
+			Self::Text(text) => Some(text),
+			Self::Parts(parts) => match parts.as_slice() {
+				[ContentPart::Text { text }] => Some(text),
+				_ => None,
+			},
+		}
+	}
+}
+
+impl Serialize for Content {
+	fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+		match self {
+			Self::Text(text) => serializer.serialize_str(text),
+			Self::Parts(parts) => match parts.as_slice() {
+				[ContentPart::Text { text }] => serializer.serialize_str(text),
+				parts => parts.serialize(serializer),
+			},
+		}
+	}
+}
+
+impl From<String> for Content {
+	fn from(text: String) -> Self {
+		Self::Text(text)
+	}
+}
+
+impl From<&str> for Content {
+	fn from(text: &str) -> Self {
+		Self::Text(text.to_string())
+	}
+}
+
+impl From<Vec<ContentPart>> for Content {
+	fn from(parts: Vec<ContentPart>) -> Self {
+		Self::Parts(parts)
+	}
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ContentPart {
+	Text {
+		text: String,
+	},
+	ImageUrl {
+		image_url: ImageUrl,
+	},
+	/// Audio for the audio-capable models, see `ContentPart::input_audio`.
+	InputAudio {
+		input_audio: InputAudio,
+	},
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct InputAudio {
+	/// The base64 encoded audio.
+	pub data: String,
+	/// The format of the audio, wav or mp3.
+	pub format: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ImageUrl {
+	/// Either a URL of the image or the base64 encoded image data as a data URL.
+	pub url: String,
+	/// Specifies the detail level of the image, `None` leaving it to the API, which uses auto.
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub detail: Option<ImageDetail>,
+}
+
+/// The detail level of an image, which the token cost of the image depends on.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ImageDetail {
+	/// A 512px low resolution version of the image, for a fixed and low cost.
+	Low,
+	/// The low resolution version, then detailed crops of the image, costing more tokens the
+	/// larger the image.
+	High,
+	/// Lets the model choose between low and high from the size of the image.
+	#[default]
+	Auto,
+}
+
+/// The length of the `data:` URL of an image above which sending it with `ImageDetail::High`
+/// logs a warning, the image most likely costing many tokens.
+const LARGE_IMAGE_URL_LEN: usize = 4 << 20;
+
+impl ImageUrl {
+	/// Logs a warning for a large image sent inline with `ImageDetail::High`.
+	pub(crate) fn warn_if_costly(&self) {
+		if self.detail == Some(ImageDetail::High)
+			&& self.url.starts_with("data:")
+			&& self.url.len() > LARGE_IMAGE_URL_LEN
+		{
+			log::warn!(
+				"Sending an image of {} bytes with detail high, which costs many tokens, \
+				 consider detail low or a smaller image",
+				self.url.len()
+			);
+		}
+	}
+}
+
+impl ContentPart {
+	pub fn text(text: impl Into<String>) -> ContentPart {
+		ContentPart::Text { text: text.into() }
+	}
+
+	pub fn image_url(url: impl Into<String>) -> ContentPart {
+		ContentPart::ImageUrl { image_url: ImageUrl { url: url.into(), detail: None } }
+	}
+
+	/// An image sent inline as a `data:` URL, e.g. `image_from_bytes(png, mime::IMAGE_PNG)`.
+	pub fn image_from_bytes(bytes: &[u8], mime: Mime) -> ContentPart {
+		let data = base64::engine::general_purpose::STANDARD.encode(bytes);
+		ContentPart::image_url(format!("data:{mime};base64,{data}"))
+	}
+
+	/// Sets the detail level of an image part, other parts are left unchanged.
+	pub fn with_detail(mut self, level: ImageDetail) -> ContentPart {
+		if let ContentPart::ImageUrl { image_url } = &mut self {
+			image_url.detail = Some(level);
+		}
+		self
+	}
+
+	/// Audio sent inline, e.g. `input_audio(wav, "wav")`.
+	pub fn input_audio(bytes: &[u8], format: impl Into<String>) -> ContentPart {
+		let data = base64::engine::general_purpose::STANDARD.encode(bytes);
+		ContentPart::InputAudio { input_audio: InputAudio { data, format: format.into() } }
+	}
+}
+
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
 pub enum Role {
 	System,
+	/// Replaces `System` for the o-series reasoning models, see `ChatBody::use_developer_role`.
+	Developer,
 	Assistant,
 	User,
+	Tool,
 }
 
 impl Clone for Role {
 	fn clone(&self) -> Self {
 		match self {
 			Self::System => Self::System,
+			Self::Developer => Self::Developer,
 			Self::Assistant => Self::Assistant,
 			Self::User => Self::User,
+			Self::Tool => Self::Tool,
+		}
+	}
+}
+
+/// A call of a function tool generated by the model.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ToolCall {
+	/// The ID of the tool call, to send back as `Message::tool_call_id`.
+	pub id: String,
+	/// The type of the tool. Currently, only function is supported.
+	#[serde(rename = "type")]
+	pub kind: String,
+	pub function: FunctionCall,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FunctionCall {
+	/// The name of the function to call.
+	pub name: String,
+	/// The arguments to call the function with, as generated by the model in JSON format.
+	/// The model does not always generate valid JSON, validate the arguments before calling
+	/// your function.
+	pub arguments: String,
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::Message;
+
+	#[test]
+	fn test_usage_add() {
+		let mut usage = Usage {
+			prompt_tokens: Some(10),
+			completion_tokens: Some(5),
+			total_tokens: Some(15),
+			completion_tokens_details: Some(CompletionTokensDetails {
+				reasoning_tokens: Some(2),
+				..Default::default()
+			}),
+			prompt_tokens_details: None,
+		};
+		usage += Usage { prompt_tokens: Some(1), total_tokens: Some(1), ..Default::default() };
+		let usage = usage.clone() + usage;
+		assert_eq!(usage.prompt_tokens, Some(22));
+		assert_eq!(usage.completion_tokens, Some(10));
+		assert_eq!(usage.total_tokens, Some(32));
+		let details = usage.completion_tokens_details.unwrap();
+		assert_eq!(details.reasoning_tokens, Some(4));
+		assert_eq!(details.accepted_prediction_tokens, None);
+		assert_eq!(Vec::<Usage>::new().into_iter().sum::<Usage>(), Usage::default());
+
+		let mut usage = Usage { total_tokens: Some(u32::MAX - 1), ..Default::default() };
+		usage += Usage { total_tokens: Some(10), ..Default::default() };
+		assert_eq!(usage.total_tokens, Some(u32::MAX));
+	}
+
+	#[test]
+	fn test_usage_details() {
+		let usage: Usage = serde_json::from_str(
+			r#"{"prompt_tokens": 2006, "completion_tokens": 300, "total_tokens": 2306,
+			"prompt_tokens_details": {"cached_tokens": 1920, "audio_tokens": 0},
+			"completion_tokens_details": {"reasoning_tokens": 192, "audio_tokens": 0,
+				"accepted_prediction_tokens": 0, "rejected_prediction_tokens": 0}}"#,
+		)
+		.unwrap();
+		let prompt = usage.prompt_tokens_details.as_ref().unwrap();
+		assert_eq!((prompt.cached_tokens, prompt.audio_tokens), (Some(1920), Some(0)));
+		let completion = usage.completion_tokens_details.as_ref().unwrap();
+		assert_eq!(completion.reasoning_tokens, Some(192));
+		assert_eq!(completion.audio_tokens, Some(0));
+		assert_eq!(completion.rejected_prediction_tokens, Some(0));
+
+		let total = usage.clone() + Usage { prompt_tokens: Some(4), ..Default::default() } + usage;
+		assert_eq!(total.prompt_tokens_details.unwrap().cached_tokens, Some(3840));
+		assert_eq!(total.completion_tokens_details.unwrap().reasoning_tokens, Some(384));
+
+		let usage: Usage = serde_json::from_str(r#"{"prompt_tokens": 1}"#).unwrap();
+		assert!(usage.prompt_tokens_details.is_none());
+		assert!(serde_json::to_value(&usage).unwrap().get("prompt_tokens_details").is_none());
+	}
+
+	#[test]
+	fn test_end_user() {
+		let chat = chat::ChatBody::builder().model("gpt-4o").user("Hi").end_user("user-1");
+		let completions = completions::CompletionsBody::default();
+		let embeddings = embeddings::EmbeddingsBody {
+			model: "text-embedding-3-small".into(),
+			input: "Hi".into(),
+			dimensions: None,
+			encoding_format: None,
+			user: None,
+		};
+		let images = images::ImagesBody {
+			model: None,
+			prompt: "A cat".to_string(),
+			n: None,
+			size: None,
+			quality: None,
+			style: None,
+			response_format: None,
+			user: None,
+			partial_images: None,
+		};
+		let moderations =
+			moderations::ModerationsBody { input: "Hi".into(), model: None, user: None };
+		assert!(serde_json::to_value(&moderations).unwrap().get("user").is_none());
+
+		let bodies = [
+			serde_json::to_value(chat.build().unwrap()),
+			serde_json::to_value(completions.end_user("user-1")),
+			serde_json::to_value(embeddings.end_user("user-1")),
+			serde_json::to_value(images.end_user("user-1")),
+			serde_json::to_value(moderations.end_user("user-1")),
+		];
+		for body in bodies {
+			assert_eq!(body.unwrap()["user"], "user-1");
+		}
+		let body = chat::ChatBody { messages: vec![Message::user("Hi")], ..Default::default() };
+		assert!(serde_json::to_value(body).unwrap().get("user").is_none());
+	}
+
+	#[test]
+	fn test_message_tool_calls() {
+		let json = serde_json::json!({
+			"role": "assistant",
+			"content": null,
+			"tool_calls": [{
+				"id": "call_1",
+				"type": "function",
+				"function": {"name": "get_weather", "arguments": "{}"},
+			}],
+		});
+		let message: Message = serde_json::from_value(json.clone()).unwrap();
+		assert!(message.content.is_none() && message.text().is_none());
+		assert_eq!(message.tool_calls()[0].function.name, "get_weather");
+		assert_eq!(serde_json::to_value(&message).unwrap(), json);
+
+		let message: Message =
+			serde_json::from_value(serde_json::json!({"role": "assistant"})).unwrap();
+		assert!(message.content.is_none() && message.tool_calls().is_empty());
+	}
+
+	#[test]
+	fn test_content_filter_results() {
+		let severity = |severity: FilterSeverity| ContentFilterSeverity {
+			filtered: false,
+			severity: Some(severity),
+		};
+		let json = serde_json::json!({
+			"id": "chatcmpl-1",
+			"object": "chat.completion",
+			"created": 1,
+			"model": "gpt-4o-2024-08-06",
+			"prompt_filter_results": [{
+				"prompt_index": 0,
+				"content_filter_results": {
+					"hate": {"filtered": false, "severity": "safe"},
+					"jailbreak": {"filtered": false, "detected": false},
+					"self_harm": {"filtered": false, "severity": "safe"},
+					"sexual": {"filtered": false, "severity": "safe"},
+					"violence": {"filtered": false, "severity": "low"},
+				},
+			}],
+			"choices": [{
+				"index": 0,
+				"finish_reason": "stop",
+				"message": {"role": "assistant", "content": "Hello!"},
+				"content_filter_results": {
+					"hate": {"filtered": false, "severity": "safe"},
+					"protected_material_code": {"filtered": false, "detected": false},
+					"protected_material_text": {"filtered": false, "detected": false},
+					"self_harm": {"filtered": false, "severity": "safe"},
+					"sexual": {"filtered": true, "severity": "medium"},
+					"violence": {"filtered": false, "severity": "safe"},
+					"custom_blocklists": {"filtered": false, "details": []},
+				},
+			}],
+			"usage": {"prompt_tokens": 9, "completion_tokens": 2, "total_tokens": 11},
+		});
+		let completion: completions::Completion = serde_json::from_value(json).unwrap();
+		assert!(completion.extra.is_empty());
+		let prompt = &completion.prompt_filter_results.as_ref().unwrap()[0];
+		assert_eq!(prompt.content_filter_results.violence, Some(severity(FilterSeverity::Low)));
+		assert_eq!(prompt.content_filter_results.jailbreak.as_ref().unwrap().detected, Some(false));
+		assert!(!prompt.content_filter_results.is_filtered());
+		let results = completion.choices[0].content_filter_results.as_ref().unwrap();
+		assert_eq!(results.sexual.as_ref().unwrap().severity, Some(FilterSeverity::Medium));
+		assert!(results.is_filtered());
+		assert!(results.extra.contains_key("custom_blocklists"));
+
+		let error = serde_json::json!({"error": {"code": "content_filter_error", "message": "x"}});
+		let results: ContentFilterResults = serde_json::from_value(error).unwrap();
+		assert_eq!(results.error.unwrap().code.as_deref(), Some("content_filter_error"));
+
+		// Absent from OpenAI responses, and not serialized back.
+		let json = serde_json::json!({
+			"created": 1,
+			"choices": [{"index": 0, "message": {"role": "assistant", "content": "Hi"}}],
+		});
+		let completion: completions::Completion = serde_json::from_value(json).unwrap();
+		assert!(completion.prompt_filter_results.is_none());
+		assert!(completion.choices[0].content_filter_results.is_none());
+		let json = serde_json::to_value(&completion).unwrap();
+		assert!(json.get("prompt_filter_results").is_none());
+		assert!(json["choices"][0].get("content_filter_results").is_none());
+	}
+
+	#[test]
+	fn test_completion_response() {
+		use crate::stream::{new_test_stream, Stream};
+
+		fn summary<T: CompletionResponse>(responses: &[T]) -> (String, bool, Option<u32>) {
+			let text = responses.iter().filter_map(|response| response.choice_text(0)).collect();
+			let truncated = responses.last().is_some_and(T::was_truncated);
+			(text, truncated, total_usage(responses).total_tokens)
+		}
+
+		let completion = |choice: serde_json::Value| -> completions::Completion {
+			serde_json::from_value(serde_json::json!({
+				"created": 1,
+				"choices": [choice],
+				"usage": {"prompt_tokens": 1, "completion_tokens": 2, "total_tokens": 3},
+			}))
+			.unwrap()
+		};
+		let responses = [
+			completion(serde_json::json!({"index": 0, "text": "Hello", "finish_reason": "stop"})),
+			completion(serde_json::json!({"index": 0, "finish_reason": "length",
+				"message": {"role": "assistant", "content": " world"}})),
+		];
+		assert_eq!(summary(&responses), ("Hello world".to_string(), true, Some(6)));
+
+		let chat = "data: {\"created\": 1, \"choices\": [{\"index\": 0, \"delta\": {\"content\": \"Hi\"}}]}\n\n\
+			data: {\"created\": 1, \"choices\": [{\"index\": 0, \"delta\": {}, \"finish_reason\": \"length\"}]}\n\n\
+			data: {\"created\": 1, \"choices\": [], \"usage\": {\"total_tokens\": 4}}\n\n\
+			data: [DONE]\n\n";
+		let completion = "data: {\"created\": 1, \"choices\": [{\"index\": 0, \"text\": \"Hi\"}]}\n\n\
+			data: {\"created\": 1, \"choices\": [{\"index\": 0, \"text\": \"\", \"finish_reason\": \"stop\"}]}\n\n\
+			data: {\"created\": 1, \"choices\": [], \"usage\": {\"total_tokens\": 4}}\n\n\
+			data: [DONE]\n\n";
+		let chunks: Vec<chat::ChatCompletionChunk> =
+			new_test_stream(chat, 7).map(Result::unwrap).collect();
+		assert_eq!(summary(&chunks[..2]), ("Hi".to_string(), true, None));
+		assert_eq!(total_usage(&chunks).total_tokens, Some(4));
+		let chunks: Vec<completions::CompletionChunk> =
+			new_test_stream(completion, 7).map(Result::unwrap).collect();
+		assert_eq!(summary(&chunks), ("Hi".to_string(), false, Some(4)));
+
+		let stream: Stream<chat::ChatCompletionChunk> = new_test_stream(chat, 7);
+		assert_eq!(stream.collect_text().unwrap(), ("Hi".to_string(), Some(FinishReason::Length)));
+		let stream: Stream<completions::CompletionChunk> = new_test_stream(completion, 7);
+		assert_eq!(stream.collect_text().unwrap(), ("Hi".to_string(), Some(FinishReason::Stop)));
+	}
+
+	#[test]
+	fn test_created() {
+		let json = r#"{"id": "file-abc", "object": "file", "bytes": 12, "created_at": 1700000000,
+			"filename": "training.jsonl", "purpose": "fine-tune"}"#;
+		let file: files::FileObject = serde_json::from_str(json).unwrap();
+		assert_eq!(file.created_secs(), Some(1_700_000_000));
+		assert_eq!(file.created_time(), Some(UNIX_EPOCH + Duration::from_secs(1_700_000_000)));
+		#[cfg(feature = "chrono")]
+		assert_eq!(file.created_datetime().unwrap().to_rfc3339(), "2023-11-14T22:13:20+00:00");
+
+		let model: models::Model = serde_json::from_str(r#"{"id": "gpt-4o"}"#).unwrap();
+		assert!(model.created_time().is_none());
+	}
+
+	#[test]
+	fn test_audio_content() {
+		let message = Message::user(vec![
+			ContentPart::text("What is in this recording?"),
+			ContentPart::input_audio(b"RIFF", "wav"),
+		]);
+		assert_eq!(
+			serde_json::to_value(&message).unwrap()["content"][1],
+			serde_json::json!({"type": "input_audio", "input_audio": {"data": "UklGRg==", "format": "wav"}})
+		);
+
+		let json = serde_json::json!({
+			"role": "assistant",
+			"content": null,
+			"audio": {"id": "audio_abc", "data": "UklGRg==", "transcript": "Hello!", "expires_at": 1700000000},
+		});
+		let message: Message = serde_json::from_value(json.clone()).unwrap();
+		let audio = message.audio.as_ref().unwrap();
+		assert_eq!(audio.transcript, "Hello!");
+		assert_eq!(audio.bytes().unwrap(), b"RIFF");
+		assert_eq!(serde_json::to_value(&message).unwrap(), json);
+	}
+
+	#[test]
+	fn test_image_detail() {
+		let part = ContentPart::image_url("https://example.com/cat.png");
+		assert_eq!(
+			serde_json::to_value(&part).unwrap(),
+			serde_json::json!({"type": "image_url", "image_url": {"url": "https://example.com/cat.png"}})
+		);
+		for (detail, value) in
+			[(ImageDetail::Low, "low"), (ImageDetail::High, "high"), (ImageDetail::Auto, "auto")]
+		{
+			let part = part.clone().with_detail(detail);
+			let json = serde_json::to_value(&part).unwrap();
+			assert_eq!(json["image_url"]["detail"], value);
+			assert_eq!(serde_json::from_value::<ContentPart>(json).unwrap(), part);
 		}
+		assert_eq!(ImageDetail::default(), ImageDetail::Auto);
+		assert_eq!(ContentPart::text("hi").with_detail(ImageDetail::High), ContentPart::text("hi"));
 	}
 }
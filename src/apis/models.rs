@@ -18,10 +18,22 @@ use super::MODELS_RETRIEVE;
 pub struct Model {
 	pub id: String,
 	pub object: Option<String>,
+	/// The Unix timestamp (in seconds) when the model was created.
+	pub created: Option<u64>,
 	pub owned_by: Option<String>,
+	/// No longer returned by the API for newer models.
+	#[serde(default)]
 	pub permission: Vec<Permission>,
 }
 
+/// The response of deleting a fine-tuned model.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DeletedModel {
+	pub id: String,
+	pub object: Option<String>,
+	pub deleted: bool,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Permission {
 	pub id: String,
@@ -38,6 +50,153 @@ pub struct Permission {
 	pub is_blocking: bool,
 }
 
+/// The names of common models, with `Custom` for any other model, fine-tuned model
+/// or deployment name.
+///
+/// Request bodies take a `ModelName`, which names convert into:
+/// `ChatBody { model: ModelName::Gpt4oMini, .. }` or `ChatBody { model: "my-model".into(), .. }`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ModelName {
+	Gpt4o,
+	Gpt4oMini,
+	Gpt4Turbo,
+	Gpt4,
+	Gpt35Turbo,
+	O1,
+	O1Mini,
+	O3Mini,
+	TextEmbedding3Small,
+	TextEmbedding3Large,
+	TextEmbeddingAda002,
+	Whisper1,
+	Tts1,
+	Tts1Hd,
+	DallE2,
+	DallE3,
+	OmniModerationLatest,
+	Custom(String),
+}
+
+/// The known models, matched by their name in `From<&str>`.
+const KNOWN_MODELS: [ModelName; 17] = [
+	ModelName::Gpt4o,
+	ModelName::Gpt4oMini,
+	ModelName::Gpt4Turbo,
+	ModelName::Gpt4,
+	ModelName::Gpt35Turbo,
+	ModelName::O1,
+	ModelName::O1Mini,
+	ModelName::O3Mini,
+	ModelName::TextEmbedding3Small,
+	ModelName::TextEmbedding3Large,
+	ModelName::TextEmbeddingAda002,
+	ModelName::Whisper1,
+	ModelName::Tts1,
+	ModelName::Tts1Hd,
+	ModelName::DallE2,
+	ModelName::DallE3,
+	ModelName::OmniModerationLatest,
+];
+
+impl ModelName {
+	/// The exact name the API expects.
+	pub fn as_str(&self) -> &str {
+		match self {
+			ModelName::Gpt4o => "gpt-4o",
+			ModelName::Gpt4oMini => "gpt-4o-mini",
+			ModelName::Gpt4Turbo => "gpt-4-turbo",
+			ModelName::Gpt4 => "gpt-4",
+			ModelName::Gpt35Turbo => "gpt-3.5-turbo",
+			ModelName::O1 => "o1",
+			ModelName::O1Mini => "o1-mini",
+			ModelName::O3Mini => "o3-mini",
+			ModelName::TextEmbedding3Small => "text-embedding-3-small",
+			ModelName::TextEmbedding3Large => "text-embedding-3-large",
+			ModelName::TextEmbeddingAda002 => "text-embedding-ada-002",
+			ModelName::Whisper1 => "whisper-1",
+			ModelName::Tts1 => "tts-1",
+			ModelName::Tts1Hd => "tts-1-hd",
+			ModelName::DallE2 => "dall-e-2",
+			ModelName::DallE3 => "dall-e-3",
+			ModelName::OmniModerationLatest => "omni-moderation-latest",
+			ModelName::Custom(name) => name,
+		}
+	}
+
+	/// Whether the name is empty, e.g. to fall back to `OpenAI::with_default_models`.
+	pub fn is_empty(&self) -> bool {
+		self.as_str().is_empty()
+	}
+}
+
+/// An empty `Custom` name, for bodies taking the model of `OpenAI::with_default_models`.
+impl Default for ModelName {
+	fn default() -> Self {
+		ModelName::Custom(String::new())
+	}
+}
+
+impl PartialEq<str> for ModelName {
+	fn eq(&self, other: &str) -> bool {
+		self.as_str() == other
+	}
+}
+
+impl PartialEq<&str> for ModelName {
+	fn eq(&self, other: &&str) -> bool {
+		self.as_str() == *other
+	}
+}
+
+impl AsRef<str> for ModelName {
+	fn as_ref(&self) -> &str {
+		self.as_str()
+	}
+}
+
+impl std::fmt::Display for ModelName {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.write_str(self.as_str())
+	}
+}
+
+impl From<&str> for ModelName {
+	/// The known model named `name`, or `Custom`.
+	fn from(name: &str) -> Self {
+		match KNOWN_MODELS.iter().find(|model| model.as_str() == name) {
+			Some(model) => model.clone(),
+			None => ModelName::Custom(name.to_string()),
+		}
+	}
+}
+
+impl From<String> for ModelName {
+	fn from(name: String) -> Self {
+		ModelName::from(name.as_str())
+	}
+}
+
+impl From<ModelName> for String {
+	fn from(model: ModelName) -> Self {
+		match model {
+			ModelName::Custom(name) => name,
+			model => model.as_str().to_string(),
+		}
+	}
+}
+
+impl Serialize for ModelName {
+	fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+		serializer.serialize_str(self.as_str())
+	}
+}
+
+impl<'de> Deserialize<'de> for ModelName {
+	fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+		String::deserialize(deserializer).map(ModelName::from)
+	}
+}
+
 pub trait ModelsApi {
 	/// Lists the currently available models,
 	/// and provides basic information about each one such as the owner and availability.
@@ -45,6 +204,9 @@ pub trait ModelsApi {
 	/// Retrieves a model instance,
 	/// providing basic information about the model such as the owner and permissioning.
 	fn models_retrieve(&self, model_id: &str) -> ApiResult<Model>;
+	/// Deletes a fine-tuned model.
+	/// You must have the Owner role in your organization to delete a model.
+	fn models_delete(&self, model_id: &str) -> ApiResult<DeletedModel>;
 }
 
 impl ModelsApi for OpenAI {
@@ -52,7 +214,7 @@ impl ModelsApi for OpenAI {
 		let res: Json = self.get(MODELS_LIST)?;
 		let data = res.as_object().unwrap().get("data");
 		if let Some(data) = data {
-			let models: Vec<Model> = serde_json::from_value(data.clone()).unwrap();
+			let models: Vec<Model> = from_json(data)?;
 			return Ok(models);
 		}
 		Err(Error::ApiError("No data".to_string()))
@@ -60,14 +222,24 @@ impl ModelsApi for OpenAI {
 
 	fn models_retrieve(&self, model_id: &str) -> ApiResult<Model> {
 		let res: Json = self.get(&(MODELS_RETRIEVE.to_owned() + model_id))?;
-		let model: Model = serde_json::from_value(res).unwrap();
+		let model: Model = from_json(&res)?;
 		Ok(model)
 	}
+
+	fn models_delete(&self, model_id: &str) -> ApiResult<DeletedModel> {
+		let res: Json = self.delete(&(MODELS_RETRIEVE.to_owned() + model_id))?;
+		let deleted: DeletedModel = from_json(&res)?;
+		Ok(deleted)
+	}
 }
 
 #[cfg(test)]
 mod tests {
-	use crate::{apis::models::ModelsApi, openai::new_test_openai};
+	use crate::{
+		apis::models::{ModelName, ModelsApi, KNOWN_MODELS},
+		chat::ChatBody,
+		openai::{new_test_openai, new_test_response, new_test_server, Auth, OpenAI},
+	};
 
 	#[test]
 	fn test_models() {
@@ -82,4 +254,58 @@ mod tests {
 		let model = openai.models_retrieve("babbage").unwrap();
 		assert_eq!("babbage", model.id);
 	}
+
+	#[test]
+	fn test_models_offline() {
+		let model = r#"{"id": "ft:gpt-4o-mini:acme::abc", "object": "model", "created": 1700000000,
+			"owned_by": "acme"}"#;
+		let list = format!(r#"{{"object": "list", "data": [{model}]}}"#);
+		let (url, server) = new_test_server(vec![
+			new_test_response(200, &[], &list),
+			new_test_response(200, &[], model),
+			new_test_response(
+				200,
+				&[],
+				r#"{"id": "ft:gpt-4o-mini:acme::abc", "object": "model", "deleted": true}"#,
+			),
+		]);
+		let openai = OpenAI::new(Auth::new("sk-test"), &url);
+
+		let models = openai.models_list().unwrap();
+		assert_eq!(models[0].created, Some(1700000000));
+		assert!(models[0].permission.is_empty());
+		let model = openai.models_retrieve("ft:gpt-4o-mini:acme::abc").unwrap();
+		assert_eq!(model.owned_by.as_deref(), Some("acme"));
+		assert!(openai.models_delete("ft:gpt-4o-mini:acme::abc").unwrap().deleted);
+
+		let requests = server.join().unwrap();
+		assert!(requests[0].starts_with("GET /v1/models "));
+		assert!(requests[1].starts_with("GET /v1/models/ft:gpt-4o-mini:acme::abc "));
+		assert!(requests[2].starts_with("DELETE /v1/models/ft:gpt-4o-mini:acme::abc "));
+	}
+
+	#[test]
+	fn test_model_name() {
+		assert_eq!(ModelName::Gpt4oMini.to_string(), "gpt-4o-mini");
+		assert_eq!(ModelName::from("o3-mini"), ModelName::O3Mini);
+		assert_eq!(
+			ModelName::from("ft:gpt-4o-mini:acme::abc").as_str(),
+			"ft:gpt-4o-mini:acme::abc"
+		);
+		assert_eq!(
+			serde_json::to_value(ModelName::TextEmbedding3Small).unwrap(),
+			"text-embedding-3-small"
+		);
+		let model: ModelName = serde_json::from_value("dall-e-3".into()).unwrap();
+		assert_eq!(model, ModelName::DallE3);
+
+		for model in KNOWN_MODELS {
+			assert_eq!(ModelName::from(model.as_str()), model);
+		}
+
+		let body = ChatBody::builder().model(ModelName::Gpt4o).user("Hello!").build().unwrap();
+		assert_eq!(body.model, ModelName::Gpt4o);
+		let body = ChatBody { model: "gpt-4o-mini".into(), ..Default::default() };
+		assert_eq!(serde_json::to_value(&body).unwrap()["model"], "gpt-4o-mini");
+	}
 }
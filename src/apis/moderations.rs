@@ -0,0 +1,180 @@
+// Given some input text, outputs if the model classifies it as potentially harmful
+// across several categories.
+// See: https://platform.openai.com/docs/api-reference/moderations
+
+//! Moderations API
+
+use serde::{Deserialize, Serialize};
+
+use crate::models::ModelName;
+use crate::requests::Requests;
+use crate::*;
+
+use super::MODERATIONS_CREATE;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ModerationsBody {
+	/// The input text to classify, a string or an array of strings.
+	pub input: ModerationInput,
+	/// The content moderation model to use, e.g. omni-moderation-latest or text-moderation-latest.
+	/// Defaults to omni-moderation-latest
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub model: Option<ModelName>,
+	/// A unique identifier representing your end-user, which can help OpenAI to monitor and detect abuse.
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub user: Option<String>,
+}
+
+/// A single input, or a batch of inputs classified in one request.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum ModerationInput {
+	Single(String),
+	Batch(Vec<String>),
+}
+
+impl From<&str> for ModerationInput {
+	fn from(input: &str) -> Self {
+		Self::Single(input.to_string())
+	}
+}
+
+impl From<String> for ModerationInput {
+	fn from(input: String) -> Self {
+		Self::Single(input)
+	}
+}
+
+impl From<Vec<String>> for ModerationInput {
+	fn from(input: Vec<String>) -> Self {
+		Self::Batch(input)
+	}
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Moderation {
+	pub id: String,
+	pub model: String,
+	/// One result per input, in the same order.
+	pub results: Vec<ModerationResult>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ModerationResult {
+	/// Whether any of the categories are flagged.
+	pub flagged: bool,
+	pub categories: Categories,
+	pub category_scores: CategoryScores,
+}
+
+/// Whether the input is flagged for each category.
+/// Categories unknown to the model used are `false`.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Categories {
+	pub harassment: bool,
+	#[serde(rename = "harassment/threatening")]
+	pub harassment_threatening: bool,
+	pub hate: bool,
+	#[serde(rename = "hate/threatening")]
+	pub hate_threatening: bool,
+	pub illicit: bool,
+	#[serde(rename = "illicit/violent")]
+	pub illicit_violent: bool,
+	#[serde(rename = "self-harm")]
+	pub self_harm: bool,
+	#[serde(rename = "self-harm/intent")]
+	pub self_harm_intent: bool,
+	#[serde(rename = "self-harm/instructions")]
+	pub self_harm_instructions: bool,
+	pub sexual: bool,
+	#[serde(rename = "sexual/minors")]
+	pub sexual_minors: bool,
+	pub violence: bool,
+	#[serde(rename = "violence/graphic")]
+	pub violence_graphic: bool,
+}
+
+/// The score of the input for each category, between 0 and 1.
+/// Categories unknown to the model used are `0`.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CategoryScores {
+	pub harassment: f64,
+	#[serde(rename = "harassment/threatening")]
+	pub harassment_threatening: f64,
+	pub hate: f64,
+	#[serde(rename = "hate/threatening")]
+	pub hate_threatening: f64,
+	pub illicit: f64,
+	#[serde(rename = "illicit/violent")]
+	pub illicit_violent: f64,
+	#[serde(rename = "self-harm")]
+	pub self_harm: f64,
+	#[serde(rename = "self-harm/intent")]
+	pub self_harm_intent: f64,
+	#[serde(rename = "self-harm/instructions")]
+	pub self_harm_instructions: f64,
+	pub sexual: f64,
+	#[serde(rename = "sexual/minors")]
+	pub sexual_minors: f64,
+	pub violence: f64,
+	#[serde(rename = "violence/graphic")]
+	pub violence_graphic: f64,
+}
+
+pub trait ModerationsApi {
+	/// Classifies if text is potentially harmful.
+	fn moderation_create(&self, moderations_body: &ModerationsBody) -> ApiResult<Moderation>;
+	/// Whether `text` is flagged by the default moderation model.
+	fn is_flagged(&self, text: &str) -> ApiResult<bool>;
+}
+
+impl ModerationsApi for OpenAI {
+	fn moderation_create(&self, moderations_body: &ModerationsBody) -> ApiResult<Moderation> {
+		let request_body = serde_json::to_value(moderations_body).unwrap();
+		let res = self.post(MODERATIONS_CREATE, request_body)?;
+		let moderation: Moderation = from_json(&res)?;
+		Ok(moderation)
+	}
+
+	fn is_flagged(&self, text: &str) -> ApiResult<bool> {
+		let body = ModerationsBody { input: text.into(), model: None, user: None };
+		let moderation = self.moderation_create(&body)?;
+		Ok(moderation.results.iter().any(|result| result.flagged))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::{
+		apis::moderations::ModerationsApi,
+		openai::{new_test_response, new_test_server, Auth, OpenAI},
+	};
+
+	#[test]
+	fn test_moderation_create() {
+		let response = r#"{
+			"id": "modr-1",
+			"model": "text-moderation-007",
+			"results": [{
+				"flagged": true,
+				"categories": {"harassment": false, "self-harm": true, "violence/graphic": false},
+				"category_scores": {"harassment": 0.01, "self-harm": 0.93, "violence/graphic": 0.002}
+			}]
+		}"#;
+		let (url, server) = new_test_server(vec![new_test_response(200, &[], response)]);
+		let openai = OpenAI::new(Auth::new("sk-test"), &url);
+		assert!(openai.is_flagged("...").unwrap());
+
+		let request = &server.join().unwrap()[0];
+		assert!(request.starts_with("POST /v1/moderations "));
+		assert!(request.ends_with(r#"{"input":"..."}"#));
+
+		let moderation: super::Moderation = serde_json::from_str(response).unwrap();
+		let result = &moderation.results[0];
+		assert!(result.categories.self_harm && !result.categories.illicit);
+		assert_eq!(result.category_scores.self_harm, 0.93);
+		assert_eq!(result.category_scores.violence_graphic, 0.002);
+	}
+}
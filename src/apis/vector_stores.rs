@@ -0,0 +1,384 @@
+// Store and index files for the file_search tool of assistants.
+// See: https://platform.openai.com/docs/api-reference/vector-stores
+
+//! Vector Stores API
+//!
+//! Files uploaded with the `assistants` purpose are added to a vector store, in batches with
+//! `vector_store_file_batch_create`, then the store is attached to an assistant or a thread
+//! through the `tool_resources` of the file_search tool.
+
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::requests::Requests;
+use crate::*;
+
+use super::{ListParams, Paginated, Paginator, PollConfig, VECTOR_STORES, VECTOR_STORES_RETRIEVE};
+
+/// The maximum page size of the list endpoints.
+const VECTOR_STORES_MAX_LIMIT: u32 = 100;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct VectorStoreBody {
+	/// The name of the vector store.
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub name: Option<String>,
+	/// The IDs of files to add to the vector store.
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub file_ids: Option<Vec<String>>,
+	/// The expiration policy of the vector store.
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub expires_after: Option<ExpiresAfter>,
+	/// How the files are split into chunks, the auto strategy by default.
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub chunking_strategy: Option<Json>,
+	/// Set of 16 key-value pairs that can be attached to an object.
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub metadata: Option<HashMap<String, String>>,
+}
+
+/// Expires a vector store `days` after its `anchor`, currently only `last_active_at`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExpiresAfter {
+	pub anchor: String,
+	pub days: u32,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct VectorStore {
+	pub id: String,
+	pub object: Option<String>,
+	/// The Unix timestamp (in seconds) for when the vector store was created.
+	pub created_at: u64,
+	pub name: Option<String>,
+	/// The total number of bytes used by the files in the vector store.
+	#[serde(default)]
+	pub usage_bytes: u64,
+	pub file_counts: FileCounts,
+	/// One of expired, in_progress or completed. A store is ready for use once completed.
+	pub status: String,
+	pub expires_after: Option<ExpiresAfter>,
+	pub expires_at: Option<u64>,
+	pub last_active_at: Option<u64>,
+	pub metadata: Option<HashMap<String, String>>,
+}
+
+/// The number of files of a vector store or a file batch, by status.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct FileCounts {
+	pub in_progress: u32,
+	pub completed: u32,
+	pub failed: u32,
+	pub cancelled: u32,
+	pub total: u32,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DeletedVectorStore {
+	pub id: String,
+	pub object: Option<String>,
+	pub deleted: bool,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FileBatchBody {
+	/// The IDs of the files to add to the vector store, at most 500.
+	pub file_ids: Vec<String>,
+	/// How the files are split into chunks, the auto strategy by default.
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub chunking_strategy: Option<Json>,
+}
+
+/// A batch of files being added to a vector store.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FileBatch {
+	pub id: String,
+	pub object: Option<String>,
+	pub created_at: u64,
+	pub vector_store_id: String,
+	/// One of in_progress, completed, cancelled or failed.
+	pub status: String,
+	pub file_counts: FileCounts,
+}
+
+/// A file of a vector store.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct VectorStoreFile {
+	pub id: String,
+	pub object: Option<String>,
+	pub created_at: u64,
+	pub vector_store_id: String,
+	/// One of in_progress, completed, cancelled or failed.
+	pub status: String,
+	#[serde(default)]
+	pub usage_bytes: u64,
+	/// Why the file failed to be added, `None` unless its status is failed.
+	pub last_error: Option<VectorStoreFileError>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VectorStoreFileError {
+	/// One of server_error, unsupported_file or invalid_file.
+	pub code: String,
+	pub message: String,
+}
+
+/// Requests of this API carry the `OpenAI-Beta: assistants=v2` header.
+pub trait VectorStoresApi {
+	/// Creates a vector store, optionally with files.
+	fn vector_store_create(&self, vector_store_body: &VectorStoreBody) -> ApiResult<VectorStore>;
+	/// Returns a list of vector stores, most recent first.
+	fn vector_stores_list(&self, params: &ListParams) -> ApiResult<Paginated<VectorStore>>;
+	/// Iterates over all the vector stores starting from `params`,
+	/// fetching pages of the maximum size unless `params.limit` is set.
+	fn vector_stores_iter(&self, params: ListParams) -> Paginator<'_, VectorStore>;
+	/// Retrieves a vector store.
+	fn vector_store_retrieve(&self, vector_store_id: &str) -> ApiResult<VectorStore>;
+	/// Deletes a vector store. Its files are not deleted, see `FilesApi::file_delete`.
+	fn vector_store_delete(&self, vector_store_id: &str) -> ApiResult<DeletedVectorStore>;
+	/// Adds files to a vector store, see `vector_store_file_batch_poll`.
+	fn vector_store_file_batch_create(
+		&self,
+		vector_store_id: &str,
+		file_batch_body: &FileBatchBody,
+	) -> ApiResult<FileBatch>;
+	/// Retrieves a file batch.
+	fn vector_store_file_batch_retrieve(
+		&self,
+		vector_store_id: &str,
+		batch_id: &str,
+	) -> ApiResult<FileBatch>;
+	/// Iterates over the files of a batch whose status is `status`, e.g. failed,
+	/// or over all of them if `status` is `None`.
+	fn vector_store_file_batch_files(
+		&self,
+		vector_store_id: &str,
+		batch_id: &str,
+		status: Option<&str>,
+	) -> Paginator<'_, VectorStoreFile>;
+	/// Retrieves the batch until it is no longer in progress, with the delays and the timeout of
+	/// `poll`, then returns it along with the files that failed to be added and their
+	/// `last_error`. Fails with `Error::Timeout` once the timeout elapses.
+	fn vector_store_file_batch_poll(
+		&self,
+		vector_store_id: &str,
+		batch_id: &str,
+		poll: &PollConfig,
+	) -> ApiResult<(FileBatch, Vec<VectorStoreFile>)>;
+}
+
+impl OpenAI {
+	fn file_batch_url(vector_store_id: &str, batch_id: &str) -> String {
+		format!("{VECTOR_STORES_RETRIEVE}{vector_store_id}/file_batches/{batch_id}")
+	}
+}
+
+impl VectorStoresApi for OpenAI {
+	fn vector_store_create(&self, vector_store_body: &VectorStoreBody) -> ApiResult<VectorStore> {
+		let request_body = serde_json::to_value(vector_store_body).unwrap();
+		let res = self.assistants_beta().post(VECTOR_STORES, request_body)?;
+		let vector_store: VectorStore = from_json(&res)?;
+		Ok(vector_store)
+	}
+
+	fn vector_stores_list(&self, params: &ListParams) -> ApiResult<Paginated<VectorStore>> {
+		let res = self.assistants_beta().get(&params.apply(VECTOR_STORES))?;
+		let vector_stores: Paginated<VectorStore> = from_json(&res)?;
+		Ok(vector_stores)
+	}
+
+	fn vector_stores_iter(&self, params: ListParams) -> Paginator<'_, VectorStore> {
+		Paginator::new(
+			params,
+			VECTOR_STORES_MAX_LIMIT,
+			|vector_store| vector_store.id.clone(),
+			|params| self.vector_stores_list(params),
+		)
+	}
+
+	fn vector_store_retrieve(&self, vector_store_id: &str) -> ApiResult<VectorStore> {
+		let sub_url = VECTOR_STORES_RETRIEVE.to_owned() + vector_store_id;
+		let res = self.assistants_beta().get(&sub_url)?;
+		let vector_store: VectorStore = from_json(&res)?;
+		Ok(vector_store)
+	}
+
+	fn vector_store_delete(&self, vector_store_id: &str) -> ApiResult<DeletedVectorStore> {
+		let sub_url = VECTOR_STORES_RETRIEVE.to_owned() + vector_store_id;
+		let res = self.assistants_beta().delete(&sub_url)?;
+		let deleted: DeletedVectorStore = from_json(&res)?;
+		Ok(deleted)
+	}
+
+	fn vector_store_file_batch_create(
+		&self,
+		vector_store_id: &str,
+		file_batch_body: &FileBatchBody,
+	) -> ApiResult<FileBatch> {
+		let request_body = serde_json::to_value(file_batch_body).unwrap();
+		let sub_url = format!("{VECTOR_STORES_RETRIEVE}{vector_store_id}/file_batches");
+		let res = self.assistants_beta().post(&sub_url, request_body)?;
+		let file_batch: FileBatch = from_json(&res)?;
+		Ok(file_batch)
+	}
+
+	fn vector_store_file_batch_retrieve(
+		&self,
+		vector_store_id: &str,
+		batch_id: &str,
+	) -> ApiResult<FileBatch> {
+		let res = self.assistants_beta().get(&OpenAI::file_batch_url(vector_store_id, batch_id))?;
+		let file_batch: FileBatch = from_json(&res)?;
+		Ok(file_batch)
+	}
+
+	fn vector_store_file_batch_files(
+		&self,
+		vector_store_id: &str,
+		batch_id: &str,
+		status: Option<&str>,
+	) -> Paginator<'_, VectorStoreFile> {
+		let sub_url = OpenAI::file_batch_url(vector_store_id, batch_id) + "/files";
+		let filter = status.map(|status| format!("filter={status}"));
+		Paginator::new(
+			ListParams::default(),
+			VECTOR_STORES_MAX_LIMIT,
+			|file| file.id.clone(),
+			move |params| {
+				let mut url = params.apply(&sub_url);
+				if let Some(filter) = &filter {
+					url.push(if url.contains('?') { '&' } else { '?' });
+					url.push_str(filter);
+				}
+				let res = self.assistants_beta().get(&url)?;
+				let files: Paginated<VectorStoreFile> = from_json(&res)?;
+				Ok(files)
+			},
+		)
+	}
+
+	fn vector_store_file_batch_poll(
+		&self,
+		vector_store_id: &str,
+		batch_id: &str,
+		poll: &PollConfig,
+	) -> ApiResult<(FileBatch, Vec<VectorStoreFile>)> {
+		let file_batch = poll.poll(
+			|| self.vector_store_file_batch_retrieve(vector_store_id, batch_id),
+			|file_batch| (file_batch.status == "in_progress").then(|| file_batch.status.clone()),
+		)?;
+		if file_batch.file_counts.failed == 0 {
+			return Ok((file_batch, Vec::new()));
+		}
+		let failed = self
+			.vector_store_file_batch_files(vector_store_id, batch_id, Some("failed"))
+			.collect::<ApiResult<_>>()?;
+		Ok((file_batch, failed))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::time::Duration;
+
+	use crate::{
+		apis::vector_stores::{FileBatchBody, VectorStoreBody, VectorStoresApi},
+		openai::new_test_mock,
+		testing::{MockResponse, MockTransport},
+		ListParams, PollConfig,
+	};
+
+	const VECTOR_STORE: &str = r#"{"id": "vs_abc", "object": "vector_store",
+		"created_at": 1700000000, "name": "Docs", "usage_bytes": 0, "file_counts":
+		{"in_progress": 0, "completed": 0, "failed": 0, "cancelled": 0, "total": 0},
+		"status": "completed", "expires_after": null, "expires_at": null,
+		"last_active_at": 1700000000, "metadata": {}}"#;
+
+	fn file_batch(status: &str, completed: u32, failed: u32) -> String {
+		let in_progress = 2 - completed - failed;
+		format!(
+			r#"{{"id": "vsfb_abc", "object": "vector_store.file_batch", "created_at": 1700000000,
+			"vector_store_id": "vs_abc", "status": "{status}", "file_counts": {{"in_progress":
+			{in_progress}, "completed": {completed}, "failed": {failed}, "cancelled": 0,
+			"total": 2}}}}"#
+		)
+	}
+
+	#[test]
+	fn test_vector_stores() {
+		let vector_stores =
+			format!(r#"{{"object": "list", "data": [{VECTOR_STORE}], "has_more": false}}"#);
+		let mock = MockTransport::new()
+			.with_response("vector_stores", MockResponse::new(200, VECTOR_STORE))
+			.with_response("vector_stores", MockResponse::new(200, vector_stores))
+			.with_response("vector_stores/vs_abc", MockResponse::new(200, VECTOR_STORE))
+			.with_response(
+				"vector_stores/vs_abc",
+				MockResponse::new(200, r#"{"id": "vs_abc", "deleted": true}"#),
+			);
+		let openai = new_test_mock(&mock);
+
+		let body = VectorStoreBody { name: Some("Docs".to_string()), ..Default::default() };
+		let vector_store = openai.vector_store_create(&body).unwrap();
+		assert_eq!(vector_store.status, "completed");
+		let vector_stores: Vec<_> =
+			openai.vector_stores_iter(ListParams::default()).map(Result::unwrap).collect();
+		assert_eq!(vector_stores[0].id, "vs_abc");
+		assert_eq!(openai.vector_store_retrieve("vs_abc").unwrap().name.as_deref(), Some("Docs"));
+		assert!(openai.vector_store_delete("vs_abc").unwrap().deleted);
+
+		let requests = mock.requests();
+		assert!(requests.iter().all(|r| r.header("OpenAI-Beta") == Some("assistants=v2")));
+		assert_eq!(requests[0].json().unwrap(), serde_json::json!({"name": "Docs"}));
+		assert_eq!(requests[1].url, "https://api.openai.com/v1/vector_stores?limit=100");
+		assert_eq!(requests[3].method, "DELETE");
+	}
+
+	#[test]
+	fn test_file_batch_poll() {
+		let failed = r#"{"object": "list", "data": [{"id": "file-2", "object":
+			"vector_store.file", "created_at": 1700000000, "vector_store_id": "vs_abc",
+			"status": "failed", "last_error": {"code": "unsupported_file",
+			"message": "The file type is not supported."}}], "has_more": false}"#;
+		let mock = MockTransport::new()
+			.with_response(
+				"vector_stores/vs_abc/file_batches",
+				MockResponse::new(200, file_batch("in_progress", 0, 0)),
+			)
+			.with_response(
+				"file_batches/vsfb_abc",
+				MockResponse::new(200, file_batch("in_progress", 1, 0)),
+			)
+			.with_response(
+				"file_batches/vsfb_abc",
+				MockResponse::new(200, file_batch("completed", 1, 1)),
+			)
+			.with_response("file_batches/vsfb_abc/files", MockResponse::new(200, failed));
+		let openai = new_test_mock(&mock);
+
+		let body = FileBatchBody {
+			file_ids: vec!["file-1".to_string(), "file-2".to_string()],
+			..Default::default()
+		};
+		let file_batch = openai.vector_store_file_batch_create("vs_abc", &body).unwrap();
+		let poll = PollConfig { min_delay: Duration::from_millis(1), ..Default::default() };
+		let (file_batch, failed) =
+			openai.vector_store_file_batch_poll("vs_abc", &file_batch.id, &poll).unwrap();
+		assert_eq!(file_batch.status, "completed");
+		assert_eq!(file_batch.file_counts.completed, 1);
+		assert_eq!(failed.len(), 1);
+		assert_eq!(failed[0].id, "file-2");
+		assert_eq!(failed[0].last_error.as_ref().unwrap().code, "unsupported_file");
+
+		let requests = mock.requests();
+		assert_eq!(requests.len(), 4);
+		assert_eq!(requests[0].json().unwrap()["file_ids"][1], "file-2");
+		assert_eq!(requests[1].path, "/v1/vector_stores/vs_abc/file_batches/vsfb_abc");
+		assert_eq!(
+			requests[3].url,
+			"https://api.openai.com/v1/vector_stores/vs_abc/file_batches/vsfb_abc/files\
+			?limit=100&filter=failed"
+		);
+	}
+}
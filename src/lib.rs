@@ -7,18 +7,18 @@
 //! [Options](struct.Options.html) struct.
 //!
 //! # Example
-//! ```rust
+//! ```rust,no_run
 //! use openai_api_rust::*;
 //! use openai_api_rust::chat::*;
 //! use openai_api_rust::completions::*;
-//! 
+//!
 //! fn main() {
 //!     // Load API key from environment OPENAI_API_KEY.
 //!     // You can also hadcode through `Auth::new(<your_api_key>)`, but it is not recommended.
 //!     let auth = Auth::from_env().unwrap();
 //!     let openai = OpenAI::new(auth, "https://api.openai.com/v1/");
 //!     let body = ChatBody {
-//!         model: "gpt-3.5-turbo".to_string(),
+//!         model: "gpt-3.5-turbo".into(),
 //!         max_tokens: Some(7),
 //!         temperature: Some(0_f32),
 //!         top_p: Some(0_f32),
@@ -29,60 +29,363 @@
 //!         frequency_penalty: None,
 //!         logit_bias: None,
 //!         user: None,
-//!         messages: vec![Message { role: Role::User, content: "Hello!".to_string() }],
+//!         messages: vec![Message::user("Hello!")],
+//!         ..Default::default()
 //!     };
 //!     let rs = openai.chat_completion_create(&body);
 //!     let choice = rs.unwrap().choices;
 //!     let message = &choice[0].message.as_ref().unwrap();
-//!     assert!(message.content.contains("Hello"));
+//!     assert!(message.text().unwrap().contains("Hello"));
 //! }
 //! ```
-//! 
+//!
 //! ## Use proxy
-//! 
+//!
 //! ```rust
+//! # use openai_api_rust::*;
+//! # let auth = Auth::new("sk-...");
 //! // Load proxy from env
-//! let openai = OpenAI::new(auth, "https://api.openai.com/v1/")
+//! let openai = OpenAI::new(auth.clone(), "https://api.openai.com/v1/")
 //!        .use_env_proxy();
-//! 
+//!
 //! // Set the proxy manually
 //! let openai = OpenAI::new(auth, "https://api.openai.com/v1/")
 //!        .set_proxy("http://127.0.0.1:1080");
 //! ```
-
-
+//!
+//! ## Clone requests and clients
+//!
+//! The request bodies are `Clone`, except those holding a file or a stream, such as `AudioBody`,
+//! `FilesBody`, `ImagesEditBody` and `Multipart`. Cloning a body copies its messages and fields.
+//! Cloning `OpenAI` is cheap, the clones share the connection pool.
+//!
+//! ```rust
+//! # use openai_api_rust::*;
+//! # use openai_api_rust::chat::*;
+//! let base = ChatBody::builder().model("gpt-4o").user("Hello!").build().unwrap();
+//! let variant = ChatBody { temperature: Some(1.5), ..base.clone() };
+//! assert_eq!(variant.messages.len(), base.messages.len());
+//! ```
 
 #![warn(unused_crate_dependencies)]
 
 pub mod apis;
-use std::fmt::{Display, Formatter, self};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::fmt::{self, Display, Formatter};
 
 pub use apis::*;
 pub mod openai;
 pub use openai::*;
-mod multipart;
+pub mod model_info;
+pub mod multipart;
+pub mod pricing;
+mod random;
+pub mod rate_limit;
 mod requests;
+pub mod stream;
+pub mod testing;
+#[cfg(feature = "tokenizer")]
+pub mod tokenizer;
+pub mod transport;
 
 use log as _;
+// A dev-dependency for the tests of the `async` feature.
+#[cfg(all(test, not(feature = "async")))]
+use tokio as _;
 
 pub type Json = serde_json::Value;
 pub type ApiResult<T> = Result<T, Error>;
 
-pub use openai::*;
-
 #[derive(Debug)]
 pub enum Error {
-	/// An Error returned by the API
+	/// An Error returned by the API with an error status, boxed to keep results small
+	Api(Box<ApiError>),
+	/// A response of the API that isn't what it documents, e.g. missing the data requested
 	ApiError(String),
 	/// An Error not related to the API
 	RequestError(String),
+	/// A request that failed before its response was received, e.g. a refused or reset
+	/// connection, or a timeout
+	Connection(String),
+	/// A request rejected before being sent because the API would reject it
+	InvalidRequest(String),
+	/// A stream aborted with `StreamHandle::abort`
+	Cancelled,
+	/// A stream whose connection closed before `[DONE]` was received, with the number of chunks
+	/// received until then. The API sends about one token per chunk, and can't resume a stream,
+	/// so the request has to be sent again for a complete response.
+	StreamInterrupted(usize),
+	/// Polling that didn't complete within its timeout, with the last status retrieved
+	Timeout(String),
+	/// A response body larger than `OpenAI::with_max_response_bytes`, with the limit in bytes
+	ResponseTooLarge(u64),
+	/// A response body that can't be parsed, e.g. after a change of the API or from a
+	/// compatible server returning other fields
+	Deserialization {
+		source: serde_json::Error,
+		/// The type the body was parsed as
+		target: &'static str,
+		/// The start of the body, at most `BODY_SNIPPET_LEN` bytes, credentials redacted
+		body_snippet: String,
+	},
+}
+
+/// The maximum length of the body in `Error::Deserialization`.
+pub const BODY_SNIPPET_LEN: usize = 512;
+/// The prefixes of the credentials redacted from bodies in errors.
+const CREDENTIAL_PREFIXES: [&str; 2] = ["bearer ", "api-key: "];
+
+/// The error object returned by the API along with a non-2xx status,
+/// `{"error": {"message": ..., "type": ..., "param": ..., "code": ...}}`. Error bodies that
+/// aren't error objects, e.g. the HTML page of a proxy, are kept whole as the message.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ApiError {
+	/// The HTTP status of the response
+	#[serde(skip)]
+	pub status: u16,
+	pub message: String,
+	#[serde(rename = "type")]
+	pub kind: Option<String>,
+	/// The request parameter the error is related to
+	pub param: Option<String>,
+	/// A machine-readable code, e.g. `rate_limit_exceeded`
+	pub code: Option<String>,
+	/// The `x-request-id` header of the response, to mention when contacting OpenAI support
+	#[serde(skip)]
+	pub request_id: Option<String>,
+}
+
+impl ApiError {
+	/// Parses an error response body, returning `None` if it is not an error object.
+	pub(crate) fn parse(status: u16, body: &str) -> Option<ApiError> {
+		#[derive(Deserialize)]
+		struct Body {
+			error: ApiError,
+		}
+		let body: Body = serde_json::from_str(body).ok()?;
+		Some(ApiError { status, ..body.error })
+	}
+}
+
+/// E.g. `API error (400): Invalid type for 'messages[2].content' (param: messages[2].content,
+/// code: invalid_type)`.
+impl Display for ApiError {
+	fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+		write!(f, "API error ({}): {}", self.status, self.message)?;
+		match (&self.param, &self.code) {
+			(Some(param), Some(code)) => write!(f, " (param: {}, code: {})", param, code),
+			(Some(param), None) => write!(f, " (param: {})", param),
+			(None, Some(code)) => write!(f, " (code: {})", code),
+			(None, None) => Ok(()),
+		}
+	}
+}
+
+impl Error {
+	/// Whether sending the request again may succeed: after a `429 Too Many Requests` or `5xx`
+	/// status, a connection error or an interrupted stream. Invalid requests, authentication
+	/// failures and responses that can't be parsed fail again, and so are not retried by
+	/// `OpenAI::with_retry`.
+	pub fn is_retryable(&self) -> bool {
+		match self {
+			Error::Api(err) => requests::is_retryable_status(err.status),
+			Error::Connection(_) | Error::StreamInterrupted(_) => true,
+			_ => false,
+		}
+	}
+
+	/// The error of `body` failing to parse as `T`.
+	pub(crate) fn deserialization<T>(source: serde_json::Error, body: &[u8]) -> Error {
+		let mut end = body.len().min(BODY_SNIPPET_LEN);
+		// Cuts before a UTF-8 continuation byte, a character being at most 4 bytes.
+		for _ in 0..3 {
+			if end > 0 && end < body.len() && body[end] & 0xC0 == 0x80 {
+				end -= 1;
+			}
+		}
+		let mut body_snippet = redact_credentials(&String::from_utf8_lossy(&body[..end]));
+		if end < body.len() {
+			body_snippet.push('…');
+		}
+		Error::Deserialization { source, target: std::any::type_name::<T>(), body_snippet }
+	}
+}
+
+/// Parses a response body already read as JSON, failing with `Error::Deserialization`.
+pub(crate) fn from_json<T: DeserializeOwned>(json: &Json) -> ApiResult<T> {
+	T::deserialize(json).map_err(|e| Error::deserialization::<T>(e, json.to_string().as_bytes()))
+}
+
+/// Replaces the tokens following `CREDENTIAL_PREFIXES` in `text`, e.g. of an `Authorization`
+/// header echoed by a proxy.
+fn redact_credentials(text: &str) -> String {
+	let lowercase = text.to_ascii_lowercase();
+	let mut redacted = String::with_capacity(text.len());
+	let mut copied = 0;
+	let mut starts: Vec<usize> = CREDENTIAL_PREFIXES
+		.iter()
+		.flat_map(|prefix| lowercase.match_indices(prefix).map(|(i, prefix)| i + prefix.len()))
+		.collect();
+	starts.sort_unstable();
+	for start in starts {
+		if start < copied {
+			continue;
+		}
+		let len = text[start..]
+			.find(|c: char| c.is_whitespace() || matches!(c, '"' | '\'' | ',' | '\\'))
+			.unwrap_or(text.len() - start);
+		if len > 0 {
+			redacted.push_str(&text[copied..start]);
+			redacted.push_str("[REDACTED]");
+			copied = start + len;
+		}
+	}
+	redacted.push_str(&text[copied..]);
+	redacted
 }
 
 impl Display for Error {
-    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        match self {
-            Error::ApiError(msg) => write!(f, "API error: {}", msg),
-            Error::RequestError(msg) => write!(f, "Request error: {}", msg),
-        }
-    }
-}
\ No newline at end of file
+	fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+		match self {
+			Error::Api(err) => write!(f, "{}", err),
+			Error::ApiError(msg) => write!(f, "API error: {}", msg),
+			Error::RequestError(msg) => write!(f, "Request error: {}", msg),
+			Error::Connection(msg) => write!(f, "Connection error: {}", msg),
+			Error::InvalidRequest(msg) => write!(f, "Invalid request: {}", msg),
+			Error::Cancelled => write!(f, "Stream cancelled"),
+			Error::StreamInterrupted(chunks) => {
+				write!(f, "Stream interrupted after {} chunks", chunks)
+			},
+			Error::Timeout(status) => write!(f, "Timed out with the status {}", status),
+			Error::ResponseTooLarge(limit) => {
+				write!(f, "Response larger than the limit of {} bytes", limit)
+			},
+			Error::Deserialization { source, target, body_snippet } => {
+				write!(
+					f,
+					"Failed to parse the response as {}: {}, body: {}",
+					target, source, body_snippet
+				)
+			},
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::openai::new_test_mock;
+
+	#[test]
+	fn test_parse_api_error() {
+		let body = r#"{"error": {"message": "Rate limit reached", "type": "requests",
+			"param": null, "code": "rate_limit_exceeded"}}"#;
+		let err = ApiError::parse(429, body).unwrap();
+		assert_eq!(err.status, 429);
+		assert_eq!(err.message, "Rate limit reached");
+		assert_eq!(err.kind.as_deref(), Some("requests"));
+		assert_eq!(err.param, None);
+		assert_eq!(err.code.as_deref(), Some("rate_limit_exceeded"));
+
+		assert!(ApiError::parse(502, "<html>Bad Gateway</html>").is_none());
+		assert!(ApiError::parse(500, r#"{"detail": "oops"}"#).is_none());
+	}
+
+	#[test]
+	fn test_api_error_param() {
+		use crate::chat::{ChatApi, ChatBody};
+		use crate::testing::{MockResponse, MockTransport};
+
+		let body = r#"{"error": {"message": "Invalid type for 'messages[2].content'.",
+			"type": "invalid_request_error", "param": "messages[2].content",
+			"code": "invalid_type"}}"#;
+		let mock =
+			MockTransport::new().with_response("chat/completions", MockResponse::new(400, body));
+		let openai = new_test_mock(&mock);
+		let body = ChatBody::builder().model("gpt-4o").user("Hi").build().unwrap();
+		let err = openai.chat_completion_create(&body).unwrap_err();
+		let Error::Api(api_err) = &err else { panic!("unexpected error {err:?}") };
+		assert_eq!(api_err.status, 400);
+		assert_eq!(api_err.param.as_deref(), Some("messages[2].content"));
+		assert_eq!(api_err.code.as_deref(), Some("invalid_type"));
+		assert_eq!(
+			err.to_string(),
+			"API error (400): Invalid type for 'messages[2].content'. \
+			(param: messages[2].content, code: invalid_type)"
+		);
+
+		let err = ApiError::parse(500, r#"{"error": {"message": "Oops"}}"#).unwrap();
+		assert_eq!(err.to_string(), "API error (500): Oops");
+	}
+
+	#[test]
+	fn test_is_retryable() {
+		let api_error = |status| {
+			Error::Api(Box::new(ApiError::parse(status, r#"{"error": {"message": "m"}}"#).unwrap()))
+		};
+		for status in [429, 500, 502, 503, 504] {
+			assert!(api_error(status).is_retryable(), "{status}");
+		}
+		for status in [400, 401, 403, 404, 409, 422] {
+			assert!(!api_error(status).is_retryable(), "{status}");
+		}
+		assert!(Error::Connection("connection reset".to_string()).is_retryable());
+		assert!(Error::StreamInterrupted(3).is_retryable());
+		assert!(!Error::RequestError("expected value at line 1".to_string()).is_retryable());
+		assert!(!Error::ApiError("No data".to_string()).is_retryable());
+		assert!(!Error::InvalidRequest("missing model".to_string()).is_retryable());
+		assert!(!Error::Cancelled.is_retryable());
+
+		// Error bodies that aren't error objects, e.g. of a proxy, keep their status.
+		use crate::requests::Requests;
+		use crate::testing::{MockResponse, MockTransport};
+		let mock = MockTransport::new()
+			.with_response("models", MockResponse::new(502, "<html>Bad Gateway</html>"));
+		let openai = new_test_mock(&mock);
+		let err = openai.get("models").unwrap_err();
+		assert!(err.is_retryable());
+		assert!(
+			matches!(err, Error::Api(err) if err.status == 502 && err.message == "<html>Bad Gateway</html>")
+		);
+	}
+
+	#[test]
+	fn test_deserialization_error() {
+		use crate::models::ModelsApi;
+		use crate::testing::{MockResponse, MockTransport};
+
+		let mock = MockTransport::new()
+			.with_response("models/gpt-4o", MockResponse::new(200, r#"{"id": 4}"#))
+			.with_response("models/other", MockResponse::new(200, "<html>OK</html>"));
+		let openai = new_test_mock(&mock);
+		let Err(err) = openai.models_retrieve("gpt-4o") else { panic!("expected an error") };
+		let Error::Deserialization { target, body_snippet, .. } = &err else { panic!("{err}") };
+		assert_eq!(*target, "openai_api_rust::apis::models::Model");
+		assert_eq!(body_snippet, r#"{"id":4}"#);
+		assert!(err.to_string().contains("invalid type: integer `4`, expected a string"));
+		let Err(Error::Deserialization { body_snippet, .. }) = openai.models_retrieve("other")
+		else {
+			panic!("expected an error")
+		};
+		assert_eq!(body_snippet, "<html>OK</html>");
+
+		let body = format!(r#"{{"auth": "Bearer sk-secrets", "text": "{}"}}"#, "é".repeat(300));
+		let source = serde_json::from_str::<u32>("").unwrap_err();
+		let Error::Deserialization { body_snippet, .. } =
+			Error::deserialization::<u32>(source, body.as_bytes())
+		else {
+			unreachable!()
+		};
+		assert!(body_snippet.starts_with(r#"{"auth": "Bearer [REDACTED]", "text": "éé"#));
+		assert!(!body_snippet.contains("sk-secret") && !body_snippet.contains('\u{FFFD}'));
+		assert!(body_snippet.ends_with("é…"));
+		// The 512th byte starts a character, which is cut.
+		let redacted_len = BODY_SNIPPET_LEN - 1 + "[REDACTED]".len() - "sk-secrets".len();
+		assert_eq!(body_snippet.len(), redacted_len + '…'.len_utf8());
+		assert_eq!(
+			redact_credentials("api-key: abc\r\nBEARER xyz"),
+			"api-key: [REDACTED]\r\nBEARER [REDACTED]"
+		);
+	}
+}
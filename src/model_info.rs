@@ -0,0 +1,167 @@
+// The limits and prices of common models, to fit requests in their context window and estimate
+// their cost without a request to the API, which doesn't return them.
+// See: https://platform.openai.com/docs/models and https://openai.com/api/pricing
+
+//! Model metadata
+
+use std::sync::RwLock;
+
+/// What is known of a model: its limits in tokens, and its list prices in US dollars per
+/// thousand tokens, cached input tokens and batch discounts aside. Fields are `None` when they
+/// are unknown or don't apply, e.g. the output price of embedding models.
+///
+/// ```
+/// # use openai_api_rust::model_info::ModelInfo;
+/// let info = ModelInfo::lookup("gpt-4o-2024-08-06").unwrap();
+/// assert_eq!(info.context_window, Some(128_000));
+/// assert!(ModelInfo::lookup("my-model").is_none());
+///
+/// ModelInfo::register("my-model", ModelInfo { context_window: Some(32_768), ..Default::default() });
+/// assert_eq!(ModelInfo::lookup("my-model-v2").unwrap().context_window, Some(32_768));
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ModelInfo {
+	/// The number of tokens the prompt and the completion share.
+	pub context_window: Option<u32>,
+	/// The number of tokens generated in a reply, reasoning tokens included.
+	pub max_output_tokens: Option<u32>,
+	pub input_price_per_1k: Option<f64>,
+	pub output_price_per_1k: Option<f64>,
+}
+
+impl ModelInfo {
+	const fn new(context_window: u32, max_output_tokens: u32, input: f64, output: f64) -> Self {
+		Self {
+			context_window: Some(context_window),
+			max_output_tokens: Some(max_output_tokens),
+			input_price_per_1k: Some(input),
+			output_price_per_1k: Some(output),
+		}
+	}
+
+	const fn embedding(context_window: u32, input: f64) -> Self {
+		Self {
+			context_window: Some(context_window),
+			max_output_tokens: None,
+			input_price_per_1k: Some(input),
+			output_price_per_1k: Some(0.0),
+		}
+	}
+
+	/// The info of `model`, `None` if it is unknown. A model is described by the entry of the
+	/// longest name it starts with, followed by nothing or a `-`: snapshots such as
+	/// `gpt-4o-2024-08-06` are described as `gpt-4o`, and `gpt-4o-mini` as itself.
+	/// Registered entries come before the built-in entry of the same name.
+	pub fn lookup(model: &str) -> Option<ModelInfo> {
+		let registered = REGISTERED.read().unwrap();
+		let registered = registered.iter().map(|(name, info)| (name.as_str(), info));
+		let built_in = MODEL_INFOS.iter().map(|(name, info)| (*name, info));
+		// `max_by_key` returns the last maximum, the registered entries are iterated last.
+		built_in
+			.chain(registered)
+			.filter(|(name, _)| describes(name, model))
+			.max_by_key(|(name, _)| name.len())
+			.map(|(_, info)| *info)
+	}
+
+	/// Registers `info` for `model` and its snapshots for the rest of the process, replacing the
+	/// built-in entry or a previous registration of the same name as a whole, e.g. for a new
+	/// model, a fine-tuned one, or when prices change. `tokenizer::context_window`,
+	/// `chat::output_token_limit` and `Usage::estimated_cost` consult it.
+	pub fn register(model: &str, info: ModelInfo) {
+		let mut registered = REGISTERED.write().unwrap();
+		registered.retain(|(name, _)| name != model);
+		registered.push((model.to_string(), info));
+	}
+
+	/// Every entry, the built-in ones first, then the registered ones.
+	pub(crate) fn entries() -> Vec<(String, ModelInfo)> {
+		let built_in = MODEL_INFOS.iter().map(|(name, info)| (name.to_string(), *info));
+		built_in.chain(REGISTERED.read().unwrap().iter().cloned()).collect()
+	}
+}
+
+/// Whether the entry `name` describes `model`, see `ModelInfo::lookup`.
+pub(crate) fn describes(name: &str, model: &str) -> bool {
+	model.strip_prefix(name).is_some_and(|rest| rest.is_empty() || rest.starts_with('-'))
+}
+
+static REGISTERED: RwLock<Vec<(String, ModelInfo)>> = RwLock::new(Vec::new());
+
+/// The built-in entries, as of the release of this version.
+const MODEL_INFOS: [(&str, ModelInfo); 19] = [
+	("gpt-4o", ModelInfo::new(128_000, 16_384, 0.0025, 0.01)),
+	("gpt-4o-2024-05-13", ModelInfo::new(128_000, 4_096, 0.005, 0.015)),
+	("gpt-4o-mini", ModelInfo::new(128_000, 16_384, 0.000_15, 0.000_6)),
+	("chatgpt-4o-latest", ModelInfo::new(128_000, 16_384, 0.005, 0.015)),
+	("gpt-4-turbo", ModelInfo::new(128_000, 4_096, 0.01, 0.03)),
+	("gpt-4-1106-preview", ModelInfo::new(128_000, 4_096, 0.01, 0.03)),
+	("gpt-4-0125-preview", ModelInfo::new(128_000, 4_096, 0.01, 0.03)),
+	("gpt-4-32k", ModelInfo::new(32_768, 8_192, 0.06, 0.12)),
+	("gpt-4", ModelInfo::new(8_192, 8_192, 0.03, 0.06)),
+	("gpt-3.5-turbo", ModelInfo::new(16_385, 4_096, 0.000_5, 0.001_5)),
+	("gpt-3.5-turbo-instruct", ModelInfo::new(4_096, 4_096, 0.001_5, 0.002)),
+	("o1", ModelInfo::new(200_000, 100_000, 0.015, 0.06)),
+	("o1-mini", ModelInfo::new(128_000, 65_536, 0.001_1, 0.004_4)),
+	("o1-preview", ModelInfo::new(128_000, 32_768, 0.015, 0.06)),
+	("o3-mini", ModelInfo::new(200_000, 100_000, 0.001_1, 0.004_4)),
+	("o4-mini", ModelInfo::new(200_000, 100_000, 0.001_1, 0.004_4)),
+	("text-embedding-3-small", ModelInfo::embedding(8_191, 0.000_02)),
+	("text-embedding-3-large", ModelInfo::embedding(8_191, 0.000_13)),
+	("text-embedding-ada-002", ModelInfo::embedding(8_191, 0.000_1)),
+];
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_lookup() {
+		let info = ModelInfo::lookup("gpt-4o-mini-2024-07-18").unwrap();
+		assert_eq!(info.context_window, Some(128_000));
+		assert_eq!(info.max_output_tokens, Some(16_384));
+		assert_eq!(info.input_price_per_1k, Some(0.000_15));
+		assert_eq!(ModelInfo::lookup("gpt-4o-2024-05-13").unwrap().max_output_tokens, Some(4_096));
+		assert_eq!(ModelInfo::lookup("text-embedding-3-small").unwrap().max_output_tokens, None);
+		assert_eq!(ModelInfo::lookup("gpt-4ox"), None);
+		assert_eq!(ModelInfo::lookup("test-lookup-model"), None);
+
+		let info = ModelInfo { context_window: Some(1_000), ..Default::default() };
+		ModelInfo::register("test-lookup-model", info);
+		assert_eq!(ModelInfo::lookup("test-lookup-model-v2"), Some(info));
+		let info = ModelInfo { context_window: Some(2_000), ..info };
+		ModelInfo::register("test-lookup-model", info);
+		assert_eq!(ModelInfo::lookup("test-lookup-model"), Some(info));
+		assert_eq!(
+			ModelInfo::entries().iter().filter(|(name, _)| name == "test-lookup-model").count(),
+			1
+		);
+	}
+
+	#[test]
+	fn test_register_consulted() {
+		let usage = crate::Usage {
+			prompt_tokens: Some(2_000),
+			completion_tokens: Some(1_000),
+			..Default::default()
+		};
+		assert_eq!(usage.estimated_cost("test-consulted-model"), None);
+		assert_eq!(crate::chat::output_token_limit("test-consulted-model"), None);
+		let info = ModelInfo {
+			context_window: Some(10_000),
+			max_output_tokens: Some(2_000),
+			input_price_per_1k: Some(0.5),
+			output_price_per_1k: Some(2.0),
+		};
+		ModelInfo::register("test-consulted-model", info);
+		assert_eq!(usage.estimated_cost("test-consulted-model-001"), Some(3.0));
+		assert_eq!(crate::chat::output_token_limit("test-consulted-model"), Some(2_000));
+		#[cfg(feature = "tokenizer")]
+		assert_eq!(crate::tokenizer::context_window("test-consulted-model"), Some(10_000));
+
+		// Without both prices, the model can't be priced.
+		let info = ModelInfo { output_price_per_1k: None, ..info };
+		ModelInfo::register("test-consulted-model", info);
+		assert_eq!(usage.estimated_cost("test-consulted-model"), None);
+	}
+}
@@ -1,20 +1,145 @@
-use mime::Mime;
+pub use mime::Mime;
 use std::borrow::Cow;
+use std::collections::VecDeque;
+use std::fs::File;
 use std::io::prelude::*;
 use std::io::Cursor;
 use std::io::Result;
+use std::path::Path;
+use std::sync::mpsc;
+
+#[cfg(feature = "async")]
+use std::{
+	pin::Pin,
+	task::{Context, Poll},
+};
+#[cfg(feature = "async")]
+use tokio::io::{AsyncRead, ReadBuf};
+
+/// The content types of the files uploaded to the API, to pass to `add_stream` and the like
+/// without depending on the `mime` crate. The types `mime` doesn't define can't be constants,
+/// they are functions.
+///
+/// ```
+/// # use openai_api_rust::multipart::{mimes, Multipart};
+/// let mut multipart = Multipart::new();
+/// multipart.add_stream("file", &b"ID3"[..], Some("speech.mp3"), Some(mimes::audio_mpeg()));
+/// multipart.add_stream("image", &b"\x89PNG"[..], Some("image.png"), Some(mimes::IMAGE_PNG));
+/// ```
+pub mod mimes {
+	use mime::Mime;
+
+	pub use mime::{
+		APPLICATION_JSON, APPLICATION_OCTET_STREAM, APPLICATION_PDF, IMAGE_GIF, IMAGE_JPEG,
+		IMAGE_PNG, TEXT_PLAIN, TEXT_PLAIN_UTF_8,
+	};
+
+	fn parse(mime: &str) -> Mime {
+		mime.parse().unwrap()
+	}
+
+	pub fn image_webp() -> Mime {
+		parse("image/webp")
+	}
+
+	/// For mp3, mpeg and mpga files.
+	pub fn audio_mpeg() -> Mime {
+		parse("audio/mpeg")
+	}
+
+	/// For mp4 and m4a files.
+	pub fn audio_mp4() -> Mime {
+		parse("audio/mp4")
+	}
+
+	pub fn audio_wav() -> Mime {
+		parse("audio/wav")
+	}
+
+	pub fn audio_webm() -> Mime {
+		parse("audio/webm")
+	}
+
+	pub fn audio_flac() -> Mime {
+		parse("audio/flac")
+	}
+
+	pub fn audio_ogg() -> Mime {
+		parse("audio/ogg")
+	}
+
+	/// For the input files of batches and fine-tuning jobs.
+	pub fn application_jsonl() -> Mime {
+		parse("application/jsonl")
+	}
+
+	pub fn text_markdown() -> Mime {
+		parse("text/markdown")
+	}
+}
+
+/// Length of the random boundary token, and the minimum accepted by `set_boundary_len`.
+const BOUNDARY_LEN: usize = 16;
+/// RFC 2046 limits boundaries to 70 characters.
+const MAX_BOUNDARY_LEN: usize = 70;
 
 pub struct Multipart<'d> {
 	fields: Vec<(String, Data<'d>)>,
+	boundary_len: usize,
+}
+
+impl<'d> Default for Multipart<'d> {
+	fn default() -> Self {
+		Self::new()
+	}
 }
 
 impl<'d> Multipart<'d> {
 	pub fn new() -> Self {
-		Self { fields: Vec::new() }
+		Self { fields: Vec::new(), boundary_len: BOUNDARY_LEN }
+	}
+
+	/// Sets the length of the random boundary token, clamped to `16..=70`.
+	///
+	/// `prepare` regenerates the boundary when it occurs in a text field, a field name or a
+	/// filename, but stream contents are never scanned since they are read lazily. A longer
+	/// boundary makes an accidental collision with binary stream data even less likely.
+	pub fn set_boundary_len(&mut self, len: usize) {
+		self.boundary_len = len.clamp(BOUNDARY_LEN, MAX_BOUNDARY_LEN);
+	}
+
+	/// The number of fields added since the last `prepare` or `clear`.
+	pub fn len(&self) -> usize {
+		self.fields.len()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.fields.is_empty()
+	}
+
+	/// Removes all fields, keeping the boundary length.
+	pub fn clear(&mut self) {
+		self.fields.clear();
+	}
+
+	/// Whether a field is named `name`.
+	pub(crate) fn has_field(&self, name: &str) -> bool {
+		self.fields.iter().any(|(other, _)| other == name)
 	}
 
 	pub fn add_text(&mut self, name: impl ToString, text: impl Into<Cow<'d, str>>) {
-		self.fields.push((name.to_string(), Data::Text(text.into())));
+		self.fields.push((name.to_string(), Data::Text(text.into(), None)));
+	}
+
+	/// Same as `add_text`, with a `Content-Type` header for the part,
+	/// e.g. `text/plain; charset=utf-8` for servers that don't assume UTF-8.
+	pub fn add_text_with_mime(
+		&mut self,
+		name: impl ToString,
+		text: impl Into<Cow<'d, str>>,
+		mime: Mime,
+	) {
+		self.fields.push((name.to_string(), Data::Text(text.into(), Some(mime))));
 	}
 
 	pub fn add_stream(
@@ -27,118 +152,475 @@ impl<'d> Multipart<'d> {
 		let data = Stream {
 			content_type: mime.unwrap_or(mime::APPLICATION_OCTET_STREAM),
 			filename: filename.map(|f| f.to_string()),
-			stream: Box::new(stream),
+			stream: Body::Sync(Box::new(stream)),
+			len: None,
 		};
 		self.fields.push((name.to_string(), Data::Stream(data)));
 	}
 
+	/// Adds the file at `path`, named after its filename, with a content type inferred from its
+	/// extension, `application/octet-stream` if it is unknown. The file is opened right away but
+	/// only read when the body is, sized by its length when it is opened, see `add_sized_stream`.
+	pub fn add_file(&mut self, name: impl ToString, path: impl AsRef<Path>) -> Result<()> {
+		let path = path.as_ref();
+		let file = File::open(path)?;
+		let len = file.metadata()?.len();
+		let filename = path.file_name().map(|f| f.to_string_lossy().into_owned());
+		let mime = mime_guess::from_path(path).first_or_octet_stream();
+		self.add_sized_stream(name, file, len, filename, Some(mime));
+		Ok(())
+	}
+
+	/// Adds one part per `(filename, stream)` pair, all sharing the field `name`,
+	/// e.g. for endpoints accepting repeated `file[]` fields.
+	pub fn add_streams<F, R>(
+		&mut self,
+		name: impl ToString,
+		streams: impl IntoIterator<Item = (F, R)>,
+		mime: Option<Mime>,
+	) where
+		F: ToString,
+		R: Read + 'd,
+	{
+		let name = name.to_string();
+		for (filename, stream) in streams {
+			self.add_stream(&name, stream, Some(filename), mime.clone());
+		}
+	}
+
+	/// Same as `add_stream`, for a stream that is known to yield exactly `len` bytes.
+	/// When every stream is sized, `PreparedFields::content_length` can report the body length.
+	///
+	/// The body holds exactly `len` bytes of the stream, so that it matches the length sent
+	/// in `Content-Length`: the stream is read no further, and reading the body fails with
+	/// `ErrorKind::UnexpectedEof` if the stream ends before.
+	pub fn add_sized_stream(
+		&mut self,
+		name: impl ToString,
+		stream: impl Read + 'd,
+		len: u64,
+		filename: Option<impl ToString>,
+		mime: Option<Mime>,
+	) {
+		let data = Stream {
+			content_type: mime.unwrap_or(mime::APPLICATION_OCTET_STREAM),
+			filename: filename.map(|f| f.to_string()),
+			stream: Body::Sync(Box::new(stream)),
+			len: Some(len),
+		};
+		self.fields.push((name.to_string(), Data::Stream(data)));
+	}
+
+	/// Adds a `Bytes` buffer, e.g. an upload being forwarded, without copying it. The buffer is
+	/// read in place, synchronously and through `AsyncRead` alike, and is sized like the streams
+	/// of `add_sized_stream`.
+	#[cfg(feature = "bytes")]
+	pub fn add_bytes(
+		&mut self,
+		name: impl ToString,
+		bytes: bytes::Bytes,
+		filename: Option<impl ToString>,
+		mime: Option<Mime>,
+	) {
+		use bytes::Buf;
+		let len = bytes.len() as u64;
+		self.add_sized_stream(name, bytes.reader(), len, filename, mime);
+	}
+
+	/// Adds a stream that is polled through `AsyncRead` when the prepared body is read
+	/// asynchronously. Bodies containing such a stream can only be read through `AsyncRead`.
+	#[cfg(feature = "async")]
+	pub fn add_async_stream(
+		&mut self,
+		name: impl ToString,
+		stream: impl AsyncRead + 'd,
+		filename: Option<impl ToString>,
+		mime: Option<Mime>,
+	) {
+		let data = Stream {
+			content_type: mime.unwrap_or(mime::APPLICATION_OCTET_STREAM),
+			filename: filename.map(|f| f.to_string()),
+			stream: Body::Async(Box::pin(stream)),
+			len: None,
+		};
+		self.fields.push((name.to_string(), Data::Stream(data)));
+	}
+
+	/// Same as `add_file`, opening the file with `tokio::fs` and adding it as an async stream.
+	/// The file is read as the body is polled, a chunk at a time, so it is never read faster
+	/// than the body is consumed nor held in memory as a whole. `tokio::fs` needs to run
+	/// within a Tokio runtime.
+	#[cfg(feature = "async")]
+	pub async fn add_async_file(
+		&mut self,
+		name: impl ToString,
+		path: impl AsRef<Path>,
+	) -> Result<()> {
+		let path = path.as_ref();
+		let file = tokio::fs::File::open(path).await?;
+		let len = file.metadata().await?.len();
+		let data = Stream {
+			content_type: mime_guess::from_path(path).first_or_octet_stream(),
+			filename: path.file_name().map(|f| f.to_string_lossy().into_owned()),
+			stream: Body::Async(Box::pin(file)),
+			len: Some(len),
+		};
+		self.fields.push((name.to_string(), Data::Stream(data)));
+		Ok(())
+	}
+
+	/// Moves the fields into a body ready to be read, leaving this `Multipart` empty, with its
+	/// allocation and boundary length, so that it can be filled again for another body.
+	/// See `into_prepared` to consume it instead.
+	///
+	/// The boundary is drawn from `rand::thread_rng()`, or, without the `rand` feature, from a
+	/// counter seeded once per process from the clock. Both make boundaries unlikely to collide
+	/// within a process, but the latter are predictable, which only matters to a sender of
+	/// fields crafted to contain the boundary of a later body.
 	pub fn prepare(&mut self) -> Result<PreparedFields<'d>> {
-		use rand::Rng;
-		let mut boundary = format!(
-			"\r\n--{}",
-			rand::thread_rng()
-				.sample_iter(&rand::distributions::Alphanumeric)
-				.take(16)
+		self.prepare_with_token(crate::random::alphanumeric)
+	}
+
+	/// Same as `prepare`, consuming the `Multipart` so that it can't be mistakenly reused
+	/// once its fields are gone.
+	pub fn into_prepared(mut self) -> Result<PreparedFields<'d>> {
+		self.prepare()
+	}
+
+	/// Same as `prepare`, generating the boundary with `rng` rather than `rand::thread_rng()`,
+	/// so that a seeded RNG produces the same body every time, e.g. to compare it byte for byte.
+	#[cfg(feature = "rand")]
+	pub fn prepare_with_rng(&mut self, rng: &mut impl rand::Rng) -> Result<PreparedFields<'d>> {
+		self.prepare_with_token(|len| {
+			rand::Rng::sample_iter(&mut *rng, rand::distributions::Alphanumeric)
+				.take(len)
 				.map(|c| c as char)
-				.collect::<String>()
-		);
+				.collect()
+		})
+	}
+
+	/// Same as `prepare`, with the boundary made of the tokens of `boundary_len` characters
+	/// returned by `new_token`, the first which doesn't occur in the text fields.
+	fn prepare_with_token(
+		&mut self,
+		mut new_token: impl FnMut(usize) -> String,
+	) -> Result<PreparedFields<'d>> {
+		let token = loop {
+			let token = new_token(self.boundary_len);
+			if !self.collides(&token) {
+				break token;
+			}
+		};
+		let mut boundary = format!("\r\n--{}", token);
 
-		let mut text_data = Vec::new();
-		let mut streams = Vec::new();
+		let mut parts: VecDeque<PreparedField<'d>> = VecDeque::new();
 
 		for field in self.fields.drain(..) {
 			match field.1 {
-				Data::Text(text) => write!(
-					text_data,
-					"{}\r\nContent-Disposition: form-data; \
-                     name=\"{}\"\r\n\r\n{}",
-					boundary, field.0, text
-				)
-				.unwrap(),
+				Data::Text(text, mime) => {
+					// Consecutive text fields are read as one part.
+					if parts.back().is_none_or(|part| part.stream.is_some()) {
+						parts.push_back(PreparedField {
+							header: Cursor::new(Vec::new()),
+							stream: None,
+							len: Some(0),
+							remaining: None,
+						});
+					}
+					let text_data = parts.back_mut().unwrap().header.get_mut();
+					write!(
+						text_data,
+						"{}\r\nContent-Disposition: form-data; name=\"{}\"",
+						boundary,
+						escape_quoted(&field.0)
+					)
+					.unwrap();
+					if let Some(mime) = mime {
+						write!(text_data, "\r\nContent-Type: {}", mime).unwrap();
+					}
+					write!(text_data, "\r\n\r\n{}", text).unwrap();
+				},
 				Data::Stream(stream) => {
-					streams.push(PreparedField::from_stream(
+					parts.push_back(PreparedField::from_stream(
 						&field.0,
 						&boundary,
 						&stream.content_type,
-						stream.filename.as_ref().map(|f| &**f),
+						stream.filename.as_deref(),
 						stream.stream,
+						stream.len,
 					));
 				},
 			}
 		}
 
-		if text_data.is_empty() && streams.is_empty() {
+		if parts.is_empty() {
 			boundary = String::new();
 		} else {
 			boundary.push_str("--");
 		}
 
-		Ok(PreparedFields {
-			text_data: Cursor::new(text_data),
-			streams,
-			end_boundary: Cursor::new(boundary),
+		Ok(PreparedFields { parts, end_boundary: Cursor::new(boundary), error: None })
+	}
+
+	/// Whether `token` appears in any of the data we control: field names, text values and
+	/// filenames.
+	fn collides(&self, token: &str) -> bool {
+		self.fields.iter().any(|(name, data)| {
+			name.contains(token)
+				|| match data {
+					Data::Text(text, _) => text.contains(token),
+					Data::Stream(stream) => {
+						stream.filename.as_ref().is_some_and(|f| f.contains(token))
+					},
+				}
 		})
 	}
 }
 
 enum Data<'d> {
-	Text(Cow<'d, str>),
+	Text(Cow<'d, str>, Option<Mime>),
 	Stream(Stream<'d>),
 }
 
 struct Stream<'d> {
 	filename: Option<String>,
 	content_type: Mime,
-	stream: Box<dyn Read + 'd>,
+	stream: Body<'d>,
+	len: Option<u64>,
+}
+
+enum Body<'d> {
+	Sync(Box<dyn Read + 'd>),
+	#[cfg(feature = "async")]
+	Async(Pin<Box<dyn AsyncRead + 'd>>),
 }
 
+/// A multipart body, read as it is sent.
+///
+/// Each stream is dropped as soon as it has been read to the end, or when reading it fails with
+/// anything but `ErrorKind::Interrupted`, after which the body ends. Dropping the body before it
+/// has been fully read, e.g. because the request failed, drops the unread streams with it.
 pub struct PreparedFields<'d> {
-	text_data: Cursor<Vec<u8>>,
-	streams: Vec<PreparedField<'d>>,
+	/// The fields left to read, in the order they were added.
+	parts: VecDeque<PreparedField<'d>>,
 	end_boundary: Cursor<String>,
+	/// The error of a read that had already filled part of its buffer, returned by the next
+	/// read, since a failed read can't return the bytes it read.
+	error: Option<std::io::Error>,
 }
 
 impl<'d> PreparedFields<'d> {
+	/// The boundary token, empty if the body has no fields.
 	pub fn boundary(&self) -> &str {
 		let boundary = self.end_boundary.get_ref();
 
-		&boundary[4..boundary.len() - 2]
+		boundary.get(4..boundary.len().saturating_sub(2)).unwrap_or_default()
+	}
+
+	/// The value of the `Content-Type` header of the body,
+	/// i.e. `multipart/form-data; boundary=` followed by the boundary.
+	pub fn content_type(&self) -> String {
+		format!("multipart/form-data; boundary={}", self.boundary())
+	}
+
+	/// The total length of the body in bytes, or `None` if any stream was added without a
+	/// known length, in which case the body has to be sent with chunked transfer encoding.
+	pub fn content_length(&self) -> Option<u64> {
+		let mut len = self.end_boundary.get_ref().len() as u64;
+		for field in &self.parts {
+			len += field.header.get_ref().len() as u64 + field.len?;
+		}
+		Some(len)
+	}
+
+	/// Reads the whole body, streams included, e.g. to dump the exact bytes of an upload along
+	/// with its `content_type` when debugging it, or for a transport that needs the body at
+	/// once. The body is buffered in memory, so keep it to small payloads.
+	pub fn into_bytes(mut self) -> Result<Vec<u8>> {
+		let mut body = Vec::with_capacity(self.content_length().unwrap_or(0) as usize);
+		self.read_to_end(&mut body)?;
+		Ok(body)
+	}
+
+	/// Drops the unread streams after a failed read, so their resources are released right away
+	/// rather than with the body, and ends the body since it can't be completed anymore.
+	fn abort(&mut self) {
+		self.parts.clear();
+		let end = self.end_boundary.get_ref().len() as u64;
+		self.end_boundary.set_position(end);
+	}
+
+	/// Wraps the body so that `callback` is invoked with the cumulative number of bytes read
+	/// every time a `read` call produces data.
+	pub fn with_progress<F: FnMut(u64)>(self, callback: F) -> Progress<'d, F> {
+		Progress { fields: self, read: 0, callback }
+	}
+}
+
+/// A multipart body reporting upload progress, see `PreparedFields::with_progress`.
+pub struct Progress<'d, F> {
+	fields: PreparedFields<'d>,
+	read: u64,
+	callback: F,
+}
+
+impl<'d, F> Progress<'d, F> {
+	pub fn boundary(&self) -> &str {
+		self.fields.boundary()
+	}
+
+	pub fn content_type(&self) -> String {
+		self.fields.content_type()
+	}
+
+	pub fn content_length(&self) -> Option<u64> {
+		self.fields.content_length()
+	}
+}
+
+impl<'d, F: FnMut(u64)> Read for Progress<'d, F> {
+	fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+		let read = self.fields.read(buf)?;
+		if read > 0 {
+			self.read += read as u64;
+			(self.callback)(self.read);
+		}
+		Ok(read)
 	}
 }
 
 impl<'d> Read for PreparedFields<'d> {
 	fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+		if let Some(err) = self.error.take() {
+			return Err(err);
+		}
+		let mut total_read = 0;
+
+		while total_read < buf.len() && !cursor_at_end(&self.end_boundary) {
+			match self.read_once(&mut buf[total_read..]) {
+				Ok(read) => total_read += read.unwrap_or(0),
+				Err(e) if total_read > 0 => {
+					self.error = Some(e);
+					break;
+				},
+				Err(e) => return Err(e),
+			}
+		}
+
+		Ok(total_read)
+	}
+}
+
+impl<'d> PreparedFields<'d> {
+	/// Like `read`, returning once the current segment is read rather than filling `buf`:
+	/// consecutive text fields, the header of a stream field, a single read of its stream, or
+	/// the end boundary. A writer can then send each segment as soon as it is available, e.g. the
+	/// header before waiting on a slow stream, at the cost of more, smaller writes. `read`
+	/// fills `buf` across segments, which is usually more efficient.
+	///
+	/// Returns 0 once the whole body has been read.
+	pub fn read_segment(&mut self, buf: &mut [u8]) -> Result<usize> {
+		if let Some(err) = self.error.take() {
+			return Err(err);
+		}
 		if buf.is_empty() {
 			return Ok(0);
 		}
+		while !cursor_at_end(&self.end_boundary) {
+			if let Some(read) = self.read_once(buf)? {
+				return Ok(read);
+			}
+		}
+		Ok(0)
+	}
 
-		let mut total_read = 0;
+	/// A single read of the current segment into the non-empty `buf`, `None` when it is a
+	/// stream that just ended.
+	fn read_once(&mut self, buf: &mut [u8]) -> Result<Option<usize>> {
+		let Some(mut field) = self.parts.pop_front() else {
+			return self.end_boundary.read(buf).map(Some);
+		};
+		match field.read(buf) {
+			Ok(0) => Ok(None),
+			Err(e) if e.kind() != std::io::ErrorKind::Interrupted => {
+				drop(field);
+				self.abort();
+				Err(e)
+			},
+			res => {
+				self.parts.push_front(field);
+				res.map(Some)
+			},
+		}
+	}
+}
 
-		while total_read < buf.len() && !cursor_at_end(&self.end_boundary) {
-			let buf = &mut buf[total_read..];
-
-			total_read += if !cursor_at_end(&self.text_data) {
-				self.text_data.read(buf)?
-			} else if let Some(mut field) = self.streams.pop() {
-				match field.read(buf) {
-					Ok(0) => continue,
-					res => {
-						self.streams.push(field);
-						res
+/// Mirrors the `Read` implementation: the fields in the order they were added, then the end
+/// boundary. A stream returning `Poll::Pending` is kept in place, so the next poll resumes
+/// exactly where the previous one stopped.
+#[cfg(feature = "async")]
+impl<'d> AsyncRead for PreparedFields<'d> {
+	fn poll_read(
+		self: Pin<&mut Self>,
+		cx: &mut Context<'_>,
+		buf: &mut ReadBuf<'_>,
+	) -> Poll<Result<()>> {
+		let this = self.get_mut();
+		if let Some(err) = this.error.take() {
+			return Poll::Ready(Err(err));
+		}
+		let start = buf.filled().len();
+		// An error is returned by the next poll once the bytes already filled are returned.
+		let fail = |this: &mut Self, buf: &ReadBuf<'_>, e| {
+			if buf.filled().len() > start {
+				this.error = Some(e);
+				return Poll::Ready(Ok(()));
+			}
+			Poll::Ready(Err(e))
+		};
+
+		while buf.remaining() > 0 && !cursor_at_end(&this.end_boundary) {
+			let read = if let Some(mut field) = this.parts.pop_front() {
+				match field.poll_read(cx, buf.initialize_unfilled()) {
+					Poll::Ready(Ok(0)) => continue,
+					Poll::Ready(Err(e)) if e.kind() != std::io::ErrorKind::Interrupted => {
+						drop(field);
+						this.abort();
+						return fail(this, buf, e);
+					},
+					Poll::Ready(Ok(read)) => {
+						this.parts.push_front(field);
+						read
 					},
-				}?
+					Poll::Ready(Err(e)) => {
+						this.parts.push_front(field);
+						return fail(this, buf, e);
+					},
+					Poll::Pending => {
+						this.parts.push_front(field);
+						if buf.filled().len() > start {
+							return Poll::Ready(Ok(()));
+						}
+						return Poll::Pending;
+					},
+				}
 			} else {
-				self.end_boundary.read(buf)?
+				this.end_boundary.read(buf.initialize_unfilled())?
 			};
+			buf.advance(read);
 		}
 
-		Ok(total_read)
+		Poll::Ready(Ok(()))
 	}
 }
 
+/// A stream field, or consecutive text fields whose whole part is in `header`, without stream.
 struct PreparedField<'d> {
 	header: Cursor<Vec<u8>>,
-	stream: Box<dyn Read + 'd>,
+	stream: Option<Body<'d>>,
+	len: Option<u64>,
+	/// The bytes of a sized stream left to read.
+	remaining: Option<u64>,
 }
 
 impl<'d> PreparedField<'d> {
@@ -147,29 +629,113 @@ impl<'d> PreparedField<'d> {
 		boundary: &str,
 		content_type: &Mime,
 		filename: Option<&str>,
-		stream: Box<dyn Read + 'd>,
+		stream: Body<'d>,
+		len: Option<u64>,
 	) -> Self {
 		let mut header = Vec::new();
 
-		write!(header, "{}\r\nContent-Disposition: form-data; name=\"{}\"", boundary, name)
-			.unwrap();
+		write!(
+			header,
+			"{}\r\nContent-Disposition: form-data; name=\"{}\"",
+			boundary,
+			escape_quoted(name)
+		)
+		.unwrap();
 
 		if let Some(filename) = filename {
-			write!(header, "; filename=\"{}\"", filename).unwrap();
+			if filename.is_ascii() {
+				write!(header, "; filename=\"{}\"", escape_quoted(filename)).unwrap();
+			} else {
+				// RFC 5987: servers that understand `filename*` prefer it, the others fall
+				// back to the ASCII approximation.
+				write!(
+					header,
+					"; filename=\"{}\"; filename*=UTF-8''{}",
+					escape_quoted(&ascii_fallback(filename)),
+					ext_value(filename)
+				)
+				.unwrap();
+			}
 		}
 
 		write!(header, "\r\nContent-Type: {}\r\n\r\n", content_type).unwrap();
 
-		PreparedField { header: Cursor::new(header), stream }
+		PreparedField { header: Cursor::new(header), stream: Some(stream), len, remaining: len }
+	}
+
+	/// The part of `buf` the stream may fill, up to its remaining length if it is sized.
+	fn limit<'b>(&self, buf: &'b mut [u8]) -> &'b mut [u8] {
+		match self.remaining {
+			Some(remaining) => {
+				let len = buf.len().min(usize::try_from(remaining).unwrap_or(usize::MAX));
+				&mut buf[..len]
+			},
+			None => buf,
+		}
+	}
+
+	/// Counts the `read` bytes of a sized stream, failing if it ended before its length.
+	fn advance(&mut self, read: usize) -> Result<usize> {
+		if let Some(remaining) = &mut self.remaining {
+			if read == 0 && *remaining > 0 {
+				return Err(std::io::Error::new(
+					std::io::ErrorKind::UnexpectedEof,
+					format!("stream ended {remaining} bytes before its length"),
+				));
+			}
+			*remaining -= read as u64;
+		}
+		Ok(read)
 	}
 }
 
 impl<'d> Read for PreparedField<'d> {
 	fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
 		if !cursor_at_end(&self.header) {
-			self.header.read(buf)
-		} else {
-			self.stream.read(buf)
+			return self.header.read(buf);
+		}
+		if buf.is_empty() || self.remaining == Some(0) {
+			return Ok(0);
+		}
+		let buf = self.limit(buf);
+		match &mut self.stream {
+			None => Ok(0),
+			Some(Body::Sync(stream)) => {
+				let read = stream.read(buf)?;
+				self.advance(read)
+			},
+			#[cfg(feature = "async")]
+			Some(Body::Async(_)) => Err(std::io::Error::new(
+				std::io::ErrorKind::Unsupported,
+				"async multipart stream cannot be read synchronously",
+			)),
+		}
+	}
+}
+
+#[cfg(feature = "async")]
+impl<'d> PreparedField<'d> {
+	fn poll_read(&mut self, cx: &mut Context<'_>, buf: &mut [u8]) -> Poll<Result<usize>> {
+		if !cursor_at_end(&self.header) {
+			return Poll::Ready(self.header.read(buf));
+		}
+		if buf.is_empty() || self.remaining == Some(0) {
+			return Poll::Ready(Ok(0));
+		}
+		let buf = self.limit(buf);
+		match &mut self.stream {
+			None => Poll::Ready(Ok(0)),
+			Some(Body::Sync(stream)) => {
+				Poll::Ready(stream.read(buf).and_then(|read| self.advance(read)))
+			},
+			Some(Body::Async(stream)) => {
+				let mut buf = ReadBuf::new(buf);
+				match stream.as_mut().poll_read(cx, &mut buf) {
+					Poll::Ready(Ok(())) => Poll::Ready(self.advance(buf.filled().len())),
+					Poll::Ready(Err(e)) => Poll::Ready(Err(e)),
+					Poll::Pending => Poll::Pending,
+				}
+			},
 		}
 	}
 }
@@ -177,3 +743,884 @@ impl<'d> Read for PreparedField<'d> {
 fn cursor_at_end<T: AsRef<[u8]>>(cursor: &Cursor<T>) -> bool {
 	cursor.position() == (cursor.get_ref().as_ref().len() as u64)
 }
+
+/// A stream reading the chunks of bytes sent on a channel, e.g. the lines of a JSONL file
+/// generated by another thread, to upload data without a temporary file. The stream ends once
+/// every sender is dropped.
+///
+/// ```
+/// # use openai_api_rust::multipart::{ChannelReader, Multipart};
+/// let (sender, reader) = ChannelReader::channel();
+/// std::thread::spawn(move || {
+///     for i in 0..3 {
+///         sender.send(format!("{{\"custom_id\": \"{i}\"}}\n").into_bytes()).unwrap();
+///     }
+/// });
+/// let mut multipart = Multipart::new();
+/// multipart.add_stream("file", reader, Some("input.jsonl"), None);
+/// ```
+#[derive(Debug)]
+pub struct ChannelReader {
+	receiver: mpsc::Receiver<Vec<u8>>,
+	chunk: Vec<u8>,
+	/// The number of bytes of `chunk` already read.
+	pos: usize,
+}
+
+impl ChannelReader {
+	pub fn new(receiver: mpsc::Receiver<Vec<u8>>) -> Self {
+		Self { receiver, chunk: Vec::new(), pos: 0 }
+	}
+
+	/// A reader and the sender feeding it.
+	pub fn channel() -> (mpsc::Sender<Vec<u8>>, ChannelReader) {
+		let (sender, receiver) = mpsc::channel();
+		(sender, ChannelReader::new(receiver))
+	}
+}
+
+impl Read for ChannelReader {
+	fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+		// Empty chunks are skipped, since reading 0 bytes would end the stream.
+		while self.pos == self.chunk.len() {
+			match self.receiver.recv() {
+				Ok(chunk) => {
+					self.chunk = chunk;
+					self.pos = 0;
+				},
+				Err(_) => return Ok(0),
+			}
+		}
+		let len = buf.len().min(self.chunk.len() - self.pos);
+		buf[..len].copy_from_slice(&self.chunk[self.pos..self.pos + len]);
+		self.pos += len;
+		Ok(len)
+	}
+}
+
+/// The `AsyncRead` counterpart of `ChannelReader`, over a tokio channel, to add with
+/// `Multipart::add_async_stream`.
+#[cfg(feature = "async")]
+#[derive(Debug)]
+pub struct AsyncChannelReader {
+	receiver: tokio::sync::mpsc::Receiver<Vec<u8>>,
+	chunk: Vec<u8>,
+	pos: usize,
+}
+
+#[cfg(feature = "async")]
+impl AsyncChannelReader {
+	pub fn new(receiver: tokio::sync::mpsc::Receiver<Vec<u8>>) -> Self {
+		Self { receiver, chunk: Vec::new(), pos: 0 }
+	}
+
+	/// A reader and the sender feeding it, buffering up to `capacity` chunks.
+	pub fn channel(capacity: usize) -> (tokio::sync::mpsc::Sender<Vec<u8>>, AsyncChannelReader) {
+		let (sender, receiver) = tokio::sync::mpsc::channel(capacity);
+		(sender, AsyncChannelReader::new(receiver))
+	}
+}
+
+#[cfg(feature = "async")]
+impl AsyncRead for AsyncChannelReader {
+	fn poll_read(
+		mut self: Pin<&mut Self>,
+		cx: &mut Context<'_>,
+		buf: &mut ReadBuf<'_>,
+	) -> Poll<Result<()>> {
+		while self.pos == self.chunk.len() {
+			match self.receiver.poll_recv(cx) {
+				Poll::Ready(Some(chunk)) => {
+					self.chunk = chunk;
+					self.pos = 0;
+				},
+				Poll::Ready(None) => return Poll::Ready(Ok(())),
+				Poll::Pending => return Poll::Pending,
+			}
+		}
+		let len = buf.remaining().min(self.chunk.len() - self.pos);
+		let pos = self.pos;
+		buf.put_slice(&self.chunk[pos..pos + len]);
+		self.pos += len;
+		Poll::Ready(Ok(()))
+	}
+}
+
+/// Escapes a `name` or `filename` parameter value following the WHATWG multipart/form-data
+/// encoding, so that it can neither terminate the quoted string nor split the header.
+fn escape_quoted(value: &str) -> Cow<'_, str> {
+	if !value.contains(['"', '\r', '\n']) {
+		return Cow::Borrowed(value);
+	}
+	Cow::Owned(value.replace('"', "%22").replace('\r', "%0D").replace('\n', "%0A"))
+}
+
+/// Replaces every non-ASCII character with `_`.
+fn ascii_fallback(filename: &str) -> String {
+	filename.chars().map(|c| if c.is_ascii() { c } else { '_' }).collect()
+}
+
+/// Percent-encodes every byte that is not an RFC 5987 `attr-char`.
+fn ext_value(filename: &str) -> String {
+	let mut encoded = String::with_capacity(filename.len());
+	for byte in filename.bytes() {
+		match byte {
+			b'a'..=b'z'
+			| b'A'..=b'Z'
+			| b'0'..=b'9'
+			| b'!'
+			| b'#'
+			| b'$'
+			| b'&'
+			| b'+'
+			| b'-'
+			| b'.'
+			| b'^'
+			| b'_'
+			| b'`'
+			| b'|'
+			| b'~' => encoded.push(byte as char),
+			_ => encoded.push_str(&format!("%{:02X}", byte)),
+		}
+	}
+	encoded
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::sync::atomic::{AtomicBool, Ordering};
+	use std::sync::Arc;
+
+	fn read_body(fields: &mut PreparedFields) -> String {
+		let mut body = String::new();
+		fields.read_to_string(&mut body).unwrap();
+		body.replace(fields.boundary(), "BOUNDARY")
+	}
+
+	#[test]
+	fn test_prepare() {
+		let mut multipart = Multipart::new();
+		multipart.add_text("model", "whisper-1");
+		multipart.add_stream("file", Cursor::new(b"audio"), Some("audio.mp3"), None);
+		let mut fields = multipart.prepare().unwrap();
+		assert_eq!(
+			fields.content_type(),
+			format!("multipart/form-data; boundary={}", fields.boundary())
+		);
+		assert_eq!(
+			read_body(&mut fields),
+			"\r\n--BOUNDARY\r\nContent-Disposition: form-data; name=\"model\"\r\n\r\nwhisper-1\
+			 \r\n--BOUNDARY\r\nContent-Disposition: form-data; name=\"file\"; filename=\"audio.mp3\"\
+			 \r\nContent-Type: application/octet-stream\r\n\r\naudio\r\n--BOUNDARY--"
+		);
+	}
+
+	#[test]
+	fn test_text_with_mime() {
+		let mut multipart = Multipart::new();
+		multipart.add_text_with_mime("prompt", "café", mime::TEXT_PLAIN_UTF_8);
+		multipart.add_text("model", "whisper-1");
+		assert_eq!(
+			read_body(&mut multipart.prepare().unwrap()),
+			"\r\n--BOUNDARY\r\nContent-Disposition: form-data; name=\"prompt\"\
+			 \r\nContent-Type: text/plain; charset=utf-8\r\n\r\ncafé\
+			 \r\n--BOUNDARY\r\nContent-Disposition: form-data; name=\"model\"\r\n\r\nwhisper-1\
+			 \r\n--BOUNDARY--"
+		);
+	}
+
+	#[test]
+	fn test_insertion_order() {
+		let mut multipart = Multipart::new();
+		multipart.add_text("model", "whisper-1");
+		multipart.add_stream("file", Cursor::new(b"first"), Some("a.mp3"), None);
+		multipart.add_text("language", "en");
+		multipart.add_stream("file", Cursor::new(b"second"), Some("b.mp3"), None);
+		let expected =
+			"\r\n--BOUNDARY\r\nContent-Disposition: form-data; name=\"model\"\r\n\r\nwhisper-1\
+			 \r\n--BOUNDARY\r\nContent-Disposition: form-data; name=\"file\"; filename=\"a.mp3\"\
+			 \r\nContent-Type: application/octet-stream\r\n\r\nfirst\
+			 \r\n--BOUNDARY\r\nContent-Disposition: form-data; name=\"language\"\r\n\r\nen\
+			 \r\n--BOUNDARY\r\nContent-Disposition: form-data; name=\"file\"; filename=\"b.mp3\"\
+			 \r\nContent-Type: application/octet-stream\r\n\r\nsecond\r\n--BOUNDARY--";
+		let mut fields = multipart.prepare().unwrap();
+		assert_eq!(read_body(&mut fields), expected);
+	}
+
+	#[test]
+	#[cfg(feature = "bytes")]
+	fn test_add_bytes() {
+		let bytes = bytes::Bytes::from_static(b"forwarded upload");
+		let mut multipart = Multipart::new();
+		multipart.add_text("purpose", "batch");
+		multipart.add_bytes("file", bytes, Some("input.jsonl"), None);
+		let mut fields = multipart.prepare().unwrap();
+		let expected =
+			"\r\n--BOUNDARY\r\nContent-Disposition: form-data; name=\"purpose\"\r\n\r\nbatch\
+			 \r\n--BOUNDARY\r\nContent-Disposition: form-data; name=\"file\"; \
+			 filename=\"input.jsonl\"\r\nContent-Type: application/octet-stream\r\n\r\n\
+			 forwarded upload\r\n--BOUNDARY--";
+		let content_length = fields.content_length();
+		let body = read_body(&mut fields);
+		assert_eq!(body, expected);
+		let boundary_len = "BOUNDARY".len() as u64;
+		let token_len = fields.boundary().len() as u64;
+		assert_eq!(content_length, Some(body.len() as u64 + 3 * (token_len - boundary_len)));
+	}
+
+	#[test]
+	#[cfg(feature = "rand")]
+	fn test_prepare_with_rng() {
+		use rand::SeedableRng;
+
+		let body = || {
+			let mut multipart = Multipart::new();
+			multipart.add_text("model", "whisper-1");
+			multipart.add_stream("file", Cursor::new(b"audio"), Some("audio.mp3"), None);
+			let mut rng = rand::rngs::StdRng::seed_from_u64(7);
+			let mut body = Vec::new();
+			multipart.prepare_with_rng(&mut rng).unwrap().read_to_end(&mut body).unwrap();
+			body
+		};
+		assert_eq!(body(), body());
+	}
+
+	#[test]
+	#[cfg(feature = "rand")]
+	fn test_binary_stream() {
+		use rand::SeedableRng;
+
+		let prepare = |data: Vec<u8>| {
+			let mut multipart = Multipart::new();
+			multipart.add_text("model", "gpt-image-1");
+			multipart.add_stream(
+				"image",
+				Cursor::new(data),
+				Some("image.png"),
+				Some(mime::IMAGE_PNG),
+			);
+			multipart.prepare_with_rng(&mut rand::rngs::StdRng::seed_from_u64(7)).unwrap()
+		};
+		let boundary = prepare(Vec::new()).boundary().to_string();
+		// Every byte value, and the boundary delimiters, which must not end the part early.
+		let mut data: Vec<u8> = (0..=255).chain([0x00, 0xFF, 0x00]).collect();
+		data.extend(format!("\r\n--{boundary}\r\n--{boundary}--\r\n").as_bytes());
+		data.extend([0xFF; 10_000]);
+
+		let header = format!(
+			"\r\n--{boundary}\r\nContent-Disposition: form-data; name=\"model\"\r\n\r\ngpt-image-1\
+			 \r\n--{boundary}\r\nContent-Disposition: form-data; name=\"image\"; \
+			 filename=\"image.png\"\r\nContent-Type: image/png\r\n\r\n"
+		);
+		let mut expected = header.into_bytes();
+		expected.extend(&data);
+		expected.extend(format!("\r\n--{boundary}--").as_bytes());
+		for buf_len in [1, 7, 4096, 65536] {
+			let mut fields = prepare(data.clone());
+			assert_eq!(fields.boundary(), boundary);
+			let mut body: Vec<u8> = Vec::new();
+			let mut buf = vec![0; buf_len];
+			loop {
+				match fields.read(&mut buf).unwrap() {
+					0 => break,
+					read => body.extend(&buf[..read]),
+				}
+			}
+			assert_eq!(body, expected, "buffer of {buf_len} bytes");
+		}
+	}
+
+	#[test]
+	fn test_read_segment() {
+		let mut multipart = Multipart::new();
+		multipart.add_text("model", "whisper-1");
+		multipart.add_text("language", "en");
+		multipart.add_stream(
+			"file",
+			ChunkedReader(vec![b"abc".to_vec(), b"de".to_vec()]),
+			Some("a.bin"),
+			None,
+		);
+		let mut fields = multipart.prepare().unwrap();
+		let boundary = fields.boundary().to_string();
+		let mut segments = Vec::new();
+		let mut buf = [0; 1024];
+		loop {
+			match fields.read_segment(&mut buf).unwrap() {
+				0 => break,
+				read => segments.push(String::from_utf8(buf[..read].to_vec()).unwrap()),
+			}
+		}
+		assert_eq!(
+			segments,
+			[
+				format!(
+					"\r\n--{boundary}\r\nContent-Disposition: form-data; name=\"model\"\r\n\r\n\
+					 whisper-1\r\n--{boundary}\r\nContent-Disposition: form-data; \
+					 name=\"language\"\r\n\r\nen"
+				),
+				format!(
+					"\r\n--{boundary}\r\nContent-Disposition: form-data; name=\"file\"; filename=\"a.bin\"\r\n\
+					 Content-Type: application/octet-stream\r\n\r\n"
+				),
+				"abc".to_string(),
+				"de".to_string(),
+				format!("\r\n--{boundary}--"),
+			]
+		);
+
+		// A small buffer splits the segments, without merging them.
+		let mut multipart = Multipart::new();
+		multipart.add_stream("file", ChunkedReader(vec![b"abc".to_vec()]), Some("a.bin"), None);
+		let mut fields = multipart.prepare().unwrap();
+		let mut buf = [0; 2];
+		let mut header = 0;
+		while header < fields.parts[0].header.get_ref().len() {
+			header += fields.read_segment(&mut buf).unwrap();
+		}
+		assert_eq!(header, fields.parts[0].header.get_ref().len());
+		assert_eq!(fields.read_segment(&mut buf).unwrap(), 2);
+		assert_eq!(&buf, b"ab");
+	}
+
+	/// Returns one chunk per read.
+	struct ChunkedReader(Vec<Vec<u8>>);
+
+	impl Read for ChunkedReader {
+		fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+			if self.0.is_empty() {
+				return Ok(0);
+			}
+			let chunk = &mut self.0[0];
+			let len = buf.len().min(chunk.len());
+			buf[..len].copy_from_slice(&chunk[..len]);
+			chunk.drain(..len);
+			if chunk.is_empty() {
+				self.0.remove(0);
+			}
+			Ok(len)
+		}
+	}
+
+	#[test]
+	fn test_into_bytes() {
+		let mut multipart = Multipart::new();
+		multipart.add_text("purpose", "batch");
+		multipart.add_sized_stream("file", Cursor::new(b"{}\n"), 3, Some("input.jsonl"), None);
+		let fields = multipart.prepare().unwrap();
+		let content_type = fields.content_type();
+		let len = fields.content_length().unwrap();
+		let boundary = fields.boundary().to_string();
+		let body = fields.into_bytes().unwrap();
+		assert_eq!(body.len() as u64, len);
+		assert_eq!(content_type, format!("multipart/form-data; boundary={boundary}"));
+		assert!(body.ends_with(format!("\r\n{{}}\n\r\n--{boundary}--").as_bytes()));
+
+		struct Failing;
+		impl Read for Failing {
+			fn read(&mut self, _: &mut [u8]) -> Result<usize> {
+				Err(std::io::Error::other("disk error"))
+			}
+		}
+		let mut multipart = Multipart::new();
+		multipart.add_stream("file", Failing, Some("input.jsonl"), None);
+		assert!(multipart.prepare().unwrap().into_bytes().is_err());
+	}
+
+	#[test]
+	fn test_len() {
+		let mut multipart = Multipart::default();
+		assert!(multipart.is_empty());
+		multipart.add_text("model", "whisper-1");
+		multipart.add_stream("file", Cursor::new(b"audio"), Some("audio.mp3"), None);
+		assert_eq!(multipart.len(), 2);
+		multipart.clear();
+		assert!(multipart.is_empty());
+
+		multipart.add_text("model", "whisper-1");
+		multipart.prepare().unwrap();
+		assert_eq!(multipart.len(), 0);
+	}
+
+	#[test]
+	fn test_add_file() {
+		let path = std::env::temp_dir().join(format!("openai-{}-speech.mp3", std::process::id()));
+		std::fs::write(&path, b"audio").unwrap();
+		let mut multipart = Multipart::new();
+		multipart.add_file("file", &path).unwrap();
+		let mut fields = multipart.prepare().unwrap();
+		let len = fields.content_length().unwrap();
+		let mut body = String::new();
+		fields.read_to_string(&mut body).unwrap();
+		std::fs::remove_file(&path).unwrap();
+		assert_eq!(len, body.len() as u64);
+		let filename = path.file_name().unwrap().to_str().unwrap();
+		assert!(body.contains(&format!("filename=\"{filename}\"\r\nContent-Type: audio/mpeg")));
+		assert!(body.contains("\r\n\r\naudio\r\n"));
+
+		let mut multipart = Multipart::new();
+		assert!(multipart.add_file("file", "/nonexistent/file.mp3").is_err());
+		assert!(multipart.is_empty());
+	}
+
+	#[test]
+	fn test_add_streams() {
+		let mut multipart = Multipart::new();
+		let files = [("a.txt", Cursor::new(b"a")), ("b.txt", Cursor::new(b"b"))];
+		multipart.add_streams("file[]", files, Some(mime::TEXT_PLAIN));
+		let body = read_body(&mut multipart.prepare().unwrap());
+		assert_eq!(body.matches("Content-Disposition").count(), 2);
+		assert_eq!(body.matches("Content-Disposition: form-data; name=\"file[]\"").count(), 2);
+		assert!(body.contains("filename=\"a.txt\""));
+		assert!(body.contains("filename=\"b.txt\""));
+	}
+
+	#[test]
+	fn test_content_length() {
+		let mut multipart = Multipart::new();
+		multipart.add_text("model", "whisper-1");
+		multipart.add_sized_stream("file", Cursor::new(b"audio"), 5, Some("audio.mp3"), None);
+		let mut fields = multipart.prepare().unwrap();
+		let len = fields.content_length().unwrap();
+		let mut body = Vec::new();
+		fields.read_to_end(&mut body).unwrap();
+		assert_eq!(len, body.len() as u64);
+
+		let mut multipart = Multipart::new();
+		multipart.add_sized_stream("image", Cursor::new(b"png"), 3, Some("image.png"), None);
+		multipart.add_stream("mask", Cursor::new(b"png"), Some("mask.png"), None);
+		assert_eq!(multipart.prepare().unwrap().content_length(), None);
+
+		assert_eq!(Multipart::new().prepare().unwrap().content_length(), Some(0));
+		assert_eq!(Multipart::new().prepare().unwrap().boundary(), "");
+	}
+
+	#[test]
+	fn test_sized_stream_length() {
+		// A longer stream is cut at its length, which the body length matches.
+		let mut multipart = Multipart::new();
+		multipart.add_sized_stream("file", Cursor::new(b"audio and more"), 5, Some("a.mp3"), None);
+		multipart.add_text("model", "whisper-1");
+		let mut fields = multipart.prepare().unwrap();
+		let len = fields.content_length().unwrap();
+		let mut body = Vec::new();
+		fields.read_to_end(&mut body).unwrap();
+		assert_eq!(len, body.len() as u64);
+		let body = String::from_utf8(body).unwrap();
+		assert!(body.contains("\r\n\r\naudio\r\n--"));
+		assert!(!body.contains("more"));
+
+		// A shorter stream fails the body rather than leaving the request hanging.
+		let mut multipart = Multipart::new();
+		multipart.add_sized_stream("file", Cursor::new(b"aud"), 5, Some("a.mp3"), None);
+		let err = multipart.prepare().unwrap().read_to_end(&mut Vec::new()).unwrap_err();
+		assert_eq!(err.kind(), std::io::ErrorKind::UnexpectedEof);
+	}
+
+	#[test]
+	fn test_progress() {
+		let mut multipart = Multipart::new();
+		multipart.add_text("purpose", "fine-tune");
+		multipart.add_sized_stream(
+			"file",
+			Cursor::new(vec![b'x'; 100]),
+			100,
+			Some("a.jsonl"),
+			None,
+		);
+		multipart.add_sized_stream("file", Cursor::new(vec![]), 0, Some("empty.jsonl"), None);
+		let fields = multipart.prepare().unwrap();
+		let total = fields.content_length().unwrap();
+
+		let mut reported = Vec::new();
+		let mut progress = fields.with_progress(|read| reported.push(read));
+		let mut buf = [0; 7];
+		while progress.read(&mut buf).unwrap() > 0 {}
+		drop(progress);
+
+		assert!(reported.windows(2).all(|w| w[0] < w[1]));
+		assert_eq!(reported.last(), Some(&total));
+	}
+
+	/// Sets its flag when dropped.
+	struct DropFlag<R>(R, Arc<AtomicBool>);
+
+	impl<R: Read> Read for DropFlag<R> {
+		fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+			self.0.read(buf)
+		}
+	}
+
+	impl<R> Drop for DropFlag<R> {
+		fn drop(&mut self) {
+			self.1.store(true, Ordering::Relaxed);
+		}
+	}
+
+	struct Failing;
+
+	impl Read for Failing {
+		fn read(&mut self, _: &mut [u8]) -> Result<usize> {
+			Err(std::io::Error::new(std::io::ErrorKind::BrokenPipe, "boom"))
+		}
+	}
+
+	#[test]
+	fn test_drop_streams() {
+		let flags: Vec<_> = (0..2).map(|_| Arc::new(AtomicBool::new(false))).collect();
+		let mut multipart = Multipart::new();
+		multipart.add_text("purpose", "fine-tune");
+		for flag in &flags {
+			let stream = DropFlag(Cursor::new(vec![b'x'; 1000]), flag.clone());
+			multipart.add_stream("file", stream, Some("a.jsonl"), None);
+		}
+		let mut fields = multipart.prepare().unwrap();
+		let mut buf = [0; 64];
+		fields.read_exact(&mut buf).unwrap();
+		assert!(flags.iter().all(|flag| !flag.load(Ordering::Relaxed)));
+		drop(fields);
+		assert!(flags.iter().all(|flag| flag.load(Ordering::Relaxed)));
+
+		// A failed read drops the unread streams and ends the body.
+		let flag = Arc::new(AtomicBool::new(false));
+		let mut multipart = Multipart::new();
+		multipart.add_stream("file", DropFlag(Cursor::new(b"a"), flag.clone()), Some("a"), None);
+		multipart.add_stream("file", Failing, Some("b"), None);
+		let mut fields = multipart.prepare().unwrap();
+		assert!(fields.read_to_end(&mut Vec::new()).is_err());
+		assert!(flag.load(Ordering::Relaxed));
+		assert_eq!(fields.read(&mut buf).unwrap(), 0);
+	}
+
+	#[test]
+	fn test_non_ascii_filename() {
+		let mut multipart = Multipart::new();
+		multipart.add_stream("file", Cursor::new(b""), Some("my \"réunion\".mp3"), None);
+		let body = read_body(&mut multipart.prepare().unwrap());
+		assert!(body.contains(
+			"name=\"file\"; filename=\"my %22r_union%22.mp3\"; \
+			 filename*=UTF-8''my%20%22r%C3%A9union%22.mp3\r\n"
+		));
+
+		let mut multipart = Multipart::new();
+		multipart.add_stream("file", Cursor::new(b""), Some("my audio.mp3"), None);
+		let body = read_body(&mut multipart.prepare().unwrap());
+		assert!(body.contains("name=\"file\"; filename=\"my audio.mp3\"\r\n"));
+	}
+
+	#[test]
+	fn test_escape_header_parameters() {
+		let mut multipart = Multipart::new();
+		multipart.add_text("evil\"\r\nX-Injected: 1", "text");
+		multipart.add_stream("evil\"\r\nX-Injected: 1", Cursor::new(b""), Some("a\".mp3"), None);
+		let body = read_body(&mut multipart.prepare().unwrap());
+		assert!(!body.contains("\r\nX-Injected"));
+		assert!(body.contains(
+			"Content-Disposition: form-data; name=\"evil%22%0D%0AX-Injected: 1\"\r\n\r\ntext"
+		));
+		assert!(body.contains(
+			"Content-Disposition: form-data; name=\"evil%22%0D%0AX-Injected: 1\"; \
+			 filename=\"a%22.mp3\"\r\nContent-Type"
+		));
+	}
+
+	#[test]
+	fn test_boundary_collision() {
+		let mut multipart = Multipart::new();
+		multipart.add_text("prompt", "contains --Abc123 in the text");
+		multipart.add_stream("file", Cursor::new(b""), Some("Xyz789.mp3"), None);
+		assert!(multipart.collides("Abc123"));
+		assert!(multipart.collides("Xyz789"));
+		assert!(multipart.collides("promp"));
+		assert!(!multipart.collides("Qwe456"));
+	}
+
+	#[test]
+	fn test_boundary_collision_redrawn() {
+		let text = "a prompt quoting --FIRSTTOKEN mid-sentence";
+		let data = b"\r\n--FIRSTTOKEN\r\nbinary data\r\n--FIRSTTOKEN--".to_vec();
+		let mut multipart = Multipart::new();
+		multipart.add_text("prompt", text);
+		multipart.add_stream("file", Cursor::new(data.clone()), Some("FIRSTTOKEN.bin"), None);
+		let mut tokens = ["FIRSTTOKEN", "SECONDTOKEN"].into_iter();
+		let mut fields =
+			multipart.prepare_with_token(|_| tokens.next().unwrap().to_string()).unwrap();
+		assert_eq!(fields.boundary(), "SECONDTOKEN");
+
+		let mut body = Vec::new();
+		fields.read_to_end(&mut body).unwrap();
+		let body = String::from_utf8(body).unwrap();
+		let parts: Vec<&str> = body.split("\r\n--SECONDTOKEN").collect();
+		assert_eq!(parts.len(), 4);
+		assert_eq!(parts[0], "");
+		assert_eq!(parts[3], "--");
+		let content = |part: &str| part.split_once("\r\n\r\n").unwrap().1.to_string();
+		assert!(parts[1].contains("name=\"prompt\""));
+		assert_eq!(content(parts[1]), text);
+		assert!(parts[2].contains("filename=\"FIRSTTOKEN.bin\""));
+		assert_eq!(content(parts[2]).into_bytes(), data);
+	}
+
+	#[test]
+	fn test_set_boundary_len() {
+		let mut multipart = Multipart::new();
+		multipart.add_text("model", "whisper-1");
+		assert_eq!(multipart.prepare().unwrap().boundary().len(), 16);
+
+		multipart.add_text("model", "whisper-1");
+		multipart.set_boundary_len(40);
+		assert_eq!(multipart.prepare().unwrap().boundary().len(), 40);
+
+		multipart.add_text("model", "whisper-1");
+		multipart.set_boundary_len(1000);
+		assert_eq!(multipart.prepare().unwrap().boundary().len(), 70);
+
+		multipart.add_text("model", "whisper-1");
+		multipart.set_boundary_len(4);
+		assert_eq!(multipart.prepare().unwrap().boundary().len(), 16);
+	}
+
+	#[cfg(feature = "async")]
+	mod async_read {
+		use super::*;
+
+		/// Yields one byte per poll, returning `Poll::Pending` before every byte.
+		struct Trickle {
+			data: Vec<u8>,
+			pos: usize,
+			ready: bool,
+		}
+
+		impl AsyncRead for Trickle {
+			fn poll_read(
+				mut self: Pin<&mut Self>,
+				cx: &mut Context<'_>,
+				buf: &mut ReadBuf<'_>,
+			) -> Poll<Result<()>> {
+				if !self.ready {
+					self.ready = true;
+					cx.waker().wake_by_ref();
+					return Poll::Pending;
+				}
+				self.ready = false;
+				if let Some(&byte) = self.data.get(self.pos) {
+					self.pos += 1;
+					buf.put_slice(&[byte]);
+				}
+				Poll::Ready(Ok(()))
+			}
+		}
+
+		fn poll_to_end(fields: &mut PreparedFields, chunk: usize) -> Vec<u8> {
+			let mut cx = Context::from_waker(std::task::Waker::noop());
+			let mut body = Vec::new();
+			let mut chunk = vec![0; chunk];
+			loop {
+				let mut buf = ReadBuf::new(&mut chunk);
+				match Pin::new(&mut *fields).poll_read(&mut cx, &mut buf) {
+					Poll::Ready(Ok(())) if buf.filled().is_empty() => return body,
+					Poll::Ready(Ok(())) => body.extend_from_slice(buf.filled()),
+					Poll::Ready(Err(e)) => panic!("{e}"),
+					Poll::Pending => {},
+				}
+			}
+		}
+
+		fn multipart(async_stream: bool) -> Multipart<'static> {
+			let mut multipart = Multipart::new();
+			multipart.add_text("purpose", "fine-tune");
+			let data = b"{\"prompt\": \"a\"}\n{\"prompt\": \"b\"}\n".to_vec();
+			if async_stream {
+				let stream = Trickle { data, pos: 0, ready: false };
+				multipart.add_async_stream("file", stream, Some("data.jsonl"), None);
+			} else {
+				multipart.add_stream("file", Cursor::new(data), Some("data.jsonl"), None);
+			}
+			multipart
+		}
+
+		#[test]
+		fn test_poll_read_matches_read() {
+			let mut sync_fields = multipart(false).prepare().unwrap();
+			let expected = read_body(&mut sync_fields);
+
+			for chunk in [1, 7, 4096] {
+				let mut async_fields = multipart(true).prepare().unwrap();
+				let body = poll_to_end(&mut async_fields, chunk);
+				let body = String::from_utf8(body).unwrap();
+				assert_eq!(body.replace(async_fields.boundary(), "BOUNDARY"), expected);
+			}
+		}
+
+		#[tokio::test]
+		async fn test_add_async_file() {
+			use tokio::io::AsyncReadExt;
+
+			let path = std::env::temp_dir().join(format!("upload-{}.jsonl", std::process::id()));
+			let data: Vec<u8> = (0..8 * 1024 * 1024).map(|i| (i % 251) as u8).collect();
+			std::fs::write(&path, &data).unwrap();
+			let mut multipart = Multipart::new();
+			multipart.add_text("purpose", "fine-tune");
+			multipart.add_async_file("file", &path).await.unwrap();
+			let mut fields = multipart.prepare().unwrap();
+			// The length is known from the metadata of the file, before reading it.
+			let len = fields.content_length().unwrap();
+			assert!(len > data.len() as u64);
+
+			let mut buf = [0; 8192];
+			let first = AsyncReadExt::read(&mut fields, &mut buf).await.unwrap();
+			assert!(first > 0);
+			// The end of the file is only read when the body reaches it: rewriting it once the
+			// body is being read changes what is sent, which a buffered file wouldn't.
+			let rewritten = [0xAB; 32];
+			{
+				use std::io::{Seek, SeekFrom};
+				let mut file = std::fs::OpenOptions::new().write(true).open(&path).unwrap();
+				file.seek(SeekFrom::End(-32)).unwrap();
+				file.write_all(&rewritten).unwrap();
+			}
+
+			// Only the last bytes are kept, to check the file was streamed to its end.
+			let (mut total, mut tail) = (first as u64, buf[..first].to_vec());
+			loop {
+				let read = AsyncReadExt::read(&mut fields, &mut buf).await.unwrap();
+				if read == 0 {
+					break;
+				}
+				tail.extend_from_slice(&buf[..read]);
+				tail.drain(..tail.len().saturating_sub(128));
+				total += read as u64;
+			}
+			std::fs::remove_file(&path).unwrap();
+
+			assert_eq!(total, len);
+			let end = format!("\r\n--{}--", fields.boundary());
+			assert!(tail.ends_with(end.as_bytes()));
+			let file_end = tail.len() - end.len();
+			assert_eq!(tail[file_end - 32..file_end], rewritten);
+			assert_ne!(tail[file_end - 33], rewritten[0]);
+		}
+
+		#[tokio::test]
+		async fn test_async_file_length_changes() {
+			use tokio::io::AsyncReadExt;
+
+			let path = std::env::temp_dir().join(format!("resized-{}.jsonl", std::process::id()));
+			std::fs::write(&path, b"{}\n").unwrap();
+			let mut multipart = Multipart::new();
+			multipart.add_async_file("file", &path).await.unwrap();
+			let mut fields = multipart.prepare().unwrap();
+			let len = fields.content_length().unwrap();
+			// A file growing during the upload is sent up to its length when it was added.
+			std::fs::write(&path, b"{}\n{}\n").unwrap();
+			let mut body = Vec::new();
+			AsyncReadExt::read_to_end(&mut fields, &mut body).await.unwrap();
+			assert_eq!(len, body.len() as u64);
+			assert!(
+				body.ends_with(format!("\r\n\r\n{{}}\n\r\n--{}--", fields.boundary()).as_bytes())
+			);
+
+			let mut multipart = Multipart::new();
+			multipart.add_async_file("file", &path).await.unwrap();
+			let mut fields = multipart.prepare().unwrap();
+			std::fs::write(&path, b"{}\n").unwrap();
+			let err = AsyncReadExt::read_to_end(&mut fields, &mut Vec::new()).await.unwrap_err();
+			std::fs::remove_file(&path).unwrap();
+			assert_eq!(err.kind(), std::io::ErrorKind::UnexpectedEof);
+		}
+
+		#[test]
+		fn test_sync_read_of_async_stream_fails() {
+			let mut fields = multipart(true).prepare().unwrap();
+			let mut body = Vec::new();
+			let err = fields.read_to_end(&mut body).unwrap_err();
+			assert_eq!(err.kind(), std::io::ErrorKind::Unsupported);
+		}
+	}
+
+	#[test]
+	fn test_mimes() {
+		assert_eq!(mimes::audio_mpeg().essence_str(), "audio/mpeg");
+		assert_eq!(mimes::application_jsonl().essence_str(), "application/jsonl");
+		assert_eq!(mimes::image_webp().type_(), mime::IMAGE);
+
+		let mut multipart = Multipart::new();
+		multipart.add_stream("file", &b"fLaC"[..], Some("a.flac"), Some(mimes::audio_flac()));
+		let body = read_body(&mut multipart.prepare().unwrap());
+		assert!(body.contains("Content-Type: audio/flac\r\n"), "{body}");
+	}
+
+	#[test]
+	fn test_prepare_reuse() {
+		let mut multipart = Multipart::new();
+		multipart.set_boundary_len(32);
+		multipart.add_text("purpose", "batch");
+		let body = read_body(&mut multipart.prepare().unwrap());
+		assert!(body.contains("name=\"purpose\"\r\n\r\nbatch"));
+
+		// The drained `Multipart` keeps its settings and takes new fields.
+		multipart.add_text("purpose", "fine-tune");
+		let mut fields = multipart.into_prepared().unwrap();
+		assert_eq!(fields.boundary().len(), 32);
+		let body = read_body(&mut fields);
+		assert!(body.contains("fine-tune") && !body.contains("batch"));
+	}
+
+	#[test]
+	fn test_channel_reader() {
+		let (sender, mut reader) = ChannelReader::channel();
+		let producer = std::thread::spawn(move || {
+			for chunk in [&b"{\"a\":"[..], b"", b" 1}\n{\"b\"", b": 2}\n"] {
+				sender.send(chunk.to_vec()).unwrap();
+			}
+		});
+		// Reads smaller than the chunks, so that chunks are split across reads.
+		let mut body = Vec::new();
+		let mut buf = [0; 3];
+		loop {
+			let n = reader.read(&mut buf).unwrap();
+			if n == 0 {
+				break;
+			}
+			body.extend_from_slice(&buf[..n]);
+		}
+		producer.join().unwrap();
+		assert_eq!(body, b"{\"a\": 1}\n{\"b\": 2}\n");
+		assert_eq!(reader.read(&mut buf).unwrap(), 0);
+	}
+
+	#[cfg(all(feature = "async", feature = "bytes"))]
+	#[tokio::test]
+	async fn test_async_add_bytes() {
+		use tokio::io::AsyncReadExt;
+
+		let mut multipart = Multipart::new();
+		multipart.add_bytes("file", bytes::Bytes::from(vec![7; 100_000]), Some("data.bin"), None);
+		let mut fields = multipart.prepare().unwrap();
+		let mut body = Vec::new();
+		AsyncReadExt::read_to_end(&mut fields, &mut body).await.unwrap();
+		assert_eq!(body.iter().filter(|b| **b == 7).count(), 100_000);
+		assert!(body.ends_with(format!("{}--", fields.boundary()).as_bytes()));
+	}
+
+	#[cfg(feature = "async")]
+	#[tokio::test]
+	async fn test_async_channel_reader() {
+		use tokio::io::AsyncReadExt;
+
+		let (sender, reader) = AsyncChannelReader::channel(1);
+		tokio::spawn(async move {
+			for i in 0..100 {
+				sender.send(format!("{{\"n\": {i}}}\n").into_bytes()).await.unwrap();
+			}
+		});
+		let mut multipart = Multipart::new();
+		multipart.add_async_stream("file", reader, Some("input.jsonl"), None);
+		let mut fields = multipart.prepare().unwrap();
+		let mut body = String::new();
+		AsyncReadExt::read_to_string(&mut fields, &mut body).await.unwrap();
+		let expected: String = (0..100).map(|i| format!("{{\"n\": {i}}}\n")).collect();
+		assert!(body.contains(&expected));
+	}
+}
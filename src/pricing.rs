@@ -0,0 +1,133 @@
+// Estimates of the cost of requests from their token usage.
+// See: https://openai.com/api/pricing
+
+//! Pricing
+
+use std::collections::HashMap;
+
+use crate::model_info::{describes, ModelInfo};
+use crate::Usage;
+
+/// The price of a model in US dollars per million tokens.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ModelPrice {
+	pub input: f64,
+	pub output: f64,
+}
+
+impl ModelPrice {
+	pub const fn new(input: f64, output: f64) -> Self {
+		Self { input, output }
+	}
+
+	/// The price of a model whose input and output prices are both known.
+	pub(crate) fn from_info(info: &ModelInfo) -> Option<Self> {
+		let per_million = |price: Option<f64>| price.map(|price| price * 1000.0);
+		Some(Self::new(
+			per_million(info.input_price_per_1k)?,
+			per_million(info.output_price_per_1k)?,
+		))
+	}
+
+	/// The estimated cost of `usage` in US dollars.
+	pub fn cost(&self, usage: &Usage) -> f64 {
+		let tokens = |tokens: Option<u32>| tokens.unwrap_or(0) as f64 / 1_000_000.0;
+		tokens(usage.prompt_tokens) * self.input + tokens(usage.completion_tokens) * self.output
+	}
+}
+
+/// The prices used to estimate costs, starting from those of `ModelInfo`, built-in or
+/// registered, which [`set`] updates for this table only.
+///
+/// A model is priced by the longest name it starts with, so snapshots such as
+/// `gpt-4o-2024-08-06` are priced as `gpt-4o`, and `gpt-4o-mini` as itself. As with
+/// `ModelInfo::lookup`, an entry without prices leaves the models it describes unpriced rather
+/// than priced by a shorter name.
+///
+/// [`set`]: PriceTable::set
+#[derive(Debug, Clone)]
+pub struct PriceTable {
+	/// `None` for the entries of `ModelInfo` without prices.
+	prices: HashMap<String, Option<ModelPrice>>,
+}
+
+impl Default for PriceTable {
+	fn default() -> Self {
+		// Registered entries come last, replacing the built-in entry of the same name.
+		let prices = ModelInfo::entries()
+			.into_iter()
+			.map(|(model, info)| (model, ModelPrice::from_info(&info)))
+			.collect();
+		Self { prices }
+	}
+}
+
+impl PriceTable {
+	/// The prices of `ModelInfo`.
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// A table without any price.
+	pub fn empty() -> Self {
+		Self { prices: HashMap::new() }
+	}
+
+	/// Sets the price of `model` and its snapshots.
+	pub fn set(&mut self, model: &str, price: ModelPrice) -> &mut Self {
+		self.prices.insert(model.to_string(), Some(price));
+		self
+	}
+
+	/// The price of `model`, `None` if it is unknown.
+	pub fn price(&self, model: &str) -> Option<ModelPrice> {
+		self.prices
+			.iter()
+			.filter(|(name, _)| describes(name, model))
+			.max_by_key(|(name, _)| name.len())
+			.and_then(|(_, price)| *price)
+	}
+
+	/// The estimated cost of `usage` with `model` in US dollars, `None` if the model is unknown.
+	pub fn cost(&self, model: &str, usage: &Usage) -> Option<f64> {
+		Some(self.price(model)?.cost(usage))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_cost() {
+		let usage = Usage {
+			prompt_tokens: Some(1_000_000),
+			completion_tokens: Some(500_000),
+			total_tokens: Some(1_500_000),
+			..Default::default()
+		};
+		assert_eq!(usage.estimated_cost("gpt-4o"), Some(7.5));
+		assert_eq!(usage.estimated_cost("gpt-4o-2024-08-06"), Some(7.5));
+		assert!((usage.estimated_cost("gpt-4o-mini").unwrap() - 0.45).abs() < 1e-9);
+		assert_eq!(usage.estimated_cost("gpt-4ox"), None);
+		assert_eq!(usage.estimated_cost("my-model"), None);
+
+		let mut prices = PriceTable::new();
+		prices.set("gpt-4o", ModelPrice::new(1.0, 2.0)).set("my-model", ModelPrice::new(1.0, 0.0));
+		assert_eq!(prices.cost("gpt-4o", &usage), Some(2.0));
+		assert_eq!(prices.cost("my-model", &usage), Some(1.0));
+		assert_eq!(PriceTable::empty().cost("gpt-4o", &usage), None);
+	}
+
+	#[test]
+	fn test_unpriced_override() {
+		let usage = Usage { prompt_tokens: Some(1_000), ..Default::default() };
+		let model = "gpt-4o-test-unpriced";
+		assert_eq!(usage.estimated_cost(model), usage.estimated_cost("gpt-4o"));
+		ModelInfo::register(model, ModelInfo { context_window: Some(1_000), ..Default::default() });
+		assert_eq!(usage.estimated_cost(model), None);
+		assert_eq!(usage.estimated_cost("gpt-4o-test-unpriced-001"), None);
+		assert_eq!(PriceTable::new().cost(model, &usage), None);
+		assert!(PriceTable::new().cost("gpt-4o-2024-08-06", &usage).is_some());
+	}
+}
@@ -0,0 +1,110 @@
+// Random tokens for multipart boundaries and idempotency keys, and random retry delays.
+// Drawn from `rand::thread_rng()` by default. Without the `rand` feature, from a counter
+// mixed with a seed taken once per process from the clock, which doesn't depend on `rand`
+// but is predictable: fine for tokens that only have to be unique, not secret.
+
+use std::time::Duration;
+
+#[cfg(any(test, not(feature = "rand")))]
+const ALPHANUMERIC: &[u8; 62] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789";
+
+/// A token of `len` random ASCII letters and digits.
+#[cfg(feature = "rand")]
+pub(crate) fn alphanumeric(len: usize) -> String {
+	use rand::Rng;
+	rand::thread_rng()
+		.sample_iter(&rand::distributions::Alphanumeric)
+		.take(len)
+		.map(char::from)
+		.collect()
+}
+
+/// A token of `len` ASCII letters and digits. Tokens start with the 10 digits in base 62 of
+/// a distinct counter value, so the tokens of a process are unlikely to collide, but follow
+/// from the seed.
+#[cfg(not(feature = "rand"))]
+pub(crate) fn alphanumeric(len: usize) -> String {
+	let mut token = String::with_capacity(len);
+	while token.len() < len {
+		let mut bits = counter::next_u64();
+		// 62^10 < 2^64, so that each digit is about uniform.
+		for _ in 0..10.min(len - token.len()) {
+			token.push(ALPHANUMERIC[(bits % 62) as usize] as char);
+			bits /= 62;
+		}
+	}
+	token
+}
+
+/// A random delay between zero and `max`.
+pub(crate) fn jitter(max: Duration) -> Duration {
+	if max.is_zero() {
+		return Duration::ZERO;
+	}
+	#[cfg(feature = "rand")]
+	{
+		use rand::Rng;
+		rand::thread_rng().gen_range(Duration::ZERO..=max)
+	}
+	#[cfg(not(feature = "rand"))]
+	{
+		let max_nanos = u64::try_from(max.as_nanos()).unwrap_or(u64::MAX);
+		Duration::from_nanos(counter::next_u64() % max_nanos.saturating_add(1))
+	}
+}
+
+#[cfg(not(feature = "rand"))]
+mod counter {
+	use std::sync::atomic::{AtomicU64, Ordering};
+	use std::sync::OnceLock;
+	use std::time::{SystemTime, UNIX_EPOCH};
+
+	static COUNTER: AtomicU64 = AtomicU64::new(0);
+	static SEED: OnceLock<u64> = OnceLock::new();
+
+	/// The next value of the counter, mixed with the seed of the process. SplitMix64 is a
+	/// bijection, so values don't repeat within the process.
+	pub(super) fn next_u64() -> u64 {
+		let seed = *SEED.get_or_init(|| {
+			let nanos = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+			mix(nanos.as_nanos() as u64 ^ (u64::from(std::process::id()) << 32))
+		});
+		let count = COUNTER.fetch_add(1, Ordering::Relaxed);
+		mix(seed.wrapping_add(count.wrapping_mul(0x9E37_79B9_7F4A_7C15)))
+	}
+
+	/// The SplitMix64 finalizer.
+	fn mix(mut z: u64) -> u64 {
+		z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+		z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+		z ^ (z >> 31)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::collections::HashSet;
+
+	use super::*;
+
+	#[test]
+	fn test_alphanumeric() {
+		let tokens: HashSet<String> = (0..10_000).map(|_| alphanumeric(16)).collect();
+		assert_eq!(tokens.len(), 10_000);
+		for token in &tokens {
+			assert_eq!(token.len(), 16);
+			assert!(token.bytes().all(|c| ALPHANUMERIC.contains(&c)), "{token}");
+		}
+		assert_eq!(alphanumeric(0), "");
+		assert_eq!(alphanumeric(25).len(), 25);
+	}
+
+	#[test]
+	fn test_jitter() {
+		assert_eq!(jitter(Duration::ZERO), Duration::ZERO);
+		let max = Duration::from_millis(250);
+		let delays: Vec<Duration> = (0..1000).map(|_| jitter(max)).collect();
+		assert!(delays.iter().all(|delay| *delay <= max));
+		assert!(delays.iter().any(|delay| *delay != delays[0]));
+	}
+}
@@ -0,0 +1,117 @@
+// The rate limits of the account, sent in the `x-ratelimit-*` headers of every response.
+// See: https://platform.openai.com/docs/guides/rate-limits
+
+//! Rate limits
+
+use std::time::Duration;
+
+/// The rate limits reported by the latest response, see `OpenAI::rate_limit`.
+/// Every field is `None` when its header is missing.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RateLimitInfo {
+	/// The maximum number of requests permitted before exhausting the rate limit.
+	pub limit_requests: Option<u64>,
+	/// The maximum number of tokens permitted before exhausting the rate limit.
+	pub limit_tokens: Option<u64>,
+	/// The remaining number of requests permitted before exhausting the rate limit.
+	pub remaining_requests: Option<u64>,
+	/// The remaining number of tokens permitted before exhausting the rate limit.
+	pub remaining_tokens: Option<u64>,
+	/// The time until the request rate limit resets to its initial state.
+	pub reset_requests: Option<Duration>,
+	/// The time until the token rate limit resets to its initial state.
+	pub reset_tokens: Option<Duration>,
+}
+
+impl RateLimitInfo {
+	/// Parses the headers returned by `header`, or returns `None` if there are none.
+	pub(crate) fn from_headers<'a>(header: impl Fn(&str) -> Option<&'a str>) -> Option<Self> {
+		let number = |name: &str| header(name)?.trim().parse().ok();
+		let duration = |name: &str| parse_duration(header(name)?);
+		let info = RateLimitInfo {
+			limit_requests: number("x-ratelimit-limit-requests"),
+			limit_tokens: number("x-ratelimit-limit-tokens"),
+			remaining_requests: number("x-ratelimit-remaining-requests"),
+			remaining_tokens: number("x-ratelimit-remaining-tokens"),
+			reset_requests: duration("x-ratelimit-reset-requests"),
+			reset_tokens: duration("x-ratelimit-reset-tokens"),
+		};
+		(info != RateLimitInfo::default()).then_some(info)
+	}
+
+	/// The time to wait before the next request, `None` unless the remaining requests or tokens
+	/// are exhausted.
+	pub fn wait_time(&self) -> Option<Duration> {
+		let requests = self.reset_requests.filter(|_| self.remaining_requests == Some(0));
+		let tokens = self.reset_tokens.filter(|_| self.remaining_tokens == Some(0));
+		requests.max(tokens)
+	}
+}
+
+/// Parses durations such as `1h2m3s`, `6m0s`, `1.5s` or `20ms`.
+pub fn parse_duration(s: &str) -> Option<Duration> {
+	let mut rest = s.trim();
+	if rest.is_empty() {
+		return None;
+	}
+	let mut total = Duration::ZERO;
+	while !rest.is_empty() {
+		let end = rest.find(|c: char| !c.is_ascii_digit() && c != '.').unwrap_or(rest.len());
+		let value: f64 = rest[..end].parse().ok()?;
+		rest = &rest[end..];
+		let unit_end = rest.find(|c: char| c.is_ascii_digit() || c == '.').unwrap_or(rest.len());
+		let seconds = match &rest[..unit_end] {
+			"h" => 3600.0,
+			"m" => 60.0,
+			"s" => 1.0,
+			"ms" => 0.001,
+			_ => return None,
+		};
+		rest = &rest[unit_end..];
+		total += Duration::try_from_secs_f64(value * seconds).ok()?;
+	}
+	Some(total)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_parse_duration() {
+		assert_eq!(parse_duration("6m0s"), Some(Duration::from_secs(360)));
+		assert_eq!(parse_duration("1.5s"), Some(Duration::from_millis(1500)));
+		assert_eq!(parse_duration("20ms"), Some(Duration::from_millis(20)));
+		assert_eq!(parse_duration("1h2m3s"), Some(Duration::from_secs(3723)));
+		assert_eq!(parse_duration(""), None);
+		assert_eq!(parse_duration("1d"), None);
+		assert_eq!(parse_duration("s"), None);
+	}
+
+	#[test]
+	fn test_from_headers() {
+		let headers = [
+			("x-ratelimit-limit-requests", "60"),
+			("x-ratelimit-remaining-requests", "59"),
+			("x-ratelimit-remaining-tokens", "149984"),
+			("x-ratelimit-reset-requests", "1s"),
+			("x-ratelimit-reset-tokens", "6m0s"),
+		];
+		let header = |name: &str| headers.iter().find(|(n, _)| *n == name).map(|(_, v)| *v);
+		let info = RateLimitInfo::from_headers(header).unwrap();
+		assert_eq!(info.limit_requests, Some(60));
+		assert_eq!(info.limit_tokens, None);
+		assert_eq!(info.remaining_requests, Some(59));
+		assert_eq!(info.remaining_tokens, Some(149984));
+		assert_eq!(info.reset_requests, Some(Duration::from_secs(1)));
+		assert_eq!(info.reset_tokens, Some(Duration::from_secs(360)));
+
+		assert!(RateLimitInfo::from_headers(|_| None).is_none());
+		assert_eq!(info.wait_time(), None);
+
+		let info = RateLimitInfo { remaining_requests: Some(0), ..info };
+		assert_eq!(info.wait_time(), Some(Duration::from_secs(1)));
+		let info = RateLimitInfo { remaining_tokens: Some(0), ..info };
+		assert_eq!(info.wait_time(), Some(Duration::from_secs(360)));
+	}
+}
@@ -1,92 +1,743 @@
-use crate::openai::OpenAI;
-use crate::*;
 use crate::multipart::Multipart;
+use crate::openai::{OpenAI, ResponseMeta};
+use crate::rate_limit::RateLimitInfo;
+use crate::transport::{HttpRequest, HttpResponse, RequestBody};
+use crate::*;
+use std::io::Read;
+use std::time::Duration;
 
 #[cfg(not(test))]
-use log::{debug, error, info};
+use log::{debug, error, info, warn};
 
 #[cfg(test)]
-use std::{eprintln as error, println as info, println as debug};
+use std::{eprintln as error, eprintln as warn, println as info, println as debug};
 
 pub trait Requests {
 	fn post(&self, sub_url: &str, body: Json) -> ApiResult<Json>;
 	fn post_multipart(&self, sub_url: &str, multipart: Multipart) -> ApiResult<Json>;
+	/// Like `post_multipart`, for endpoints answering with plain text rather than JSON.
+	fn post_multipart_text(&self, sub_url: &str, multipart: Multipart) -> ApiResult<String>;
 	fn get(&self, sub_url: &str) -> ApiResult<Json>;
+	/// Like `get`, returning the body as a reader rather than reading it as JSON.
+	fn get_stream(&self, sub_url: &str) -> ApiResult<Box<dyn Read + Send + Sync>>;
+	fn delete(&self, sub_url: &str) -> ApiResult<Json>;
+	fn post_stream(&self, sub_url: &str, body: Json) -> ApiResult<Box<dyn Read + Send + Sync>>;
+	/// Like `post_stream`, sending `multipart` as the body.
+	fn post_multipart_stream(
+		&self,
+		sub_url: &str,
+		multipart: Multipart,
+	) -> ApiResult<Box<dyn Read + Send + Sync>>;
+	/// Like `post`, returning the body as a reader, for endpoints answering with binary data.
+	fn post_raw(&self, sub_url: &str, body: Json) -> ApiResult<Box<dyn Read + Send + Sync>>;
 }
 
 impl Requests for OpenAI {
 	fn post(&self, sub_url: &str, body: Json) -> ApiResult<Json> {
+		let body = self.merge_extra_body(body);
 		info!("===> 🚀\n\tPost api: {sub_url}, body: {body}");
 
-		let response = self
-			.agent
-			.post(&(self.api_url.clone() + sub_url))
-			.set("Content-Type", "application/json")
-			.set("OpenAI-Organization", &self.auth.organization.clone().unwrap_or_default())
-			.set("Authorization", &format!("Bearer {}", self.auth.api_key))
-			.send_json(body);
+		let response = self.send_retrying(sub_url, || {
+			self.request("POST", sub_url)
+				.set("Content-Type", "application/json")
+				.body(RequestBody::Json(&body))
+		});
 
-		deal_response(response, sub_url)
+		self.deal_response(response, sub_url)
 	}
 
 	fn get(&self, sub_url: &str) -> ApiResult<Json> {
 		info!("===> 🚀\n\tGet api: {sub_url}");
 
-		let response = self
-			.agent
-			.get(&(self.api_url.clone() + sub_url))
-			.set("Content-Type", "application/json")
-			.set("OpenAI-Organization", &self.auth.organization.clone().unwrap_or_default())
-			.set("Authorization", &format!("Bearer {}", self.auth.api_key))
-			.call();
+		let response = self.send_retrying(sub_url, || {
+			self.request("GET", sub_url).set("Content-Type", "application/json")
+		});
+
+		self.deal_response(response, sub_url)
+	}
+
+	fn get_stream(&self, sub_url: &str) -> ApiResult<Box<dyn Read + Send + Sync>> {
+		info!("===> 🚀\n\tGet stream api: {sub_url}");
+
+		let response = self.send_retrying(sub_url, || self.stream_request("GET", sub_url))?;
+
+		debug!("<== ✔️\n\tStreaming api: {sub_url}");
+		Ok(response.body)
+	}
+
+	fn delete(&self, sub_url: &str) -> ApiResult<Json> {
+		info!("===> 🚀\n\tDelete api: {sub_url}");
+
+		let response = self.send_retrying(sub_url, || self.request("DELETE", sub_url));
+
+		self.deal_response(response, sub_url)
+	}
+
+	fn post_multipart(&self, sub_url: &str, multipart: Multipart) -> ApiResult<Json> {
+		info!("===> 🚀\n\tPost multipart api: {sub_url}");
+
+		let response = self.send_multipart(self.request("POST", sub_url), sub_url, multipart);
 
-		deal_response(response, sub_url)
+		self.deal_response(response, sub_url)
 	}
 
-	fn post_multipart(&self, sub_url: &str, mut multipart: Multipart) -> ApiResult<Json> {
+	fn post_multipart_text(&self, sub_url: &str, multipart: Multipart) -> ApiResult<String> {
 		info!("===> 🚀\n\tPost multipart api: {sub_url}");
 
-		let form_data = multipart.prepare().unwrap();
+		let response = self.send_multipart(self.request("POST", sub_url), sub_url, multipart)?;
+		let body = self.read_body(response, sub_url)?;
+		let text = String::from_utf8(body).map_err(|e| Error::RequestError(e.to_string()))?;
+		debug!("<== ✔️\n\tDone api: {sub_url}, resp: {text}");
+		Ok(text)
+	}
+
+	fn post_stream(&self, sub_url: &str, body: Json) -> ApiResult<Box<dyn Read + Send + Sync>> {
+		let body = self.merge_extra_body(body);
+		info!("===> 🚀\n\tPost stream api: {sub_url}, body: {body}");
+
+		let response = self.send_retrying(sub_url, || {
+			self.stream_request("POST", sub_url)
+				.set("Content-Type", "application/json")
+				.set("Accept", "text/event-stream")
+				.body(RequestBody::Json(&body))
+		})?;
+
+		debug!("<== ✔️\n\tStreaming api: {sub_url}");
+		Ok(response.body)
+	}
+
+	fn post_multipart_stream(
+		&self,
+		sub_url: &str,
+		multipart: Multipart,
+	) -> ApiResult<Box<dyn Read + Send + Sync>> {
+		info!("===> 🚀\n\tPost multipart stream api: {sub_url}");
 
-		let response = self
-			.agent
-			.post(&(self.api_url.clone() + sub_url))
-			.set("Content-Type", &format!("multipart/form-data; boundary={}", form_data.boundary()))
-			.set("OpenAI-Organization", &self.auth.organization.clone().unwrap_or_default())
-			.set("Authorization", &format!("Bearer {}", self.auth.api_key))
-			.send(form_data);
+		let request = self.stream_request("POST", sub_url).set("Accept", "text/event-stream");
+		let response = self.send_multipart(request, sub_url, multipart)?;
 
-		deal_response(response, sub_url)
+		debug!("<== ✔️\n\tStreaming api: {sub_url}");
+		Ok(response.body)
+	}
+
+	fn post_raw(&self, sub_url: &str, body: Json) -> ApiResult<Box<dyn Read + Send + Sync>> {
+		let body = self.merge_extra_body(body);
+		info!("===> 🚀\n\tPost raw api: {sub_url}, body: {body}");
+
+		let response = self.send_retrying(sub_url, || {
+			self.stream_request("POST", sub_url)
+				.set("Content-Type", "application/json")
+				.body(RequestBody::Json(&body))
+		})?;
+
+		debug!("<== ✔️\n\tStreaming api: {sub_url}");
+		Ok(response.body)
 	}
 }
 
-fn deal_response(response: Result<ureq::Response, ureq::Error>, sub_url: &str) -> ApiResult<Json> {
-	match response {
-		Ok(resp) => {
-			let json = resp.into_json::<Json>().unwrap();
-			debug!("<== ✔️\n\tDone api: {sub_url}, resp: {json}");
-			return Ok(json);
-		},
-		Err(err) => match err {
-			ureq::Error::Status(status, response) => {
-				let error_msg = response.into_json::<Json>().unwrap();
-				error!("<== ❌\n\tError api: {sub_url}, status: {status}, error: {error_msg}");
-				return Err(Error::ApiError(format!("{error_msg}")));
+impl OpenAI {
+	/// Like `post`, parsing the response straight from its bytes as `T` rather than through a
+	/// `Json` value, for large responses such as batches of embeddings.
+	pub(crate) fn post_as<T: DeserializeOwned>(&self, sub_url: &str, body: Json) -> ApiResult<T> {
+		let body = self.merge_extra_body(body);
+		info!("===> 🚀\n\tPost api: {sub_url}, body: {body}");
+
+		let response = self.send_retrying(sub_url, || {
+			self.request("POST", sub_url)
+				.set("Content-Type", "application/json")
+				.body(RequestBody::Json(&body))
+		})?;
+
+		let body = self.read_body(response, sub_url)?;
+		let value =
+			serde_json::from_slice(&body).map_err(|e| Error::deserialization::<T>(e, &body))?;
+		debug!("<== ✔️\n\tDone api: {sub_url}, resp: {} bytes", body.len());
+		Ok(value)
+	}
+
+	/// A request whose whole response must be received within the timeout.
+	fn request(&self, method: &str, sub_url: &str) -> HttpRequest<'static> {
+		let mut request = self.stream_request(method, sub_url);
+		request.timeout = self.timeout;
+		request
+	}
+
+	/// A request whose response body is read as it arrives, the timeout only applies to
+	/// connecting and to each read.
+	fn stream_request(&self, method: &str, sub_url: &str) -> HttpRequest<'static> {
+		let mut request = HttpRequest::new(method, self.url(sub_url));
+		for (name, value) in self.headers() {
+			request = request.set(name, &value);
+		}
+		for (name, value) in &self.extra_headers {
+			request = request.set(name, value);
+		}
+		if let Some(key) = self.idempotency_key.as_ref().filter(|_| method == "POST") {
+			request = request.set(IDEMPOTENCY_KEY, key);
+		}
+		request
+	}
+
+	fn deal_response(&self, response: ApiResult<HttpResponse>, sub_url: &str) -> ApiResult<Json> {
+		let body = self.read_body(response?, sub_url)?;
+		let json: Json =
+			serde_json::from_slice(&body).map_err(|e| Error::deserialization::<Json>(e, &body))?;
+		debug!("<== ✔️\n\tDone api: {sub_url}, resp: {json}");
+		Ok(json)
+	}
+
+	/// Reads the whole body of `response`, failing once it exceeds `max_response_bytes`.
+	fn read_body(&self, mut response: HttpResponse, sub_url: &str) -> ApiResult<Vec<u8>> {
+		let mut body = Vec::new();
+		match self.max_response_bytes {
+			Some(limit) => {
+				let read = response.body.take(limit.saturating_add(1)).read_to_end(&mut body);
+				read.map_err(|err| deal_error(err, sub_url))?;
+				if body.len() as u64 > limit {
+					error!("<== ❌\n\tError api: {sub_url}, response larger than {limit} bytes");
+					return Err(Error::ResponseTooLarge(limit));
+				}
 			},
-			ureq::Error::Transport(e) => {
-				error!("<== ❌\n\tError api: {sub_url}, error: {:?}", e.to_string());
-				return Err(Error::RequestError(e.to_string()));
+			None => {
+				response.body.read_to_end(&mut body).map_err(|err| deal_error(err, sub_url))?;
 			},
-		},
+		}
+		Ok(body)
+	}
+
+	/// Sends `multipart` with `request`, once since the streams of its fields can't be read
+	/// again.
+	fn send_multipart(
+		&self,
+		request: HttpRequest<'static>,
+		sub_url: &str,
+		mut multipart: Multipart,
+	) -> ApiResult<HttpResponse> {
+		for (name, value) in &self.extra_body {
+			if !multipart.has_field(name) {
+				match value {
+					Json::String(text) => multipart.add_text(name, text.clone()),
+					value => multipart.add_text(name, value.to_string()),
+				}
+			}
+		}
+		let form_data = multipart.into_prepared().unwrap();
+
+		let request = request.set("Content-Type", &form_data.content_type());
+		#[cfg(feature = "gzip")]
+		if self.gzip_uploads {
+			let body = flate2::read::GzEncoder::new(form_data, flate2::Compression::default());
+			let request =
+				request.set("Content-Encoding", "gzip").body(RequestBody::Reader(Box::new(body)));
+			return self.check_response(self.transport().send(request), sub_url);
+		}
+		// Sized bodies are sent with their length, the others with chunked transfer encoding.
+		let request = match form_data.content_length() {
+			Some(len) => request.set("Content-Length", &len.to_string()),
+			None => request,
+		};
+		let request = request.body(RequestBody::Reader(Box::new(form_data)));
+		self.check_response(self.transport().send(request), sub_url)
+	}
+
+	/// Turns transport errors and error statuses into an `Error`,
+	/// recording the rate limits of successful responses.
+	fn check_response(
+		&self,
+		response: std::io::Result<HttpResponse>,
+		sub_url: &str,
+	) -> ApiResult<HttpResponse> {
+		let response = response.map_err(|err| deal_error(err, sub_url))?;
+		let rate_limit = RateLimitInfo::from_headers(|name| response.header(name));
+		if let Some(slot) = &self.response_meta {
+			*slot.lock().unwrap() = Some(ResponseMeta {
+				status: response.status,
+				request_id: response.header(REQUEST_ID).map(str::to_string),
+				rate_limit: rate_limit.clone(),
+				headers: response.headers.clone(),
+			});
+		}
+		if response.status >= 400 {
+			return Err(self.deal_status(response, sub_url));
+		}
+		if let Some(info) = rate_limit {
+			*self.rate_limit.lock().unwrap() = Some(info);
+		}
+		Ok(response)
+	}
+
+	/// The error of a response with an error status, whose body is read within
+	/// `max_response_bytes` like the others, even for streaming requests.
+	fn deal_status(&self, response: HttpResponse, sub_url: &str) -> Error {
+		let status = response.status;
+		let request_id = response.header(REQUEST_ID).map(str::to_string);
+		let body = match self.read_body(response, sub_url) {
+			Ok(body) => body,
+			Err(err @ Error::ResponseTooLarge(_)) => return err,
+			Err(_) => Vec::new(),
+		};
+		let error_msg = String::from_utf8_lossy(&body).into_owned();
+		error!("<== ❌\n\tError api: {sub_url}, status: {status}, error: {error_msg}");
+		let err = ApiError::parse(status, &error_msg).unwrap_or(ApiError {
+			status,
+			message: error_msg,
+			kind: None,
+			param: None,
+			code: None,
+			request_id: None,
+		});
+		Error::Api(Box::new(ApiError { request_id, ..err }))
+	}
+
+	/// Sends the request built by `request` until it succeeds, fails with an error that is not
+	/// worth retrying, or the retry config is exhausted. Requests other than GET are only retried
+	/// when they were never sent, see `RetryConfig`.
+	fn send_retrying<'a>(
+		&self,
+		sub_url: &str,
+		request: impl Fn() -> HttpRequest<'a>,
+	) -> ApiResult<HttpResponse> {
+		// Generated once, so that all the attempts of this call share it.
+		let idempotency_key = self.retry.as_ref().map(|_| new_idempotency_key());
+		let mut attempt = 1;
+		loop {
+			let mut request = request();
+			if let Some(key) = &idempotency_key {
+				if request.method == "POST" && request.header(IDEMPOTENCY_KEY).is_none() {
+					request = request.set(IDEMPOTENCY_KEY, key);
+				}
+			}
+			let idempotent = request.method == "GET";
+			let response = self.transport().send(request);
+			let unsent = response.as_ref().is_err_and(|err| {
+				matches!(
+					err.kind(),
+					std::io::ErrorKind::NotConnected | std::io::ErrorKind::ConnectionRefused
+				)
+			});
+			let retry_after = response.as_ref().ok().and_then(retry_after);
+			let err = match self.check_response(response, sub_url) {
+				Ok(response) => return Ok(response),
+				Err(err) => err,
+			};
+			let retryable = err.is_retryable() && (idempotent || unsent);
+			let Some(retry) =
+				self.retry.as_ref().filter(|retry| retryable && attempt < retry.max_attempts)
+			else {
+				return Err(err);
+			};
+			let delay = retry_after
+				.map_or_else(|| retry.backoff(attempt), |delay| delay.min(retry.max_delay));
+			warn!("<== 🔁\n\tRetry api: {sub_url}, attempt: {attempt}, delay: {delay:?}");
+			std::thread::sleep(delay);
+			attempt += 1;
+		}
+	}
+}
+
+const IDEMPOTENCY_KEY: &str = "Idempotency-Key";
+const REQUEST_ID: &str = "x-request-id";
+
+fn new_idempotency_key() -> String {
+	format!("openai-rs-{}", crate::random::alphanumeric(32))
+}
+
+/// Whether a response with `status` may succeed when sent again, see `Error::is_retryable`.
+pub(crate) fn is_retryable_status(status: u16) -> bool {
+	status == 429 || (500..600).contains(&status)
+}
+
+/// The delay requested by the `Retry-After` header, in seconds.
+fn retry_after(response: &HttpResponse) -> Option<Duration> {
+	let seconds: f64 = response.header("retry-after")?.trim().parse().ok()?;
+	Duration::try_from_secs_f64(seconds).ok()
+}
+
+/// The error of a request that failed without response. Those of kind `InvalidInput` can't
+/// succeed on another attempt, see `Transport`.
+pub(crate) fn deal_error(err: std::io::Error, sub_url: &str) -> Error {
+	error!("<== ❌\n\tError api: {sub_url}, error: {:?}", err.to_string());
+	match err.kind() {
+		std::io::ErrorKind::InvalidInput => Error::RequestError(err.to_string()),
+		_ => Error::Connection(err.to_string()),
 	}
 }
 
 #[cfg(test)]
 mod tests {
 	use super::*;
-	use crate::openai;
+	use crate::openai::{self, new_test_mock, new_test_response, new_test_server, RetryConfig};
 	use ureq::json;
 
+	#[test]
+	fn test_retry() {
+		let (url, server) = new_test_server(vec![
+			new_test_response(503, &[], r#"{"error": {"message": "overloaded"}}"#),
+			new_test_response(
+				429,
+				&[("Retry-After", "0.01")],
+				r#"{"error": {"message": "slow down"}}"#,
+			),
+			new_test_response(200, &[], r#"{"object": "list"}"#),
+		]);
+		let retry = RetryConfig {
+			max_attempts: 3,
+			base_delay: Duration::from_millis(1),
+			jitter: Duration::ZERO,
+			..Default::default()
+		};
+		let openai = OpenAI::new(Auth::new("sk-test"), &url).with_retry(retry.clone());
+		assert_eq!(openai.get("models").unwrap(), json!({"object": "list"}));
+		assert_eq!(server.join().unwrap().len(), 3);
+
+		let (url, server) = new_test_server(vec![
+			new_test_response(500, &[], r#"{"error": {"message": "oops"}}"#),
+			new_test_response(500, &[], r#"{"error": {"message": "oops"}}"#),
+		]);
+		let openai = OpenAI::new(Auth::new("sk-test"), &url)
+			.with_retry(RetryConfig { max_attempts: 2, ..retry.clone() });
+		assert!(matches!(openai.get("models"), Err(Error::Api(err)) if err.status == 500));
+		server.join().unwrap();
+
+		let (url, server) = new_test_server(vec![new_test_response(
+			400,
+			&[],
+			r#"{"error": {"message": "bad request"}}"#,
+		)]);
+		let openai = OpenAI::new(Auth::new("sk-test"), &url).with_retry(retry);
+		assert!(
+			matches!(openai.post("chat/completions", json!({})), Err(Error::Api(err)) if err.status == 400)
+		);
+		assert_eq!(server.join().unwrap().len(), 1);
+	}
+
+	#[test]
+	fn test_retry_classification() {
+		use crate::testing::{MockResponse, MockTransport};
+		use crate::transport::Transport;
+		use std::sync::atomic::{AtomicU32, Ordering};
+		use std::sync::Arc;
+
+		/// Fails every request with an error of kind `.1`, counting them.
+		#[derive(Debug, Clone)]
+		struct Failing(Arc<AtomicU32>, std::io::ErrorKind);
+		impl Transport for Failing {
+			fn send(&self, _: HttpRequest) -> std::io::Result<HttpResponse> {
+				self.0.fetch_add(1, Ordering::SeqCst);
+				Err(std::io::Error::new(self.1, "failed"))
+			}
+		}
+
+		let retry = RetryConfig {
+			max_attempts: 3,
+			base_delay: Duration::ZERO,
+			jitter: Duration::ZERO,
+			..Default::default()
+		};
+		let openai = crate::OpenAI::new(crate::Auth::new("sk-test"), "https://api.openai.com/v1/")
+			.with_retry(retry);
+		let reset = Failing(Arc::default(), std::io::ErrorKind::ConnectionReset);
+		let rs = openai.clone().with_transport(reset.clone()).get("models");
+		assert!(matches!(rs, Err(Error::Connection(_))));
+		assert_eq!(reset.0.load(Ordering::SeqCst), 3);
+		let invalid = Failing(Arc::default(), std::io::ErrorKind::InvalidInput);
+		let rs = openai.clone().with_transport(invalid.clone()).get("models");
+		assert!(matches!(rs, Err(Error::RequestError(_))));
+		assert_eq!(invalid.0.load(Ordering::SeqCst), 1);
+
+		// Other requests than GET are only retried when they were never sent.
+		let reset = Failing(Arc::default(), std::io::ErrorKind::ConnectionReset);
+		let rs = openai.clone().with_transport(reset.clone()).post("chat/completions", json!({}));
+		assert!(matches!(rs, Err(Error::Connection(_))));
+		assert_eq!(reset.0.load(Ordering::SeqCst), 1);
+		for kind in [std::io::ErrorKind::NotConnected, std::io::ErrorKind::ConnectionRefused] {
+			let unsent = Failing(Arc::default(), kind);
+			let rs =
+				openai.clone().with_transport(unsent.clone()).post("fine_tuning/jobs", json!({}));
+			assert!(matches!(rs, Err(Error::Connection(_))));
+			assert_eq!(unsent.0.load(Ordering::SeqCst), 3);
+		}
+
+		let mock = MockTransport::new()
+			.with_response("models", MockResponse::new(502, "<html>Bad Gateway</html>"))
+			.with_response("models", MockResponse::new(200, "{}"))
+			.with_response("files", MockResponse::new(401, r#"{"error": {"message": "bad key"}}"#))
+			.with_response(
+				"chat/completions",
+				MockResponse::new(503, r#"{"error": {"message": "busy"}}"#),
+			)
+			.with_response("chat/completions", MockResponse::new(200, "{}"));
+		let openai = openai.with_transport(mock.clone());
+		assert_eq!(openai.get("models").unwrap(), json!({}));
+		assert!(matches!(openai.get("files"), Err(Error::Api(err)) if err.status == 401));
+		let rs = openai.post("chat/completions", json!({}));
+		assert!(matches!(rs, Err(Error::Api(err)) if err.status == 503));
+		assert_eq!(mock.requests().len(), 4);
+	}
+
+	#[test]
+	fn test_idempotency_key() {
+		use crate::testing::{MockResponse, MockTransport};
+		use crate::transport::Transport;
+		use std::sync::{Arc, Mutex};
+
+		/// Refuses the connection of the first requests, `.0` of them, recording the keys sent.
+		#[derive(Debug, Clone)]
+		struct Refusing(Arc<Mutex<u32>>, Arc<Mutex<Vec<Option<String>>>>, MockTransport);
+		impl Transport for Refusing {
+			fn send(&self, request: HttpRequest) -> std::io::Result<HttpResponse> {
+				self.1.lock().unwrap().push(request.header(IDEMPOTENCY_KEY).map(str::to_string));
+				let mut refusals = self.0.lock().unwrap();
+				if *refusals > 0 {
+					*refusals -= 1;
+					return Err(std::io::ErrorKind::ConnectionRefused.into());
+				}
+				self.2.send(request)
+			}
+		}
+
+		let mock = MockTransport::new()
+			.with_response("batches", MockResponse::new(200, "{}"))
+			.with_response("batches", MockResponse::new(200, "{}"));
+		let transport = Refusing(Arc::new(Mutex::new(1)), Arc::default(), mock);
+		let retry = RetryConfig {
+			max_attempts: 2,
+			base_delay: Duration::ZERO,
+			jitter: Duration::ZERO,
+			..Default::default()
+		};
+		let openai = OpenAI::new(Auth::new("sk-test"), "https://api.openai.com/v1/")
+			.with_retry(retry)
+			.with_transport(transport.clone());
+		openai.post("batches", json!({})).unwrap();
+		openai.post("batches", json!({})).unwrap();
+		openai.clone().with_idempotency_key("key-1").post("batches", json!({})).unwrap();
+		openai.get("batches").unwrap();
+
+		let keys = transport.1.lock().unwrap().clone();
+		assert!(keys[0].is_some());
+		assert_eq!(keys[0], keys[1]);
+		assert!(keys[2].is_some());
+		assert_ne!(keys[1], keys[2]);
+		assert_eq!(keys[3].as_deref(), Some("key-1"));
+		assert_eq!(keys[4], None);
+	}
+
+	#[test]
+	fn test_response_meta() {
+		let (url, server) = new_test_server(vec![
+			new_test_response(
+				200,
+				&[("x-request-id", "req_123"), ("x-ratelimit-remaining-requests", "59")],
+				r#"{"object": "list"}"#,
+			),
+			new_test_response(
+				400,
+				&[("x-request-id", "req_456")],
+				r#"{"error": {"message": "bad request"}}"#,
+			),
+		]);
+		let openai = OpenAI::new(Auth::new("sk-test"), &url);
+		let (json, meta) = openai.with_response_meta(|openai| openai.get("models")).unwrap();
+		assert_eq!(json, json!({"object": "list"}));
+		assert_eq!(meta.status, 200);
+		assert_eq!(meta.request_id.as_deref(), Some("req_123"));
+		assert_eq!(meta.rate_limit.unwrap().remaining_requests, Some(59));
+		assert!(meta.headers.iter().any(|(name, _)| name.eq_ignore_ascii_case("content-type")));
+
+		let rs = openai.post("chat/completions", json!({}));
+		assert!(matches!(rs, Err(Error::Api(err)) if err.request_id.as_deref() == Some("req_456")));
+		assert!(openai.response_meta.is_none());
+		server.join().unwrap();
+	}
+
+	#[test]
+	fn test_rate_limit() {
+		let (url, server) = new_test_server(vec![
+			new_test_response(200, &[], "{}"),
+			new_test_response(
+				200,
+				&[("x-ratelimit-remaining-tokens", "149984"), ("x-ratelimit-reset-tokens", "6m0s")],
+				"{}",
+			),
+		]);
+		let openai = OpenAI::new(Auth::new("sk-test"), &url);
+		openai.get("models").unwrap();
+		assert!(openai.rate_limit().is_none());
+		openai.clone().get("models").unwrap();
+		let info = openai.rate_limit().unwrap();
+		assert_eq!(info.remaining_tokens, Some(149984));
+		assert_eq!(info.reset_tokens, Some(Duration::from_secs(360)));
+		server.join().unwrap();
+	}
+
+	#[test]
+	fn test_timeout() {
+		// Accepts connections but never answers.
+		let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+		let url = format!("http://{}/v1/", listener.local_addr().unwrap());
+		let openai = OpenAI::new(Auth::new("sk-test"), &url);
+
+		let start = std::time::Instant::now();
+		let rs = openai.clone().with_timeout(Duration::from_millis(100)).get("models");
+		assert!(matches!(rs, Err(Error::Connection(_))));
+		let rs = openai.with_timeout(Duration::from_millis(100)).post_stream("chat", json!({}));
+		assert!(matches!(rs, Err(Error::Connection(_))));
+		assert!(start.elapsed() < Duration::from_secs(5));
+		drop(listener);
+	}
+
+	#[test]
+	fn test_backoff() {
+		let retry = RetryConfig {
+			max_attempts: 5,
+			base_delay: Duration::from_millis(100),
+			jitter: Duration::from_millis(10),
+			max_delay: Duration::from_millis(500),
+		};
+		for (attempt, expected) in [(1, 100), (2, 200), (3, 400)] {
+			let delay = retry.backoff(attempt);
+			assert!(delay >= Duration::from_millis(expected));
+			assert!(delay <= Duration::from_millis(expected + 10));
+		}
+		assert_eq!(retry.backoff(4), Duration::from_millis(500));
+		assert_eq!(retry.backoff(40), Duration::from_millis(500));
+
+		// `Retry-After` is clamped to `max_delay` too.
+		use crate::testing::{MockResponse, MockTransport};
+		let mock = MockTransport::new()
+			.with_response(
+				"models",
+				MockResponse::new(429, "{}").with_header("Retry-After", "86400"),
+			)
+			.with_response("models", MockResponse::new(200, "{}"));
+		let retry = RetryConfig { max_delay: Duration::from_millis(10), ..retry };
+		let openai = OpenAI::new(Auth::new("sk-test"), "https://api.openai.com/v1/")
+			.with_retry(retry)
+			.with_transport(mock.clone());
+		let start = std::time::Instant::now();
+		assert_eq!(openai.get("models").unwrap(), json!({}));
+		assert!(start.elapsed() < Duration::from_secs(5));
+		assert_eq!(mock.requests().len(), 2);
+	}
+
+	#[test]
+	fn test_multipart_content_length() {
+		let (url, server) = new_test_server(vec![
+			new_test_response(200, &[], "{}"),
+			new_test_response(200, &[], "{}"),
+		]);
+		let openai = crate::OpenAI::new(crate::Auth::new("sk-test"), &url);
+		let sized = || {
+			let mut multipart = Multipart::new();
+			multipart.add_text("purpose", "fine-tune");
+			multipart.add_sized_stream("file", &b"{}"[..], 2, Some("a.jsonl"), None);
+			multipart
+		};
+		let len = sized().prepare().unwrap().content_length();
+		openai.post_multipart("files", sized()).unwrap();
+		let mut multipart = Multipart::new();
+		multipart.add_stream("file", &b"{}"[..], Some("a.jsonl"), None);
+		openai.post_multipart("files", multipart).unwrap();
+
+		let requests: Vec<_> = server.join().unwrap().iter().map(|r| r.to_lowercase()).collect();
+		assert!(requests[0].contains(&format!("content-length: {}\r\n", len.unwrap())));
+		assert!(!requests[0].contains("transfer-encoding"));
+		assert!(!requests[1].contains("content-length"));
+		assert!(requests[1].contains("transfer-encoding: chunked\r\n"));
+	}
+
+	#[test]
+	fn test_user_agent() {
+		let (url, server) = new_test_server(vec![
+			new_test_response(200, &[], "{}"),
+			new_test_response(200, &[], "{}"),
+			new_test_response(200, &[], "{}"),
+		]);
+		let openai = crate::OpenAI::new(crate::Auth::new("sk-test"), &url);
+		openai.post("chat/completions", json!({})).unwrap();
+		let openai = openai.with_user_agent_suffix("my-app/2.0");
+		openai.get("models").unwrap();
+		let mut multipart = Multipart::new();
+		multipart.add_text("purpose", "batch");
+		openai.post_multipart("files", multipart).unwrap();
+
+		let requests: Vec<_> = server.join().unwrap().iter().map(|r| r.to_lowercase()).collect();
+		let user_agent = openai::USER_AGENT.to_lowercase();
+		assert!(requests[0].contains(&format!("user-agent: {user_agent}\r\n")), "{}", requests[0]);
+		for request in &requests[1..] {
+			assert!(request.contains(&format!("user-agent: {user_agent} my-app/2.0\r\n")));
+		}
+	}
+
+	#[test]
+	fn test_max_response_bytes() {
+		use crate::testing::{MockResponse, MockTransport};
+
+		let body = r#"{"object": "list", "data": []}"#;
+		let mock = MockTransport::new()
+			.with_response("models", MockResponse::new(200, body))
+			.with_response("files/file-1/content", MockResponse::new(200, body))
+			.with_response("audio/transcriptions", MockResponse::new(200, "hello"));
+		let openai = new_test_mock(&mock);
+		let limited = openai.clone().with_max_response_bytes(body.len() as u64);
+		assert_eq!(limited.get("models").unwrap()["object"], "list");
+
+		let limited = openai.clone().with_max_response_bytes(body.len() as u64 - 1);
+		let err = limited.get("models").unwrap_err();
+		assert!(matches!(err, Error::ResponseTooLarge(limit) if limit == body.len() as u64 - 1));
+		let tiny = openai.clone().with_max_response_bytes(4);
+		let err = tiny.post_multipart_text("audio/transcriptions", Multipart::new()).unwrap_err();
+		assert!(matches!(err, Error::ResponseTooLarge(4)));
+
+		// Error bodies are limited too, streaming requests included.
+		let error = format!(r#"{{"error": {{"message": "{}"}}}}"#, "x".repeat(1024));
+		let mock = MockTransport::new()
+			.with_response("models", MockResponse::new(500, error.as_str()))
+			.with_response("chat/completions", MockResponse::new(500, error.as_str()));
+		let openai = new_test_mock(&mock);
+		let err = openai.clone().with_max_response_bytes(512).get("models").unwrap_err();
+		assert!(matches!(err, Error::ResponseTooLarge(512)));
+		let rs =
+			openai.clone().with_max_response_bytes(512).post_stream("chat/completions", json!({}));
+		assert!(matches!(rs, Err(Error::ResponseTooLarge(512))));
+		let err = openai.with_max_response_bytes(2048).get("models").unwrap_err();
+		assert!(matches!(err, Error::Api(err) if err.status == 500 && err.message.len() == 1024));
+
+		// Streamed bodies are exempt
+		let mut content = String::new();
+		limited.get_stream("files/file-1/content").unwrap().read_to_string(&mut content).unwrap();
+		assert_eq!(content, body);
+	}
+
+	#[cfg(feature = "gzip")]
+	#[test]
+	fn test_gzip_uploads() {
+		use crate::testing::{MockResponse, MockTransport};
+
+		let mock = MockTransport::new().with_response("files", MockResponse::new(200, "{}"));
+		let openai = new_test_mock(&mock);
+		let multipart = || {
+			let mut multipart = Multipart::new();
+			multipart.add_text("purpose", "fine-tune");
+			multipart.add_stream("file", std::io::Cursor::new(b"{}"), Some("a.jsonl"), None);
+			multipart
+		};
+		openai.post_multipart("files", multipart()).unwrap();
+		openai.clone().with_gzip_uploads().post_multipart("files", multipart()).unwrap();
+
+		let requests = mock.requests();
+		assert_eq!(requests[0].header("content-encoding"), None);
+		assert_eq!(requests[1].header("content-encoding"), Some("gzip"));
+		let mut body = String::new();
+		flate2::read::GzDecoder::new(requests[1].body.as_slice())
+			.read_to_string(&mut body)
+			.unwrap();
+		let boundary = |body: &str| body.lines().nth(1).unwrap().to_string();
+		let plain = String::from_utf8(requests[0].body.clone()).unwrap();
+		assert_eq!(body.replace(&boundary(&body), ""), plain.replace(&boundary(&plain), ""));
+	}
+
 	#[test]
 	fn test_post() {
 		let openai = openai::new_test_openai();
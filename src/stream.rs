@@ -0,0 +1,678 @@
+// Decoding of `text/event-stream` responses returned when `stream` is set.
+// See: https://developer.mozilla.org/en-US/docs/Web/API/Server-sent_events
+
+//! Streaming responses
+
+use std::io::Read;
+use std::marker::PhantomData;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use serde::de::DeserializeOwned;
+
+use crate::*;
+
+/// The payload the API sends to terminate a stream.
+const DONE: &str = "[DONE]";
+const READ_SIZE: usize = 8 * 1024;
+
+/// An iterator over the chunks of a streamed response,
+/// decoded from the `data:` lines of every server-sent event until `[DONE]` is received.
+///
+/// A stream whose connection closes or fails before `[DONE]` ends with
+/// `Error::StreamInterrupted`, so that a truncated response isn't mistaken for a complete one.
+pub struct Stream<T> {
+	reader: Box<dyn Read + Send + Sync>,
+	decoder: SseDecoder,
+	done: bool,
+	/// The number of chunks decoded so far.
+	received: usize,
+	usage: Option<Usage>,
+	/// The `type` of the chunk ending streams without `[DONE]`, see `ending_with`.
+	final_type: Option<&'static str>,
+	/// Whether chunks without choices nor usage are skipped, see `skipping_empty_choices`.
+	skip_empty_choices: bool,
+	/// Whether chunks are parsed along with their event type, see `tagging_events`.
+	tag_events: bool,
+	cancelled: Arc<AtomicBool>,
+	_chunk: PhantomData<T>,
+}
+
+/// Aborts a `Stream` from another thread, see `Stream::handle`.
+#[derive(Debug, Clone)]
+pub struct StreamHandle {
+	cancelled: Arc<AtomicBool>,
+}
+
+impl StreamHandle {
+	/// Makes the stream close its connection and end with `Error::Cancelled`.
+	///
+	/// The stream notices it the next time it is polled, or when its pending read returns,
+	/// which happens as soon as the API sends the next chunk.
+	pub fn abort(&self) {
+		self.cancelled.store(true, Ordering::Relaxed);
+	}
+}
+
+impl<T> Stream<T> {
+	pub(crate) fn new(reader: Box<dyn Read + Send + Sync>) -> Self {
+		Self {
+			reader,
+			decoder: SseDecoder::new(),
+			done: false,
+			received: 0,
+			usage: None,
+			final_type: None,
+			skip_empty_choices: false,
+			tag_events: false,
+			cancelled: Arc::new(AtomicBool::new(false)),
+			_chunk: PhantomData,
+		}
+	}
+
+	/// Ends the stream after the chunk whose `type` is `final_type`, for endpoints that don't
+	/// send `[DONE]`.
+	pub(crate) fn ending_with(mut self, final_type: &'static str) -> Self {
+		self.final_type = Some(final_type);
+		self
+	}
+
+	/// Skips the chunks whose `choices` is empty and that carry no `usage`, which the API
+	/// sometimes sends to prime or keep alive completion streams.
+	pub(crate) fn skipping_empty_choices(mut self) -> Self {
+		self.skip_empty_choices = true;
+		self
+	}
+
+	/// Parses the chunks as `{"event": ..., "data": ...}`, with the `event:` type of their
+	/// event, for endpoints sending chunks of different types told apart by their event type.
+	pub(crate) fn tagging_events(mut self) -> Self {
+		self.tag_events = true;
+		self
+	}
+
+	/// A handle to abort the stream, e.g. when the user is no longer waiting for it.
+	/// Dropping the stream also closes its connection.
+	pub fn handle(&self) -> StreamHandle {
+		StreamHandle { cancelled: self.cancelled.clone() }
+	}
+
+	/// Drops the connection and the buffered events if the stream was aborted.
+	fn check_cancelled(&mut self) -> bool {
+		if self.done || !self.cancelled.load(Ordering::Relaxed) {
+			return false;
+		}
+		self.done = true;
+		self.reader = Box::new(std::io::empty());
+		self.decoder = SseDecoder::new();
+		true
+	}
+
+	/// The token usage of the whole request, sent in the last chunk when
+	/// `stream_options.include_usage` is set. Only available once that chunk has been read.
+	pub fn usage(&self) -> Option<&Usage> {
+		self.usage.as_ref()
+	}
+
+	/// Stops reading the stream and closes its connection, like dropping it. The API stops
+	/// generating the response, no longer billing its tokens, and the next request opens a new
+	/// connection. Prefer it to `drain` to abandon a response.
+	pub fn close(mut self) {
+		self.done = true;
+		self.reader = Box::new(std::io::empty());
+	}
+
+	/// Reads the rest of the response without decoding it, so that its connection returns to
+	/// the pool to be reused by the next request. This waits for the API to finish generating
+	/// the response, whose tokens are billed, see `close` to stop it instead.
+	///
+	/// Fails with `Error::StreamInterrupted` if the connection fails before the end of the
+	/// response, or `Error::Cancelled` if the stream is aborted meanwhile.
+	pub fn drain(mut self) -> ApiResult<()> {
+		let mut chunk = [0; READ_SIZE];
+		while !self.done {
+			let read = self.reader.read(&mut chunk);
+			if self.check_cancelled() {
+				return Err(Error::Cancelled);
+			}
+			match read {
+				Ok(0) => self.done = true,
+				Ok(_) => {},
+				Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {},
+				Err(e) => {
+					log::warn!("Stream interrupted: {e}");
+					return Err(Error::StreamInterrupted(self.received));
+				},
+			}
+		}
+		Ok(())
+	}
+}
+
+impl<T: DeserializeOwned> Iterator for Stream<T> {
+	type Item = ApiResult<T>;
+
+	fn next(&mut self) -> Option<Self::Item> {
+		self.next_chunk().map(|chunk| chunk.map(|raw| raw.chunk))
+	}
+}
+
+/// A chunk of a `RawStream`, along with the `data:` payload it was parsed from.
+#[derive(Debug, Clone)]
+pub struct RawChunk<T> {
+	pub chunk: T,
+	/// The JSON of the chunk, as received.
+	pub data: String,
+}
+
+/// A `Stream` also yielding the payloads of its chunks, to log exactly what the API sent,
+/// including the fields this crate doesn't model. See `Stream::with_raw`.
+pub struct RawStream<T>(Stream<T>);
+
+impl<T> Stream<T> {
+	/// Yields every chunk along with its `data:` payload.
+	pub fn with_raw(self) -> RawStream<T> {
+		RawStream(self)
+	}
+}
+
+impl<T> RawStream<T> {
+	/// See `Stream::handle`.
+	pub fn handle(&self) -> StreamHandle {
+		self.0.handle()
+	}
+
+	/// See `Stream::usage`.
+	pub fn usage(&self) -> Option<&Usage> {
+		self.0.usage()
+	}
+
+	/// See `Stream::close`.
+	pub fn close(self) {
+		self.0.close()
+	}
+
+	/// See `Stream::drain`.
+	pub fn drain(self) -> ApiResult<()> {
+		self.0.drain()
+	}
+}
+
+impl<T: DeserializeOwned> Iterator for RawStream<T> {
+	type Item = ApiResult<RawChunk<T>>;
+
+	fn next(&mut self) -> Option<Self::Item> {
+		self.0.next_chunk()
+	}
+}
+
+impl<T: DeserializeOwned + CompletionResponse> Stream<T> {
+	/// Reads the stream to the end, concatenating the text of the first choice, for chat and
+	/// completion streams alike. Whether the text was truncated is given by the finish reason
+	/// of the last chunk, see `CompletionResponse::was_truncated`.
+	pub fn collect_text(self) -> ApiResult<(String, Option<FinishReason>)> {
+		let mut text = String::new();
+		let mut finish_reason = None;
+		for chunk in self {
+			let chunk = chunk?;
+			text.push_str(chunk.choice_text(0).unwrap_or_default());
+			finish_reason = chunk.finish_reason(0).or(finish_reason);
+		}
+		Ok((text, finish_reason))
+	}
+}
+
+impl<T: DeserializeOwned> Stream<T> {
+	/// The next chunk, along with its payload to return it from `RawStream`.
+	fn next_chunk(&mut self) -> Option<ApiResult<RawChunk<T>>> {
+		while !self.done {
+			if self.check_cancelled() {
+				return Some(Err(Error::Cancelled));
+			}
+			let event = match self.decoder.next_event() {
+				Some(event) => event,
+				None => {
+					let mut chunk = [0; READ_SIZE];
+					let read = self.reader.read(&mut chunk);
+					if self.check_cancelled() {
+						return Some(Err(Error::Cancelled));
+					}
+					match read {
+						Ok(0) => {
+							// An unterminated event is only complete if it is `[DONE]`.
+							self.done = true;
+							match self.decoder.finish() {
+								Some(event) if event.data == DONE => return None,
+								_ => return Some(Err(Error::StreamInterrupted(self.received))),
+							}
+						},
+						Ok(n) => {
+							self.decoder.feed(&chunk[..n]);
+							continue;
+						},
+						// A read interrupted by a signal is retried, the connection is fine.
+						Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+						Err(e) => {
+							self.done = true;
+							log::warn!("Stream interrupted: {e}");
+							return Some(Err(Error::StreamInterrupted(self.received)));
+						},
+					}
+				},
+			};
+
+			if event.data == DONE {
+				self.done = true;
+				return None;
+			}
+			let chunk = match self.parse_chunk(&event.data, event.event.as_deref()) {
+				Ok(None) => continue,
+				Ok(Some(chunk)) => Ok(RawChunk { chunk, data: event.data }),
+				Err(e) => Err(e),
+			};
+			match &chunk {
+				Ok(_) => self.received += 1,
+				// The API closes the connection after an error.
+				Err(Error::Api(_) | Error::ApiError(_)) => self.done = true,
+				Err(_) => {},
+			}
+			return Some(chunk);
+		}
+		None
+	}
+}
+
+/// An event of a `text/event-stream`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SseEvent {
+	/// The `event:` field, `None` for the default `message` type.
+	pub event: Option<String>,
+	/// The `data:` lines, joined with newlines.
+	pub data: String,
+	pub id: Option<String>,
+}
+
+/// An incremental decoder of server-sent events, fed with the bytes of a response as they are
+/// received, whatever their boundaries.
+///
+/// Comment lines, such as the `: ping` keep-alives some proxies inject, and unknown fields are
+/// skipped. Events without `data:` lines are not dispatched, as the specification requires.
+///
+/// The bytes received are kept in a single buffer, which is compacted when more bytes are fed
+/// rather than reallocated per line, so decoding allocates mostly for the events themselves.
+///
+/// ```
+/// # use openai_api_rust::stream::SseDecoder;
+/// let mut decoder = SseDecoder::new();
+/// decoder.feed(b": ping\n\ndata: {\"n\": 1}\n");
+/// assert!(decoder.next_event().is_none());
+/// decoder.feed(b"\n");
+/// assert_eq!(decoder.next_event().unwrap().data, r#"{"n": 1}"#);
+/// ```
+#[derive(Debug, Default)]
+pub struct SseDecoder {
+	buf: Vec<u8>,
+	/// The start of the bytes not decoded yet.
+	pos: usize,
+	/// The end of the bytes searched for a line ending, so that a long line received in many
+	/// pieces isn't searched again from its start.
+	scanned: usize,
+	fields: EventFields,
+}
+
+/// The fields of the event being decoded.
+#[derive(Debug, Default)]
+struct EventFields {
+	event: Option<String>,
+	data: Option<String>,
+	id: Option<String>,
+}
+
+impl SseDecoder {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Appends received bytes, the events they complete are returned by `next_event`.
+	pub fn feed(&mut self, bytes: &[u8]) {
+		if self.pos > 0 {
+			self.buf.drain(..self.pos);
+			self.scanned -= self.pos;
+			self.pos = 0;
+		}
+		self.buf.extend_from_slice(bytes);
+	}
+
+	/// Removes the next complete event, if any.
+	pub fn next_event(&mut self) -> Option<SseEvent> {
+		loop {
+			let found = self.buf[self.scanned..].iter().position(|&b| b == b'\n' || b == b'\r');
+			let Some(end) = found.map(|i| self.scanned + i) else {
+				self.scanned = self.buf.len();
+				return None;
+			};
+			// A `\r` at the end of the buffer may be followed by a `\n` not received yet.
+			if self.buf[end] == b'\r' && end + 1 == self.buf.len() {
+				self.scanned = end;
+				return None;
+			}
+			let crlf = self.buf[end] == b'\r' && self.buf[end + 1] == b'\n';
+			let line = String::from_utf8_lossy(&self.buf[self.pos..end]);
+			self.pos = end + if crlf { 2 } else { 1 };
+			self.scanned = self.pos;
+			if let Some(event) = self.fields.line(&line) {
+				return Some(event);
+			}
+		}
+	}
+
+	/// Ends the stream, returning the event left unterminated, if any.
+	pub fn finish(&mut self) -> Option<SseEvent> {
+		if let Some(event) = self.next_event() {
+			return Some(event);
+		}
+		let rest = String::from_utf8_lossy(&self.buf[self.pos..]);
+		let rest = rest.strip_suffix('\r').unwrap_or(&rest);
+		let event = self.fields.line(rest);
+		self.buf.clear();
+		self.pos = 0;
+		self.scanned = 0;
+		event.or_else(|| self.fields.line(""))
+	}
+}
+
+impl EventFields {
+	/// Processes a line, returning the event it dispatches if it is blank.
+	fn line(&mut self, line: &str) -> Option<SseEvent> {
+		if line.is_empty() {
+			let event = self.event.take();
+			let id = self.id.take();
+			return self.data.take().map(|data| SseEvent { event, data, id });
+		}
+		if line.starts_with(':') {
+			return None;
+		}
+		let (field, value) = line.split_once(':').unwrap_or((line, ""));
+		let value = value.strip_prefix(' ').unwrap_or(value);
+		match field {
+			"data" => match &mut self.data {
+				Some(data) => {
+					data.push('\n');
+					data.push_str(value);
+				},
+				None => self.data = Some(value.to_string()),
+			},
+			"event" => self.event = Some(value.to_string()),
+			"id" => self.id = Some(value.to_string()),
+			_ => {},
+		}
+		None
+	}
+}
+
+impl<T: DeserializeOwned> Stream<T> {
+	/// The chunk of `data`, sent with the event type `event`, `None` if it is skipped.
+	fn parse_chunk(&mut self, data: &str, event: Option<&str>) -> ApiResult<Option<T>> {
+		let json: Json = serde_json::from_str(data)
+			.map_err(|e| Error::deserialization::<Json>(e, data.as_bytes()))?;
+		if json.get("error").is_some() {
+			return Err(match ApiError::parse(200, data) {
+				Some(err) => Error::Api(Box::new(err)),
+				None => Error::ApiError(data.to_string()),
+			});
+		}
+		let usage = json.get("usage").filter(|usage| !usage.is_null());
+		if let Some(usage) = usage {
+			self.usage = serde_json::from_value(usage.clone()).ok();
+		}
+		let no_choices = json["choices"].as_array().is_some_and(Vec::is_empty);
+		if self.skip_empty_choices && no_choices && usage.is_none() {
+			return Ok(None);
+		}
+		if self.final_type.is_some() && json["type"].as_str() == self.final_type {
+			self.done = true;
+		}
+		if self.tag_events {
+			return from_json(&serde_json::json!({ "event": event, "data": json })).map(Some);
+		}
+		from_json(&json).map(Some)
+	}
+}
+
+/// Returns at most `.1` bytes per read, to split events across reads.
+#[cfg(test)]
+struct Chunked<R>(R, usize);
+
+#[cfg(test)]
+impl<R: Read> Read for Chunked<R> {
+	fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+		let len = buf.len().min(self.1);
+		self.0.read(&mut buf[..len])
+	}
+}
+
+/// A stream decoding `body`, delivered `size` bytes at a time.
+#[cfg(test)]
+pub fn new_test_stream<T>(body: &'static str, size: usize) -> Stream<T> {
+	Stream::new(Box::new(Chunked(body.as_bytes(), size)))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_stream() {
+		let body = "data: {\"n\": 1}\n\ndata: {\"n\": 2}\n\ndata: [DONE]\n\ndata: {\"n\": 3}\n\n";
+		for size in [1, 3, 1024] {
+			let chunks: Vec<Json> = new_test_stream(body, size).map(Result::unwrap).collect();
+			assert_eq!(chunks, vec![serde_json::json!({"n": 1}), serde_json::json!({"n": 2})]);
+		}
+	}
+
+	#[test]
+	fn test_sse_decoder() {
+		let body =
+			b": ping\n\nevent: delta\nid: 1\ndata: {\"a\":\n: ping\ndata: 1}\nretry: 10\n\n\n\
+			:ping\r\nfoo: bar\r\ndata\r\n\r\ndata: last";
+		let expected = vec![
+			SseEvent {
+				event: Some("delta".to_string()),
+				data: "{\"a\":\n1}".to_string(),
+				id: Some("1".to_string()),
+			},
+			SseEvent { data: String::new(), ..Default::default() },
+			SseEvent { data: "last".to_string(), ..Default::default() },
+		];
+		for size in [1, 2, 5, body.len()] {
+			let mut decoder = SseDecoder::new();
+			let mut events = Vec::new();
+			for chunk in body.chunks(size) {
+				decoder.feed(chunk);
+				events.extend(std::iter::from_fn(|| decoder.next_event()));
+			}
+			events.extend(decoder.finish());
+			assert_eq!(events, expected);
+			assert!(decoder.finish().is_none());
+		}
+
+		let body = ": keep-alive\n\ndata: {\"n\": 1}\n\n: keep-alive\n\ndata: [DONE]\n\n";
+		let chunks: Vec<Json> = new_test_stream(body, 3).map(Result::unwrap).collect();
+		assert_eq!(chunks, vec![serde_json::json!({"n": 1})]);
+	}
+
+	#[test]
+	fn test_sse_decoder_split() {
+		// Every split of the `\r\n\r\n` separators, including between their `\r` and `\n`.
+		let body = b"data: {\"n\": 1}\r\n\r\ndata: {\"n\": 2}\r\n\r\ndata: [DONE]\r\n\r\n";
+		for split in 1..body.len() {
+			let mut decoder = SseDecoder::new();
+			let mut events = Vec::new();
+			for chunk in [&body[..split], &body[split..]] {
+				decoder.feed(chunk);
+				events.extend(std::iter::from_fn(|| decoder.next_event()));
+			}
+			let data: Vec<_> = events.iter().map(|event| event.data.as_str()).collect();
+			assert_eq!(data, [r#"{"n": 1}"#, r#"{"n": 2}"#, "[DONE]"], "split at {split}");
+			assert!(decoder.finish().is_none());
+		}
+
+		// 10k chunks of a few bytes, as when the API streams one token per chunk: the buffer
+		// only holds the event being received, however long the stream.
+		let event = "data: {\"choices\":[{\"delta\":{\"content\":\"token\"}}]}\r\n\r\n";
+		let body = event.repeat(2_000);
+		let mut decoder = SseDecoder::new();
+		let mut events = 0;
+		for chunk in body.as_bytes().chunks(body.len() / 10_000) {
+			decoder.feed(chunk);
+			while let Some(event) = decoder.next_event() {
+				assert_eq!(event.data, r#"{"choices":[{"delta":{"content":"token"}}]}"#);
+				events += 1;
+			}
+			assert!(decoder.buf.capacity() <= 2 * event.len(), "{}", decoder.buf.capacity());
+		}
+		assert_eq!(events, 2_000);
+		assert!(decoder.finish().is_none());
+	}
+
+	#[test]
+	fn test_raw_stream() {
+		#[derive(serde::Deserialize)]
+		struct Chunk {
+			n: u32,
+		}
+
+		let body =
+			"data: {\"n\": 1, \"new\": true}\n\ndata: {\"n\": 2,\ndata: \"usage\": null}\n\n\
+			data: [DONE]\n\n";
+		let chunks: Vec<_> =
+			new_test_stream::<Chunk>(body, 5).with_raw().map(Result::unwrap).collect();
+		assert_eq!(chunks.len(), 2);
+		assert_eq!(chunks[0].chunk.n, 1);
+		assert_eq!(chunks[0].data, r#"{"n": 1, "new": true}"#);
+		assert_eq!(chunks[1].chunk.n, 2);
+		assert_eq!(chunks[1].data, "{\"n\": 2,\n\"usage\": null}");
+	}
+
+	#[test]
+	fn test_stream_abort() {
+		let body = "data: {\"n\": 1}\n\ndata: {\"n\": 2}\n\ndata: [DONE]\n\n";
+		let mut stream = new_test_stream::<Json>(body, 1024);
+		let handle = stream.handle();
+		assert!(stream.next().unwrap().is_ok());
+		handle.abort();
+		assert!(matches!(stream.next(), Some(Err(Error::Cancelled))));
+		assert!(stream.next().is_none());
+
+		// Aborting from another thread while the stream is reading.
+		let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+		let reader = std::net::TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+		let (mut writer, _) = listener.accept().unwrap();
+		let mut stream = Stream::<Json>::new(Box::new(reader));
+		let handle = stream.handle();
+		let thread = std::thread::spawn(move || stream.next());
+		std::thread::sleep(std::time::Duration::from_millis(50));
+		handle.abort();
+		std::io::Write::write_all(&mut writer, b"data: {\"n\": 1}\n\n").unwrap();
+		assert!(matches!(thread.join().unwrap(), Some(Err(Error::Cancelled))));
+	}
+
+	#[test]
+	fn test_stream_close_and_drain() {
+		use std::io::Write;
+		use std::time::Duration;
+
+		let connect = || {
+			let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+			let reader = std::net::TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+			let (writer, _) = listener.accept().unwrap();
+			writer.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+			(Stream::<Json>::new(Box::new(reader)), writer)
+		};
+		let body = b"data: {\"n\": 1}\n\ndata: {\"n\": 2}\n\ndata: {\"n\": 3}\n\n";
+
+		// Closing drops the connection, which the server sees as the end of the stream.
+		let (mut stream, mut writer) = connect();
+		writer.write_all(body).unwrap();
+		assert_eq!(stream.next().unwrap().unwrap()["n"], 1);
+		stream.close();
+		assert_eq!(std::io::Read::read(&mut writer, &mut [0; 16]).unwrap(), 0);
+
+		// Draining reads the rest of the response, up to the end sent by the server.
+		let (mut stream, mut writer) = connect();
+		writer.write_all(body).unwrap();
+		assert_eq!(stream.next().unwrap().unwrap()["n"], 1);
+		let thread = std::thread::spawn(move || stream.drain());
+		writer.write_all(b"data: {\"n\": 4}\n\ndata: [DONE]\n\n").unwrap();
+		drop(writer);
+		assert!(thread.join().unwrap().is_ok());
+
+		let body = "data: {\"n\": 1}\n\ndata: {\"n\": 2}\n\ndata: [DONE]\n\n";
+		let mut stream = new_test_stream::<Json>(body, 4).with_raw();
+		assert!(stream.next().unwrap().is_ok());
+		assert!(stream.drain().is_ok());
+
+		struct Reset;
+		impl Read for Reset {
+			fn read(&mut self, _: &mut [u8]) -> std::io::Result<usize> {
+				Err(std::io::ErrorKind::ConnectionReset.into())
+			}
+		}
+		let stream = Stream::<Json>::new(Box::new(Reset));
+		assert!(matches!(stream.drain(), Err(Error::StreamInterrupted(0))));
+	}
+
+	#[test]
+	fn test_stream_interrupted() {
+		let body = "data: {\"n\": 1}\n\ndata: {\"n\": 2}\n\ndata: {\"n\"";
+		let mut stream = new_test_stream::<Json>(body, 4);
+		assert!(stream.next().unwrap().is_ok());
+		assert!(stream.next().unwrap().is_ok());
+		assert!(matches!(stream.next(), Some(Err(Error::StreamInterrupted(2)))));
+		assert!(stream.next().is_none());
+
+		let mut stream = new_test_stream::<Json>("data: {\"n\": 1}\n\ndata: [DONE]", 4);
+		assert!(stream.next().unwrap().is_ok());
+		assert!(stream.next().is_none());
+
+		struct Reset;
+		impl Read for Reset {
+			fn read(&mut self, _: &mut [u8]) -> std::io::Result<usize> {
+				Err(std::io::ErrorKind::ConnectionReset.into())
+			}
+		}
+		let mut stream = Stream::<Json>::new(Box::new(Reset));
+		assert!(matches!(stream.next(), Some(Err(Error::StreamInterrupted(0)))));
+		assert!(stream.next().is_none());
+	}
+
+	#[test]
+	fn test_stream_read_interrupted() {
+		/// Fails its first read with `ErrorKind::Interrupted`, then reads `body`.
+		struct Interrupted(bool, &'static [u8]);
+		impl Read for Interrupted {
+			fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+				if !std::mem::replace(&mut self.0, true) {
+					return Err(std::io::ErrorKind::Interrupted.into());
+				}
+				self.1.read(buf)
+			}
+		}
+		let body = b"data: {\"n\": 1}\n\ndata: [DONE]\n\n";
+
+		let mut stream = Stream::<Json>::new(Box::new(Interrupted(false, body)));
+		assert_eq!(stream.next().unwrap().unwrap()["n"], 1);
+		assert!(stream.next().is_none());
+
+		let stream = Stream::<Json>::new(Box::new(Interrupted(false, body)));
+		assert!(stream.drain().is_ok());
+	}
+
+	#[test]
+	fn test_stream_error() {
+		let body = "data: {\"error\": {\"message\": \"boom\"}}\n\n";
+		let mut stream = new_test_stream::<Json>(body, 1024);
+		assert!(matches!(stream.next(), Some(Err(Error::Api(err))) if err.message == "boom"));
+		assert!(stream.next().is_none());
+	}
+}
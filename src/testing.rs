@@ -0,0 +1,206 @@
+// Canned responses for testing code built on this crate without network access.
+
+//! Testing
+//!
+//! ```
+//! use openai_api_rust::*;
+//! use openai_api_rust::models::*;
+//! use openai_api_rust::testing::{MockResponse, MockTransport};
+//!
+//! let mock = MockTransport::new()
+//!     .with_response("models/gpt-4o", MockResponse::new(200, r#"{"id": "gpt-4o"}"#));
+//! let openai = OpenAI::new(Auth::new("sk-test"), "https://api.openai.com/v1/")
+//!     .with_transport(mock.clone());
+//! assert_eq!(openai.models_retrieve("gpt-4o").unwrap().id, "gpt-4o");
+//! assert_eq!(mock.requests()[0].path, "/v1/models/gpt-4o");
+//! ```
+
+use std::collections::HashMap;
+use std::io::{self, Read};
+use std::sync::{Arc, Mutex};
+
+use crate::transport::{HttpRequest, HttpResponse, RequestBody, Transport};
+use crate::Json;
+
+/// A canned response of a `MockTransport`.
+#[derive(Debug, Clone)]
+pub struct MockResponse {
+	pub status: u16,
+	pub headers: Vec<(String, String)>,
+	pub body: Vec<u8>,
+}
+
+impl MockResponse {
+	pub fn new(status: u16, body: impl Into<Vec<u8>>) -> Self {
+		Self { status, headers: Vec::new(), body: body.into() }
+	}
+
+	pub fn with_header(mut self, name: &str, value: &str) -> Self {
+		self.headers.push((name.to_string(), value.to_string()));
+		self
+	}
+}
+
+/// A request received by a `MockTransport`.
+#[derive(Debug, Clone)]
+pub struct MockRequest {
+	pub method: String,
+	/// The path of the URL, without the query, e.g. `/v1/chat/completions`.
+	pub path: String,
+	pub url: String,
+	pub headers: Vec<(String, String)>,
+	pub body: Vec<u8>,
+}
+
+impl MockRequest {
+	/// The value of the header `name`, matched case insensitively.
+	pub fn header(&self, name: &str) -> Option<&str> {
+		self.headers
+			.iter()
+			.find(|(other, _)| other.eq_ignore_ascii_case(name))
+			.map(|(_, value)| value.as_str())
+	}
+
+	/// The body parsed as JSON, `None` if it isn't JSON.
+	pub fn json(&self) -> Option<Json> {
+		serde_json::from_slice(&self.body).ok()
+	}
+}
+
+#[derive(Debug, Default)]
+struct MockState {
+	responses: HashMap<String, Vec<MockResponse>>,
+	requests: Vec<MockRequest>,
+}
+
+/// A `Transport` answering with canned responses keyed by path, and recording the requests it
+/// receives. Clones share their responses and requests, so a clone can be given to the client
+/// and the original inspected afterwards.
+///
+/// A path is matched against the end of the request path, so `chat/completions` matches
+/// `/v1/chat/completions`. Requests to a path without response get a `404` error.
+#[derive(Debug, Clone, Default)]
+pub struct MockTransport {
+	state: Arc<Mutex<MockState>>,
+}
+
+impl MockTransport {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Answers requests to `path` with `response`. When several responses are given for the
+	/// same path, they are returned in order, and the last one is repeated. When several paths
+	/// match a request, the longest one is used.
+	pub fn with_response(self, path: &str, response: MockResponse) -> Self {
+		let path = path.trim_matches('/').to_string();
+		self.state.lock().unwrap().responses.entry(path).or_default().push(response);
+		self
+	}
+
+	/// The requests received so far, in order.
+	pub fn requests(&self) -> Vec<MockRequest> {
+		self.state.lock().unwrap().requests.clone()
+	}
+
+	fn response(&self, path: &str) -> Option<MockResponse> {
+		let mut state = self.state.lock().unwrap();
+		let path = path.trim_end_matches('/');
+		let (_, responses) = state
+			.responses
+			.iter_mut()
+			.filter(|(key, _)| {
+				path.strip_suffix(key.as_str())
+					.is_some_and(|rest| rest.is_empty() || rest.ends_with('/'))
+			})
+			.max_by_key(|(key, _)| key.len())?;
+		if responses.len() > 1 {
+			Some(responses.remove(0))
+		} else {
+			responses.first().cloned()
+		}
+	}
+}
+
+impl Transport for MockTransport {
+	fn send(&self, request: HttpRequest) -> io::Result<HttpResponse> {
+		let body = match request.body {
+			RequestBody::Empty => Vec::new(),
+			RequestBody::Json(json) => serde_json::to_vec(json)?,
+			RequestBody::Reader(mut reader) => {
+				let mut body = Vec::new();
+				reader.read_to_end(&mut body)?;
+				body
+			},
+		};
+		let url = request.url;
+		let path = url.split_once("://").map_or(url.as_str(), |(_, rest)| rest);
+		let path = path.find('/').map_or("/", |start| &path[start..]);
+		let path = path.split(['?', '#']).next().unwrap_or_default().to_string();
+
+		let response = self.response(&path).unwrap_or_else(|| {
+			let message = format!("No mock response for {path}");
+			MockResponse::new(404, serde_json::json!({"error": {"message": message}}).to_string())
+		});
+		self.state.lock().unwrap().requests.push(MockRequest {
+			method: request.method,
+			path,
+			url,
+			headers: request.headers,
+			body,
+		});
+		Ok(HttpResponse {
+			status: response.status,
+			headers: response.headers,
+			body: Box::new(io::Cursor::new(response.body)),
+		})
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::chat::{ChatApi, ChatBody};
+	use crate::models::ModelsApi;
+	use crate::openai::new_test_mock;
+	use crate::{Error, Message};
+
+	#[test]
+	fn test_mock_transport() {
+		let completion = r#"{"id": "chatcmpl-1", "object": "chat.completion", "created": 1,
+			"model": "gpt-4o", "choices": [{"index": 0, "finish_reason": "stop",
+			"message": {"role": "assistant", "content": "Hi!"}}],
+			"usage": {"prompt_tokens": 1, "completion_tokens": 1, "total_tokens": 2}}"#;
+		let mock = MockTransport::new()
+			.with_response("chat/completions", MockResponse::new(500, "{}"))
+			.with_response(
+				"chat/completions",
+				MockResponse::new(200, completion)
+					.with_header("x-ratelimit-remaining-requests", "9"),
+			);
+		let openai = new_test_mock(&mock);
+		let body = ChatBody {
+			model: "gpt-4o".into(),
+			messages: vec![Message::user("Hello!")],
+			..Default::default()
+		};
+		let rs = openai.chat_completion_create(&body);
+		assert!(matches!(rs, Err(Error::Api(err)) if err.status == 500 && err.message == "{}"));
+		let completion = openai.chat_completion_create(&body).unwrap();
+		assert_eq!(completion.choices[0].message.as_ref().unwrap().text(), Some("Hi!"));
+		assert_eq!(openai.chat_completion_create(&body).unwrap().id, completion.id);
+		assert_eq!(openai.rate_limit().unwrap().remaining_requests, Some(9));
+
+		let rs = openai.clone().with_transport(MockTransport::new()).models_list();
+		assert!(matches!(rs, Err(Error::Api(err)) if err.status == 404));
+
+		let requests = mock.requests();
+		assert_eq!(requests.len(), 3);
+		assert_eq!(requests[0].method, "POST");
+		assert_eq!(requests[0].path, "/v1/chat/completions");
+		assert_eq!(requests[0].header("authorization"), Some("Bearer sk-test"));
+		let json = requests[0].json().unwrap();
+		assert_eq!(json["model"], "gpt-4o");
+		assert_eq!(json["messages"][0]["content"], "Hello!");
+	}
+}
@@ -0,0 +1,285 @@
+// Local token counting with the BPE encodings of OpenAI models, to check that a request fits
+// in the context window before sending it.
+// See: https://github.com/openai/openai-cookbook/blob/main/examples/How_to_count_tokens_with_tiktoken.ipynb
+
+//! Token counting
+
+use tiktoken_rs::tokenizer::{get_tokenizer, Tokenizer};
+use tiktoken_rs::{
+	cl100k_base_singleton, o200k_base_singleton, p50k_base_singleton, p50k_edit_singleton,
+	r50k_base_singleton, CoreBPE,
+};
+
+use std::collections::HashMap;
+
+use crate::model_info::ModelInfo;
+use crate::{ApiResult, Content, ContentPart, Error, Message, Role};
+
+/// The number of tokens of `text` with the encoding of `model`.
+/// Unknown models are assumed to use `o200k_base`, the encoding of the latest models.
+pub fn count_tokens(model: &str, text: &str) -> usize {
+	with_bpe(model, |bpe| bpe.encode_with_special_tokens(text).len())
+}
+
+/// The number of prompt tokens of a chat completion request with `messages`,
+/// including the tokens formatting every message and priming the reply.
+///
+/// Only text is counted: image parts and tool definitions are not.
+pub fn count_chat_tokens(model: &str, messages: &[Message]) -> usize {
+	// Every reply is primed with <|start|>assistant<|message|>.
+	message_tokens(model, messages).iter().sum::<usize>() + 3
+}
+
+/// The number of tokens of each of `messages`, see `count_chat_tokens`.
+fn message_tokens(model: &str, messages: &[Message]) -> Vec<usize> {
+	// Every message follows <|start|>{role/name}\n{content}<|end|>\n.
+	let (tokens_per_message, tokens_per_name): (usize, isize) =
+		if model.starts_with("gpt-3.5-turbo-0301") {
+			// The role is omitted when there's a name.
+			(4, -1)
+		} else {
+			(3, 1)
+		};
+
+	with_bpe(model, |bpe| {
+		let count = |text: &str| bpe.encode_with_special_tokens(text).len();
+
+		let message_tokens = |message: &Message| {
+			let mut tokens = tokens_per_message + count(role(&message.role));
+			match &message.content {
+				Some(Content::Text(text)) => tokens += count(text),
+				Some(Content::Parts(parts)) => {
+					for part in parts {
+						if let ContentPart::Text { text } = part {
+							tokens += count(text);
+						}
+					}
+				},
+				None => {},
+			}
+			if let Some(name) = &message.name {
+				tokens = (tokens + count(name)).saturating_add_signed(tokens_per_name);
+			}
+			for tool_call in message.tool_calls() {
+				tokens += count(&tool_call.function.name) + count(&tool_call.function.arguments);
+			}
+			tokens
+		};
+		messages.iter().map(message_tokens).collect()
+	})
+}
+
+/// The number of tokens the prompt and the completion of `model` share, `None` if the model
+/// is unknown, see `ModelInfo::lookup`.
+pub fn context_window(model: &str) -> Option<usize> {
+	ModelInfo::lookup(model)?.context_window.map(|tokens| tokens as usize)
+}
+
+/// Drops the oldest messages of a conversation until its prompt has at most `max_tokens`
+/// tokens of `model`, counted with `count_chat_tokens`.
+///
+/// System and developer messages, and the last user message, are kept. The tool messages
+/// answering a dropped assistant message are dropped along with it, since the API rejects tool
+/// messages without the call they answer. Fails with `Error::InvalidRequest` if the kept
+/// messages don't fit.
+pub fn truncate_to_fit(
+	model: &str,
+	messages: &mut Vec<Message>,
+	max_tokens: usize,
+) -> ApiResult<()> {
+	// Messages are only counted once, the dropped ones are subtracted from the total.
+	let mut counts = message_tokens(model, messages);
+	let mut tokens = counts.iter().sum::<usize>() + 3;
+	while tokens > max_tokens {
+		let last_user = messages.iter().rposition(|message| message.role == Role::User);
+		let oldest = messages.iter().enumerate().position(|(index, message)| {
+			!matches!(message.role, Role::System | Role::Developer) && Some(index) != last_user
+		});
+		let Some(oldest) = oldest else {
+			return Err(Error::InvalidRequest(format!(
+				"the prompt doesn't fit in {max_tokens} tokens, even without its history"
+			)));
+		};
+		let answers =
+			messages[oldest + 1..].iter().take_while(|message| message.role == Role::Tool).count();
+		messages.drain(oldest..=oldest + answers);
+		tokens -= counts.drain(oldest..=oldest + answers).sum::<usize>();
+	}
+	Ok(())
+}
+
+/// The `logit_bias` biasing every token of each text, tokenized with the encoding of `model`.
+/// Words are usually tokenized with their leading space, so bias `" Paris"` rather than `"Paris"`
+/// to bias the word within a sentence.
+///
+/// A text of several tokens biases each of them, e.g. also in other words starting with the
+/// same token, which is logged as a warning.
+pub fn logit_bias<'a>(
+	model: &str,
+	biases: impl IntoIterator<Item = (&'a str, i32)>,
+) -> HashMap<u32, i32> {
+	with_bpe(model, |bpe| {
+		let mut logit_bias = HashMap::new();
+		for (text, bias) in biases {
+			let tokens = bpe.encode_with_special_tokens(text);
+			if tokens.len() > 1 {
+				log::warn!(
+					"{text:?} is {} tokens for {model}, each of them is biased",
+					tokens.len()
+				);
+			}
+			logit_bias.extend(tokens.into_iter().map(|token| (token, bias)));
+		}
+		logit_bias
+	})
+}
+
+/// Splits `text` into chunks of at most `max_tokens` tokens of `model`, e.g. to embed a text
+/// longer than the model accepts. Chunks end between tokens, moved back a token or two where
+/// the boundary would split a character, so joining the chunks gives back `text`.
+pub fn split_tokens(model: &str, text: &str, max_tokens: usize) -> Vec<String> {
+	let max_tokens = max_tokens.max(1);
+	with_bpe(model, |bpe| {
+		let tokens = bpe.encode_with_special_tokens(text);
+		let mut chunks = Vec::new();
+		let mut start = 0;
+		while start < tokens.len() {
+			let mut end = (start + max_tokens).min(tokens.len());
+			// A character of several bytes may be split across tokens, which don't decode apart.
+			let chunk = loop {
+				match bpe.decode(tokens[start..end].to_vec()) {
+					Ok(chunk) => break Some(chunk),
+					Err(_) if end > start + 1 => end -= 1,
+					Err(_) => break None,
+				}
+			};
+			let chunk = chunk.unwrap_or_else(|| {
+				// A single token holding part of a character: grow until the character ends,
+				// at the latest at the end of `text`.
+				loop {
+					end += 1;
+					if let Ok(chunk) = bpe.decode(tokens[start..end].to_vec()) {
+						return chunk;
+					}
+				}
+			});
+			chunks.push(chunk);
+			start = end;
+		}
+		chunks
+	})
+}
+
+/// Calls `f` with the encoding of `model`, the encodings are only loaded once.
+fn with_bpe<T>(model: &str, f: impl FnOnce(&CoreBPE) -> T) -> T {
+	let bpe = match get_tokenizer(model) {
+		Some(Tokenizer::Cl100kBase) => cl100k_base_singleton(),
+		Some(Tokenizer::P50kBase) => p50k_base_singleton(),
+		Some(Tokenizer::P50kEdit) => p50k_edit_singleton(),
+		Some(Tokenizer::R50kBase | Tokenizer::Gpt2) => r50k_base_singleton(),
+		Some(Tokenizer::O200kBase) | None => o200k_base_singleton(),
+	};
+	let bpe = bpe.lock();
+	f(&bpe)
+}
+
+fn role(role: &Role) -> &'static str {
+	match role {
+		Role::System => "system",
+		Role::Developer => "developer",
+		Role::Assistant => "assistant",
+		Role::User => "user",
+		Role::Tool => "tool",
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_count_tokens() {
+		assert_eq!(count_tokens("gpt-4", "hello world"), 2);
+		assert_eq!(count_tokens("gpt-4o", "hello world"), 2);
+		assert_eq!(count_tokens("gpt-4", "tiktoken is great!"), 6);
+	}
+
+	#[test]
+	fn test_count_chat_tokens() {
+		let messages = vec![Message::user("hello world")];
+		// 3 per message, 1 for the role, 2 for the content and 3 for the reply.
+		assert_eq!(count_chat_tokens("gpt-4", &messages), 9);
+
+		let messages =
+			vec![Message { name: Some("example_user".to_string()), ..messages[0].clone() }];
+		let name = count_tokens("gpt-4", "example_user");
+		assert_eq!(count_chat_tokens("gpt-4", &messages), 9 + name + 1);
+		assert_eq!(count_chat_tokens("gpt-3.5-turbo-0301", &messages), 10 + name - 1);
+	}
+
+	#[test]
+	fn test_truncate_to_fit() {
+		assert_eq!(context_window("gpt-4o-2024-08-06"), Some(128_000));
+		assert_eq!(context_window("gpt-4-0613"), Some(8_192));
+		assert_eq!(context_window("gpt-4-32k-0613"), Some(32_768));
+		assert_eq!(context_window("my-model"), None);
+
+		let call = crate::ToolCall {
+			id: "call_1".to_string(),
+			kind: "function".to_string(),
+			function: crate::FunctionCall {
+				name: "get_weather".to_string(),
+				arguments: "{}".to_string(),
+			},
+		};
+		let history = vec![
+			Message::system("You are a helpful assistant."),
+			Message::user("What is the weather like in Paris?"),
+			Message { tool_calls: Some(vec![call]), ..Message::assistant("") },
+			Message::tool("call_1", "Sunny"),
+			Message::assistant("It is sunny in Paris."),
+			Message::user("And in London?"),
+		];
+		let mut messages = history.clone();
+		truncate_to_fit("gpt-4o", &mut messages, 1000).unwrap();
+		assert_eq!(messages.len(), 6);
+
+		let kept = [history[0].clone(), history[4].clone(), history[5].clone()];
+		truncate_to_fit("gpt-4o", &mut messages, count_chat_tokens("gpt-4o", &kept)).unwrap();
+		let roles: Vec<_> = messages.iter().map(|message| message.role.clone()).collect();
+		assert_eq!(roles, [Role::System, Role::Assistant, Role::User]);
+		assert_eq!(messages[2].text(), Some("And in London?"));
+		assert_eq!(count_chat_tokens("gpt-4o", &messages), count_chat_tokens("gpt-4o", &kept));
+
+		let rs = truncate_to_fit("gpt-4o", &mut messages, 10);
+		assert!(matches!(rs, Err(Error::InvalidRequest(_))));
+		assert_eq!(messages.len(), 2);
+	}
+
+	#[test]
+	fn test_logit_bias() {
+		let paris = with_bpe("gpt-4o", |bpe| bpe.encode_with_special_tokens(" Paris"));
+		assert_eq!(paris.len(), 1);
+		assert_eq!(logit_bias("gpt-4o", [(" Paris", -100)]), [(paris[0], -100)].into());
+
+		let biases = logit_bias("gpt-4", [(" Paris", 5), ("tiktoken is great!", -1)]);
+		assert_eq!(biases.len(), 1 + count_tokens("gpt-4", "tiktoken is great!"));
+		assert_eq!(biases.values().filter(|bias| **bias == 5).count(), 1);
+	}
+
+	#[test]
+	fn test_split_tokens() {
+		let text = "tiktoken is great! ".repeat(10);
+		let chunks = split_tokens("gpt-4", &text, 4);
+		assert_eq!(chunks.concat(), text);
+		assert!(chunks.iter().all(|chunk| count_tokens("gpt-4", chunk) <= 4));
+		assert_eq!(split_tokens("gpt-4", &text, 1000), [text.as_str()]);
+		assert!(split_tokens("gpt-4", "", 4).is_empty());
+
+		// Emojis are several tokens each, never split apart.
+		let text = "🦀🦀🦀 crabs";
+		let chunks = split_tokens("gpt-4", text, 1);
+		assert_eq!(chunks.concat(), text);
+		assert!(chunks.iter().all(|chunk| !chunk.is_empty()));
+	}
+}
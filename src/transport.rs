@@ -0,0 +1,230 @@
+// The HTTP layer requests are sent through, `ureq` unless the client is given another one.
+
+//! Transport
+
+use std::fmt::Debug;
+use std::io::{self, Read};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::Json;
+
+/// Sends the HTTP requests of a client, see `OpenAI::with_transport`.
+///
+/// Responses are returned whatever their status, the client turns error statuses into
+/// `Error::Api`. Transport errors are retried according to `OpenAI::with_retry`, except those of
+/// kind `io::ErrorKind::InvalidInput`, for requests which can't succeed on another attempt.
+/// Those of kind `NotConnected` or `ConnectionRefused` are for requests that were never sent,
+/// which are retried whatever their method.
+pub trait Transport: Debug + Send + Sync {
+	fn send(&self, request: HttpRequest) -> io::Result<HttpResponse>;
+}
+
+pub struct HttpRequest<'a> {
+	pub method: String,
+	/// The full URL, including the query.
+	pub url: String,
+	pub headers: Vec<(String, String)>,
+	pub body: RequestBody<'a>,
+	/// The time within which the whole response must be received.
+	/// Unset for streaming requests, which are only bounded by the timeouts of the transport.
+	pub timeout: Option<Duration>,
+}
+
+impl<'a> HttpRequest<'a> {
+	pub(crate) fn new(method: &str, url: String) -> Self {
+		Self {
+			method: method.to_string(),
+			url,
+			headers: Vec::new(),
+			body: RequestBody::Empty,
+			timeout: None,
+		}
+	}
+
+	/// Sets the header `name`, replacing any header of the same name.
+	pub fn set(mut self, name: &str, value: &str) -> Self {
+		self.headers.retain(|(other, _)| !other.eq_ignore_ascii_case(name));
+		self.headers.push((name.to_string(), value.to_string()));
+		self
+	}
+
+	/// The value of the header `name`, matched case insensitively.
+	pub fn header(&self, name: &str) -> Option<&str> {
+		find_header(&self.headers, name)
+	}
+
+	pub(crate) fn body(mut self, body: RequestBody<'a>) -> Self {
+		self.body = body;
+		self
+	}
+}
+
+pub enum RequestBody<'a> {
+	Empty,
+	Json(&'a Json),
+	/// A body read as it is sent, e.g. a multipart form.
+	Reader(Box<dyn Read + 'a>),
+}
+
+pub struct HttpResponse {
+	pub status: u16,
+	pub headers: Vec<(String, String)>,
+	pub body: Box<dyn Read + Send + Sync>,
+}
+
+impl HttpResponse {
+	/// The value of the header `name`, matched case insensitively.
+	pub fn header(&self, name: &str) -> Option<&str> {
+		find_header(&self.headers, name)
+	}
+}
+
+fn find_header<'h>(headers: &'h [(String, String)], name: &str) -> Option<&'h str> {
+	headers
+		.iter()
+		.find(|(other, _)| other.eq_ignore_ascii_case(name))
+		.map(|(_, value)| value.as_str())
+}
+
+/// A request as it would be sent, see `OpenAI::dry_run`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PreparedRequest {
+	pub method: String,
+	/// The full URL, including the query.
+	pub url: String,
+	/// The headers set by the client, without those the transport adds such as `Content-Length`.
+	pub headers: Vec<(String, String)>,
+	pub body: Vec<u8>,
+}
+
+impl PreparedRequest {
+	/// The value of the header `name`, matched case insensitively.
+	pub fn header(&self, name: &str) -> Option<&str> {
+		find_header(&self.headers, name)
+	}
+
+	/// The body parsed as JSON, `None` if it isn't JSON.
+	pub fn json(&self) -> Option<Json> {
+		serde_json::from_slice(&self.body).ok()
+	}
+}
+
+/// Records the first request it is given and fails it, so that nothing is sent.
+#[derive(Debug, Default)]
+pub(crate) struct DryRun {
+	pub(crate) request: Arc<Mutex<Option<PreparedRequest>>>,
+}
+
+impl Transport for DryRun {
+	fn send(&self, request: HttpRequest) -> io::Result<HttpResponse> {
+		let body = match request.body {
+			RequestBody::Empty => Vec::new(),
+			RequestBody::Json(json) => serde_json::to_vec(json)?,
+			RequestBody::Reader(mut reader) => {
+				let mut body = Vec::new();
+				reader.read_to_end(&mut body)?;
+				body
+			},
+		};
+		let prepared = PreparedRequest {
+			method: request.method,
+			url: request.url,
+			headers: request.headers,
+			body,
+		};
+		self.request.lock().unwrap().get_or_insert(prepared);
+		// Not retried, see `Transport`.
+		Err(io::Error::new(io::ErrorKind::InvalidInput, "dry run"))
+	}
+}
+
+/// The default transport, configured by `OpenAI::set_proxy` and `OpenAI::with_timeout`.
+impl Transport for ureq::Agent {
+	fn send(&self, request: HttpRequest) -> io::Result<HttpResponse> {
+		let mut req = self.request(&request.method, &request.url);
+		for (name, value) in &request.headers {
+			req = req.set(name, value);
+		}
+		if let Some(timeout) = request.timeout {
+			req = req.timeout(timeout);
+		}
+		let response = match request.body {
+			RequestBody::Empty => req.call(),
+			RequestBody::Json(json) => req.send_json(json),
+			RequestBody::Reader(reader) => req.send(reader),
+		};
+		let response = match response {
+			Ok(response) | Err(ureq::Error::Status(_, response)) => response,
+			Err(ureq::Error::Transport(e)) => {
+				return Err(io::Error::new(io_error_kind(e.kind()), e));
+			},
+		};
+		let headers = response
+			.headers_names()
+			.into_iter()
+			.filter_map(|name| {
+				let value = response.header(&name)?.to_string();
+				Some((name, value))
+			})
+			.collect();
+		Ok(HttpResponse { status: response.status(), headers, body: response.into_reader() })
+	}
+}
+
+/// The kind of the error of a failed ureq request, `InvalidInput` for the requests that can't
+/// succeed as they are, which are not retried, see `Error::is_retryable`. Failing to resolve
+/// or to reach the host may be transient, like the other network errors.
+fn io_error_kind(kind: ureq::ErrorKind) -> io::ErrorKind {
+	match kind {
+		ureq::ErrorKind::Dns => io::ErrorKind::NotConnected,
+		ureq::ErrorKind::ConnectionFailed | ureq::ErrorKind::ProxyConnect => {
+			io::ErrorKind::ConnectionRefused
+		},
+		ureq::ErrorKind::Io | ureq::ErrorKind::BadStatus => io::ErrorKind::Other,
+		ureq::ErrorKind::InvalidUrl
+		| ureq::ErrorKind::UnknownScheme
+		| ureq::ErrorKind::BadHeader
+		| ureq::ErrorKind::InsecureRequestHttpsOnly
+		| ureq::ErrorKind::TooManyRedirects
+		| ureq::ErrorKind::InvalidProxyUrl
+		| ureq::ErrorKind::ProxyUnauthorized
+		| ureq::ErrorKind::HTTP => io::ErrorKind::InvalidInput,
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::requests::Requests;
+	use crate::{Auth, Error, OpenAI};
+
+	#[test]
+	fn test_io_error_kind() {
+		// Transient failures to reach the API are retried.
+		for kind in [
+			ureq::ErrorKind::Dns,
+			ureq::ErrorKind::ConnectionFailed,
+			ureq::ErrorKind::ProxyConnect,
+			ureq::ErrorKind::Io,
+		] {
+			let error = crate::requests::deal_error(io::Error::new(io_error_kind(kind), "x"), "");
+			assert!(matches!(error, Error::Connection(_)) && error.is_retryable(), "{kind:?}");
+		}
+		// Invalid requests are not.
+		for kind in [
+			ureq::ErrorKind::InvalidUrl,
+			ureq::ErrorKind::UnknownScheme,
+			ureq::ErrorKind::BadHeader,
+		] {
+			let error = crate::requests::deal_error(io::Error::new(io_error_kind(kind), "x"), "");
+			assert!(matches!(error, Error::RequestError(_)) && !error.is_retryable(), "{kind:?}");
+		}
+
+		let openai = OpenAI::new(Auth::new("sk-test"), "ftp://api.example.invalid/v1/");
+		assert!(matches!(openai.get("models"), Err(Error::RequestError(_))));
+		// Nothing listens on port 1, the connection is refused.
+		let openai = OpenAI::new(Auth::new("sk-test"), "http://127.0.0.1:1/v1/");
+		assert!(matches!(openai.get("models"), Err(Error::Connection(_))));
+	}
+}